@@ -16,6 +16,21 @@ namespace UTOC.Stream.Emulator.Configuration
         [Description("Creates a dump of emulated IO Store files (.utoc + .ucas) as they are written.")]
         [DefaultValue(LogSeverity.Information)]
         public bool DumpFiles { get; set; } = false;
+
+        [DisplayName("AES Key")]
+        [Description("AES-256 key (64 hex characters, e.g 0x0123...) used to read encrypted IO Store containers.\nLeave empty if the game's containers aren't encrypted.")]
+        [DefaultValue("")]
+        public string AesKey { get; set; } = "";
+
+        [DisplayName("Game Profile")]
+        [Description("Profile for the game that's being modded, which sets the engine version, project name and any known quirks.\nStandard profiles: ue4.25+, ue4.26, ue4.27. Game specific profiles: p3r, scarlet-nexus.\nLeave empty to detect the engine version from the game's containers.")]
+        [DefaultValue("")]
+        public string GameProfile { get; set; } = "";
+
+        [DisplayName("Deduplicate Identical Files")]
+        [Description("Files with identical contents (such as the same .ubulk under different paths) share one copy in the emulated container.\nThis makes the container smaller, but every file that's the same size as another one is read while it's being built.")]
+        [DefaultValue(false)]
+        public bool DeduplicateChunks { get; set; } = false;
     }
 
     /// <summary>
@@ -67,6 +67,11 @@ namespace UTOC.Stream.Emulator
             _log = new Logger(_logger, _configuration.LogLevel);
             _log.Info("Starting UTOC.Stream.Emulator");
             _emu = new UtocEmulator(_log, _configuration.DumpFiles);
+            if (!string.IsNullOrWhiteSpace(_configuration.AesKey) && !RustApi.AddEncryptionKey(_configuration.AesKey, null))
+                _log.Error("AES Key in the configuration is invalid, encrypted containers can't be read");
+            if (!string.IsNullOrWhiteSpace(_configuration.GameProfile) && !RustApi.SetGameProfile(_configuration.GameProfile))
+                _log.Error($"Game profile \"{_configuration.GameProfile}\" doesn't exist, using the default profile");
+            RustApi.SetChunkDeduplication(_configuration.DeduplicateChunks);
 
             _modLoader.ModLoading += OnModLoading;
             _modLoader.ModUnloading += OnModUnloading;
@@ -13,11 +13,35 @@ namespace UTOC.Stream.Emulator
         [DllImport("fileemu_utoc_stream_emulator")] // Collect assets
         public static extern void AddFromFolders(string mod_id, string mod_path);
 
+        [DllImport("fileemu_utoc_stream_emulator")] // Add a file from memory at a game path (e.g /Game/Folder/Asset.uasset)
+        public static extern bool AddVirtualFile(string modId, string gamePath, IntPtr data, ulong length);
+
+        [UnmanagedFunctionPointer(CallingConvention.Cdecl)] // Called while building UTOC, contents are copied before returning
+        public delegate IntPtr VirtualFileProducer(IntPtr userData, ref ulong length);
+
+        [DllImport("fileemu_utoc_stream_emulator")] // Add a file at a game path that gets generated when building UTOC
+        public static extern bool AddVirtualFileProducer(string modId, string gamePath, VirtualFileProducer producer, IntPtr userData);
+
+        [DllImport("fileemu_utoc_stream_emulator")] // Key for encrypted containers, keyGuid can be null to use it for any container
+        public static extern bool AddEncryptionKey(string key, string? keyGuid);
+
+        [DllImport("fileemu_utoc_stream_emulator")] // Pick the game's profile (engine version, project name, quirks) by id
+        public static extern bool SetGameProfile(string profileId);
+
+        [DllImport("fileemu_utoc_stream_emulator")] // Share one range of the container between files with identical contents
+        public static extern void SetChunkDeduplication(bool enabled);
+
         [DllImport("fileemu_utoc_stream_emulator")] // Build UTOC
         public static extern IntPtr BuildTableOfContents(string tocPath, IntPtr settings, uint settingsLength, ref long length);
 
         [DllImport("fileemu_utoc_stream_emulator")] // Build UCAS
-        public static extern bool GetContainerBlocks(string casPath, ref nint blocks, ref nint blockCount, ref nint header, ref nint headerSize);
+        public static extern bool GetContainerBlocks(string casPath, ref nint blocks, ref nint blockCount, ref nint header, ref nint headerSize, ref uint alignment);
+
+        [DllImport("fileemu_utoc_stream_emulator")] // Build PAK for files that aren't packages
+        public static extern bool GetPakBlocks(string pakPath, ref nint blocks, ref nint blockCount, ref nint index, ref nint indexSize);
+
+        [DllImport("fileemu_utoc_stream_emulator")] // Extract the files that the emulated container serves into a folder
+        public static extern bool ExtractVirtualContainer(string outPath);
 
         [DllImport("fileemu_utoc_stream_emulator")]
         public static extern void SafeToDropContainerMetadata(); // Container entry data was copied over to managed C#, drop on Rust side
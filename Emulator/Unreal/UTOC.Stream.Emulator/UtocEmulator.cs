@@ -21,17 +21,19 @@ namespace UTOC.Stream.Emulator
     // Must be kept in sync with PartitionBlock in toc_factory.rs
     public struct PartitionBlock
     {
-        public IntPtr osPath; // *const u8
+        public IntPtr osPath; // *const u8 (null for memory blocks)
         public long start; // u64
         public long length; // u64
+        public IntPtr data; // *const u8 (null for file blocks)
+        public long fileOffset; // u64
     }
     public class UtocEmulator : IEmulator
     {
         public static readonly string UtocExtension = ".utoc";
         public static readonly string UcasExtension = ".ucas";
+        public static readonly string PakExtension = ".pak";
         public static readonly string DumpFolderParent = "FEmulator-Dumps";
         public static readonly string DumpFolderToc = "UTOCEmulator";
-        public static readonly int DefaultCompressionBlockAlignment = 0x800;
         public bool DumpFiles { get; set; }
         public Logger _logger { get; init; }
         private readonly ConcurrentDictionary<string, Strim?> _pathToStream = new(StringComparer.OrdinalIgnoreCase);
@@ -78,40 +80,81 @@ namespace UTOC.Stream.Emulator
             nint blockPtr = 0;
             nint headerSize = 0;
             nint headerPtr = 0;
+            uint alignment = 0;
             _pathToStream[path] = null;
-            if (!RustApi.GetContainerBlocks(path, ref blockPtr, ref blockCount, ref headerPtr, ref headerSize)) return false;
+            if (!RustApi.GetContainerBlocks(path, ref blockPtr, ref blockCount, ref headerPtr, ref headerSize, ref alignment)) return false;
+            stream = CreateBlockStream(blockPtr, blockCount, headerPtr, headerSize, (int)alignment);
+            
+            _pathToStream.TryAdd(path, stream);
+            emulated = new EmulatedFile<Strim>(stream);
+            _logger.Info($"[UtocEmulator] Created Emulated Container File with Path {path}");
+            //RustApi.SafeToDropContainerMetadata(); // keep it alive for now
+            if (CanDump)
+                DumpFile(path, stream);
+            return true;
+        }
+
+        public bool TryCreatePak(string path, ref IEmulatedFile? emulated, out Strim? stream)
+        {
+            stream = null;
+            nint blockCount = 0;
+            nint blockPtr = 0;
+            nint indexSize = 0;
+            nint indexPtr = 0;
+            _pathToStream[path] = null;
+            // Pak entries aren't aligned, each entry's data comes straight after it's header
+            if (!RustApi.GetPakBlocks(path, ref blockPtr, ref blockCount, ref indexPtr, ref indexSize)) return false;
+            stream = CreateBlockStream(blockPtr, blockCount, indexPtr, indexSize, 1);
+
+            _pathToStream.TryAdd(path, stream);
+            emulated = new EmulatedFile<Strim>(stream);
+            _logger.Info($"[UtocEmulator] Created Emulated Pak File with Path {path}");
+            if (CanDump)
+                DumpFile(path, stream);
+            return true;
+        }
+
+        // Lay out each block at it's start offset, padding the end of each one to alignment, followed by the trailing memory block
+        private Strim CreateBlockStream(nint blockPtr, nint blockCount, nint trailerPtr, nint trailerSize, int alignment)
+        {
             var streams = new List<StreamOffsetPair<Strim>>();
             long streamEnd = 0;
             for (int i = 0; i < blockCount; i++)
             {
                 var containerBlock = Marshal.PtrToStructure<PartitionBlock>(blockPtr);
-                streams.Add(new(
-                    new FileStream(Marshal.PtrToStringAnsi(containerBlock.osPath), FileMode.Open),
-                    OffsetRange.FromStartAndLength(containerBlock.start, containerBlock.length)
-                ));
+                if (containerBlock.osPath != IntPtr.Zero && containerBlock.fileOffset == 0)
+                    streams.Add(new(
+                        new FileStream(Marshal.PtrToStringAnsi(containerBlock.osPath)!, FileMode.Open),
+                        OffsetRange.FromStartAndLength(containerBlock.start, containerBlock.length)
+                    ));
+                else if (containerBlock.osPath != IntPtr.Zero) // chunk inside of a mod's .ucas
+                    streams.Add(new(
+                        new FileSliceStreamFs(new FileSlice(containerBlock.fileOffset, (int)containerBlock.length, Marshal.PtrToStringAnsi(containerBlock.osPath)!), _logger),
+                        OffsetRange.FromStartAndLength(containerBlock.start, containerBlock.length)
+                    ));
+                else
+                    unsafe
+                    {
+                        streams.Add(new(
+                            new UnmanagedMemoryStream((byte*)containerBlock.data, containerBlock.length),
+                            OffsetRange.FromStartAndLength(containerBlock.start, containerBlock.length)
+                        ));
+                    }
                 var containerBlockEnd = containerBlock.start + containerBlock.length;
-                var diff = Mathematics.RoundUp(containerBlockEnd, DefaultCompressionBlockAlignment) - containerBlockEnd;
+                var diff = Mathematics.RoundUp(containerBlockEnd, alignment) - containerBlockEnd;
                 if (diff > 0)
                     streams.Add(new(new PaddingStream(0, (int)diff), OffsetRange.FromStartAndLength(containerBlockEnd, diff)));
                 unsafe { blockPtr += sizeof(PartitionBlock); }
-                streamEnd = Mathematics.RoundUp(containerBlockEnd, DefaultCompressionBlockAlignment);
+                streamEnd = Mathematics.RoundUp(containerBlockEnd, alignment);
             }
             unsafe
             {
                 streams.Add(new(
-                    new UnmanagedMemoryStream((byte*)headerPtr, headerSize),
-                    OffsetRange.FromStartAndLength(streamEnd, (long)headerSize)
+                    new UnmanagedMemoryStream((byte*)trailerPtr, trailerSize),
+                    OffsetRange.FromStartAndLength(streamEnd, (long)trailerSize)
                 ));
             }
-            stream = new MultiStream(streams, _logger);
-            
-            _pathToStream.TryAdd(path, stream);
-            emulated = new EmulatedFile<Strim>(stream);
-            _logger.Info($"[UtocEmulator] Created Emulated Container File with Path {path}");
-            //RustApi.SafeToDropContainerMetadata(); // keep it alive for now
-            if (CanDump)
-                DumpFile(path, stream);
-            return true;
+            return new MultiStream(streams, _logger);
         }
 
         /// <summary>
@@ -135,6 +178,9 @@ namespace UTOC.Stream.Emulator
             } else if (srcDataPath.EndsWith(UcasExtension, StringComparison.OrdinalIgnoreCase))
             {
                 if (TryCreateIoStoreContainer(srcDataPath, ref emulated!, out _)) return true;
+            } else if (srcDataPath.EndsWith(PakExtension, StringComparison.OrdinalIgnoreCase))
+            {
+                if (TryCreatePak(srcDataPath, ref emulated!, out _)) return true;
             }
             return false;
         }
@@ -150,6 +196,19 @@ namespace UTOC.Stream.Emulator
         }
 
         public void OnModLoading(string mod_id, string dir_path) => RustApi.AddFromFolders(mod_id, dir_path);
+
+        /// <summary>
+        /// Adds a file that doesn't exist on disk into the emulated container.
+        /// </summary>
+        /// <param name="modId">Mod that owns the file, used for the asset collector report.</param>
+        /// <param name="gamePath">Path of the file inside the game (e.g. /Game/Folder/Asset.uasset).</param>
+        /// <param name="data">Contents of the file. These are copied, so the array can be reused afterwards.</param>
+        /// <returns>True if the file was added, false if the path or file type wasn't valid.</returns>
+        public unsafe bool AddVirtualFile(string modId, string gamePath, byte[] data)
+        {
+            fixed (byte* dataPtr = data)
+                return RustApi.AddVirtualFile(modId, gamePath, (IntPtr)dataPtr, (ulong)data.Length);
+        }
         public void OnLoaderInit() => RustApi.PrintAssetCollectorResults();
     }
 }
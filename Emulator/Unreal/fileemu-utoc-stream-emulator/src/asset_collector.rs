@@ -1,20 +1,30 @@
 use crate::{
+    delta_patch::DELTA_PATCH_EXTENSION,
+    game_profile::{self, Endianness},
     io_package,
+    pak_package::CustomVersion,
+    pak_reader::PakReader,
     platform::Metadata,
-    toc_factory::TARGET_TOC
+    toc_factory::TARGET_TOC,
+    toc_reader::TocReader
 };
 use std::{
     cell::RefCell,
     collections::BTreeSet,
+    fmt,
+    error::Error,
     fs, fs::File,
-    io::BufReader,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
     path::{Path, PathBuf},
+    ptr::{addr_of, addr_of_mut},
     rc::{Rc, Weak},
+    sync::atomic::{AtomicU64, Ordering},
     time::Instant
 };
 
 pub type TocDirectoryRef = Rc<RefCell<TocDirectory>>;
 pub type TocFileRef = Rc<RefCell<TocFile>>;
+pub type TocFileProducer = Rc<dyn Fn() -> Result<Vec<u8>, Box<dyn Error>>>;
 
 pub const FILE_EMULATION_FRAMEWORK_FOLDER:  &'static str = "FEmulator";
 pub const EMULATOR_NAME:                    &'static str = "UTOC";
@@ -23,6 +33,9 @@ pub const PROJECT_NAME:                     &'static str = "UnrealEssentials";
 // Root TOC directory (needs to be global)
 pub static mut ROOT_DIRECTORY: Option<TocDirectoryRef> = None;
 pub static mut ASSET_COLLECTOR_PROFILER: Option<AssetCollectorProfiler> = None;
+pub static mut PACKAGE_REDIRECTS: Option<Vec<PackageRedirect>> = None;
+// Files that IO Store can't carry, laid out the same way as ROOT_DIRECTORY (see pak_factory)
+pub static mut PAK_ROOT_DIRECTORY: Option<TocDirectoryRef> = None;
 
 // Create tree of assets that can be used to build a TOC
 pub fn add_from_folders(mod_id: &str, mod_path: &str) {
@@ -31,6 +44,10 @@ pub fn add_from_folders(mod_id: &str, mod_path: &str) {
             ASSET_COLLECTOR_PROFILER = Some(AssetCollectorProfiler::new());
         }
     }
+    let redirects_path: PathBuf = [mod_path, FILE_EMULATION_FRAMEWORK_FOLDER, EMULATOR_NAME, PACKAGE_REDIRECTS_FILE].iter().collect();
+    if Path::exists(&redirects_path) {
+        add_package_redirects(mod_id, &redirects_path);
+    }
     let mod_path: PathBuf = [mod_path, FILE_EMULATION_FRAMEWORK_FOLDER, EMULATOR_NAME, TARGET_TOC].iter().collect();
     if Path::exists(Path::new(&mod_path)) {
         let mut profiler_mod = AssetCollectorProfilerMod::new(mod_id, mod_path.to_str().unwrap());
@@ -41,10 +58,328 @@ pub fn add_from_folders(mod_id: &str, mod_path: &str) {
             if let None = ROOT_DIRECTORY {
                 ROOT_DIRECTORY = Some(TocDirectory::new_rc(PROJECT_NAME)); // ProjectName
             }
-            add_from_folders_inner(Rc::clone(&ROOT_DIRECTORY.as_ref().unwrap()), &mod_path, &mut profiler_mod.data);
+            add_from_folders_inner(Rc::clone((*addr_of!(ROOT_DIRECTORY)).as_ref().unwrap()), &mod_path, &mut profiler_mod.data);
             profiler_mod.set_time_to_tree();
-            ASSET_COLLECTOR_PROFILER.as_mut().unwrap().mods_loaded.push(profiler_mod);
+            (*addr_of_mut!(ASSET_COLLECTOR_PROFILER)).as_mut().unwrap().mods_loaded.push(profiler_mod);
+        }
+    }
+}
+
+// Cooked packages that were saved with a newer custom version than the game's get refused by the engine when they're loaded
+fn check_custom_versions(pak: &PakReader, entry_index: usize) {
+    let game_profile = game_profile::get_game_profile();
+    if game_profile.custom_versions.is_empty() {
+        return;
+    }
+    // the summary's fixed fields come before the custom versions, and no game has more custom versions than this
+    let summary_size = 0x20 + CustomVersion::SERIALIZED_SIZE * 0x100;
+    let versions = pak.read_entry_start(entry_index, summary_size).and_then(|summary| match game_profile.endianness {
+        Endianness::Little => CustomVersion::list_from_summary::<_, byteorder::LittleEndian>(&mut Cursor::new(summary)),
+        Endianness::Big => CustomVersion::list_from_summary::<_, byteorder::BigEndian>(&mut Cursor::new(summary))
+    });
+    let path = &pak.entries[entry_index].path;
+    match versions {
+        Ok(versions) => for (key, version, game_version) in game_profile.get_newer_custom_versions(&versions) {
+            println!("WARNING: {} in {} was saved with version {} of custom version {:032X}, but {} uses version {}",
+                path, pak.pak_path.display(), version, key, game_profile.name, game_version);
+        },
+        Err(e) => println!("WARNING: Couldn't read the custom versions of {} in {}: {}", path, pak.pak_path.display(), e)
+    }
+}
+
+// Add a file that doesn't exist on disk into the tree. game_path can either be a mounted path (/Game/Folder/Asset.uasset)
+// or a path relative to the project root (Content/Folder/Asset.uasset). This follows the same priority rules as add_from_folders,
+// so virtual files added later will replace files with the same path. Files that aren't packages go into the companion pak
+pub fn add_virtual_file(mod_id: &str, game_path: &str, source: TocFileSource, file_size: u64) -> Result<TocFileAddType, String> {
+    let (dir_names, file_name) = game_path_to_components(game_path)?;
+    let is_package = match PathBuf::from(file_name).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => game_profile::is_container_extension(ext),
+        None => return Err(format!("Virtual file \"{}\" has no file extension", game_path))
+    };
+    unsafe {
+        if (*addr_of!(ASSET_COLLECTOR_PROFILER)).is_none() {
+            ASSET_COLLECTOR_PROFILER = Some(AssetCollectorProfiler::new());
+        }
+        if (*addr_of!(ROOT_DIRECTORY)).is_none() {
+            ROOT_DIRECTORY = Some(TocDirectory::new_rc(PROJECT_NAME)); // ProjectName
+        }
+        let profiler = (*addr_of_mut!(ASSET_COLLECTOR_PROFILER)).as_mut().unwrap().get_or_add_mod(mod_id);
+        let root = match is_package {
+            true => Rc::clone((*addr_of!(ROOT_DIRECTORY)).as_ref().unwrap()),
+            false => get_pak_root_directory()
+        };
+        Ok(add_file_at_path(root, &dir_names, TocFile::new_rc(file_name, file_size, source), &mut profiler.data))
+    }
+}
+
+// Add a file that IO Store can't carry into the pak tree, at the same path as the directory that it was found in
+fn add_pak_file(dir: &TocDirectoryRef, file: TocFileRef, profiler: &mut AssetCollectorProfilerModContents) -> TocFileAddType {
+    let mut dir_names = vec![];
+    let mut curr_dir = Rc::clone(dir);
+    loop {
+        let parent = curr_dir.borrow().parent.upgrade();
+        match parent {
+            Some(parent) => {
+                dir_names.push(curr_dir.borrow().name.clone());
+                curr_dir = parent;
+            },
+            None => break // the root directory is the project folder, which the pak path adds back in
+        }
+    }
+    dir_names.reverse();
+    let dir_names: Vec<&str> = dir_names.iter().map(|d| d.as_str()).collect();
+    add_file_at_path(get_pak_root_directory(), &dir_names, file, profiler)
+}
+
+fn get_pak_root_directory() -> TocDirectoryRef {
+    unsafe { Rc::clone((*addr_of_mut!(PAK_ROOT_DIRECTORY)).get_or_insert_with(|| TocDirectory::new_rc(PROJECT_NAME))) } // ProjectName
+}
+
+// True if any mod added a file that goes into the pak
+pub fn has_pak_files() -> bool {
+    unsafe { (*addr_of!(PAK_ROOT_DIRECTORY)).is_some() }
+}
+
+// Add a file into the tree below the given list of directories, creating any directories that don't exist yet
+fn add_file_at_path(root: TocDirectoryRef, dir_names: &[&str], file: TocFileRef, profiler: &mut AssetCollectorProfilerModContents) -> TocFileAddType {
+    let mut parent = root;
+    for dir_name in dir_names {
+        parent = match TocDirectory::get_child_dir(Rc::clone(&parent), dir_name) {
+            Some(child_dir) => child_dir,
+            None => {
+                let new_dir = TocDirectory::new_rc(dir_name);
+                TocDirectory::add_directory(Rc::clone(&parent), Rc::clone(&new_dir));
+                profiler.add_directory();
+                new_dir
+            }
+        };
+    }
+    let file_size = file.borrow().file_size;
+    let add_type = TocDirectory::add_or_replace_file(parent, file);
+    match add_type {
+        TocFileAddType::Addition => profiler.add_added_file(file_size),
+        TocFileAddType::Replacement => profiler.add_replaced_file(file_size)
+    }
+    add_type
+}
+
+// Add every file from a mod's .utoc/.ucas pair into the tree. Files are placed by their package path, so they follow the same
+// priority rules as loose files. Uncompressed chunks are read straight from the mod's .ucas, while compressed chunks get
+// decompressed when the TOC is built
+pub fn add_from_container(toc_path: &Path, profiler: &mut AssetCollectorProfilerModContents) {
+    let toc_path_str = toc_path.to_str().unwrap();
+    let toc = match TocReader::from_file(toc_path) {
+        Ok(toc) => Rc::new(toc),
+        Err(e) => {
+            profiler.add_failed_fs_object(toc_path_str, e.to_string());
+            return;
+        }
+    };
+    let mount_point = toc.get_mount_point_relative().to_owned();
+    for file in toc.get_files() {
+        let file_size = toc.offsets_and_lengths[file.chunk_index].get_length();
+        let full_path = mount_point.clone() + &file.path;
+        let display_path = format!("{}:{}", toc_path_str, &full_path);
+        // [ProjectName]/Content/... is the only part of the container that gets mounted at /Game
+        let mut components: Vec<&str> = full_path.split('/').filter(|c| !c.is_empty()).collect();
+        if components.len() < 3 || components[1] != "Content" || components[0] == "Engine" {
+            profiler.add_skipped_file(&display_path, String::from("Not inside of the game's Content folder"), file_size);
+            continue
+        }
+        let file_name = components.pop().unwrap();
+        match PathBuf::from(file_name).extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if game_profile::is_container_extension(ext) => (),
+            _ => {
+                profiler.add_skipped_file(&display_path, String::from("Unsupported file type"), file_size);
+                continue
+            }
+        }
+        if let Err(e) = toc.check_chunk_readable(file.chunk_index) {
+            profiler.add_skipped_file(&display_path, e.to_string(), file_size);
+            continue
+        }
+        let source = match toc.get_chunk_file_range(file.chunk_index) {
+            Some((cas_path, offset)) => TocFileSource::OsPathSlice(cas_path.to_str().unwrap().to_owned(), offset),
+            None => {
+                let toc = Rc::clone(&toc);
+                let chunk_index = file.chunk_index;
+                TocFileSource::Producer(Rc::new(move || toc.read_chunk(chunk_index)
+                    .map_err(|e| format!("Failed to read chunk {} from {}: {}", chunk_index, toc.toc_path.display(), e).into())))
+            }
+        };
+        let root = unsafe { Rc::clone((*addr_of!(ROOT_DIRECTORY)).as_ref().unwrap()) };
+        add_file_at_path(root, &components[1..], TocFile::new_rc(file_name, file_size, source), profiler);
+    }
+}
+
+// Add every file from a mod's .pak into the tree, following the same priority rules as loose files. Packages that are already in
+// IO Store's format go into the container, while everything else goes into the companion pak. That includes cooked packages, which
+// keep their .uexp, .ubulk and .uptnl with them since the game loads all of them from the same place
+pub fn add_from_pak(pak_path: &Path, profiler: &mut AssetCollectorProfilerModContents) {
+    let pak_path_str = pak_path.to_str().unwrap();
+    let pak = match PakReader::from_file(pak_path) {
+        Ok(pak) => Rc::new(pak),
+        Err(e) => {
+            profiler.add_failed_fs_object(pak_path_str, e.to_string());
+            return;
+        }
+    };
+    let mount_point = pak.get_mount_point_relative().to_owned();
+    let mut cooked_packages = BTreeSet::new();
+    for (i, entry) in pak.entries.iter().enumerate() {
+        if entry.path.ends_with(".uasset") {
+            if let Ok(data) = pak.read_entry_start(i, 4) {
+                if data.len() >= 4 && !io_package::is_valid_asset_type::<Cursor<Vec<u8>>, byteorder::NativeEndian>(&mut Cursor::new(data)) {
+                    cooked_packages.insert((mount_point.clone() + &entry.path[..entry.path.len() - 7]).to_lowercase());
+                    check_custom_versions(&pak, i);
+                }
+            }
+        }
+    }
+    for (i, entry) in pak.entries.iter().enumerate() {
+        let full_path = mount_point.clone() + &entry.path;
+        let display_path = format!("{}:{}", pak_path_str, &full_path);
+        if let Err(e) = pak.check_entry_readable(i) {
+            profiler.add_skipped_file(&display_path, e.to_string(), entry.uncompressed_size);
+            continue
+        }
+        let mut components: Vec<&str> = full_path.split('/').filter(|c| !c.is_empty()).collect();
+        let file_name = components.pop().unwrap_or_default();
+        if components.is_empty() {
+            profiler.add_skipped_file(&display_path, String::from("Not inside of the game's project or Engine folder"), entry.uncompressed_size);
+            continue
+        }
+        let is_io_store_package = match full_path.rsplit_once('.') {
+            Some((stem, ext)) => game_profile::is_container_extension(ext) && !cooked_packages.contains(&stem.to_lowercase()),
+            None => false
+        };
+        // [ProjectName]/Content/... is the only part of the container that gets mounted at /Game
+        if is_io_store_package && (components.len() < 2 || components[1] != "Content" || components[0] == "Engine") {
+            profiler.add_skipped_file(&display_path, String::from("Not inside of the game's Content folder"), entry.uncompressed_size);
+            continue
+        }
+        let source = match pak.get_entry_file_offset(i) {
+            Some(offset) => TocFileSource::OsPathSlice(pak_path_str.to_owned(), offset),
+            None => {
+                let pak = Rc::clone(&pak);
+                TocFileSource::Producer(Rc::new(move || pak.read_entry(i)
+                    .map_err(|e| format!("Failed to read {} from {}: {}", pak.entries[i].path, pak.pak_path.display(), e).into())))
+            }
+        };
+        let file = TocFile::new_rc(file_name, entry.uncompressed_size, source);
+        match (is_io_store_package, components[0]) {
+            (true, _) => add_file_at_path(unsafe { Rc::clone((*addr_of!(ROOT_DIRECTORY)).as_ref().unwrap()) }, &components[1..], file, profiler),
+            (false, "Engine") => add_file_at_path(get_pak_root_directory(), &components, file, profiler),
+            (false, _) => add_file_at_path(get_pak_root_directory(), &components[1..], file, profiler)
+        };
+    }
+}
+
+// Mods can redirect packages by adding PackageRedirects.txt next to their UnrealEssentials_P.utoc folder. Each line redirects
+// one package to another using their mounted paths, with lines starting with # being comments:
+// /Game/Folder/OldAsset = /Game/Folder/NewAsset
+// Redirects are checked when the TOC is built, since the target can come from any mod
+pub const PACKAGE_REDIRECTS_FILE: &str = "PackageRedirects.txt";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageRedirect {
+    pub mod_id: String,
+    pub source: String, // mounted package paths without an extension (/Game/Folder/Asset)
+    pub target: String
+}
+
+pub fn parse_package_redirects(mod_id: &str, manifest: &str) -> Result<Vec<PackageRedirect>, String> {
+    let mut redirects = vec![];
+    for (i, line) in manifest.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
         }
+        let (source, target) = line.split_once('=').ok_or_else(|| format!("Line {}: expected \"Source = Target\"", i + 1))?;
+        let source = get_package_path(source.trim()).map_err(|e| format!("Line {}: {}", i + 1, e))?;
+        let target = get_package_path(target.trim()).map_err(|e| format!("Line {}: {}", i + 1, e))?;
+        if source.eq_ignore_ascii_case(&target) {
+            return Err(format!("Line {}: {} redirects to itself", i + 1, source));
+        }
+        redirects.push(PackageRedirect { mod_id: mod_id.to_owned(), source, target });
+    }
+    Ok(redirects)
+}
+
+// Package paths are mounted paths (/Game/...) which can optionally include the .uasset extension
+fn get_package_path(path: &str) -> Result<String, String> {
+    let path = path.replace('\\', "/");
+    if !path.starts_with("/Game/") {
+        return Err(format!("Package path \"{}\" must begin with /Game/", path));
+    }
+    Ok(path.strip_suffix(".uasset").unwrap_or(&path).to_owned())
+}
+
+fn add_package_redirects(mod_id: &str, redirects_path: &Path) {
+    let result = fs::read_to_string(redirects_path).map_err(|e| e.to_string())
+        .and_then(|manifest| parse_package_redirects(mod_id, &manifest));
+    unsafe {
+        match result {
+            Ok(redirects) => (*addr_of_mut!(PACKAGE_REDIRECTS)).get_or_insert_with(Vec::new).extend(redirects),
+            Err(e) => (*addr_of_mut!(ASSET_COLLECTOR_PROFILER)).as_mut().unwrap().get_or_add_mod(mod_id).data
+                .add_failed_fs_object(redirects_path.to_str().unwrap(), e)
+        }
+    }
+}
+
+// Unreal treats paths as case insensitive, and chunk ids are hashed from the lowercased path, so Foo.uasset and foo.uasset must be
+// the same file (otherwise both end up in the container with the same chunk id)
+pub fn is_same_name(a: &str, b: &str) -> bool {
+    a == b || a.to_lowercase() == b.to_lowercase()
+}
+
+// Find a file in the tree from it's mounted path (/Game/Folder/Asset.uasset)
+pub fn find_file(game_path: &str) -> Option<TocFileRef> {
+    let (dir_names, file_name) = game_path_to_components(game_path).ok()?;
+    let mut parent = Rc::clone(unsafe { (*addr_of!(ROOT_DIRECTORY)).as_ref()? });
+    for dir_name in dir_names {
+        parent = TocDirectory::get_child_dir(parent, dir_name)?;
+    }
+    let mut curr_file = parent.borrow().first_file.as_ref().map(Rc::clone);
+    while let Some(file) = curr_file {
+        if is_same_name(&file.borrow().name, file_name) {
+            return Some(file);
+        }
+        curr_file = file.borrow().next.as_ref().map(Rc::clone);
+    }
+    None
+}
+
+// Add a virtual file backed by a byte buffer
+pub fn add_virtual_file_from_buffer(mod_id: &str, game_path: &str, buffer: Vec<u8>) -> Result<TocFileAddType, String> {
+    let file_size = buffer.len() as u64;
+    add_virtual_file(mod_id, game_path, TocFileSource::Memory(Rc::new(buffer)), file_size)
+}
+
+// Add a virtual file which gets it's contents from a callback. The callback runs once when the TOC is built, which is when
+// the file size is known
+pub fn add_virtual_file_from_producer<F: Fn() -> Vec<u8> + 'static>(mod_id: &str, game_path: &str, producer: F) -> Result<TocFileAddType, String> {
+    add_virtual_file(mod_id, game_path, TocFileSource::Producer(Rc::new(move || Ok(producer()))), 0)
+}
+
+// Split a game path into the directories below the project root and the file name
+// /Game/Folder/Asset.uasset => ([Content, Folder], Asset.uasset)
+fn game_path_to_components(game_path: &str) -> Result<(Vec<&str>, &str), String> {
+    let trimmed = game_path.trim_start_matches('/');
+    let (root, relative) = match trimmed.split_once('/') {
+        Some(("Game", rest)) => ("Content", rest),
+        Some((first, rest)) if first == PROJECT_NAME => match rest.split_once('/') {
+            Some((second, rest)) => (second, rest),
+            None => return Err(format!("Virtual file path \"{}\" doesn't contain a file", game_path))
+        },
+        Some((first, rest)) => (first, rest),
+        None => return Err(format!("Virtual file path \"{}\" must be inside of a directory", game_path))
+    };
+    let mut dir_names = vec![root];
+    dir_names.extend(relative.split('/').filter(|c| !c.is_empty()));
+    match dir_names.pop() {
+        Some(file_name) if !dir_names.is_empty() => Ok((dir_names, file_name)),
+        _ => Err(format!("Virtual file path \"{}\" doesn't contain a file", game_path))
     }
 }
 
@@ -106,7 +441,7 @@ impl TocDirectory {
     fn replace_file(
         dir: TocDirectoryRef, // containing directory
         prev_file: Option<TocFileRef>, // previous file, which links to replacee (unless it's the *first* file)
-        replacee: TocFileRef, // the file to get replaced (file merging is a future problem)
+        replacee: TocFileRef, // the file to get replaced, which is kept in replacer->replaced
         replacer: TocFileRef // file that'll take the place of replacee in the chain
     ) {
         if replacee.borrow().next.as_ref() == None { // replacee is the last file in chain, dir->last_file = weakref(replacer)
@@ -116,10 +451,12 @@ impl TocDirectory {
         }
         if prev_file == None { // replacee is at the start, set dir->first_file = replacer
             dir.borrow_mut().first_file = Some(Rc::clone(&replacer));
-        } else { // prev->next = replacer, replacee drops here
+        } else { // prev->next = replacer
             prev_file.as_ref().unwrap().borrow_mut().next = Some(Rc::clone(&replacer));
         }
-
+        // replacee is no longer in the chain, but mods that change different parts of the same file can still be merged from it
+        replacee.borrow_mut().next = None;
+        replacer.borrow_mut().replaced = Some(replacee);
     }
     // Add a file to the end of the directory's file list, which contains at least 1 existing file
     #[inline]
@@ -136,7 +473,7 @@ impl TocDirectory {
                 let mut prev: Option<TocFileRef> = None;
                 let mut curr_file = Rc::clone(dir.borrow().first_file.as_ref().unwrap());
                 loop {
-                    if curr_file.borrow().name == file.borrow().name { // we got the file, replace it
+                    if is_same_name(&curr_file.borrow().name, &file.borrow().name) { // we got the file, replace it
                         found = true;
                         break
                     }
@@ -169,6 +506,26 @@ impl TocDirectory {
             }
         }
     }
+    // Take a file out of the directory's file list, so that the file it replaced (or the game's file) is used instead
+    pub fn remove_file(dir: TocDirectoryRef, file: TocFileRef) {
+        let mut prev: Option<TocFileRef> = None;
+        let mut curr_file = dir.borrow().first_file.clone();
+        while let Some(curr) = curr_file {
+            if Rc::ptr_eq(&curr, &file) {
+                let next = curr.borrow_mut().next.take();
+                if next.is_none() { // removing the last file, so the previous one is the new tail
+                    dir.borrow_mut().last_file = prev.as_ref().map_or(Weak::new(), Rc::downgrade);
+                }
+                match prev {
+                    Some(prev) => prev.borrow_mut().next = next,
+                    None => dir.borrow_mut().first_file = next
+                }
+                return;
+            }
+            curr_file = curr.borrow().next.clone();
+            prev = Some(curr);
+        }
+    }
     // get a child directory from a parent directory if it exists
     pub fn get_child_dir(parent: TocDirectoryRef, exist: &str) -> Option<TocDirectoryRef> {
         match TocDirectory::has_children(Rc::clone(&parent)) {
@@ -176,7 +533,7 @@ impl TocDirectory {
                 let mut curr_dir = Rc::clone(&parent.borrow().first_child.as_ref().unwrap());
                 let mut result = None;
                 loop {
-                    if curr_dir.borrow().name == exist { // we got our directory
+                    if is_same_name(&curr_dir.borrow().name, exist) { // we got our directory
                         result = Some(Rc::clone(&curr_dir));
                         break;
                     }
@@ -202,37 +559,148 @@ impl TocDirectory {
     }
 }
 
+// Where the contents of a file in the virtual tree come from
+#[derive(Clone)]
+pub enum TocFileSource {
+    OsPath(String), // loose file on disk, needed so we can open it, copy it then write it into partition
+    OsPathSlice(String, u64), // range of a file on disk starting at an offset (uncompressed chunks inside of a mod's .ucas)
+    Memory(Rc<Vec<u8>>), // buffer owned by the emulator (code generated assets, patched packages, converted PAK assets)
+    Producer(TocFileProducer), // called once while building the TOC, then gets swapped out for a Memory source
+    DeltaPatch(String) // patch on disk, applied while building the TOC and swapped out for a Memory source (see delta_patch)
+}
+
+impl fmt::Debug for TocFileSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OsPath(p) => write!(f, "OsPath({})", p),
+            Self::OsPathSlice(p, o) => write!(f, "OsPathSlice({}, 0x{:x})", p, o),
+            Self::Memory(m) => write!(f, "Memory({} bytes)", m.len()),
+            Self::Producer(_) => write!(f, "Producer"),
+            Self::DeltaPatch(p) => write!(f, "DeltaPatch({})", p)
+        }
+    }
+}
+
+impl PartialEq for TocFileSource {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::OsPath(a), Self::OsPath(b)) => a == b,
+            (Self::OsPathSlice(a, ao), Self::OsPathSlice(b, bo)) => a == b && ao == bo,
+            (Self::Memory(a), Self::Memory(b)) => Rc::ptr_eq(a, b),
+            (Self::Producer(a), Self::Producer(b)) => Rc::ptr_eq(a, b),
+            (Self::DeltaPatch(a), Self::DeltaPatch(b)) => a == b,
+            _ => false
+        }
+    }
+}
+
+impl TocFileSource {
+    // Get a displayable name for the profiler
+    pub fn get_display_path(&self) -> &str {
+        match self {
+            Self::OsPath(p) | Self::OsPathSlice(p, _) | Self::DeltaPatch(p) => p,
+            Self::Memory(_) => "<memory>",
+            Self::Producer(_) => "<producer>"
+        }
+    }
+}
+
+// Mods are added in load order, so files that were added later have a higher priority
+static FILES_ADDED: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Debug, PartialEq)]
 pub struct TocFile {
     pub next: Option<Rc<RefCell<TocFile>>>,
+    pub replaced: Option<Rc<RefCell<TocFile>>>, // the lower priority file that this one replaced, kept for merging (see datatable_merge)
     pub name: String,
     pub file_size: u64,
-    pub os_file_path: String // needed so we can open it, copy it then write it into partition
+    pub source: TocFileSource,
+    pub priority: u64 // order that the file was added in, for files that collide without replacing each other
 }
 
 impl TocFile {
     // constructor
-    fn new(name: &str, file_size: u64, os_path: &str) -> Self {
+    fn new(name: &str, file_size: u64, source: TocFileSource) -> Self {
         Self {
             next: None,
+            replaced: None,
             name: String::from(name),
             file_size,
-            os_file_path: String::from(os_path)
+            source,
+            priority: FILES_ADDED.fetch_add(1, Ordering::Relaxed)
         }
     }
     #[inline] // convenience function to create reference counted toc files
-    pub fn new_rc(name: &str, file_size: u64, os_path: &str) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(TocFile::new(name, file_size, os_path)))
+    pub fn new_rc(name: &str, file_size: u64, source: TocFileSource) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(TocFile::new(name, file_size, source)))
+    }
+    // Run the producer callback (if there is one) so that the file has a known size before it's flattened
+    pub fn resolve(&mut self) -> Result<(), Box<dyn Error>> {
+        if let TocFileSource::Producer(producer) = &self.source {
+            let buffer = producer()?;
+            self.file_size = buffer.len() as u64;
+            self.source = TocFileSource::Memory(Rc::new(buffer));
+        }
+        Ok(())
+    }
+    // Resolve the file, swapping in the file that it replaced each time a producer fails. Returns the error from every producer that
+    // failed, and false if there's no file left to use (so that the game's file gets used instead)
+    pub fn resolve_or_fall_back(&mut self, errors: &mut Vec<Box<dyn Error>>) -> bool {
+        while let Err(e) = self.resolve() {
+            errors.push(e);
+            let replaced = match self.replaced.clone() {
+                Some(replaced) => replaced,
+                None => return false
+            };
+            let replaced = replaced.borrow();
+            self.file_size = replaced.file_size;
+            self.source = replaced.source.clone();
+            self.replaced = replaced.replaced.clone();
+        }
+        true
     }
 }
 
+// Read the whole contents of a file in the tree. Patches have to be applied first, since their contents depend on other files
+pub fn read_file(file: &TocFileRef) -> Result<Vec<u8>, Box<dyn Error>> {
+    let file = file.borrow();
+    Ok(match &file.source {
+        TocFileSource::OsPath(p) => fs::read(p)?,
+        TocFileSource::OsPathSlice(p, offset) => {
+            let mut reader = File::open(p)?;
+            reader.seek(SeekFrom::Start(*offset))?;
+            let mut buffer = vec![0; file.file_size as usize];
+            reader.read_exact(&mut buffer)?;
+            buffer
+        },
+        TocFileSource::Memory(b) => b.to_vec(),
+        TocFileSource::Producer(p) => p()?,
+        TocFileSource::DeltaPatch(p) => return Err(format!("Patch {} hasn't been applied yet", p).into())
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TocFileAddType {
     Addition,
     Replacement
 }
 
-pub const SUITABLE_FILE_EXTENSIONS: &'static [&'static str] = ["uasset", "ubulk", "uptnl"].as_slice();
+pub const SUITABLE_FILE_EXTENSIONS: &'static [&'static str] = ["uasset", "ubulk", "uptnl"].as_slice(); // game profiles pick from these
 pub const MOUNT_POINT: &'static str = "../../../";
+pub const LOCALIZATION_FOLDER: &str = "L10N";
+
+// Localized packages are stored in Content/L10N/[Culture]/..., and are used instead of the package at the same path outside of L10N
+// when the game is set to that culture. Given a package path relative to the project ([ProjectName]/Content/L10N/ja/UI/Title),
+// this returns the culture and the mounted path of the package that it localizes (ja, /Game/UI/Title)
+pub fn get_localized_package_source(package_path: &str) -> Option<(String, String)> {
+    let content_path = package_path.split_once(&(PROJECT_NAME.to_owned() + "/Content/"))?.1;
+    let mut components = content_path.splitn(3, '/');
+    match (components.next(), components.next(), components.next()) {
+        (Some(folder), Some(culture), Some(source)) if folder.eq_ignore_ascii_case(LOCALIZATION_FOLDER) && !culture.is_empty() && !source.is_empty() =>
+            Some((culture.to_owned(), String::from("/Game/") + source)),
+        _ => None
+    }
+}
 
 pub fn add_from_folders_inner(parent: TocDirectoryRef, os_path: &PathBuf, profiler: &mut AssetCollectorProfilerModContents) {
     // We've already checked that this path exists in AddFromFolders, so unwrap directly
@@ -265,9 +733,31 @@ pub fn add_from_folders_inner(parent: TocDirectoryRef, os_path: &PathBuf, profil
                     match PathBuf::from(&name).extension() {
                         Some(ext) => {
                             let ext_str = ext.to_str().unwrap();
-                            match SUITABLE_FILE_EXTENSIONS.iter().find(|exist| **exist == ext_str) {
+                            if ext_str == "utoc" { // mod shipped as an IO Store container
+                                add_from_container(&fs_obj.path(), profiler);
+                                continue
+                            } else if ext_str == "ucas" { // partitions are read through their .utoc
+                                continue
+                            } else if ext_str == "pak" { // mod shipped as a pak
+                                add_from_pak(&fs_obj.path(), profiler);
+                                continue
+                            } else if ext_str.eq_ignore_ascii_case(DELTA_PATCH_EXTENSION) { // Foo.uasset.patch takes the place of Foo.uasset (see delta_patch)
+                                let target_name = &name[..name.len() - ext_str.len() - 1];
+                                match PathBuf::from(target_name).extension().and_then(|e| e.to_str()) {
+                                    Some(target_ext) if game_profile::is_container_extension(target_ext) => {
+                                        let new_file = TocFile::new_rc(target_name, file_size, TocFileSource::DeltaPatch(fs_obj.path().to_str().unwrap().to_owned()));
+                                        match TocDirectory::add_or_replace_file(Rc::clone(&parent), new_file) {
+                                            TocFileAddType::Addition => profiler.add_added_file(file_size),
+                                            TocFileAddType::Replacement => profiler.add_replaced_file(file_size)
+                                        }
+                                    },
+                                    _ => profiler.add_skipped_file(fs_obj.path().to_str().unwrap(), String::from("Patch isn't for a supported file type"), file_size)
+                                }
+                                continue
+                            }
+                            match game_profile::get_game_profile().extensions.iter().find(|exist| **exist == ext_str) {
                                 // it's a matter of either replacing an existing file or adding a new file
-                                // ,,,at least until we start thinking about merging P3RE persona tables (lol)
+                                // replaced DataTables get their rows merged back together before the TOC is built (see datatable_merge)
                                 Some(io_ext) => {
                                     if *io_ext == "uasset" { // export bundles - requires checking file header to ensure that it doesn't have the cooked asset signature
                                         let current_file = File::open(fs_obj.path().to_str().unwrap()).unwrap();
@@ -277,15 +767,17 @@ pub fn add_from_folders_inner(parent: TocDirectoryRef, os_path: &PathBuf, profil
                                             continue
                                         }
                                     }
-                                    let new_file = TocFile::new_rc(&name, file_size, fs_obj.path().to_str().unwrap());
+                                    let new_file = TocFile::new_rc(&name, file_size, TocFileSource::OsPath(fs_obj.path().to_str().unwrap().to_owned()));
                                     match TocDirectory::add_or_replace_file(Rc::clone(&parent), Rc::clone(&new_file)) {
                                         TocFileAddType::Addition => profiler.add_added_file(file_size),
                                         TocFileAddType::Replacement => profiler.add_replaced_file(file_size)
                                     }
                                 },
-                                // TODO: Unsupported file extensions go into PAK
-                                // Io Store forces you to also make a pak file (hopefully DC's patches can fix this)
-                                None => profiler.add_skipped_file(fs_obj.path().to_str().unwrap(), format!("Unsupported file type"), file_size)
+                                // Io Store forces you to also make a pak file, so anything that isn't a package goes in there instead
+                                None => {
+                                    let new_file = TocFile::new_rc(&name, file_size, TocFileSource::OsPath(fs_obj.path().to_str().unwrap().to_owned()));
+                                    add_pak_file(&parent, new_file, profiler);
+                                }
                             }
                         }
                         None => profiler.add_skipped_file(fs_obj.path().to_str().unwrap(), format!("No file extension"), file_size)
@@ -297,12 +789,14 @@ pub fn add_from_folders_inner(parent: TocDirectoryRef, os_path: &PathBuf, profil
     }
 }
 
-// Safety: this checks if ASSET_COLLECTOR_PROFILER has been assigned a value first, which only happens after loading a mod
+/// # Safety
+/// Must not be called while mods are being loaded on another thread. This checks if ASSET_COLLECTOR_PROFILER has been assigned a
+/// value first, which only happens after loading a mod
 pub unsafe fn print_asset_collector_results() {
     if ASSET_COLLECTOR_PROFILER == None {
         return;
     }
-    ASSET_COLLECTOR_PROFILER.as_ref().unwrap().print();
+    (*addr_of!(ASSET_COLLECTOR_PROFILER)).as_ref().unwrap().print();
 }
 
 #[derive(Debug, PartialEq)]
@@ -406,7 +900,7 @@ impl AssetCollectorProfilerModContents {
 pub struct AssetCollectorProfilerMod {
     uid: String, // p3rpc.modname
     os_path: String,
-    data: AssetCollectorProfilerModContents
+    pub data: AssetCollectorProfilerModContents
 }
 
 impl AssetCollectorProfilerMod {
@@ -442,6 +936,16 @@ impl AssetCollectorProfiler {
             mods_loaded: vec![],
         }
     }
+    // Virtual files can be added after a mod's folders were collected, so reuse that mod's entry if it exists
+    pub fn get_or_add_mod(&mut self, mod_id: &str) -> &mut AssetCollectorProfilerMod {
+        match self.mods_loaded.iter().position(|m| m.uid == mod_id) {
+            Some(i) => &mut self.mods_loaded[i],
+            None => {
+                self.mods_loaded.push(AssetCollectorProfilerMod::new(mod_id, ""));
+                self.mods_loaded.last_mut().unwrap()
+            }
+        }
+    }
     pub fn print_centered(text: &str) {
         let left_spaces = (AssetCollectorProfiler::get_terminal_length() - text.len()) / 2;
         println!("{}{}", " ".repeat(left_spaces), text);
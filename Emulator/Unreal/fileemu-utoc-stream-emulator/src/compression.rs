@@ -0,0 +1,46 @@
+use std::{
+    error::Error,
+    io::Read
+};
+
+// Compression methods that can appear in an IO Store TOC's compression method names. The name is matched case insensitively,
+// since the engine stores whatever FName was used when cooking (e.g "Zlib", "LZ4", "Oodle")
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressionMethod {
+    None,
+    Zlib,
+    Lz4
+}
+
+impl TryFrom<&str> for CompressionMethod {
+    type Error = String;
+    fn try_from(value: &str) -> Result<CompressionMethod, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "none" | "" => Ok(CompressionMethod::None),
+            "zlib" => Ok(CompressionMethod::Zlib),
+            "lz4" => Ok(CompressionMethod::Lz4),
+            "oodle" => Err(String::from("Compression method \"Oodle\" isn't supported (Oodle is proprietary, so it can't be decompressed here)")),
+            _ => Err(format!("Compression method \"{}\" isn't supported", value))
+        }
+    }
+}
+
+impl CompressionMethod {
+    // Decompress a single compression block. compressed can be larger than the compressed data (e.g padding added for encryption),
+    // but the output is always exactly uncompressed_size bytes long
+    pub fn decompress_block(&self, compressed: &[u8], uncompressed_size: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let decompressed = match self {
+            Self::None => compressed.get(..uncompressed_size).ok_or("Uncompressed block is smaller than it's stated size")?.to_vec(),
+            Self::Zlib => {
+                let mut decompressed = Vec::with_capacity(uncompressed_size);
+                flate2::read::ZlibDecoder::new(compressed).take(uncompressed_size as u64).read_to_end(&mut decompressed)?;
+                decompressed
+            },
+            Self::Lz4 => lz4_flex::block::decompress(compressed, uncompressed_size).map_err(|e| format!("LZ4: {}", e))?
+        };
+        if decompressed.len() != uncompressed_size {
+            return Err(format!("{:?} block decompressed to 0x{:x} bytes, expected 0x{:x}", self, decompressed.len(), uncompressed_size).into());
+        }
+        Ok(decompressed)
+    }
+}
@@ -0,0 +1,607 @@
+use crate::{
+    asset_collector::{read_file, TocDirectoryRef, TocFileRef, TocFileSource},
+    game_packages,
+    game_profile::{self, Endianness},
+    io_package::{IoPackage, IoStoreObjectIndex},
+    script_objects,
+    string::Hasher16,
+    unversioned::{PropertyValue, UnversionedProperties, UnversionedReader, UnversionedWriter, NATIVE_STRUCT_SIZES, PKG_UNVERSIONED_PROPERTIES},
+    usmap
+};
+use byteorder::ByteOrder;
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    error::Error,
+    io::Cursor,
+    marker::PhantomData,
+    ptr::addr_of,
+    rc::Rc
+};
+
+// When more than one mod replaces the same DataTable, the rows that each mod changed from the base game's table (or from the lowest
+// priority mod's table if the game's containers weren't loaded) are merged into one package, which is served from memory instead of
+// the highest priority mod's file. Rows changed by more than one mod are taken from the higher priority mod and listed as conflicts.
+// Tables with unversioned properties need the game's mappings (see usmap). Tables that can't be merged keep the old behaviour
+pub static mut DATATABLE_MERGE_REPORT: Option<DataTableMergeReport> = None;
+
+pub const DATATABLE_CLASS: &str = "/Script/Engine.DataTable";
+const DATATABLE_STRUCT: &str = "DataTable";
+
+#[derive(Debug, PartialEq)]
+pub struct MergedTable {
+    pub table_path: String,
+    pub mod_count: usize, // mods that replaced the table
+    pub changed_rows: usize,
+    pub base_from_game: bool
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnmergedTable {
+    pub table_path: String,
+    pub reason: String
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RowConflict {
+    pub table_path: String,
+    pub row: String,
+    pub overridden_source: String, // lower priority mod, whose change to the row was dropped
+    pub source: String
+}
+
+#[derive(Debug, Default)]
+pub struct DataTableMergeReport {
+    pub merged_tables: Vec<MergedTable>,
+    pub unmerged_tables: Vec<UnmergedTable>,
+    pub conflicts: Vec<RowConflict>
+}
+
+impl DataTableMergeReport {
+    pub fn has_problems(&self) -> bool {
+        !self.unmerged_tables.is_empty() || !self.conflicts.is_empty()
+    }
+
+    pub fn print(&self) {
+        println!("MERGED DATATABLES: {} TABLES", self.merged_tables.len());
+        for i in &self.merged_tables {
+            println!("\"{}\": {} rows changed by {} mods", i.table_path, i.changed_rows, i.mod_count);
+            if !i.base_from_game {
+                println!("    Base game table wasn't found, so the lowest priority mod's table was used as the base");
+            }
+        }
+        if !self.unmerged_tables.is_empty() {
+            println!("{}", "-".repeat(80));
+            println!("UNMERGED DATATABLES: {} TABLES", self.unmerged_tables.len());
+            for i in &self.unmerged_tables {
+                println!("\"{}\": {}. Only the highest priority mod's table will be used", i.table_path, i.reason);
+            }
+        }
+        if !self.conflicts.is_empty() {
+            println!("{}", "-".repeat(80));
+            println!("ROW CONFLICTS: {} ROWS", self.conflicts.len());
+            for i in &self.conflicts {
+                println!("Row \"{}\" of \"{}\" is changed by \"{}\" and \"{}\", using the second", i.row, i.table_path, i.overridden_source, i.source);
+            }
+        }
+    }
+}
+
+// Go through every package under Content that replaced another mod's package, merging the ones that are DataTables
+pub fn merge_data_tables(root: TocDirectoryRef) -> DataTableMergeReport {
+    let mut report = DataTableMergeReport::default();
+    let mut curr_dir = root.borrow().first_child.clone();
+    while let Some(dir) = curr_dir {
+        if dir.borrow().name.eq_ignore_ascii_case("Content") {
+            match game_profile::get_game_profile().endianness {
+                Endianness::Little => merge_data_tables_inner::<byteorder::LittleEndian>(Rc::clone(&dir), "/Game", &mut report),
+                Endianness::Big => merge_data_tables_inner::<byteorder::BigEndian>(Rc::clone(&dir), "/Game", &mut report)
+            }
+        }
+        curr_dir = dir.borrow().next_sibling.clone();
+    }
+    report
+}
+
+fn merge_data_tables_inner<E: ByteOrder>(dir: TocDirectoryRef, game_path: &str, report: &mut DataTableMergeReport) {
+    let mut curr_file = dir.borrow().first_file.clone();
+    while let Some(file) = curr_file {
+        let name = file.borrow().name.to_owned();
+        if let Some((stem, ext)) = name.rsplit_once('.') {
+            if ext.eq_ignore_ascii_case("uasset") && file.borrow().replaced.is_some() {
+                merge_file::<E>(&file, &format!("{}/{}", game_path, stem), report);
+            }
+        }
+        curr_file = file.borrow().next.clone();
+    }
+    let mut curr_dir = dir.borrow().first_child.clone();
+    while let Some(child) = curr_dir {
+        let child_path = format!("{}/{}", game_path, child.borrow().name);
+        merge_data_tables_inner::<E>(Rc::clone(&child), &child_path, report);
+        curr_dir = child.borrow().next_sibling.clone();
+    }
+}
+
+fn merge_file<E: ByteOrder>(file: &TocFileRef, table_path: &str, report: &mut DataTableMergeReport) {
+    // only check that the highest priority version is a DataTable, since most replaced packages won't be
+    match read_file(file).and_then(|b| Ok(get_table_export(&read_package::<E>(&b)?).is_some())) {
+        Ok(true) => (),
+        _ => return
+    }
+    // every mod's version of the package, lowest priority first
+    let mut versions = vec![];
+    let mut curr_file = Some(Rc::clone(file));
+    while let Some(f) = curr_file {
+        curr_file = f.borrow().replaced.clone();
+        versions.push(f);
+    }
+    versions.reverse();
+    let mut contents = Vec::with_capacity(versions.len());
+    for version in &versions {
+        match read_file(version) {
+            Ok(b) => contents.push((version.borrow().source.get_display_path().to_owned(), b)),
+            Err(e) => {
+                report.unmerged_tables.push(UnmergedTable { table_path: table_path.to_owned(), reason: e.to_string() });
+                return;
+            }
+        }
+    }
+    match merge_versions::<E>(table_path, &contents, report) {
+        Ok(merged) => {
+            let mut file = file.borrow_mut();
+            file.file_size = merged.len() as u64;
+            file.source = TocFileSource::Memory(Rc::new(merged));
+        },
+        Err(e) => report.unmerged_tables.push(UnmergedTable { table_path: table_path.to_owned(), reason: e.to_string() })
+    }
+}
+
+fn read_package<E: ByteOrder>(package: &[u8]) -> Result<IoPackage, Box<dyn Error>> {
+    IoPackage::from_buffer::<Cursor<&[u8]>, E>(&mut Cursor::new(package), package.len() as u64)
+}
+
+// Export classes are script imports, which are compared by hash so that this works without the game's script objects
+fn get_table_export(package: &IoPackage) -> Option<usize> {
+    let class_hash = IoStoreObjectIndex::get_script_import_hash(DATATABLE_CLASS);
+    package.exports.iter().position(|e| e.class_name == IoStoreObjectIndex::ImportHash(class_hash))
+}
+
+// Row names are FNames, which are compared as (name, number) so that rows can be matched across packages
+type RowName = (String, u32);
+
+fn get_row_display_name(name: &RowName) -> String {
+    match name.1 {
+        0 => name.0.to_owned(),
+        n => format!("{}_{}", name.0, n - 1)
+    }
+}
+
+fn merge_versions<E: ByteOrder>(table_path: &str, versions: &[(String, Vec<u8>)], report: &mut DataTableMergeReport) -> Result<Vec<u8>, Box<dyn Error>> {
+    let package_id = Hasher16::get_cityhash64(table_path);
+    let (mut target, mods, base_from_game) = match game_packages::read_game_package(package_id)? {
+        Some(game_package) => (read_package::<E>(&game_package)?, versions, true),
+        None => (read_package::<E>(&versions[0].1)?, &versions[1..], false)
+    };
+    let table_index = get_table_export(&target).ok_or("Base game package isn't a DataTable")?;
+    let mut target_map = MergeTarget::new(&target);
+    let base_table = DataTableRows::from_buffer::<E>(&target, None, &mut target.export_data[table_index].clone())?;
+    let base_rows: HashMap<&RowName, &Vec<u8>> = base_table.rows.iter().map(|(k, v)| (k, v)).collect();
+    let mut rows = base_table.rows.clone();
+    let mut row_indices: HashMap<RowName, usize> = rows.iter().enumerate().map(|(i, (k, _))| (k.to_owned(), i)).collect();
+    let mut changed_by: HashMap<RowName, usize> = HashMap::new(); // row -> index into mods
+    for (mod_index, (source, package)) in mods.iter().enumerate() {
+        let package = read_package::<E>(package)?;
+        let export_index = get_table_export(&package).ok_or_else(|| format!("\"{}\" isn't a DataTable", source))?;
+        let import_count = target_map.imports.len();
+        let table = DataTableRows::from_buffer::<E>(&package, Some(&mut target_map), &mut package.export_data[export_index].clone())
+            .map_err(|e| format!("Couldn't read rows from \"{}\": {}", source, e))?;
+        for (row_name, row) in table.rows {
+            if base_rows.get(&row_name).is_some_and(|r| **r == row) {
+                continue; // not changed by this mod
+            }
+            match row_indices.get(&row_name) {
+                Some(i) => {
+                    if let Some(prev_mod) = changed_by.get(&row_name) {
+                        if rows[*i].1 != row {
+                            report.conflicts.push(RowConflict {
+                                table_path: table_path.to_owned(),
+                                row: get_row_display_name(&row_name),
+                                overridden_source: mods[*prev_mod].0.to_owned(),
+                                source: source.to_owned()
+                            });
+                        }
+                    }
+                    rows[*i].1 = row;
+                },
+                None => {
+                    row_indices.insert(row_name.clone(), rows.len());
+                    rows.push((row_name.clone(), row));
+                }
+            }
+            changed_by.insert(row_name, mod_index);
+        }
+        // rows that reference objects from other packages need those packages to be loaded first
+        if target_map.imports.len() > import_count {
+            for graph_package in package.graph_packages {
+                if !target.graph_packages.iter().any(|p| p.imported_package_id == graph_package.imported_package_id) {
+                    target.graph_packages.push(graph_package);
+                }
+            }
+        }
+    }
+    let mut table_data = target.export_data[table_index][..base_table.rows_offset].to_vec();
+    let mut value = [0; 4];
+    E::write_i32(&mut value, rows.len() as i32);
+    table_data.extend_from_slice(&value);
+    for (row_name, row) in &rows {
+        E::write_u32(&mut value, target_map.get_name_index(&row_name.0));
+        table_data.extend_from_slice(&value);
+        E::write_u32(&mut value, row_name.1);
+        table_data.extend_from_slice(&value);
+        table_data.extend_from_slice(row);
+    }
+    table_data.extend_from_slice(&base_table.trailing);
+    target.export_data[table_index] = table_data;
+    target.names = target_map.names;
+    target.imports = target_map.imports;
+    report.merged_tables.push(MergedTable { table_path: table_path.to_owned(), mod_count: versions.len(), changed_rows: changed_by.len(), base_from_game });
+    target.to_bytes::<E>()
+}
+
+// Names and imports of the merged package. Rows copied from a mod's package get their names and imports added to these
+struct MergeTarget {
+    names: Vec<String>,
+    name_indices: HashMap<String, u32>,
+    imports: Vec<u64>
+}
+
+impl MergeTarget {
+    fn new(package: &IoPackage) -> Self {
+        let name_indices = package.names.iter().enumerate().map(|(i, n)| (n.to_owned(), i as u32)).collect();
+        Self { names: package.names.clone(), name_indices, imports: package.imports.clone() }
+    }
+
+    fn get_name_index(&mut self, name: &str) -> u32 {
+        match self.name_indices.get(name) {
+            Some(i) => *i,
+            None => {
+                self.names.push(name.to_owned());
+                self.name_indices.insert(name.to_owned(), self.names.len() as u32 - 1);
+                self.names.len() as u32 - 1
+            }
+        }
+    }
+
+    fn get_import_index(&mut self, import: u64) -> usize {
+        match self.imports.iter().position(|i| *i == import) {
+            Some(i) => i,
+            None => {
+                self.imports.push(import);
+                self.imports.len() - 1
+            }
+        }
+    }
+
+    // Move an FPackageIndex from package into the target's imports. Exports can't be moved, since they belong to the package itself
+    fn remap_object(&mut self, package: &IoPackage, index: i32) -> Result<i32, Box<dyn Error>> {
+        match index.cmp(&0) {
+            Ordering::Greater => Err("Row references an export from it's own package".into()),
+            Ordering::Equal => Ok(0),
+            Ordering::Less => {
+                let import = *package.imports.get((-index - 1) as usize).ok_or_else(|| format!("Import index {} is out of range", -index - 1))?;
+                Ok(-(self.get_import_index(import) as i32) - 1)
+            }
+        }
+    }
+}
+
+// The serialized DataTable export: tagged properties for the UObject, the object's GUID, then the number of rows followed by each row's
+// name and tagged properties. Any data after the rows is kept as is
+struct DataTableRows {
+    rows_offset: usize, // offset of the row count
+    rows: Vec<(RowName, Vec<u8>)>,
+    trailing: Vec<u8>
+}
+
+impl DataTableRows {
+    // If target is set, the names and imports in each row are changed to point into the target's names and imports
+    fn from_buffer<E: ByteOrder>(package: &IoPackage, target: Option<&mut MergeTarget>, data: &mut [u8]) -> Result<Self, Box<dyn Error>> {
+        match package.summary.package_flags & PKG_UNVERSIONED_PROPERTIES != 0 {
+            true => Self::from_buffer_unversioned::<E>(package, target, data),
+            false => Self::from_buffer_tagged::<E>(package, target, data)
+        }
+    }
+
+    fn from_buffer_tagged<E: ByteOrder>(package: &IoPackage, target: Option<&mut MergeTarget>, data: &mut [u8]) -> Result<Self, Box<dyn Error>> {
+        let mut walker = PropertyWalker::<E> { package, target: None, byte_order: PhantomData };
+        let mut pos = 0;
+        walker.properties(data, &mut pos)?;
+        let (rows_offset, row_count) = read_row_count::<E>(data, &mut pos)?;
+        walker.target = target;
+        let mut rows = vec![];
+        for _ in 0..row_count {
+            let row_name = read_row_name::<E>(package, data, &mut pos)?;
+            let row_start = pos;
+            walker.properties(data, &mut pos)?;
+            rows.push((row_name, data[row_start..pos].to_vec()));
+        }
+        Ok(Self { rows_offset, rows, trailing: data[pos..].to_vec() })
+    }
+
+    // Rows from another package are decoded and written again with the target's names, since their headers don't change
+    fn from_buffer_unversioned<E: ByteOrder>(package: &IoPackage, mut target: Option<&mut MergeTarget>, data: &mut [u8]) -> Result<Self, Box<dyn Error>> {
+        let mappings = usmap::get_mappings().ok_or("Table uses unversioned properties, which can't be read without the game's mappings (.usmap)")?;
+        let reader = UnversionedReader { mappings, names: &package.names };
+        let mut cursor = Cursor::new(&*data);
+        let table_properties = reader.read_properties::<Cursor<&[u8]>, E>(DATATABLE_STRUCT, &mut cursor)?;
+        let row_struct = get_row_struct(package, &table_properties)?;
+        let mut pos = cursor.position() as usize;
+        let (rows_offset, row_count) = read_row_count::<E>(data, &mut pos)?;
+        let mut rows = vec![];
+        for _ in 0..row_count {
+            let row_name = read_row_name::<E>(package, data, &mut pos)?;
+            let mut cursor = Cursor::new(data.get(pos..).unwrap_or_default());
+            let mut row = reader.read_properties::<Cursor<&[u8]>, E>(&row_struct, &mut cursor)?;
+            let row_end = pos + cursor.position() as usize;
+            let row_data = match target.as_mut() {
+                Some(target) => {
+                    row.visit_objects(&mut |i| {
+                        *i = target.remap_object(package, *i)?;
+                        Ok(())
+                    })?;
+                    let mut writer = Cursor::new(vec![]);
+                    UnversionedWriter { get_name_index: &mut |n| target.get_name_index(n) }
+                        .write_properties::<Cursor<Vec<u8>>, E>(&row, &mut writer)?;
+                    writer.into_inner()
+                },
+                None => data[pos..row_end].to_vec()
+            };
+            rows.push((row_name, row_data));
+            pos = row_end;
+        }
+        Ok(Self { rows_offset, rows, trailing: data[pos..].to_vec() })
+    }
+}
+
+// The table's object GUID (if it has one), then the number of rows
+fn read_row_count<E: ByteOrder>(data: &[u8], pos: &mut usize) -> Result<(usize, i32), Box<dyn Error>> {
+    if read_i32::<E>(data, pos)? != 0 {
+        skip(data, pos, 0x10)?;
+    }
+    let rows_offset = *pos;
+    match read_i32::<E>(data, pos)? {
+        n if n < 0 => Err(format!("Invalid row count {}", n).into()),
+        n => Ok((rows_offset, n))
+    }
+}
+
+fn read_row_name<E: ByteOrder>(package: &IoPackage, data: &[u8], pos: &mut usize) -> Result<RowName, Box<dyn Error>> {
+    let name = package.names.get(read_u32::<E>(data, pos)? as usize).ok_or("Row name is out of range")?.to_owned();
+    Ok((name, read_u32::<E>(data, pos)?))
+}
+
+// RowStruct is an import of a script struct, which can only be named through the game's script objects
+fn get_row_struct(package: &IoPackage, table_properties: &UnversionedProperties) -> Result<String, Box<dyn Error>> {
+    let import_index = match table_properties.get("RowStruct").and_then(|p| p.value.as_ref()) {
+        Some(PropertyValue::Object(i)) if *i < 0 => (-*i - 1) as usize,
+        _ => return Err("Table's RowStruct isn't an import".into())
+    };
+    let import = *package.imports.get(import_index).ok_or("RowStruct import is out of range")?;
+    let path = script_objects::get_script_object_path(import)
+        .ok_or("Table's RowStruct isn't a script object that's been loaded from the game's global.utoc")?;
+    Ok(path.rsplit(['.', ':']).next().unwrap_or(path).to_owned())
+}
+
+// Reads through tagged properties, moving each FName and FPackageIndex into the target package if there is one. Data is always
+// sliced to the end of the property that's being read, so running past it is an error instead of reading the next property
+struct PropertyWalker<'a, E: ByteOrder> {
+    package: &'a IoPackage,
+    target: Option<&'a mut MergeTarget>,
+    byte_order: PhantomData<E>
+}
+
+impl<E: ByteOrder> PropertyWalker<'_, E> {
+    fn name(&mut self, data: &mut [u8], pos: &mut usize) -> Result<String, Box<dyn Error>> {
+        let index = read_u32::<E>(data, pos)?;
+        skip(data, pos, 4)?; // number
+        let name = self.package.names.get(index as usize).ok_or_else(|| format!("Name index {} is out of range", index))?.to_owned();
+        if let Some(target) = self.target.as_mut() {
+            E::write_u32(&mut data[*pos - 8..], target.get_name_index(&name));
+        }
+        Ok(name)
+    }
+
+    fn object(&mut self, data: &mut [u8], pos: &mut usize) -> Result<(), Box<dyn Error>> {
+        let index = read_i32::<E>(data, pos)?;
+        if let Some(target) = self.target.as_mut() {
+            E::write_i32(&mut data[*pos - 4..], target.remap_object(self.package, index)?);
+        }
+        Ok(())
+    }
+
+    fn properties(&mut self, data: &mut [u8], pos: &mut usize) -> Result<(), Box<dyn Error>> {
+        loop {
+            if self.name(data, pos)? == "None" {
+                return Ok(());
+            }
+            let property_type = self.name(data, pos)?;
+            let size = read_i32::<E>(data, pos)?;
+            skip(data, pos, 4)?; // array index
+            let mut type_names = vec![];
+            match property_type.as_str() {
+                "StructProperty" => {
+                    type_names.push(self.name(data, pos)?);
+                    skip(data, pos, 0x10)?; // struct GUID
+                },
+                "BoolProperty" => skip(data, pos, 1)?, // value is stored in the tag
+                "ByteProperty" | "EnumProperty" | "ArrayProperty" | "SetProperty" => type_names.push(self.name(data, pos)?),
+                "MapProperty" => {
+                    type_names.push(self.name(data, pos)?);
+                    type_names.push(self.name(data, pos)?);
+                },
+                _ => ()
+            }
+            if read_u8(data, pos)? != 0 { // has property GUID
+                skip(data, pos, 0x10)?;
+            }
+            let end = pos.checked_add(usize::try_from(size)?).filter(|e| *e <= data.len())
+                .ok_or_else(|| format!("{} is larger than the data that contains it", property_type))?;
+            let mut value_pos = *pos;
+            let value = &mut data[..end];
+            match property_type.as_str() {
+                "ByteProperty" => if type_names[0] != "None" { // enum value
+                    self.name(value, &mut value_pos)?;
+                },
+                "ArrayProperty" => self.array(&type_names[0], value, &mut value_pos)?,
+                "SetProperty" => self.set(&type_names[0], None, value, &mut value_pos)?,
+                "MapProperty" => self.set(&type_names[0], Some(&type_names[1]), value, &mut value_pos)?,
+                _ => self.element(&property_type, type_names.first().map(|s| s.as_str()), value, &mut value_pos)?
+            }
+            *pos = end;
+        }
+    }
+
+    // A single value of the given type, which is either the whole property or an item in an array, set or map
+    fn element(&mut self, property_type: &str, struct_name: Option<&str>, data: &mut [u8], pos: &mut usize) -> Result<(), Box<dyn Error>> {
+        match property_type {
+            "NameProperty" | "EnumProperty" => { self.name(data, pos)?; },
+            "ObjectProperty" | "ClassProperty" | "WeakObjectProperty" | "InterfaceProperty" => self.object(data, pos)?,
+            "SoftObjectProperty" | "SoftClassProperty" => {
+                self.name(data, pos)?;
+                skip_string::<E>(data, pos)?;
+            },
+            "StructProperty" => self.struct_value(struct_name.ok_or("Struct type is unknown")?, data, pos)?,
+            "BoolProperty" | "Int8Property" | "ByteProperty" => skip(data, pos, 1)?,
+            "Int16Property" | "UInt16Property" => skip(data, pos, 2)?,
+            "IntProperty" | "UInt32Property" | "FloatProperty" => skip(data, pos, 4)?,
+            "Int64Property" | "UInt64Property" | "DoubleProperty" => skip(data, pos, 8)?,
+            "StrProperty" => skip_string::<E>(data, pos)?,
+            "TextProperty" => self.text(data, pos)?,
+            t => return Err(format!("Unsupported property type {}", t).into())
+        }
+        Ok(())
+    }
+
+    fn struct_value(&mut self, struct_name: &str, data: &mut [u8], pos: &mut usize) -> Result<(), Box<dyn Error>> {
+        if let Some((_, size)) = NATIVE_STRUCT_SIZES.iter().find(|(n, _)| *n == struct_name) {
+            return skip(data, pos, *size);
+        }
+        match struct_name {
+            "SoftObjectPath" | "SoftClassPath" => {
+                self.name(data, pos)?;
+                skip_string::<E>(data, pos)
+            },
+            "GameplayTagContainer" => {
+                for _ in 0..read_i32::<E>(data, pos)? {
+                    self.name(data, pos)?;
+                }
+                Ok(())
+            },
+            _ => self.properties(data, pos)
+        }
+    }
+
+    fn array(&mut self, inner_type: &str, data: &mut [u8], pos: &mut usize) -> Result<(), Box<dyn Error>> {
+        let count = read_i32::<E>(data, pos)?;
+        match inner_type {
+            "StructProperty" => { // arrays of structs have a tag for the inner property
+                self.name(data, pos)?;
+                self.name(data, pos)?;
+                skip(data, pos, 8)?; // size, array index
+                let struct_name = self.name(data, pos)?;
+                skip(data, pos, 0x10)?;
+                if read_u8(data, pos)? != 0 {
+                    skip(data, pos, 0x10)?;
+                }
+                for _ in 0..count {
+                    self.struct_value(&struct_name, data, pos)?;
+                }
+            },
+            // enums in byte arrays are stored by name, but the array doesn't say which enum it's for
+            "ByteProperty" if count > 0 && data.len() - *pos == count as usize * 8 => {
+                for _ in 0..count {
+                    self.name(data, pos)?;
+                }
+            },
+            _ => for _ in 0..count {
+                self.element(inner_type, None, data, pos)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Sets and maps start with the keys to remove from the default value, then the items
+    fn set(&mut self, key_type: &str, value_type: Option<&str>, data: &mut [u8], pos: &mut usize) -> Result<(), Box<dyn Error>> {
+        for _ in 0..read_i32::<E>(data, pos)? {
+            self.element(key_type, None, data, pos)?;
+        }
+        for _ in 0..read_i32::<E>(data, pos)? {
+            self.element(key_type, None, data, pos)?;
+            if let Some(value_type) = value_type {
+                self.element(value_type, None, data, pos)?;
+            }
+        }
+        Ok(())
+    }
+
+    // FText is stored as it's history, only the history types that are cooked into DataTables are handled
+    fn text(&mut self, data: &mut [u8], pos: &mut usize) -> Result<(), Box<dyn Error>> {
+        skip(data, pos, 4)?; // flags
+        match read_u8(data, pos)? as i8 {
+            -1 => if read_i32::<E>(data, pos)? != 0 { // none, with an optional culture invariant string
+                skip_string::<E>(data, pos)?;
+            },
+            0 => for _ in 0..3 { // base: namespace, key, source string
+                skip_string::<E>(data, pos)?;
+            },
+            11 => { // string table entry
+                self.name(data, pos)?;
+                skip_string::<E>(data, pos)?;
+            },
+            t => return Err(format!("Unsupported text history type {}", t).into())
+        }
+        Ok(())
+    }
+}
+
+fn skip(data: &[u8], pos: &mut usize, count: usize) -> Result<(), Box<dyn Error>> {
+    match pos.checked_add(count).filter(|p| *p <= data.len()) {
+        Some(p) => {
+            *pos = p;
+            Ok(())
+        },
+        None => Err(format!("Tried to read past the end of the property (0x{:x} + 0x{:x} > 0x{:x})", pos, count, data.len()).into())
+    }
+}
+
+fn read_u8(data: &[u8], pos: &mut usize) -> Result<u8, Box<dyn Error>> {
+    skip(data, pos, 1)?;
+    Ok(data[*pos - 1])
+}
+
+fn read_u32<E: ByteOrder>(data: &[u8], pos: &mut usize) -> Result<u32, Box<dyn Error>> {
+    skip(data, pos, 4)?;
+    Ok(E::read_u32(&data[*pos - 4..]))
+}
+
+fn read_i32<E: ByteOrder>(data: &[u8], pos: &mut usize) -> Result<i32, Box<dyn Error>> {
+    Ok(read_u32::<E>(data, pos)? as i32)
+}
+
+// FStrings are stored as UTF-16 when their length is negative
+fn skip_string<E: ByteOrder>(data: &[u8], pos: &mut usize) -> Result<(), Box<dyn Error>> {
+    let length = read_i32::<E>(data, pos)?;
+    match length < 0 {
+        true => skip(data, pos, length.unsigned_abs() as usize * 2),
+        false => skip(data, pos, length as usize)
+    }
+}
+
+/// # Safety
+/// Must not be called while a TOC is being built on another thread. This checks if DATATABLE_MERGE_REPORT has been assigned a
+/// value first, which only happens after building a TOC
+pub unsafe fn print_datatable_merge_report() {
+    if let Some(report) = (*addr_of!(DATATABLE_MERGE_REPORT)).as_ref() {
+        report.print();
+    }
+}
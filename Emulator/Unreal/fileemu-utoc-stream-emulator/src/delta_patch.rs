@@ -0,0 +1,377 @@
+use crate::{
+    asset_collector::{read_file, TocDirectory, TocDirectoryRef, TocFileRef, TocFileSource},
+    game_packages,
+    io_toc::{self, IoChunkType4},
+    string::Hasher16
+};
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{Cursor, Read, Seek, Write},
+    path::Path,
+    ptr::addr_of,
+    rc::Rc
+};
+
+// Mods can ship a delta patch (Foo.uasset.patch) instead of the whole file, which is applied to the file that it replaces when the
+// TOC is built. That's the same file from a lower priority mod if there is one, otherwise it's the chunk from the game's containers.
+// Each patch stores the checksum of the file that it was made from, so a patch is refused if the file it applies to has changed (such
+// as after a game update), and the file that it replaced is used instead.
+// Patches are always little endian:
+//  Magic (u32), version (u32)
+//  Source size (u64), source checksum (u64), target size (u64), target checksum (u64). Checksums are CityHash64 of the whole file
+//  Operation count (u32), then each operation:
+//      0: copy (source offset (u64), length (u32))
+//      1: insert (length (u32), then the bytes to insert)
+pub static mut DELTA_PATCH_REPORT: Option<DeltaPatchReport> = None;
+
+pub const DELTA_PATCH_EXTENSION: &str = "patch";
+pub const DELTA_PATCH_MAGIC: u32 = 0x50444546; // FEDP
+pub const DELTA_PATCH_VERSION: u32 = 1;
+
+// Matches shorter than this are stored as inserts, since a copy operation takes 0xd bytes
+const MATCH_BLOCK_SIZE: usize = 0x20;
+const MAX_MATCH_CANDIDATES: usize = 8;
+const ROLLING_HASH_BASE: u64 = 0x100000001b3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchOperation {
+    Copy { offset: u64, length: u32 },
+    Insert(Vec<u8>)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeltaPatch {
+    pub source_size: u64,
+    pub source_checksum: u64,
+    pub target_size: u64,
+    pub target_checksum: u64,
+    pub operations: Vec<PatchOperation>
+}
+
+impl DeltaPatch {
+    pub fn get_checksum(data: &[u8]) -> u64 {
+        cityhasher::hash(data)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let data = fs::read(path)?;
+        Self::from_buffer::<Cursor<Vec<u8>>, byteorder::LittleEndian>(&mut Cursor::new(data))
+    }
+
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let magic = reader.read_u32::<E>()?;
+        if magic != DELTA_PATCH_MAGIC {
+            return Err(format!("Not a delta patch (magic is 0x{:x}, expected 0x{:x})", magic, DELTA_PATCH_MAGIC).into());
+        }
+        let version = reader.read_u32::<E>()?;
+        if version != DELTA_PATCH_VERSION {
+            return Err(format!("Unsupported delta patch version {}", version).into());
+        }
+        let source_size = reader.read_u64::<E>()?;
+        let source_checksum = reader.read_u64::<E>()?;
+        let target_size = reader.read_u64::<E>()?;
+        let target_checksum = reader.read_u64::<E>()?;
+        let operation_count = reader.read_u32::<E>()?;
+        let mut operations = vec![];
+        for _ in 0..operation_count {
+            operations.push(match reader.read_u8()? {
+                0 => PatchOperation::Copy { offset: reader.read_u64::<E>()?, length: reader.read_u32::<E>()? },
+                1 => {
+                    let length = reader.read_u32::<E>()? as usize;
+                    let mut data = vec![0; io_toc::get_list_capacity(reader, length, 1)?];
+                    reader.read_exact(&mut data)?;
+                    PatchOperation::Insert(data)
+                },
+                t => return Err(format!("Unknown delta patch operation {}", t).into())
+            });
+        }
+        Ok(Self { source_size, source_checksum, target_size, target_checksum, operations })
+    }
+
+    pub fn to_buffer<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        writer.write_u32::<E>(DELTA_PATCH_MAGIC)?;
+        writer.write_u32::<E>(DELTA_PATCH_VERSION)?;
+        writer.write_u64::<E>(self.source_size)?;
+        writer.write_u64::<E>(self.source_checksum)?;
+        writer.write_u64::<E>(self.target_size)?;
+        writer.write_u64::<E>(self.target_checksum)?;
+        writer.write_u32::<E>(self.operations.len() as u32)?;
+        for operation in &self.operations {
+            match operation {
+                PatchOperation::Copy { offset, length } => {
+                    writer.write_u8(0)?;
+                    writer.write_u64::<E>(*offset)?;
+                    writer.write_u32::<E>(*length)?;
+                },
+                PatchOperation::Insert(data) => {
+                    writer.write_u8(1)?;
+                    writer.write_u32::<E>(data.len() as u32)?;
+                    writer.write_all(data)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut writer = Cursor::new(vec![]);
+        self.to_buffer::<Cursor<Vec<u8>>, byteorder::LittleEndian>(&mut writer)?;
+        Ok(writer.into_inner())
+    }
+
+    pub fn apply(&self, source: &[u8]) -> Result<Vec<u8>, String> {
+        let source_checksum = Self::get_checksum(source);
+        if source.len() as u64 != self.source_size || source_checksum != self.source_checksum {
+            return Err(format!("Patch was made from a different version of the file (expected 0x{:x} bytes with checksum {:016X}, got 0x{:x} bytes with checksum {:016X})",
+                self.source_size, self.source_checksum, source.len(), source_checksum));
+        }
+        // the target size is only trusted as far as the patch could actually make a file that large
+        let inserted_size: u64 = self.operations.iter().map(|o| match o { PatchOperation::Insert(data) => data.len() as u64, _ => 0 }).sum();
+        let mut target = Vec::with_capacity(self.target_size.min(source.len() as u64 + inserted_size) as usize);
+        for operation in &self.operations {
+            match operation {
+                PatchOperation::Copy { offset, length } => {
+                    let end = offset.checked_add(*length as u64).filter(|end| *end <= source.len() as u64)
+                        .ok_or("Patch copies from outside of the source file")?;
+                    target.extend_from_slice(&source[*offset as usize..end as usize]);
+                },
+                PatchOperation::Insert(data) => target.extend_from_slice(data)
+            }
+            if target.len() as u64 > self.target_size {
+                return Err(String::from("Patched file is larger than the patch's target size, so the patch is corrupted"));
+            }
+        }
+        if target.len() as u64 != self.target_size || Self::get_checksum(&target) != self.target_checksum {
+            return Err(String::from("Patched file doesn't match the patch's checksum, so the patch is corrupted"));
+        }
+        Ok(target)
+    }
+
+    // Make a patch that turns source into target. Blocks of the source file are indexed by their hash, then each position in the
+    // target is looked up with a rolling hash, taking the longest match and inserting everything in between
+    pub fn create(source: &[u8], target: &[u8]) -> Self {
+        let mut block_offsets: HashMap<u64, Vec<usize>> = HashMap::new();
+        for offset in (0..source.len().saturating_sub(MATCH_BLOCK_SIZE - 1)).step_by(MATCH_BLOCK_SIZE) {
+            let offsets = block_offsets.entry(get_block_hash(&source[offset..offset + MATCH_BLOCK_SIZE])).or_default();
+            if offsets.len() < MAX_MATCH_CANDIDATES {
+                offsets.push(offset);
+            }
+        }
+        let outgoing_factor = ROLLING_HASH_BASE.wrapping_pow(MATCH_BLOCK_SIZE as u32 - 1);
+        let mut operations = vec![];
+        let mut inserted = vec![];
+        let mut pos = 0;
+        let mut hash = None;
+        while pos + MATCH_BLOCK_SIZE <= target.len() {
+            let curr_hash = hash.unwrap_or_else(|| get_block_hash(&target[pos..pos + MATCH_BLOCK_SIZE]));
+            let best_match = block_offsets.get(&curr_hash).and_then(|offsets| offsets.iter()
+                .map(|o| (*o, get_match_length(&source[*o..], &target[pos..])))
+                .filter(|(_, length)| *length >= MATCH_BLOCK_SIZE)
+                .max_by_key(|(_, length)| *length));
+            match best_match {
+                Some((offset, length)) => {
+                    // the match can also start before the block that was found
+                    let mut back = 0;
+                    while back < inserted.len() && back < offset && source[offset - back - 1] == target[pos - back - 1] {
+                        back += 1;
+                    }
+                    inserted.truncate(inserted.len() - back);
+                    if !inserted.is_empty() {
+                        operations.push(PatchOperation::Insert(std::mem::take(&mut inserted)));
+                    }
+                    operations.push(PatchOperation::Copy { offset: (offset - back) as u64, length: (length + back) as u32 });
+                    pos += length;
+                    hash = None;
+                },
+                None => {
+                    inserted.push(target[pos]);
+                    hash = target.get(pos + MATCH_BLOCK_SIZE).map(|incoming| curr_hash
+                        .wrapping_sub((target[pos] as u64).wrapping_mul(outgoing_factor))
+                        .wrapping_mul(ROLLING_HASH_BASE)
+                        .wrapping_add(*incoming as u64));
+                    pos += 1;
+                }
+            }
+        }
+        inserted.extend_from_slice(&target[pos..]);
+        if !inserted.is_empty() {
+            operations.push(PatchOperation::Insert(inserted));
+        }
+        Self {
+            source_size: source.len() as u64,
+            source_checksum: Self::get_checksum(source),
+            target_size: target.len() as u64,
+            target_checksum: Self::get_checksum(target),
+            operations
+        }
+    }
+}
+
+fn get_block_hash(block: &[u8]) -> u64 {
+    block.iter().fold(0, |hash, b| hash.wrapping_mul(ROLLING_HASH_BASE).wrapping_add(*b as u64))
+}
+
+// Copies are limited to u32::MAX bytes, the rest of a longer match is picked up by the next copy
+fn get_match_length(source: &[u8], target: &[u8]) -> usize {
+    source.iter().zip(target).take(u32::MAX as usize).take_while(|(a, b)| a == b).count()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AppliedPatch {
+    pub file_path: String,
+    pub patch_path: String,
+    pub base: String // where the file that was patched came from (another mod's file or the game's file)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RefusedPatch {
+    pub file_path: String,
+    pub patch_path: String,
+    pub reason: String,
+    pub fallback: Option<String> // file that's used instead, or None if the file isn't replaced
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DeltaPatchReport {
+    pub applied: Vec<AppliedPatch>,
+    pub refused: Vec<RefusedPatch>
+}
+
+impl DeltaPatchReport {
+    pub fn has_problems(&self) -> bool {
+        !self.refused.is_empty()
+    }
+
+    pub fn print(&self) {
+        println!("DELTA PATCHES: {} APPLIED", self.applied.len());
+        for i in &self.applied {
+            println!("\"{}\": \"{}\" applied to {}", i.file_path, i.patch_path, i.base);
+        }
+        if !self.refused.is_empty() {
+            println!("{}", "-".repeat(80));
+            println!("REFUSED PATCHES: {} PATCHES", self.refused.len());
+            for i in &self.refused {
+                match &i.fallback {
+                    Some(fallback) => println!("\"{}\": \"{}\" was refused: {}. Using \"{}\" instead", i.file_path, i.patch_path, i.reason, fallback),
+                    None => println!("\"{}\": \"{}\" was refused: {}. The file won't be replaced", i.file_path, i.patch_path, i.reason)
+                }
+            }
+        }
+    }
+}
+
+// Apply every patch in the tree. This has to run before anything else reads the tree's files (such as datatable_merge)
+pub fn apply_patches(root: TocDirectoryRef) -> DeltaPatchReport {
+    let mut report = DeltaPatchReport::default();
+    let mut curr_dir = root.borrow().first_child.clone();
+    while let Some(dir) = curr_dir {
+        // only Content is mounted (at /Game), anything else can only be patched from another mod's file
+        let dir_path = match dir.borrow().name.eq_ignore_ascii_case("Content") {
+            true => String::from("/Game"),
+            false => format!("/{}", dir.borrow().name)
+        };
+        apply_patches_inner(Rc::clone(&dir), &dir_path, &mut report);
+        curr_dir = dir.borrow().next_sibling.clone();
+    }
+    report
+}
+
+fn apply_patches_inner(dir: TocDirectoryRef, dir_path: &str, report: &mut DeltaPatchReport) {
+    let mut curr_file = dir.borrow().first_file.clone();
+    while let Some(file) = curr_file {
+        curr_file = file.borrow().next.clone();
+        let file_path = format!("{}/{}", dir_path, file.borrow().name);
+        if apply_patch(&file, &file_path, report).is_none() {
+            TocDirectory::remove_file(Rc::clone(&dir), file);
+        }
+    }
+    let mut curr_dir = dir.borrow().first_child.clone();
+    while let Some(child) = curr_dir {
+        let child_path = format!("{}/{}", dir_path, child.borrow().name);
+        apply_patches_inner(Rc::clone(&child), &child_path, report);
+        curr_dir = child.borrow().next_sibling.clone();
+    }
+}
+
+// Returns where the file's contents come from now (the patch that was applied, or the file that's used in it's place), or None if the
+// patch was refused and there's no other mod's file to use instead, so the game's file should be used
+fn apply_patch(file: &TocFileRef, file_path: &str, report: &mut DeltaPatchReport) -> Option<String> {
+    let patch_path = match &file.borrow().source {
+        TocFileSource::DeltaPatch(p) => p.to_owned(),
+        source => return Some(source.get_display_path().to_owned())
+    };
+    // patches of patches are applied lowest priority first
+    let replaced = file.borrow().replaced.clone();
+    let replaced_path = replaced.and_then(|r| apply_patch(&r, file_path, report));
+    if replaced_path.is_none() {
+        file.borrow_mut().replaced = None;
+    }
+    let replaced = file.borrow().replaced.clone();
+    match get_patched_file(&patch_path, replaced.as_ref(), replaced_path.as_deref().unwrap_or_default(), file_path) {
+        Ok((patched, base)) => {
+            let mut file = file.borrow_mut();
+            file.file_size = patched.len() as u64;
+            file.source = TocFileSource::Memory(Rc::new(patched));
+            report.applied.push(AppliedPatch { file_path: file_path.to_owned(), patch_path: patch_path.clone(), base });
+            Some(patch_path)
+        },
+        Err(e) => {
+            report.refused.push(RefusedPatch { file_path: file_path.to_owned(), patch_path, reason: e.to_string(), fallback: replaced_path.clone() });
+            let replaced = replaced?;
+            let replaced = replaced.borrow();
+            let mut file = file.borrow_mut();
+            file.file_size = replaced.file_size;
+            file.source = replaced.source.clone();
+            file.replaced = replaced.replaced.clone();
+            replaced_path
+        }
+    }
+}
+
+// The file that the patch replaced is tried first, then the game's file, since a patch made from the game's file should still work
+// when a lower priority mod replaces the whole file. Returns the patched file and a description of where the base came from
+fn get_patched_file(patch_path: &str, replaced: Option<&TocFileRef>, replaced_path: &str, file_path: &str) -> Result<(Vec<u8>, String), Box<dyn Error>> {
+    let patch = DeltaPatch::from_file(Path::new(patch_path))?;
+    let mut replaced_error = None;
+    if let Some(replaced) = replaced {
+        match patch.apply(&read_file(replaced)?) {
+            Ok(patched) => return Ok((patched, format!("\"{}\"", replaced_path))),
+            Err(e) => replaced_error = Some(e)
+        }
+    }
+    match read_game_file(file_path)? {
+        Some(base) => Ok((patch.apply(&base)?, String::from("the game's file"))),
+        None => Err(match replaced_error {
+            Some(e) => e,
+            None if unsafe { (*addr_of!(game_packages::GAME_CONTAINER_HEADER)).is_none() } => String::from("The game's containers weren't loaded, so there's no file to patch"),
+            None => String::from("File isn't in the game's containers or any other mod")
+        }.into())
+    }
+}
+
+// Read the chunk for a mounted file path (/Game/Folder/Asset.uasset) from the game's containers
+fn read_game_file(file_path: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let (package_path, ext) = match file_path.rsplit_once('.') {
+        Some(p) if p.0.starts_with("/Game/") => p,
+        _ => return Ok(None)
+    };
+    let chunk_type = match ext.to_lowercase().as_str() {
+        "uasset" => IoChunkType4::ExportBundleData,
+        "ubulk" => IoChunkType4::BulkData,
+        "uptnl" => IoChunkType4::OptionalBulkData,
+        _ => return Ok(None)
+    };
+    game_packages::read_game_chunk(Hasher16::get_cityhash64(package_path), chunk_type)
+}
+
+/// # Safety
+/// Must not be called while a TOC is being built on another thread. This checks if DELTA_PATCH_REPORT has been assigned a value
+/// first, which only happens after building a TOC
+pub unsafe fn print_delta_patch_report() {
+    if let Some(report) = (*addr_of!(DELTA_PATCH_REPORT)).as_ref() {
+        report.print();
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{
+    game_packages,
+    io_toc::ContainerHeader,
+    string::Hasher16
+};
+use std::{collections::HashMap, ptr::addr_of};
+
+// Checks the imports of every package in the emulated container before the game gets to load any of them. An import is missing when
+// it isn't in the emulated container or any of the game's containers (see game_packages), which would make the game crash or
+// silently drop the export that uses it. Packages that import each other are also listed, since these can't be given a load order
+pub static mut DEPENDENCY_REPORT: Option<DependencyReport> = None;
+
+#[derive(Debug, PartialEq)]
+pub struct MissingImport {
+    pub package_path: String, // importing package
+    pub import_id: u64,
+    pub import_path: Option<String> // None if the id didn't match any of the importing package's names
+}
+
+#[derive(Debug, Default)]
+pub struct DependencyReport {
+    pub checked_packages: usize,
+    pub checked_game_packages: bool, // false if the game's containers weren't loaded, so only cycles were checked
+    pub missing_imports: Vec<MissingImport>,
+    pub cycles: Vec<Vec<String>>
+}
+
+impl DependencyReport {
+    pub fn has_problems(&self) -> bool {
+        !self.missing_imports.is_empty() || !self.cycles.is_empty()
+    }
+
+    pub fn print(&self) {
+        println!("Checked imports for {} packages", self.checked_packages);
+        if !self.checked_game_packages {
+            println!("Base game packages weren't loaded, so missing imports weren't checked");
+        }
+        if !self.missing_imports.is_empty() {
+            println!("{}", "-".repeat(80));
+            println!("MISSING IMPORTS: {} IMPORTS", self.missing_imports.len());
+            for i in &self.missing_imports {
+                match &i.import_path {
+                    Some(path) => println!("Package \"{}\" imports \"{}\" ({:016X}), which isn't in the game or any mod", i.package_path, path, i.import_id),
+                    None => println!("Package \"{}\" imports package {:016X}, which isn't in the game or any mod", i.package_path, i.import_id)
+                }
+            }
+        }
+        if !self.cycles.is_empty() {
+            println!("{}", "-".repeat(80));
+            println!("IMPORT CYCLES: {} CYCLES", self.cycles.len());
+            for i in &self.cycles {
+                println!("{}", i.join(" -> "));
+            }
+        }
+    }
+}
+
+// package_paths maps a package id to the path of the file it was made from, for use in the report. get_package_names returns the name
+// map of a package in the container, which is only read for packages with missing imports
+pub fn check_dependencies(header: &ContainerHeader, package_paths: &HashMap<u64, String>, get_package_names: impl Fn(u64) -> Vec<String>) -> DependencyReport {
+    let get_path = |id: u64| package_paths.get(&id).cloned().unwrap_or_else(|| format!("{:016X}", id));
+    let checked_game_packages = unsafe { (*addr_of!(game_packages::GAME_CONTAINER_HEADER)).is_some() };
+    let mut missing_imports = vec![];
+    if checked_game_packages {
+        for package in &header.packages {
+            let mut names = None;
+            for import_id in &package.import_ids {
+                if !header.contains_package(*import_id) && !game_packages::is_game_package(*import_id) {
+                    let names = names.get_or_insert_with(|| get_package_names(package.hash));
+                    missing_imports.push(MissingImport { package_path: get_path(package.hash), import_id: *import_id, import_path: get_import_path(*import_id, names) });
+                }
+            }
+        }
+    }
+    let cycles = find_cycles(header).into_iter()
+        .map(|cycle| cycle.into_iter().map(|i| get_path(header.packages[i].hash)).collect())
+        .collect();
+    DependencyReport { checked_packages: header.packages.len(), checked_game_packages, missing_imports, cycles }
+}
+
+// Import ids are a hash of the imported package's path, which the importing package also has in it's name map. The game's container
+// headers can have names too, so those are checked if the package's names don't match
+fn get_import_path(import_id: u64, package_names: &[String]) -> Option<String> {
+    let game_names = unsafe { (*addr_of!(game_packages::GAME_CONTAINER_HEADER)).as_ref() }.map_or([].as_slice(), |h| h.names.as_slice());
+    package_names.iter().chain(game_names)
+        .find(|name| name.starts_with('/') && Hasher16::get_cityhash64(name) == import_id)
+        .cloned()
+}
+
+// Tarjan's strongly connected components over the import graph of packages inside of the container. Any component with more than
+// one package (or a package that imports itself) is a cycle
+fn find_cycles(header: &ContainerHeader) -> Vec<Vec<usize>> {
+    struct Tarjan<'a> {
+        edges: Vec<Vec<usize>>,
+        index: Vec<Option<usize>>,
+        low_link: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        next_index: usize,
+        cycles: Vec<Vec<usize>>,
+        header: &'a ContainerHeader
+    }
+    impl Tarjan<'_> {
+        // iterative so that long import chains can't overflow the stack. the second value is the next edge of that package to follow
+        fn visit(&mut self, root: usize) {
+            self.start(root);
+            let mut call_stack = vec![(root, 0)];
+            while let Some((v, next_edge)) = call_stack.pop() {
+                match self.edges[v].get(next_edge).copied() {
+                    Some(w) => {
+                        call_stack.push((v, next_edge + 1));
+                        match self.index[w] {
+                            None => {
+                                self.start(w);
+                                call_stack.push((w, 0));
+                            },
+                            Some(w_index) if self.on_stack[w] => self.low_link[v] = self.low_link[v].min(w_index),
+                            _ => ()
+                        }
+                    },
+                    None => {
+                        // every import has been visited, so pass the low link back to the package that imported this one
+                        if let Some((parent, _)) = call_stack.last() {
+                            self.low_link[*parent] = self.low_link[*parent].min(self.low_link[v]);
+                        }
+                        self.finish(v);
+                    }
+                }
+            }
+        }
+        fn start(&mut self, v: usize) {
+            self.index[v] = Some(self.next_index);
+            self.low_link[v] = self.next_index;
+            self.next_index += 1;
+            self.stack.push(v);
+            self.on_stack[v] = true;
+        }
+        fn finish(&mut self, v: usize) {
+            if Some(self.low_link[v]) == self.index[v] {
+                let mut component = vec![];
+                loop {
+                    let w = self.stack.pop().unwrap();
+                    self.on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                if component.len() > 1 || self.header.packages[v].import_ids.contains(&self.header.packages[v].hash) {
+                    component.reverse();
+                    self.cycles.push(component);
+                }
+            }
+        }
+    }
+    let package_indices: HashMap<u64, usize> = header.packages.iter().enumerate().map(|(i, p)| (p.hash, i)).collect();
+    let edges = header.packages.iter()
+        .map(|p| p.import_ids.iter().filter_map(|id| package_indices.get(id).copied()).collect())
+        .collect();
+    let count = header.packages.len();
+    let mut tarjan = Tarjan {
+        edges, index: vec![None; count], low_link: vec![0; count], on_stack: vec![false; count],
+        stack: vec![], next_index: 0, cycles: vec![], header
+    };
+    for v in 0..count {
+        if tarjan.index[v].is_none() {
+            tarjan.visit(v);
+        }
+    }
+    tarjan.cycles
+}
+
+/// # Safety
+/// Must not be called while a TOC is being built on another thread. This checks if DEPENDENCY_REPORT has been assigned a value
+/// first, which only happens after building a TOC
+pub unsafe fn print_dependency_report() {
+    if let Some(report) = (*addr_of!(DEPENDENCY_REPORT)).as_ref() {
+        report.print();
+    }
+}
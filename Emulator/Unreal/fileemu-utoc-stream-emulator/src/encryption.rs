@@ -0,0 +1,67 @@
+use aes::{
+    Aes256,
+    cipher::{generic_array::GenericArray, BlockDecrypt, KeyInit}
+};
+use crate::io_toc::GUID;
+use std::ptr::{addr_of, addr_of_mut};
+
+// Keys supplied by the user, used to read encrypted IO Store containers (and encrypted PAK indexes)
+// A key with a GUID of 0 is used for any container that doesn't have it's own key
+pub static mut ENCRYPTION_KEYS: Option<Vec<(GUID, AesKey)>> = None;
+
+pub const AES_BLOCK_SIZE: usize = 0x10;
+
+#[derive(Clone)]
+pub struct AesKey {
+    cipher: Aes256
+}
+
+impl AesKey {
+    pub fn new(key: [u8; 0x20]) -> Self {
+        Self { cipher: Aes256::new(GenericArray::from_slice(&key)) }
+    }
+    // Keys are usually shared as 64 hex characters with a 0x prefix (0x0123...)
+    pub fn from_hex(key: &str) -> Result<Self, String> {
+        let key = key.trim();
+        let key = key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")).unwrap_or(key);
+        if key.len() != 0x40 || !key.is_ascii() {
+            return Err(format!("AES key must be 64 hex characters long, got {}", key.len()));
+        }
+        let mut bytes = [0; 0x20];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&key[i * 2..i * 2 + 2], 16).map_err(|_| format!("AES key contains a non hex character near {}", i * 2))?;
+        }
+        Ok(Self::new(bytes))
+    }
+    // Decrypt a buffer encrypted with AES-256-ECB. The buffer must be a multiple of AES_BLOCK_SIZE
+    pub fn decrypt(&self, data: &mut [u8]) -> Result<(), String> {
+        if !data.len().is_multiple_of(AES_BLOCK_SIZE) {
+            return Err(format!("Encrypted data must be aligned to 0x{:x} bytes, got 0x{:x}", AES_BLOCK_SIZE, data.len()));
+        }
+        for block in data.chunks_exact_mut(AES_BLOCK_SIZE) {
+            self.cipher.decrypt_block(GenericArray::from_mut_slice(block));
+        }
+        Ok(())
+    }
+}
+
+pub fn align_to_aes_block(size: u64) -> u64 {
+    (size + AES_BLOCK_SIZE as u64 - 1) & !(AES_BLOCK_SIZE as u64 - 1)
+}
+
+// Add a key for containers with the given encryption key GUID (0 to use it as the default key)
+pub fn add_key(guid: GUID, key: AesKey) {
+    unsafe {
+        let keys = (*addr_of_mut!(ENCRYPTION_KEYS)).get_or_insert_with(Vec::new);
+        keys.retain(|(g, _)| *g != guid);
+        keys.push((guid, key));
+    }
+}
+
+// Get the key for a container, falling back to the default key
+pub fn get_key(guid: GUID) -> Option<AesKey> {
+    let keys = unsafe { (*addr_of!(ENCRYPTION_KEYS)).as_ref()? };
+    keys.iter().find(|(g, _)| *g == guid)
+        .or_else(|| keys.iter().find(|(g, _)| *g == 0))
+        .map(|(_, k)| k.clone())
+}
@@ -0,0 +1,337 @@
+use byteorder::ReadBytesExt;
+use crate::{
+    game_profile::{self, ContainerHeaderPosition, Endianness, GameProfile},
+    io_package::IoPackage,
+    io_toc::{IoChunkId, IoChunkType4, IoStoreTocVersion, IO_STORE_TOC_MAGIC},
+    pak_factory::{PakVersion, TARGET_PAK},
+    pak_reader::PakReader,
+    script_objects::GLOBAL_TOC,
+    toc_factory::{EngineVersion, TARGET_TOC},
+    toc_reader::{self, TocReader}
+};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufReader, Cursor, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    ptr::addr_of
+};
+
+// Work out which engine version a game uses from it's own containers, since building the TOC for the wrong version gives a
+// container that the game silently refuses to mount:
+// - The TOC version of each .utoc: Initial is 4.25 (not supported), DirectoryIndex is 4.25+ or 4.26, PartitionSize is 4.27
+//   and anything newer is UE5 (not supported). 4.25's header has no version field at all, just a 0x80 byte header size
+// - 4.25+ and 4.26 TOCs are identical, but their paks aren't: 4.25 writes v9 paks, while 4.26 writes v11
+// - Whether each container's header is it's first or last chunk
+// - The byte order of each .utoc, which console builds may use big endian for
+// - One of the game's packages is read with the 4.25+ to 4.27 package summary, which UE5 packages don't follow
+// If any of these disagree, nothing is picked and the reasons are listed in the report instead
+pub static mut ENGINE_DETECTION: Option<EngineDetection> = None;
+
+pub const TOC_HEADER_SIZE: u32 = 0x90; // FIoStoreTocHeader from 4.25+ to 4.27
+const TOC_HEADER_SIZE_INITIAL: u32 = 0x80;
+
+pub struct DetectedContainer {
+    pub toc_path: PathBuf,
+    pub version: Option<IoStoreTocVersion>, // None for 4.25's header
+    pub header_size: u32,
+    pub header_position: Option<ContainerHeaderPosition>, // None if the container has no container header (global.utoc)
+    pub endianness: Endianness
+}
+
+impl DetectedContainer {
+    fn from_file(toc_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut reader = BufReader::new(File::open(toc_path)?);
+        match toc_reader::get_toc_endianness(&mut reader)? {
+            Endianness::Little => Self::from_buffer::<byteorder::LittleEndian>(&mut reader, toc_path),
+            Endianness::Big => Self::from_buffer::<byteorder::BigEndian>(&mut reader, toc_path)
+        }
+    }
+
+    fn from_buffer<EN: byteorder::ByteOrder>(reader: &mut BufReader<File>, toc_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let endianness = Endianness::of::<EN>();
+        let mut toc_magic = [0; 0x10];
+        std::io::Read::read_exact(reader, &mut toc_magic)?;
+        if toc_magic != IO_STORE_TOC_MAGIC {
+            return Err("File is not an IO Store TOC (magic doesn't match)".into());
+        }
+        let mut version_field = [0; 4];
+        std::io::Read::read_exact(reader, &mut version_field)?;
+        if EN::read_u32(&version_field) == TOC_HEADER_SIZE_INITIAL { // there's no version, this is the header size
+            return Ok(Self { toc_path: toc_path.to_owned(), version: None, header_size: TOC_HEADER_SIZE_INITIAL, header_position: None, endianness });
+        }
+        let version = version_field[0]; // followed by padding
+        if version == IoStoreTocVersion::Invalid.into() || version > IoStoreTocVersion::PerfectHashWithOverflow.into() {
+            return Err(format!("Unknown IO Store TOC version {}", version).into());
+        }
+        let version = IoStoreTocVersion::from(version);
+        let header_size = reader.read_u32::<EN>()?;
+        let entry_count = reader.read_u32::<EN>()?;
+        let mut container = Self { toc_path: toc_path.to_owned(), version: Some(version), header_size, header_position: None, endianness };
+        if version == IoStoreTocVersion::DirectoryIndex || version == IoStoreTocVersion::PartitionSize {
+            reader.seek(SeekFrom::Start(header_size as u64))?;
+            let chunk_ids = IoChunkId::list_from_buffer::<BufReader<File>, EN>(reader, entry_count as usize)?;
+            container.header_position = match chunk_ids.iter().position(|c| c.get_type() == IoChunkType4::ContainerHeader) {
+                Some(0) => Some(ContainerHeaderPosition::First),
+                Some(i) if i == chunk_ids.len() - 1 => Some(ContainerHeaderPosition::Last),
+                _ => None
+            };
+        }
+        Ok(container)
+    }
+
+    fn get_name(&self) -> String {
+        self.toc_path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned())
+    }
+}
+
+pub enum PackageProbe {
+    Matched(String), // path of the package that was read
+    Mismatched(String), // why the package didn't fit
+    Unchecked(String) // why no package could be read (e.g it's compressed with Oodle)
+}
+
+pub struct EngineDetection {
+    pub paks_path: PathBuf,
+    pub containers: Vec<DetectedContainer>,
+    pub pak_versions: Vec<(String, Result<PakVersion, String>)>,
+    pub package_probe: PackageProbe,
+    pub engine_version: Option<EngineVersion>, // only set if every check agrees
+    pub container_header_position: Option<ContainerHeaderPosition>,
+    pub endianness: Option<Endianness>,
+    pub problems: Vec<String>
+}
+
+impl EngineDetection {
+    pub fn is_ambiguous(&self) -> bool {
+        !self.problems.is_empty()
+    }
+
+    // The container header position isn't checked here, since games accept it being last on every version
+    pub fn get_profile_conflict(&self, profile: &GameProfile) -> Option<String> {
+        self.engine_version.filter(|v| *v != profile.engine_version)
+            .map(|v| format!("The game uses {:?}, but the {} profile is for {:?}", v, profile.id, profile.engine_version))
+            .or_else(|| self.endianness.filter(|e| *e != profile.endianness)
+                .map(|e| format!("The game's containers are {:?} endian, but the {} profile is {:?} endian", e, profile.id, profile.endianness)))
+    }
+
+    pub fn print(&self) {
+        println!("ENGINE DETECTION: {}", self.paks_path.display());
+        for container in &self.containers {
+            let version = container.version.map_or(String::from("Initial (no version field)"), |v| format!("{:?}", v));
+            let position = container.header_position.map_or(String::from("no container header"), |p| format!("container header {:?}", p));
+            println!("{}: TOC version {}, header is 0x{:x} bytes, {}, {:?} endian", container.get_name(), version, container.header_size, position, container.endianness);
+        }
+        for (name, version) in &self.pak_versions {
+            match version {
+                Ok(v) => println!("{}: pak version {:?}", name, v),
+                Err(e) => println!("{}: {}", name, e)
+            }
+        }
+        match &self.package_probe {
+            PackageProbe::Matched(path) => println!("Package summary matches 4.25+ to 4.27 (read {})", path),
+            PackageProbe::Mismatched(e) => println!("{}", e),
+            PackageProbe::Unchecked(e) => println!("Package summary wasn't checked: {}", e)
+        }
+        match self.engine_version {
+            Some(version) => println!("Detected {:?}, container header {}, {:?} endian", version,
+                self.container_header_position.map_or(String::from("position unknown"), |p| format!("{:?}", p)),
+                self.endianness.unwrap_or(Endianness::Little)),
+            None => {
+                println!("{}", "-".repeat(80));
+                println!("AMBIGUOUS: {} PROBLEMS", self.problems.len());
+                for i in &self.problems {
+                    println!("{}", i);
+                }
+            }
+        }
+    }
+}
+
+// Check every container and pak in the game's Paks folder. Returns None if there aren't any IO Store containers there
+pub fn detect_engine_version(paks_path: &Path) -> Result<Option<EngineDetection>, Box<dyn Error>> {
+    let mut toc_paths = vec![];
+    let mut pak_paths = vec![];
+    for path in fs::read_dir(paks_path)?.filter_map(|e| e.ok().map(|e| e.path())) {
+        let name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("utoc") if name != TARGET_TOC => toc_paths.push(path),
+            Some("pak") if name != TARGET_PAK => pak_paths.push(path),
+            _ => ()
+        }
+    }
+    if toc_paths.is_empty() {
+        return Ok(None);
+    }
+    toc_paths.sort();
+    pak_paths.sort();
+    let mut detection = EngineDetection {
+        paks_path: paks_path.to_owned(),
+        containers: vec![],
+        pak_versions: vec![],
+        package_probe: PackageProbe::Unchecked(String::from("No containers could be read")),
+        engine_version: None,
+        container_header_position: None,
+        endianness: None,
+        problems: vec![]
+    };
+    for toc_path in &toc_paths {
+        match DetectedContainer::from_file(toc_path) {
+            Ok(container) => detection.containers.push(container),
+            Err(e) => detection.problems.push(format!("Couldn't read {}: {}", toc_path.display(), e))
+        }
+    }
+    for pak_path in &pak_paths {
+        let name = pak_path.file_name().unwrap().to_string_lossy().into_owned();
+        detection.pak_versions.push((name, PakReader::read_version(pak_path).map_err(|e| e.to_string())));
+    }
+    detection.package_probe = probe_package_summary(&toc_paths);
+    detection.resolve();
+    Ok(Some(detection))
+}
+
+impl EngineDetection {
+    fn resolve(&mut self) {
+        let mut versions = vec![];
+        for container in &self.containers {
+            if !versions.contains(&container.version) {
+                versions.push(container.version);
+            }
+        }
+        if versions.len() > 1 {
+            self.problems.push(format!("Containers have different TOC versions: {}", self.containers.iter()
+                .map(|c| format!("{} ({})", c.get_name(), c.version.map_or(String::from("Initial"), |v| format!("{:?}", v))))
+                .collect::<Vec<_>>().join(", ")));
+        }
+        for container in self.containers.iter().filter(|c| c.version.is_some() && c.header_size != TOC_HEADER_SIZE) {
+            self.problems.push(format!("{} has a 0x{:x} byte TOC header, expected 0x{:x}", container.get_name(), container.header_size, TOC_HEADER_SIZE));
+        }
+        let pak_versions: Vec<PakVersion> = self.pak_versions.iter().filter_map(|(_, v)| v.as_ref().ok().copied()).collect();
+        let mut candidates = match versions.first() {
+            Some(None) | Some(Some(IoStoreTocVersion::Initial)) => {
+                self.problems.push(String::from("Containers are from UE 4.25, which isn't supported"));
+                vec![]
+            },
+            Some(Some(IoStoreTocVersion::DirectoryIndex)) => vec![EngineVersion::UE4_25Plus, EngineVersion::UE4_26],
+            Some(Some(IoStoreTocVersion::PartitionSize)) => vec![EngineVersion::UE4_27],
+            Some(Some(v)) => {
+                self.problems.push(format!("Containers are from UE5 (TOC version {:?}), which isn't supported", v));
+                vec![]
+            },
+            None => vec![]
+        };
+        // paks narrow it down to the versions that would've written them
+        if !pak_versions.is_empty() {
+            candidates.retain(|v| pak_versions.iter().all(|p| *p == PakVersion::from(*v)));
+            if candidates.is_empty() && self.problems.is_empty() {
+                self.problems.push(format!("Pak versions ({:?}) don't match the containers' TOC version", pak_versions));
+            }
+        }
+        if candidates.len() > 1 {
+            self.problems.push(format!("Could be any of {:?}, and there are no paks to tell them apart", candidates));
+        }
+        if let PackageProbe::Mismatched(e) = &self.package_probe {
+            self.problems.push(e.to_owned());
+        }
+        let mut positions = vec![];
+        for position in self.containers.iter().filter_map(|c| c.header_position) {
+            if !positions.contains(&position) {
+                positions.push(position);
+            }
+        }
+        match positions.len() {
+            0 => (),
+            1 => self.container_header_position = Some(positions[0]),
+            _ => self.problems.push(String::from("Some containers have their container header first and others have it last"))
+        }
+        let mut byte_orders = vec![];
+        for container in &self.containers {
+            if !byte_orders.contains(&container.endianness) {
+                byte_orders.push(container.endianness);
+            }
+        }
+        match byte_orders.len() {
+            0 => (),
+            1 => self.endianness = Some(byte_orders[0]),
+            _ => self.problems.push(String::from("Some containers are little endian and others are big endian"))
+        }
+        if self.problems.is_empty() && candidates.len() == 1 {
+            self.engine_version = Some(candidates[0]);
+        }
+    }
+}
+
+// Read the first package that can be read from the game's containers. Failing to parse it means that the game's packages don't
+// use the package summary that every resolver expects
+fn probe_package_summary(toc_paths: &[PathBuf]) -> PackageProbe {
+    let mut last_error = String::from("No packages could be read from the game's containers");
+    for toc_path in toc_paths.iter().filter(|p| p.file_name().is_some_and(|n| n != GLOBAL_TOC)) {
+        let toc = match TocReader::from_file(toc_path) {
+            Ok(toc) => toc,
+            Err(e) => {
+                last_error = format!("Couldn't read {}: {}", toc_path.display(), e);
+                continue
+            }
+        };
+        let files = toc.get_files();
+        let package = files.iter().find(|f| toc.chunk_ids[f.chunk_index].get_type() == IoChunkType4::ExportBundleData
+            && toc.check_chunk_readable(f.chunk_index).is_ok());
+        if let Some(package) = package {
+            let data = match toc.read_chunk(package.chunk_index) {
+                Ok(data) => data,
+                Err(e) => return PackageProbe::Unchecked(format!("Couldn't read {}: {}", package.path, e))
+            };
+            let size = data.len() as u64;
+            let summary = match toc.endianness {
+                Endianness::Little => IoPackage::from_buffer::<Cursor<Vec<u8>>, byteorder::LittleEndian>(&mut Cursor::new(data), size),
+                Endianness::Big => IoPackage::from_buffer::<Cursor<Vec<u8>>, byteorder::BigEndian>(&mut Cursor::new(data), size)
+            };
+            return match summary {
+                Ok(_) => PackageProbe::Matched(package.path.clone()),
+                Err(e) => PackageProbe::Mismatched(format!("Package summary of {} doesn't match 4.25+ to 4.27: {}", package.path, e))
+            };
+        }
+    }
+    PackageProbe::Unchecked(last_error)
+}
+
+// Detect the game's engine version from it's Paks folder. If no game profile was picked, the standard profile for that version
+// gets used, otherwise the selected profile is checked against it. Nothing is changed if the detection was ambiguous
+pub fn apply_engine_detection(paks_path: &Path) {
+    let detection = match detect_engine_version(paks_path) {
+        Ok(Some(detection)) => detection,
+        Ok(None) => return,
+        Err(e) => {
+            println!("WARNING: Couldn't detect the engine version from {}: {}", paks_path.display(), e);
+            return
+        }
+    };
+    let profile = game_profile::get_game_profile();
+    if detection.is_ambiguous() {
+        println!("WARNING: Couldn't tell which engine version the game uses, so the {} profile is used as is", profile.id);
+        detection.print();
+    } else if game_profile::is_game_profile_selected() {
+        if let Some(conflict) = detection.get_profile_conflict(profile) {
+            println!("WARNING: {}. The TOC will be built for the profile's version, which the game may refuse to mount", conflict);
+        }
+    } else if let Some(version) = detection.engine_version {
+        let mut detected = game_profile::get_standard_profile(version).clone();
+        if let Some(position) = detection.container_header_position {
+            detected.container_header_position = position;
+        }
+        if let Some(endianness) = detection.endianness {
+            detected.endianness = endianness;
+        }
+        unsafe { game_profile::GAME_PROFILE = Some(detected); }
+    }
+    unsafe { ENGINE_DETECTION = Some(detection); }
+}
+
+/// # Safety
+/// Must not be called while the game's Paks folder is being checked on another thread. This checks if ENGINE_DETECTION has been
+/// assigned a value first, which only happens after checking the game's Paks folder
+pub unsafe fn print_engine_detection_report() {
+    if let Some(detection) = (*addr_of!(ENGINE_DETECTION)).as_ref() {
+        detection.print();
+    }
+}
@@ -1,19 +1,148 @@
-use crate::{asset_collector, toc_factory, toc_factory::PartitionBlock, toc_factory::CONTAINER_DATA, toc_factory::CONTAINER_ENTRIES_OSPATH_POOL};
+use crate::{asset_collector, encryption, game_packages, game_profile, pak_factory, toc_extractor, toc_factory, usmap, toc_factory::PartitionBlock, toc_factory::CONTAINER_DATA, toc_factory::CONTAINER_ENTRIES_OSPATH_POOL, toc_factory::CONTAINER_ENTRIES_MEMORY_POOL};
 use std::{
-    ffi::CStr,
-    os::raw::c_char
+    ffi::{c_void, CStr},
+    os::raw::c_char,
+    path::Path
 };
 
 #[no_mangle]
 #[allow(non_snake_case)]
 // modId is used by the asset collector profiler
+/// # Safety
+/// modId and modPath must be null terminated UTF-8 strings
 pub unsafe extern "C" fn AddFromFolders(modId: *const c_char, modPath: *const c_char) {
     asset_collector::add_from_folders(CStr::from_ptr(modId).to_str().unwrap(), CStr::from_ptr(modPath).to_str().unwrap());
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+// Add a file at gamePath (e.g /Game/Folder/Asset.uasset) using a copy of the given buffer
+/// # Safety
+/// modId and gamePath must be null terminated UTF-8 strings, and data must point to length bytes (or be null)
+pub unsafe extern "C" fn AddVirtualFile(modId: *const c_char, gamePath: *const c_char, data: *const u8, length: u64) -> bool {
+    // C# passes null for an empty array, which from_raw_parts doesn't allow
+    let buffer = if data.is_null() || length == 0 { vec![] } else { std::slice::from_raw_parts(data, length as usize).to_vec() };
+    match asset_collector::add_virtual_file_from_buffer(CStr::from_ptr(modId).to_str().unwrap(), CStr::from_ptr(gamePath).to_str().unwrap(), buffer) {
+        Ok(_) => true,
+        Err(e) => {
+            println!("WARNING: {}", e);
+            false
+        }
+    }
+}
+
+// Called while building the TOC. Returns a pointer to the file's contents and writes it's size into length. The returned memory
+// only needs to stay valid until the callback returns, since it gets copied over
+pub type VirtualFileProducer = unsafe extern "C" fn(user_data: *mut c_void, length: *mut u64) -> *const u8;
+
+#[no_mangle]
+#[allow(non_snake_case)]
+// Add a file at gamePath which gets it's contents from producer when the TOC is built
+/// # Safety
+/// modId and gamePath must be null terminated UTF-8 strings. producer is called with userData while the TOC is being
+/// built, and has to return either null or a pointer to the number of bytes it writes into length
+pub unsafe extern "C" fn AddVirtualFileProducer(modId: *const c_char, gamePath: *const c_char, producer: VirtualFileProducer, userData: *mut c_void) -> bool {
+    let produce = move || {
+        let mut length = 0;
+        let data = producer(userData, &mut length);
+        if data.is_null() { vec![] } else { std::slice::from_raw_parts(data, length as usize).to_vec() }
+    };
+    match asset_collector::add_virtual_file_from_producer(CStr::from_ptr(modId).to_str().unwrap(), CStr::from_ptr(gamePath).to_str().unwrap(), produce) {
+        Ok(_) => true,
+        Err(e) => {
+            println!("WARNING: {}", e);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+// Add an AES key (hex string) used to read encrypted containers. Passing a null keyGuid uses it for any container
+/// # Safety
+/// key must be a null terminated UTF-8 string, and keyGuid either null or one too
+pub unsafe extern "C" fn AddEncryptionKey(key: *const c_char, keyGuid: *const c_char) -> bool {
+    let guid = match keyGuid.is_null() {
+        true => 0,
+        false => match u128::from_str_radix(CStr::from_ptr(keyGuid).to_str().unwrap().trim_start_matches("0x"), 16) {
+            Ok(g) => g,
+            Err(e) => {
+                println!("WARNING: Invalid AES key GUID: {}", e);
+                return false;
+            }
+        }
+    };
+    match encryption::AesKey::from_hex(CStr::from_ptr(key).to_str().unwrap()) {
+        Ok(k) => {
+            encryption::add_key(guid, k);
+            true
+        },
+        Err(e) => {
+            println!("WARNING: {}", e);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+// Select the game profile (see game_profile::GAME_PROFILES) by it's id. This needs to be called before any mods are loaded
+/// # Safety
+/// profileId must be a null terminated UTF-8 string
+pub unsafe extern "C" fn SetGameProfile(profileId: *const c_char) -> bool {
+    match game_profile::set_game_profile(CStr::from_ptr(profileId).to_str().unwrap()) {
+        Ok(_) => true,
+        Err(e) => {
+            println!("WARNING: {}", e);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+// Let files with identical contents share a single range of the container. This needs to be called before the TOC is built
+/// # Safety
+/// Must not be called while a TOC is being built on another thread
+pub unsafe extern "C" fn SetChunkDeduplication(enabled: bool) {
+    toc_factory::DEDUPLICATE_CHUNKS = enabled;
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+// Read the container headers of every container in the game's Paks folder so that base game packages can be looked up
+/// # Safety
+/// paksPath must be a null terminated UTF-8 string
+pub unsafe extern "C" fn AddGameContainers(paksPath: *const c_char) -> bool {
+    match game_packages::add_game_containers_from_folder(Path::new(CStr::from_ptr(paksPath).to_str().unwrap())) {
+        Ok(_) => true,
+        Err(e) => {
+            println!("WARNING: Couldn't read game containers: {}", e);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+// Read a .usmap file so that packages using unversioned properties can be parsed
+/// # Safety
+/// path must be a null terminated UTF-8 string
+pub unsafe extern "C" fn AddMappings(path: *const c_char) -> bool {
+    match usmap::add_mappings(Path::new(CStr::from_ptr(path).to_str().unwrap())) {
+        Ok(_) => true,
+        Err(e) => {
+            println!("WARNING: Couldn't read mappings: {}", e);
+            false
+        }
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 // haiiii Reloaded!!!! :3
+/// # Safety
+/// tocPath must be a null terminated UTF-8 string and length must point to a u64
 pub unsafe extern "C" fn BuildTableOfContents(tocPath: *const c_char, settings: *const u32, settings_length: u32, length: *mut u64) -> *const u8 {
     match toc_factory::build_table_of_contents(CStr::from_ptr(tocPath).to_str().unwrap()) {
         Some(n) => {
@@ -26,10 +155,14 @@ pub unsafe extern "C" fn BuildTableOfContents(tocPath: *const c_char, settings:
 
 #[no_mangle]
 #[allow(non_snake_case)]
+/// # Safety
+/// casPath must be a null terminated UTF-8 string and every other parameter must point to a value of it's type. The
+/// blocks and header stay valid until SafeToDropContainerMetadata is called
 pub unsafe extern "C" fn GetContainerBlocks(
     casPath: *const c_char, 
     blocks: *mut *const PartitionBlock, blockCount: *mut usize, 
-    header: *mut *const u8, headerSize: *mut usize
+    header: *mut *const u8, headerSize: *mut usize,
+    alignment: *mut u32
 ) -> bool {
     let block_managed = toc_factory::get_virtual_partition(CStr::from_ptr(casPath).to_str().unwrap());
     match block_managed {
@@ -38,6 +171,7 @@ pub unsafe extern "C" fn GetContainerBlocks(
             *blocks = n.0.as_ptr();
             *headerSize = n.1.len(); // container header
             *header = n.1.as_ptr();
+            *alignment = n.2; // blocks are padded to the game profile's compression block alignment
             true
         },
         None => false
@@ -46,13 +180,59 @@ pub unsafe extern "C" fn GetContainerBlocks(
 
 #[no_mangle]
 #[allow(non_snake_case)]
+// Builds the companion pak the first time that it's requested, since the game mounts it before opening the TOC
+/// # Safety
+/// pakPath must be a null terminated UTF-8 string and every other parameter must point to a value of it's type
+pub unsafe extern "C" fn GetPakBlocks(
+    pakPath: *const c_char,
+    blocks: *mut *const PartitionBlock, blockCount: *mut usize,
+    index: *mut *const u8, indexSize: *mut usize
+) -> bool {
+    match pak_factory::get_virtual_pak(CStr::from_ptr(pakPath).to_str().unwrap()) {
+        Some(n) => {
+            *blockCount = n.0.len(); // entry headers and data
+            *blocks = n.0.as_ptr();
+            *indexSize = n.1.len(); // index and footer
+            *index = n.1.as_ptr();
+            true
+        },
+        None => false
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+// Extract every file in the container from the last BuildTableOfContents call into outPath, reading it from the same blocks
+// that GetContainerBlocks gives to C#. This has to be called before SafeToDropContainerMetadata
+/// # Safety
+/// outPath must be a null terminated UTF-8 string
+pub unsafe extern "C" fn ExtractVirtualContainer(outPath: *const c_char) -> bool {
+    match toc_extractor::extract_virtual_container(Path::new(CStr::from_ptr(outPath).to_str().unwrap()), &toc_extractor::ExtractFilter::default()) {
+        Ok(results) => {
+            results.print();
+            results.failed_files.is_empty()
+        },
+        Err(e) => {
+            println!("WARNING: Couldn't extract the virtual container: {}", e);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+/// # Safety
+/// The pointers returned by GetContainerBlocks can't be used after this
 pub unsafe extern "C" fn SafeToDropContainerMetadata() {
     CONTAINER_DATA = None;
     CONTAINER_ENTRIES_OSPATH_POOL = None;
+    CONTAINER_ENTRIES_MEMORY_POOL = None;
 }
 
 #[no_mangle]
 #[allow(non_snake_case)]
+/// # Safety
+/// Must not be called while mods are being loaded on another thread
 pub unsafe extern "C" fn PrintAssetCollectorResults() {
     asset_collector::print_asset_collector_results();
 }
\ No newline at end of file
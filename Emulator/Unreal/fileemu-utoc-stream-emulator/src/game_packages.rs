@@ -0,0 +1,102 @@
+use crate::{
+    io_package::ContainerHeaderPackage,
+    io_toc::{ContainerHeader, IoChunkId, IoChunkType4},
+    script_objects::{self, GLOBAL_TOC},
+    toc_factory::TARGET_TOC,
+    toc_reader::TocReader
+};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    ptr::{addr_of, addr_of_mut}
+};
+
+// Packages from the base game's containers, merged from each container's header. This is used to look up packages that mods
+// import or replace without needing to read any of the game's assets
+pub static mut GAME_CONTAINER_HEADER: Option<ContainerHeader> = None;
+// Containers that were added to GAME_CONTAINER_HEADER in priority order, for the few cases where a package's data is needed
+pub static mut GAME_CONTAINER_PATHS: Option<Vec<PathBuf>> = None;
+
+// Read the container header from one of the game's containers and merge it into GAME_CONTAINER_HEADER, returning the number of
+// packages in that container
+pub fn add_game_container(toc_path: &Path) -> Result<usize, Box<dyn Error>> {
+    let toc = TocReader::from_file(toc_path)?;
+    let header = toc.read_container_header()?
+        .ok_or_else(|| format!("{} doesn't have a container header", toc_path.display()))?;
+    let package_count = header.packages.len();
+    unsafe {
+        match (*addr_of_mut!(GAME_CONTAINER_HEADER)).as_mut() {
+            Some(game_header) => game_header.merge(header),
+            None => GAME_CONTAINER_HEADER = Some(header)
+        }
+        (*addr_of_mut!(GAME_CONTAINER_PATHS)).get_or_insert_with(Vec::new).push(toc_path.to_owned());
+    }
+    Ok(package_count)
+}
+
+// Patch containers mount with a higher priority the higher their patch number is. Like the engine, pakchunk0_P counts as patch 1 and
+// pakchunk0_2_P as patch 3, while a container that isn't a patch has no patch number
+pub fn get_patch_number(toc_path: &Path) -> Option<u32> {
+    let stem = toc_path.file_stem()?.to_string_lossy();
+    let name = stem.strip_suffix("_P")?;
+    match name.rsplit_once('_').and_then(|(_, version)| version.parse::<u32>().ok()) {
+        Some(version) if version >= 1 => Some(version + 1),
+        _ => Some(1)
+    }
+}
+
+// Add every container in the game's Paks folder (except for the one that the emulator makes), plus the script objects in global.utoc.
+// Patch containers (_P) are added first, highest patch number first, so that their packages take priority over the ones that they
+// replace. Containers that can't be read are skipped with a warning
+pub fn add_game_containers_from_folder(paks_path: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut toc_paths: Vec<_> = fs::read_dir(paks_path)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("utoc")))
+        .filter(|p| p.file_name().is_some_and(|n| n != TARGET_TOC && n != GLOBAL_TOC))
+        .collect();
+    toc_paths.sort_by_key(|p| (std::cmp::Reverse(get_patch_number(p)), p.to_owned()));
+    // global.utoc has no packages, but it's script objects are what packages import from /Script/
+    if paks_path.join(GLOBAL_TOC).is_file() {
+        if let Err(e) = script_objects::add_global_container(paks_path) {
+            println!("WARNING: Couldn't read script objects from {}: {}", GLOBAL_TOC, e);
+        }
+    }
+    let mut package_count = 0;
+    for toc_path in toc_paths {
+        match add_game_container(&toc_path) {
+            Ok(n) => package_count += n,
+            Err(e) => println!("WARNING: Couldn't read container header from {}: {}", toc_path.display(), e)
+        }
+    }
+    Ok(package_count)
+}
+
+pub fn get_game_package(package_id: u64) -> Option<&'static ContainerHeaderPackage> {
+    unsafe { (*addr_of!(GAME_CONTAINER_HEADER)).as_ref()?.get_package(package_id) }
+}
+
+pub fn is_game_package(package_id: u64) -> bool {
+    get_game_package(package_id).is_some()
+}
+
+// Read a package from the highest priority game container that has it, or None if it's not one of the game's packages.
+// This opens each container's TOC again, so it should only be used for a handful of packages
+pub fn read_game_package(package_id: u64) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    read_game_chunk(package_id, IoChunkType4::ExportBundleData)
+}
+
+// Same as read_game_package, but for any of the package's chunks (such as it's .ubulk, which is BulkData)
+pub fn read_game_chunk(package_id: u64, chunk_type: IoChunkType4) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    if !is_game_package(package_id) {
+        return Ok(None);
+    }
+    let chunk_id = IoChunkId::new_from_hash(package_id, chunk_type);
+    for toc_path in unsafe { (*addr_of!(GAME_CONTAINER_PATHS)).iter().flatten() } {
+        let toc = TocReader::from_file(toc_path)?;
+        if let Some(i) = toc.chunk_ids.iter().position(|c| *c == chunk_id) {
+            return Ok(Some(toc.read_chunk(i)?));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,150 @@
+use bitflags::bitflags;
+use crate::{
+    asset_collector::SUITABLE_FILE_EXTENSIONS,
+    pak_package::{CustomVersion, GUID},
+    toc_factory::{EngineVersion, DEFAULT_COMPRESSION_BLOCK_ALIGNMENT}
+};
+use std::ptr::{addr_of, addr_of_mut};
+
+// Settings that change between games. Most games only need the standard profile for their engine version, while titles
+// that ship with a modified engine or an unusual container layout get their own profile. To support a new title, add it to
+// GAME_PROFILES - the TOC and pak builders only ever read from the selected profile
+pub static mut GAME_PROFILE: Option<GameProfile> = None; // set by SetGameProfile or detected from the game's containers, standard 4.27 profile otherwise
+
+pub const DEFAULT_GAME_PROFILE: &str = "ue4.27";
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct GameQuirks: u32 {
+        const NO_COMPANION_PAK = 1 << 0; // the game checks pak signatures, so it won't mount UnrealEssentials_P.pak
+        const SKIP_DEPENDENCY_CHECK = 1 << 1; // the game mounts packages from outside of it's Paks folder, so imports can't be checked
+    }
+}
+
+// Which end of the container the container header chunk goes on. It's data is placed at the same end of the .ucas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContainerHeaderPosition {
+    First, // what the engine does for 4.25+ and 4.26, which some games rely on (Scarlet Nexus)
+    Last // 4.27, and works fine on the other versions
+}
+
+// Byte order of the TOC, container header, pak and the game's packages. Strings are hashed the same way for both, since
+// the engine hashes them on the PC that cooked the game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Endianness {
+    Little, // PC and every current console
+    Big // older consoles (toc-tool's --big-endian)
+}
+
+impl Endianness {
+    // Byte order of a serializer's type parameter, for readers that need to remember what they were read with
+    pub fn of<E: byteorder::ByteOrder>() -> Self {
+        match E::read_u16(&[0, 1]) {
+            1 => Endianness::Big,
+            _ => Endianness::Little
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameProfile {
+    pub id: &'static str, // used to pick the profile from C# (SetGameProfile) and toc-tool (--game)
+    pub name: &'static str,
+    pub engine_version: EngineVersion,
+    pub project_name: Option<&'static str>, // folder that's mounted as /Game. If this is None, it comes from the path to the game's Paks folder
+    pub compression_block_alignment: u32,
+    pub container_header_position: ContainerHeaderPosition,
+    pub custom_versions: &'static [(GUID, i32)], // custom versions that the game's packages are saved with, for cooked packages (see pak_package)
+    pub extensions: &'static [&'static str], // package types that go into the container, anything else goes into the pak
+    pub endianness: Endianness,
+    pub quirks: GameQuirks
+}
+
+impl GameProfile {
+    const fn standard(id: &'static str, name: &'static str, engine_version: EngineVersion) -> Self {
+        Self {
+            id,
+            name,
+            engine_version,
+            project_name: None,
+            compression_block_alignment: DEFAULT_COMPRESSION_BLOCK_ALIGNMENT,
+            container_header_position: ContainerHeaderPosition::Last,
+            custom_versions: &[],
+            extensions: SUITABLE_FILE_EXTENSIONS,
+            endianness: Endianness::Little,
+            quirks: GameQuirks::empty()
+        }
+    }
+
+    pub fn has_quirk(&self, quirk: GameQuirks) -> bool {
+        self.quirks.contains(quirk)
+    }
+
+    // Custom versions that a cooked package was saved with which are newer than the game's, as (key, package version, game version).
+    // The engine refuses to load these packages. Keys that the game doesn't know about are left to the engine to report
+    pub fn get_newer_custom_versions(&self, package_versions: &[CustomVersion]) -> Vec<(GUID, i32, i32)> {
+        package_versions.iter().filter_map(|v| {
+            let (_, game_version) = self.custom_versions.iter().find(|(key, _)| *key == v.key)?;
+            (v.version > *game_version).then_some((v.key, v.version, *game_version))
+        }).collect()
+    }
+}
+
+pub static GAME_PROFILES: &[GameProfile] = &[
+    GameProfile::standard("ue4.25+", "Unreal Engine 4.25+", EngineVersion::UE4_25Plus),
+    GameProfile::standard("ue4.26", "Unreal Engine 4.26", EngineVersion::UE4_26),
+    GameProfile::standard("ue4.27", "Unreal Engine 4.27", EngineVersion::UE4_27),
+    GameProfile {
+        project_name: Some("Xrd777"),
+        ..GameProfile::standard("p3r", "Persona 3 Reload", EngineVersion::UE4_27)
+    },
+    GameProfile {
+        project_name: Some("ScarletNexus"),
+        container_header_position: ContainerHeaderPosition::First,
+        ..GameProfile::standard("scarlet-nexus", "Scarlet Nexus", EngineVersion::UE4_25Plus)
+    }
+];
+
+pub fn find_game_profile(id: &str) -> Option<&'static GameProfile> {
+    GAME_PROFILES.iter().find(|p| p.id.eq_ignore_ascii_case(id))
+}
+
+// Select the profile used for the next TOC and pak that get built. This has to happen before mods are loaded, since the
+// asset collector uses it to decide which files go in the container
+pub fn set_game_profile(id: &str) -> Result<(), String> {
+    match find_game_profile(id) {
+        Some(profile) => {
+            unsafe { GAME_PROFILE = Some(profile.clone()); }
+            Ok(())
+        },
+        None => Err(format!("Unknown game profile \"{}\" (expected one of {})", id,
+            GAME_PROFILES.iter().map(|p| p.id).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+pub fn get_game_profile() -> &'static GameProfile {
+    unsafe { (*addr_of!(GAME_PROFILE)).as_ref() }.unwrap_or_else(|| find_game_profile(DEFAULT_GAME_PROFILE).unwrap())
+}
+
+// False if the default profile is being used because no profile was picked (see engine_detection)
+pub fn is_game_profile_selected() -> bool {
+    unsafe { (*addr_of!(GAME_PROFILE)).is_some() }
+}
+
+// The profile for games that use an unmodified version of the engine
+pub fn get_standard_profile(engine_version: EngineVersion) -> &'static GameProfile {
+    find_game_profile(match engine_version {
+        EngineVersion::UE4_25Plus => "ue4.25+",
+        EngineVersion::UE4_26 => "ue4.26",
+        EngineVersion::UE4_27 => "ue4.27"
+    }).unwrap()
+}
+
+// Used to override parts of the selected profile, such as toc-tool's --engine-version
+pub fn get_game_profile_mut() -> &'static mut GameProfile {
+    unsafe { (*addr_of_mut!(GAME_PROFILE)).get_or_insert_with(|| find_game_profile(DEFAULT_GAME_PROFILE).unwrap().clone()) }
+}
+
+pub fn is_container_extension(ext: &str) -> bool {
+    get_game_profile().extensions.contains(&ext)
+}
@@ -9,10 +9,12 @@ type ExportFilterFlags = u8; // and this one too...
 // Data: contents of .uexp - 4 magic bytes at end
 // Texture Bulk: all of .ubulk
 
-use byteorder::{NativeEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{ReadBytesExt, WriteBytesExt};
 use crate::{
+    game_profile::{self, Endianness},
     pak_package::{FObjectImport, FObjectExport, GameName, NameMap},
-    string::FMappedName,
+    script_objects,
+    string::{FMappedName, FString16, FStringSerializerHash, Hasher16, FStringSerializerText, NAME_HASH_ALGORITHM},
     toc_factory::{TocResolverCommon, TocResolverType2}
 };
 use std::{
@@ -29,21 +31,37 @@ pub enum IoStoreObjectIndex {
     Export(u64),            // type 0 (index, Export -> Export)
     ScriptImport(String),   // type 1 (string hash, represents Import mounted at /Script/...)
     PackageImport(String),  // type 2 (string hash, represents Import mounted at /Game/...)
-    Empty                   // type 3 (-1)
+    Empty,                  // type 3 (-1)
+    ImportHash(u64)         // type 1 or 2 read from an IO Store package, kept as is so that it's written back unchanged (see from_raw for it's name)
 }
 
 impl IoStoreObjectIndex {
     pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(&self, reader: &mut R) -> IoStoreObjectIndex {
-        let raw_value = reader.read_u64::<E>().unwrap();
-        let obj_type = raw_value & (3 << 62);
-        match obj_type {
-            0 => IoStoreObjectIndex::Export(0), // can't derive string name from hash, will likely need to separate this off to another type for container header building
-            1 => IoStoreObjectIndex::ScriptImport(String::new()),
+        IoStoreObjectIndex::from_raw(reader.read_u64::<E>().unwrap())
+    }
+    // Script imports are resolved to their path through the game's script objects (see script_objects) if global.utoc was loaded,
+    // otherwise the hash can't be reversed and the name is left empty. Package imports need the imported package's export hashes
+    pub fn from_raw(raw_value: u64) -> IoStoreObjectIndex {
+        match raw_value >> 62 {
+            0 => IoStoreObjectIndex::Export(raw_value),
+            1 => IoStoreObjectIndex::ScriptImport(script_objects::get_script_object_path(raw_value).unwrap_or_default().to_owned()),
             2 => IoStoreObjectIndex::PackageImport(String::new()),
-            3 => IoStoreObjectIndex::Empty,
-            _ => panic!("Invalid obj type {}", obj_type),
+            _ => IoStoreObjectIndex::Empty
+        }
+    }
+    pub fn from_raw_lossless(raw_value: u64) -> IoStoreObjectIndex {
+        match raw_value >> 62 {
+            0 => IoStoreObjectIndex::Export(raw_value),
+            1 | 2 => IoStoreObjectIndex::ImportHash(raw_value),
+            _ => IoStoreObjectIndex::Empty
         }
     }
+    // The hash that packages use to import a script object, such as the class of an export. '.' and ':' are hashed as '/'
+    // (/Script/Engine.DataTable is hashed as /script/engine/datatable)
+    pub fn get_script_import_hash(path: &str) -> u64 {
+        let hash = Hasher16::get_cityhash64(&path.replace(['.', ':'], "/"));
+        hash & !(3 << 62) | 1 << 62
+    }
     // TOOO: upgrade trait bounds to Write + Seek
     pub fn to_buffer<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
         match self {
@@ -51,19 +69,13 @@ impl IoStoreObjectIndex {
             Self::ScriptImport(v) => writer.write_u64::<E>(IoStoreObjectIndex::generate_hash(v, 1))?,
             Self::PackageImport(v) => writer.write_u64::<E>(IoStoreObjectIndex::generate_hash(v, 2))?,
             Self::Empty => writer.write_u64::<E>(u64::MAX)?,
+            Self::ImportHash(v) => writer.write_u64::<E>(*v)?,
         }
         Ok(())
     }
 
     fn generate_hash(import: &str, obj_type: u64) -> u64 {
-        println!("make hash for {}", import);
-        let to_hash = String::from(import).to_lowercase();
-        // hash chars are sized according to if the platform supports wide characters, which is usually the case
-        let to_hash: Vec<u16> = to_hash.encode_utf16().collect();
-        // safety: Vec is contiguous, so a Vec<u8> of length `2 * n` will take the same memory as a Vec<u16> of len `n`
-        let to_hash = unsafe { std::slice::from_raw_parts(to_hash.as_ptr() as *const u8, to_hash.len() * 2) };
-        // verified: the strings are identical (no null terminator) when using FString16
-        let mut hash: u64 = cityhasher::hash(to_hash); // cityhash it
+        let mut hash: u64 = Hasher16::get_cityhash64(import); // lowercase UTF-16, same as chunk ids
         hash &= !(3 << 62); // first 62 bits are our hash
         hash |= obj_type << 62; // stick the type in high 2 bits
         hash
@@ -101,11 +113,17 @@ pub struct PackageSummaryExports {
 }
 
 impl PackageSummaryExports {
-    fn get_export_count(&self) -> u64 {
-        (self.export_bundle_offset - self.export_offset) as u64 / IO_PACKAGE_FEXPORTMAP_SERIALIZED_SIZE
+    fn get_export_count(&self) -> Result<u64, Box<dyn Error>> {
+        let export_map_size = self.export_bundle_offset.checked_sub(self.export_offset)
+            .ok_or_else(|| format!("Export bundles start at 0x{:x}, before the export map at 0x{:x}", self.export_bundle_offset, self.export_offset))?;
+        Ok(export_map_size as u64 / IO_PACKAGE_FEXPORTMAP_SERIALIZED_SIZE)
     }
-    fn get_export_bundle_count(&self) -> u64 {
-        0
+    // Export bundles are stored as every FExportBundleHeader followed by every FExportBundleEntry, and take up everything
+    // between the export bundle offset and the graph offset
+    fn get_export_bundles_size(&self) -> Result<u64, Box<dyn Error>> {
+        let export_bundles_size = self.graph_offset.checked_sub(self.export_bundle_offset)
+            .ok_or_else(|| format!("Graph data starts at 0x{:x}, before the export bundles at 0x{:x}", self.graph_offset, self.export_bundle_offset))?;
+        Ok(export_bundles_size as u64)
     }
 }
 
@@ -141,21 +159,23 @@ impl PackageIoSummaryDeserialize for PackageSummary1 {
     }
 }
 
+pub const PACKAGE_SUMMARY_2_SERIALIZED_SIZE: u64 = 0x40;
 #[repr(C)]
+#[derive(Debug, Clone)]
 pub struct PackageSummary2 { // Unreal Engine 4.25+, 4.26-4.27 (normal, plus, chaos)
-    name: FMappedName,     
-    source_name: FMappedName,
-    package_flags: u32,
-    cooked_header_size: u32,
-    name_map_names_offset: i32,
-    name_map_names_size: i32,
-    name_map_hashes_offset: i32,
-    name_map_hashes_size: i32,
-    import_map_offset: i32,
-    export_map_offset: i32,
-    export_bundles_offset: i32,
-    graph_data_offset: i32,
-    graph_data_size: i32,
+    pub name: FMappedName,     
+    pub source_name: FMappedName,
+    pub package_flags: u32,
+    pub cooked_header_size: u32,
+    pub name_map_names_offset: i32,
+    pub name_map_names_size: i32,
+    pub name_map_hashes_offset: i32,
+    pub name_map_hashes_size: i32,
+    pub import_map_offset: i32,
+    pub export_map_offset: i32,
+    pub export_bundles_offset: i32,
+    pub graph_data_offset: i32,
+    pub graph_data_size: i32,
     pad: i32
 }
 
@@ -170,21 +190,22 @@ impl PackageIoSummaryDeserialize for PackageSummary2 {
 }
 
 impl PackageSummary2 {
-    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Self {
-        let name = reader.read_u64::<E>().unwrap().into();
-        let source_name = reader.read_u64::<E>().unwrap().into();
-        let package_flags = reader.read_u32::<E>().unwrap();
-        let cooked_header_size = reader.read_u32::<E>().unwrap();
-        let name_map_names_offset = reader.read_i32::<E>().unwrap();
-        let name_map_names_size = reader.read_i32::<E>().unwrap();
-        let name_map_hashes_offset = reader.read_i32::<E>().unwrap();
-        let name_map_hashes_size = reader.read_i32::<E>().unwrap();
-        let import_map_offset = reader.read_i32::<E>().unwrap();
-        let export_map_offset = reader.read_i32::<E>().unwrap();
-        let export_bundles_offset = reader.read_i32::<E>().unwrap();
-        let graph_data_offset = reader.read_i32::<E>().unwrap();
-        let graph_data_size = reader.read_i32::<E>().unwrap();
-        Self {
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let name = reader.read_u64::<E>()?.into(); // 0x0
+        let source_name = reader.read_u64::<E>()?.into(); // 0x8
+        let package_flags = reader.read_u32::<E>()?; // 0x10
+        let cooked_header_size = reader.read_u32::<E>()?; // 0x14
+        let name_map_names_offset = reader.read_i32::<E>()?; // 0x18
+        let name_map_names_size = reader.read_i32::<E>()?; // 0x1c
+        let name_map_hashes_offset = reader.read_i32::<E>()?; // 0x20
+        let name_map_hashes_size = reader.read_i32::<E>()?; // 0x24
+        let import_map_offset = reader.read_i32::<E>()?; // 0x28
+        let export_map_offset = reader.read_i32::<E>()?; // 0x2c
+        let export_bundles_offset = reader.read_i32::<E>()?; // 0x30
+        let graph_data_offset = reader.read_i32::<E>()?; // 0x34
+        let graph_data_size = reader.read_i32::<E>()?; // 0x38
+        reader.read_i32::<E>()?; // 0x3c padding
+        Ok(Self {
             name,
             source_name,
             package_flags,
@@ -199,7 +220,11 @@ impl PackageSummary2 {
             graph_data_offset,
             graph_data_size,
             pad: 0
-        }
+        })
+    }
+    // Exports are serialized directly after the graph data, which is the end of the package's header
+    pub fn get_header_size(&self) -> u64 {
+        (self.graph_data_offset + self.graph_data_size) as u64
     }
 }
 
@@ -245,49 +270,52 @@ pub struct ZenPackageSummaryType2 { // Unreal Engine 5.3 (untested)
 // ZenPackageSummaryType2 looks like it has something different going on with how it's dependency graph works
 // This can be worked on later
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct FGraphExternalArc {
-    from_export_bundle_index: u32,
-    to_export_bundle_index: u32
+    pub from_export_bundle_index: u32, // export bundle in the imported package
+    pub to_export_bundle_index: u32 // export bundle in this package
 }
 
 impl FGraphExternalArc {
-    fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Self {
-        let from_export_bundle_index = reader.read_u32::<E>().unwrap();
-        let to_export_bundle_index = reader.read_u32::<E>().unwrap();
-        Self { from_export_bundle_index, to_export_bundle_index }
+    fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let from_export_bundle_index = reader.read_u32::<E>()?;
+        let to_export_bundle_index = reader.read_u32::<E>()?;
+        Ok(Self { from_export_bundle_index, to_export_bundle_index })
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct FGraphPackage {
     pub imported_package_id: u64, // hashed
-    external_arcs: Vec<FGraphExternalArc>
+    pub external_arcs: Vec<FGraphExternalArc>
 }
 
 impl FGraphPackage {
-    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Self {
-        let imported_package_id = reader.read_u64::<E>().unwrap();
-        let external_arc_count = reader.read_u32::<E>().unwrap();
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let imported_package_id = reader.read_u64::<E>()?;
+        let external_arc_count = reader.read_u32::<E>()?;
         let mut external_arcs = Vec::with_capacity(external_arc_count as usize);
         for _ in 0..external_arc_count {
-            external_arcs.push(FGraphExternalArc::from_buffer::<R, E>(reader));
+            external_arcs.push(FGraphExternalArc::from_buffer::<R, E>(reader)?);
         }
-        Self {
+        Ok(Self {
             imported_package_id,
             external_arcs
-        }
+        })
     }
 
-    pub fn list_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Vec<Self> {
-        let imported_packages_count = reader.read_u32::<E>().unwrap();
+    pub fn list_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Vec<Self>, Box<dyn Error>> {
+        let imported_packages_count = reader.read_u32::<E>()?;
         let mut values = vec![];
         for _ in 0..imported_packages_count {
-            values.push(FGraphPackage::from_buffer::<R, E>(reader));
+            values.push(FGraphPackage::from_buffer::<R, E>(reader)?);
         }
-        values
+        Ok(values)
     }
 }
 
 #[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ExportBundleCommandType {
     Create = 0,
     Serialize,
@@ -304,15 +332,19 @@ impl TryFrom<u32> for ExportBundleCommandType {
         }
     }
 }
+pub const EXPORT_BUNDLE_HEADER_SERIALIZED_SIZE: u64 = 0x8;
+pub const EXPORT_BUNDLE_ENTRY_SERIALIZED_SIZE: u64 = 0x8;
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ExportBundleEntry { // same across all versions of Unreal Engine
-    local_export_index: u32,
-    command_type: ExportBundleCommandType
+    pub local_export_index: u32,
+    pub command_type: ExportBundleCommandType
 }
 pub trait ExportBundle {
-    // Create a list of export bundles from a serialized byte stream. It's up to the user to ensure that the cursor is in the correct position
-    fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Vec<ExportBundleEntry>, Box<dyn Error>>;
-    // Get the number of export bundles that a package has. This info is used to build it's entry in 
-    fn get_export_bundle_count(entries: &Vec<ExportBundleEntry>) -> u32;
+    // Read every export bundle header then the entries that they point to, given the size of the export bundles section. It's up to
+    // the user to ensure that the cursor is in the correct position
+    fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, export_bundles_size: u64) -> Result<Vec<Vec<ExportBundleEntry>>, Box<dyn Error>>;
+    // Get the number of exports referenced by a package's export bundles
+    fn get_export_count(bundles: &[Vec<ExportBundleEntry>]) -> u32;
 }
 
 #[repr(C)]
@@ -322,20 +354,41 @@ pub struct ExportBundleHeader4 { // Unreal Engine 4.25-4.27
 }
 
 impl ExportBundle for ExportBundleHeader4 {
-    fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Vec<ExportBundleEntry>, Box<dyn Error>> {
-        reader.read_u32::<E>()?; // FirstEntryIndex, not important
-        let entry_count = reader.read_u32::<E>()?;
-        let mut entries = Vec::with_capacity(entry_count as usize);
-        for _ in 0..entry_count {
-            let local_export_index = reader.read_u32::<E>()?;
-            let command_type = reader.read_u32::<E>()?.try_into()?;
-            entries.push(ExportBundleEntry{ local_export_index, command_type })
-        }
-        Ok(entries)
-    }
-    fn get_export_bundle_count(entries: &Vec<ExportBundleEntry>) -> u32 {
+    fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, export_bundles_size: u64) -> Result<Vec<Vec<ExportBundleEntry>>, Box<dyn Error>> {
+        // headers point to consecutive ranges of entries, so keep reading them until they and the entries they point to fill the section
+        let mut headers: Vec<ExportBundleHeader4> = vec![];
+        let mut entry_count = 0;
+        while (headers.len() as u64 * EXPORT_BUNDLE_HEADER_SERIALIZED_SIZE) + (entry_count * EXPORT_BUNDLE_ENTRY_SERIALIZED_SIZE) < export_bundles_size {
+            let first_entry_index = reader.read_u32::<E>()?;
+            let header_entry_count = reader.read_u32::<E>()?;
+            if first_entry_index as u64 != entry_count {
+                return Err(format!("Export bundle {} starts at entry {}, expected entry {}", headers.len(), first_entry_index, entry_count).into());
+            }
+            entry_count += header_entry_count as u64;
+            headers.push(ExportBundleHeader4 { first_entry_index, entry_count: header_entry_count });
+        }
+        let read_size = (headers.len() as u64 * EXPORT_BUNDLE_HEADER_SERIALIZED_SIZE) + (entry_count * EXPORT_BUNDLE_ENTRY_SERIALIZED_SIZE);
+        if read_size != export_bundles_size {
+            return Err(format!("{} export bundles take up 0x{:x} bytes, but the export bundles section is 0x{:x} bytes", headers.len(), read_size, export_bundles_size).into());
+        }
+        // entries for every bundle are stored together, each header points to a range of them
+        let entries_start = reader.stream_position()?;
+        let mut bundles = Vec::with_capacity(headers.len());
+        for header in &headers {
+            reader.seek(SeekFrom::Start(entries_start + header.first_entry_index as u64 * EXPORT_BUNDLE_ENTRY_SERIALIZED_SIZE))?;
+            let mut entries = Vec::with_capacity(header.entry_count as usize);
+            for _ in 0..header.entry_count {
+                let local_export_index = reader.read_u32::<E>()?;
+                let command_type = reader.read_u32::<E>()?.try_into()?;
+                entries.push(ExportBundleEntry{ local_export_index, command_type })
+            }
+            bundles.push(entries);
+        }
+        Ok(bundles)
+    }
+    fn get_export_count(bundles: &[Vec<ExportBundleEntry>]) -> u32 {
         let mut count = 0;
-        for i in entries {
+        for i in bundles.iter().flatten() {
             let export_count_maybe = i.local_export_index + 1;
             count = if export_count_maybe > count { export_count_maybe } else { count };
         }
@@ -379,45 +432,71 @@ impl ContainerHeaderPosition for ContainerHeaderPosition2 {
 
 pub const CONTAINER_HEADER_PACKAGE_SERIALIZED_SIZE: u64 = 0x20;
 pub const IO_PACKAGE_FEXPORTMAP_SERIALIZED_SIZE: u64 = 0x48;
+#[derive(Debug, Clone, PartialEq)]
 pub struct ContainerHeaderPackage {
     // An export bundle's entry in a container header
     pub hash: u64,
-    export_bundle_size: u64,
-    export_count: u32,
-    export_bundle_count: u32,
-    load_order: u32,
-    import_ids: Vec<u64>
+    pub export_bundle_size: u64,
+    pub export_count: u32,
+    pub export_bundle_count: u32,
+    pub load_order: u32,
+    pub import_ids: Vec<u64>
 }
 
 impl ContainerHeaderPackage {
+    // Read the store entry at index from a container header's StoreEntries. The imported package ids are stored after every
+    // store entry, with ImportedPackages containing an offset to them that's relative to the ImportedPackages field (0x18)
+    pub fn from_buffer_store_entry<E: byteorder::ByteOrder>(store_entries: &[u8], index: usize, hash: u64) -> Result<Self, Box<dyn Error>> {
+        let entry_offset = index as u64 * CONTAINER_HEADER_PACKAGE_SERIALIZED_SIZE;
+        let mut reader = Cursor::new(store_entries);
+        reader.seek(SeekFrom::Start(entry_offset))?;
+        let export_bundle_size = reader.read_u64::<E>()?; // 0x0
+        let export_count = reader.read_u32::<E>()?; // 0x8
+        let export_bundle_count = reader.read_u32::<E>()?; // 0xc
+        let load_order = reader.read_u32::<E>()?; // 0x10
+        reader.seek(SeekFrom::Current(4))?; // 0x14 padding
+        let imported_package_count = reader.read_u32::<E>()?; // 0x18
+        let relative_offset = reader.read_u32::<E>()?; // 0x1c
+        let mut import_ids = Vec::with_capacity(imported_package_count as usize);
+        if imported_package_count > 0 {
+            reader.seek(SeekFrom::Start(entry_offset + 0x18 + relative_offset as u64))?;
+            for _ in 0..imported_package_count {
+                import_ids.push(reader.read_u64::<E>()?);
+            }
+        }
+        Ok(Self { hash, export_bundle_size, export_count, export_bundle_count, load_order, import_ids })
+    }
+
     // Parse the package file to extract the values needed to build a store entry in the container header
     pub fn from_package_summary<
         TExportBundle: ExportBundle,
         TSummary: PackageIoSummaryDeserialize,
         TReader: Read + Seek,
         TByteOrder: byteorder::ByteOrder
-    >(file_reader: &mut TReader, hash: u64, size: u64) -> Self { // consume the file object, we're only going to need it in here
-        type Endian = byteorder::NativeEndian;
-        let package_summary = TSummary::to_package_summary::<TReader, TByteOrder>(file_reader).unwrap();
-        let export_count = package_summary.get_export_count() as u32;
-        file_reader.seek(SeekFrom::Start(package_summary.export_bundle_offset as u64)).unwrap(); // jump to FExportBundleHeader start
-        let export_bundles = TExportBundle::from_buffer::<TReader, Endian>(file_reader).unwrap(); // Deserialize ExportBundle to get export bundle count
-        let export_bundle_count = TExportBundle::get_export_bundle_count(&export_bundles); // Go through each export bundle to look for the highest index
-        file_reader.seek(SeekFrom::Start(package_summary.graph_offset as u64)).unwrap(); // go to FGraphPackage (imported_packages_count)
-        let graph_packages = FGraphPackage::list_from_buffer::<TReader, Endian>(file_reader);
+    >(file_reader: &mut TReader, hash: u64, size: u64) -> Result<Self, Box<dyn Error>> { // consume the file object, we're only going to need it in here
+        let package_summary = TSummary::to_package_summary::<TReader, TByteOrder>(file_reader)?;
+        let export_count = package_summary.get_export_count()? as u32;
+        file_reader.seek(SeekFrom::Start(package_summary.export_bundle_offset as u64))?; // jump to FExportBundleHeader start
+        let export_bundles = TExportBundle::from_buffer::<TReader, TByteOrder>(file_reader, package_summary.get_export_bundles_size()?)?; // Deserialize ExportBundle to check that it's export count matches
+        let export_bundle_count = export_bundles.len() as u32;
+        if TExportBundle::get_export_count(&export_bundles) > export_count {
+            println!("WARNING: Package {:X} has export bundles that reference more exports than it's export map contains", hash);
+        }
+        file_reader.seek(SeekFrom::Start(package_summary.graph_offset as u64))?; // go to FGraphPackage (imported_packages_count)
+        let graph_packages = FGraphPackage::list_from_buffer::<TReader, TByteOrder>(file_reader)?;
         let mut import_ids = Vec::with_capacity(graph_packages.len());
         for i in &graph_packages {
             import_ids.push(i.imported_package_id);
         }
-        let load_order = 0; // This doesn't seem to matter?
-        Self {
+        let load_order = 0; // set once every package is known, see ContainerHeader::set_load_order
+        Ok(Self {
             hash,
             export_bundle_size: size,
             export_count,
             export_bundle_count,
             load_order,
             import_ids
-        }
+        })
     }
     // Do a very incomplete serialization of an IO Store packaged asset to obtain it's export count, export bundle count and imported packages
     // Imports are Header.ExportMapOffset - Header.ImportMapOffset / 8
@@ -439,7 +518,7 @@ impl ContainerHeaderPackage {
         let imported_package_count = reader.read_u32::<E>().unwrap();
         let mut import_ids: Vec<u64> = Vec::with_capacity(imported_package_count as usize);
         for _ in 0..imported_package_count {
-            import_ids.push(FGraphPackage::from_buffer::<R, E>(reader).imported_package_id);
+            import_ids.push(FGraphPackage::from_buffer::<R, E>(reader).unwrap().imported_package_id);
         }
         let load_order = 0; // For now, we'll see if this makes things crash
         Self {
@@ -455,8 +534,7 @@ impl ContainerHeaderPackage {
     pub fn to_buffer_store_entry<W: Write + Seek, E: byteorder::ByteOrder>(&self, writer: &mut W, base_offset: u64, curr_offset: &mut u64) -> Result<(), Box<dyn Error>> {
         writer.write_u64::<E>(self.export_bundle_size)?; // 0x0
         writer.write_u32::<E>(self.export_count)?; // 0x8
-        //writer.write_u32::<E>(self.export_bundle_count)?; // 0xc
-        writer.write_u32::<E>(1)?; // 0xc
+        writer.write_u32::<E>(self.export_bundle_count)?; // 0xc
         writer.write_u32::<E>(self.load_order)?; // 0x10
         writer.write_u32::<E>(0)?; // 0x14 padding
         let relative_offset = if self.import_ids.len() > 0 { Some((base_offset + *curr_offset - writer.stream_position().unwrap()) as u32) } else { None };
@@ -479,7 +557,7 @@ impl ContainerHeaderPackage {
 // Support for directly using cooked assets will hopefully be working soon...
 pub const UASSET_MAGIC: u32 = 0x9E2A83C1;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ObjectExport2 { // Unreal Engine 4.25+, 4.26-4.27 
     pub cooked_serial_offset: i64,
     pub cooked_serial_size: i64,
@@ -514,9 +592,36 @@ impl ObjectExport2 {
         Ok(())
     }
 
+    // Object indices are kept as they were read (see IoStoreObjectIndex::from_raw_lossless), use IoPackage to get their names
+    pub fn from_buffer<R: Read, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let cooked_serial_offset = reader.read_i64::<E>()?; // 0x0
+        let cooked_serial_size = reader.read_i64::<E>()?; // 0x8
+        let object_name = reader.read_u64::<E>()?.into(); // 0x10
+        let outer_index = IoStoreObjectIndex::from_raw_lossless(reader.read_u64::<E>()?); // 0x18
+        let class_name = IoStoreObjectIndex::from_raw_lossless(reader.read_u64::<E>()?); // 0x20
+        let super_name = IoStoreObjectIndex::from_raw_lossless(reader.read_u64::<E>()?); // 0x28
+        let template_name = IoStoreObjectIndex::from_raw_lossless(reader.read_u64::<E>()?); // 0x30
+        let global_import_name = IoStoreObjectIndex::from_raw_lossless(reader.read_u64::<E>()?); // 0x38
+        let object_flags = reader.read_u32::<E>()?; // 0x40
+        let filter_flags = reader.read_u8()?; // 0x44
+        reader.read_u24::<E>()?; // 0x45 padding
+        Ok(Self {
+            cooked_serial_offset,
+            cooked_serial_size,
+            object_name,
+            outer_index,
+            class_name,
+            super_name,
+            template_name,
+            global_import_name,
+            object_flags,
+            filter_flags
+        })
+    }
+
     pub fn to_buffer<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
-        writer.write_i64::<E>(self.cooked_serial_offset);
-        writer.write_i64::<E>(self.cooked_serial_size);
+        writer.write_i64::<E>(self.cooked_serial_offset)?;
+        writer.write_i64::<E>(self.cooked_serial_size)?;
         writer.write_u64::<E>(self.object_name.into())?; // object_name
         self.outer_index.to_buffer::<W, E>(writer)?;
         self.class_name.to_buffer::<W, E>(writer)?;
@@ -524,16 +629,264 @@ impl ObjectExport2 {
         self.template_name.to_buffer::<W, E>(writer)?;
         self.global_import_name.to_buffer::<W, E>(writer)?;
         writer.write_u32::<E>(self.object_flags)?;
-        writer.write_u32::<E>(0)?; // filter flags
+        writer.write_u8(self.filter_flags)?;
+        writer.write_u24::<E>(0)?; // padding
         Ok(())
     }
 }
 
+// A fully deserialized IO Store package for 4.25+, 4.26 and 4.27. The header is laid out as:
+//  FPackageSummary
+//  Name map: names as a name batch (FString16 text), then the name hash algorithm id and a hash for each name
+//  Import map: FPackageObjectIndex for each import
+//  Export map: FExportMapEntry (ObjectExport2) for each export
+//  Export bundles: every FExportBundleHeader, then every FExportBundleEntry
+//  Graph data: FGraphPackage for each imported package
+// followed by the serialized data of every export, in the order that the export bundles serialize them
+#[derive(Debug, Clone)]
+pub struct IoPackage {
+    pub summary: PackageSummary2,
+    pub names: Vec<String>,
+    pub imports: Vec<u64>, // FPackageObjectIndex, see get_import
+    pub exports: Vec<ObjectExport2>,
+    pub export_bundles: Vec<Vec<ExportBundleEntry>>,
+    pub graph_packages: Vec<FGraphPackage>,
+    pub export_data_ranges: Vec<Option<(u64, u64)>>, // (offset, size) of each export's data in the package that was read, None if it's never serialized
+    pub export_data: Vec<Vec<u8>>, // serialized data of each export, which can be edited before calling to_buffer
+    pub size: u64
+}
+
+impl IoPackage {
+    // The reader should be at the start of the package, with size being the size of the whole package
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, size: u64) -> Result<Self, Box<dyn Error>> {
+        let start = reader.stream_position()?;
+        let summary = PackageSummary2::from_buffer::<R, E>(reader)?;
+        let header_size = summary.get_header_size();
+        let offsets = [summary.name_map_names_offset, summary.name_map_hashes_offset, summary.import_map_offset,
+            summary.export_map_offset, summary.export_bundles_offset, summary.graph_data_offset];
+        if offsets.iter().any(|o| *o < PACKAGE_SUMMARY_2_SERIALIZED_SIZE as i32 || *o as u64 > header_size) || header_size > size {
+            return Err(format!("Package summary has offsets outside of the package's header (header is 0x{:x} bytes, package is 0x{:x} bytes)", header_size, size).into());
+        }
+        // name map
+        let name_count = (summary.name_map_hashes_size as u64 / 8).saturating_sub(1) as usize; // first hash is the algorithm id
+        reader.seek(SeekFrom::Start(start + summary.name_map_names_offset as u64))?;
+        let mut names = Vec::with_capacity(name_count);
+        for _ in 0..name_count {
+            names.push(FString16::from_buffer_text::<R, E>(reader)?);
+        }
+        // import map
+        reader.seek(SeekFrom::Start(start + summary.import_map_offset as u64))?;
+        let import_count = (summary.export_map_offset - summary.import_map_offset).max(0) as u64 / 8;
+        let mut imports = Vec::with_capacity(import_count as usize);
+        for _ in 0..import_count {
+            imports.push(reader.read_u64::<E>()?);
+        }
+        // export map
+        reader.seek(SeekFrom::Start(start + summary.export_map_offset as u64))?;
+        let export_count = (summary.export_bundles_offset - summary.export_map_offset).max(0) as u64 / IO_PACKAGE_FEXPORTMAP_SERIALIZED_SIZE;
+        let mut exports = Vec::with_capacity(export_count as usize);
+        for _ in 0..export_count {
+            exports.push(ObjectExport2::from_buffer::<R, E>(reader)?);
+        }
+        // export bundles
+        let export_bundles_size = PackageSummaryExports {
+            export_offset: summary.export_map_offset as u32,
+            export_bundle_offset: summary.export_bundles_offset as u32,
+            graph_offset: summary.graph_data_offset as u32
+        }.get_export_bundles_size()?;
+        reader.seek(SeekFrom::Start(start + summary.export_bundles_offset as u64))?;
+        let export_bundles = ExportBundleHeader4::from_buffer::<R, E>(reader, export_bundles_size)?;
+        // graph data
+        reader.seek(SeekFrom::Start(start + summary.graph_data_offset as u64))?;
+        let graph_packages = FGraphPackage::list_from_buffer::<R, E>(reader)?;
+        // export data is laid out in the order that the bundles serialize each export
+        let mut export_data_ranges = vec![None; exports.len()];
+        let mut data_offset = header_size;
+        for entry in export_bundles.iter().flatten().filter(|e| e.command_type == ExportBundleCommandType::Serialize) {
+            let export = exports.get(entry.local_export_index as usize)
+                .ok_or_else(|| format!("Export bundle serializes export {}, but there are only {} exports", entry.local_export_index, exports.len()))?;
+            let export_size = export.cooked_serial_size as u64;
+            if data_offset + export_size > size {
+                return Err(format!("Export {} ends at 0x{:x}, past the end of the package (0x{:x})", entry.local_export_index, data_offset + export_size, size).into());
+            }
+            export_data_ranges[entry.local_export_index as usize] = Some((data_offset, export_size));
+            data_offset += export_size;
+        }
+        let mut export_data = Vec::with_capacity(exports.len());
+        for range in &export_data_ranges {
+            let mut data = vec![];
+            if let Some((offset, size)) = range {
+                reader.seek(SeekFrom::Start(start + offset))?;
+                data.resize(*size as usize, 0);
+                reader.read_exact(&mut data)?;
+            }
+            export_data.push(data);
+        }
+        Ok(Self { summary, names, imports, exports, export_bundles, graph_packages, export_data_ranges, export_data, size })
+    }
+
+    // Write the package back out, recalculating everything that depends on the names, imports, exports and export data:
+    //  - summary offsets and sizes
+    //  - each export's cooked serial size, and it's cooked serial offset moved by the change in size of the exports before it
+    //  - export bundles, with exports that aren't in any bundle added to the last one
+    //  - graph data, without arcs to export bundles that don't exist
+    pub fn to_buffer<W: Write + Seek, E: byteorder::ByteOrder>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        if self.export_data.len() != self.exports.len() {
+            return Err(format!("Package has {} exports but data for {}", self.exports.len(), self.export_data.len()).into());
+        }
+        let mut header = Cursor::new(vec![0; PACKAGE_SUMMARY_2_SERIALIZED_SIZE as usize]);
+        header.seek(SeekFrom::End(0))?;
+        // name map
+        let name_map_names_offset = header.position();
+        for name in &self.names {
+            FString16::to_buffer_text::<Cursor<Vec<u8>>, E>(name, &mut header)?;
+        }
+        let name_map_names_size = header.position() - name_map_names_offset;
+        let alignment = (8 - header.position() % 8) % 8;
+        header.write_all(&vec![0; alignment as usize])?;
+        let name_map_hashes_offset = header.position();
+        header.write_u64::<E>(NAME_HASH_ALGORITHM)?;
+        for name in &self.names {
+            FString16::to_buffer_hash::<Cursor<Vec<u8>>, E>(name, &mut header)?;
+        }
+        let name_map_hashes_size = header.position() - name_map_hashes_offset;
+        // import map
+        let import_map_offset = header.position();
+        for import in &self.imports {
+            header.write_u64::<E>(*import)?;
+        }
+        // export map
+        let export_map_offset = header.position();
+        for export in self.get_updated_exports() {
+            export.to_buffer::<Cursor<Vec<u8>>, E>(&mut header)?;
+        }
+        // export bundles
+        let export_bundles_offset = header.position();
+        let export_bundles = self.get_updated_export_bundles()?;
+        let mut first_entry_index = 0;
+        for bundle in &export_bundles {
+            header.write_u32::<E>(first_entry_index)?;
+            header.write_u32::<E>(bundle.len() as u32)?;
+            first_entry_index += bundle.len() as u32;
+        }
+        for entry in export_bundles.iter().flatten() {
+            header.write_u32::<E>(entry.local_export_index)?;
+            header.write_u32::<E>(entry.command_type as u32)?;
+        }
+        // graph data
+        let graph_data_offset = header.position();
+        header.write_u32::<E>(self.graph_packages.len() as u32)?;
+        for package in &self.graph_packages {
+            let arcs: Vec<_> = package.external_arcs.iter().filter(|a| (a.to_export_bundle_index as usize) < export_bundles.len()).collect();
+            header.write_u64::<E>(package.imported_package_id)?;
+            header.write_u32::<E>(arcs.len() as u32)?;
+            for arc in arcs {
+                header.write_u32::<E>(arc.from_export_bundle_index)?;
+                header.write_u32::<E>(arc.to_export_bundle_index)?;
+            }
+        }
+        let graph_data_size = header.position() - graph_data_offset;
+        // summary
+        header.seek(SeekFrom::Start(0))?;
+        header.write_u64::<E>(self.summary.name.into())?;
+        header.write_u64::<E>(self.summary.source_name.into())?;
+        header.write_u32::<E>(self.summary.package_flags)?;
+        header.write_u32::<E>(self.summary.cooked_header_size)?;
+        for value in [name_map_names_offset, name_map_names_size, name_map_hashes_offset, name_map_hashes_size,
+            import_map_offset, export_map_offset, export_bundles_offset, graph_data_offset, graph_data_size] {
+            header.write_i32::<E>(value.try_into()?)?;
+        }
+        writer.write_all(&header.into_inner())?;
+        // export data, in the order that the bundles serialize it
+        for entry in export_bundles.iter().flatten().filter(|e| e.command_type == ExportBundleCommandType::Serialize) {
+            writer.write_all(&self.export_data[entry.local_export_index as usize])?;
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes<E: byteorder::ByteOrder>(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut writer = Cursor::new(vec![]);
+        self.to_buffer::<Cursor<Vec<u8>>, E>(&mut writer)?;
+        Ok(writer.into_inner())
+    }
+
+    // Cooked serial offsets point into the cooked package (.uasset + .uexp), where exports are stored in the order of their serial offset
+    fn get_updated_exports(&self) -> Vec<ObjectExport2> {
+        let mut exports = self.exports.clone();
+        let mut cooked_order: Vec<usize> = (0..exports.len()).collect();
+        cooked_order.sort_by_key(|i| exports[*i].cooked_serial_offset);
+        let mut offset_change = 0;
+        for i in cooked_order {
+            let new_size = self.export_data[i].len() as i64;
+            exports[i].cooked_serial_offset += offset_change;
+            offset_change += new_size - exports[i].cooked_serial_size;
+            exports[i].cooked_serial_size = new_size;
+        }
+        exports
+    }
+
+    fn get_updated_export_bundles(&self) -> Result<Vec<Vec<ExportBundleEntry>>, Box<dyn Error>> {
+        let mut export_bundles = self.export_bundles.clone();
+        if let Some(entry) = export_bundles.iter().flatten().find(|e| e.local_export_index as usize >= self.exports.len()) {
+            return Err(format!("Export bundle references export {}, but there are only {} exports", entry.local_export_index, self.exports.len()).into());
+        }
+        let mut bundled = vec![false; self.exports.len()];
+        export_bundles.iter().flatten().for_each(|e| bundled[e.local_export_index as usize] = true);
+        let missing: Vec<u32> = (0..self.exports.len() as u32).filter(|i| !bundled[*i as usize]).collect();
+        if !missing.is_empty() {
+            if export_bundles.is_empty() {
+                export_bundles.push(vec![]);
+            }
+            let last_bundle = export_bundles.last_mut().unwrap();
+            for command_type in [ExportBundleCommandType::Create, ExportBundleCommandType::Serialize] {
+                last_bundle.extend(missing.iter().map(|i| ExportBundleEntry { local_export_index: *i, command_type }));
+            }
+        }
+        Ok(export_bundles)
+    }
+
+    pub fn from_file(path: &std::path::Path) -> Result<Self, Box<dyn Error>> {
+        let size = std::fs::metadata(path)?.len();
+        let mut reader = BufReader::new(File::open(path)?);
+        match game_profile::get_game_profile().endianness {
+            Endianness::Little => Self::from_buffer::<BufReader<File>, byteorder::LittleEndian>(&mut reader, size),
+            Endianness::Big => Self::from_buffer::<BufReader<File>, byteorder::BigEndian>(&mut reader, size)
+        }
+    }
+
+    // FName numbers are stored + 1, with 0 meaning no number
+    pub fn get_name(&self, name: FMappedName) -> Option<String> {
+        let value = self.names.get(name.get_name_index() as usize)?;
+        Some(match name.get_extra_index() {
+            0 => value.to_owned(),
+            n => format!("{}_{}", value, n - 1)
+        })
+    }
+
+    pub fn get_package_name(&self) -> Option<String> {
+        self.get_name(self.summary.name)
+    }
+
+    pub fn get_export_name(&self, index: usize) -> Option<String> {
+        self.get_name(self.exports.get(index)?.object_name)
+    }
+
+    // Script imports are resolved through the game's script objects if they've been loaded
+    pub fn get_import(&self, index: usize) -> Option<IoStoreObjectIndex> {
+        Some(IoStoreObjectIndex::from_raw(*self.imports.get(index)?))
+    }
+
+    pub fn get_export_data<'a>(&self, package: &'a [u8], index: usize) -> Option<&'a [u8]> {
+        let (offset, size) = (*self.export_data_ranges.get(index)?)?;
+        package.get(offset as usize..(offset + size) as usize)
+    }
+}
+
 // Check that the first bytes of the file don't contain the magic used for cooked assets
 pub fn is_valid_asset_type<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> bool {
     reader.seek(SeekFrom::Start(0));
     let magic_check = reader.read_u32::<E>().unwrap();
-    magic_check != UASSET_MAGIC
+    magic_check != UASSET_MAGIC && magic_check.swap_bytes() != UASSET_MAGIC // cooked packages for either byte order
 }
 
 /*
@@ -1,21 +1,35 @@
 use bitflags::bitflags;
 use byteorder::{ReadBytesExt, WriteBytesExt};
 use crate::{
+    asset_collector::TocFileSource,
     io_package::FGraphPackage,
-    string::{FString32NoHash, FStringSerializer, Hasher, Hasher16},
+    string::{FString16, FString32NoHash, FStringDeserializer, FStringSerializer, Hasher, Hasher16},
 };
 #[cfg(feature = "hash_meta")]
 use sha1::{Sha1, Digest};
 use std::{
     cmp::Ordering,
+    collections::{HashMap, HashSet},
     error::Error,
-    io::{Cursor, Read, Seek, SeekFrom, Write}
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    mem
 };
 
 pub type IoContainerId = u64; // TODO: ContainerID is a UID as a CityHash64 of the container name
                               // represent that with a distinct CityHashID type
 pub type GUID = u128;
 
+// Entry counts are read from the file, so check that there's enough data left for them before allocating the list
+pub fn get_list_capacity<R: Read + Seek>(reader: &mut R, count: usize, entry_size: usize) -> Result<usize, Box<dyn Error>> {
+    let position = reader.stream_position()?;
+    let remaining = reader.seek(SeekFrom::End(0))?.saturating_sub(position);
+    reader.seek(SeekFrom::Start(position))?;
+    match count.checked_mul(entry_size) {
+        Some(size) if size as u64 <= remaining => Ok(count),
+        _ => Err(format!("List of {} entries at 0x{:x} is larger than the data after it", count, position).into())
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 #[repr(u8)]
 #[allow(dead_code)]
@@ -58,7 +72,8 @@ impl From<u8> for IoStoreTocVersion {
 }
 
 bitflags! {
-    struct IoContainerFlags : u8 {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct IoContainerFlags : u8 {
         const NoFlags = 0;
         const Compressed = 1 << 0;
         const Encrypted = 1 << 1;
@@ -158,7 +173,7 @@ impl IoStoreTocHeaderCommon for IoStoreTocHeaderType2 {
         writer.write_u8(self.container_flags.bits())?;
         writer.write_u24::<E>(0)?; // padding
         for _ in 0..15 {
-            writer.write_u64::<E>(0)?; // padding
+            writer.write_u32::<E>(0)?; // padding
         }
         Ok(())
     }
@@ -166,24 +181,76 @@ impl IoStoreTocHeaderCommon for IoStoreTocHeaderType2 {
 
 #[repr(C)]
 pub struct IoStoreTocHeaderType3 { // Unreal Engine 4.27
-    toc_magic: [u8; 0x10],
-    version: IoStoreTocVersion,
-    toc_header_size: u32,
-    toc_entry_count: u32,
-    toc_compressed_block_entry_count: u32,
-    toc_compressed_block_entry_size: u32, // for sanity checking
-    compression_method_name_count: u32,
-    compression_method_name_length: u32,
-    compression_block_size: u32,
-    directory_index_size: u32,
-    partition_count: u32,
-    container_id: IoContainerId, 
-    encryption_key_guid: GUID,
-    container_flags: IoContainerFlags,
-    partition_size: u64,
+    pub toc_magic: [u8; 0x10],
+    pub version: IoStoreTocVersion,
+    pub toc_header_size: u32,
+    pub toc_entry_count: u32,
+    pub toc_compressed_block_entry_count: u32,
+    pub toc_compressed_block_entry_size: u32, // for sanity checking
+    pub compression_method_name_count: u32,
+    pub compression_method_name_length: u32,
+    pub compression_block_size: u32,
+    pub directory_index_size: u32,
+    pub partition_count: u32,
+    pub container_id: IoContainerId, 
+    pub encryption_key_guid: GUID,
+    pub container_flags: IoContainerFlags,
+    pub partition_size: u64,
     reserved: [u64; 6]
 }
 
+impl IoStoreTocHeaderType3 {
+    // Read the header of an existing TOC. 4.25+ and 4.26 TOCs share the same layout, they just leave the partition fields
+    // as zero, so this is used to read every TOC version from 4.25+ to 4.27
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let mut toc_magic = [0; 0x10];
+        reader.read_exact(&mut toc_magic)?; // 0x0
+        if toc_magic != IO_STORE_TOC_MAGIC {
+            return Err("File is not an IO Store TOC (magic doesn't match)".into());
+        }
+        let version = reader.read_u8()?;
+        if version < IoStoreTocVersion::DirectoryIndex.into() || version > IoStoreTocVersion::PartitionSize.into() {
+            return Err(format!("Unsupported IO Store TOC version {} (only 4.25+ to 4.27 TOCs can be read)", version).into());
+        }
+        let version = IoStoreTocVersion::from(version);
+        reader.read_u24::<E>()?; // padding
+        let toc_header_size = reader.read_u32::<E>()?;
+        let toc_entry_count = reader.read_u32::<E>()?;
+        let toc_compressed_block_entry_count = reader.read_u32::<E>()?;
+        let toc_compressed_block_entry_size = reader.read_u32::<E>()?;
+        let compression_method_name_count = reader.read_u32::<E>()?;
+        let compression_method_name_length = reader.read_u32::<E>()?;
+        let compression_block_size = reader.read_u32::<E>()?;
+        let directory_index_size = reader.read_u32::<E>()?;
+        let partition_count = reader.read_u32::<E>()?;
+        let container_id = reader.read_u64::<E>()?;
+        let encryption_key_guid = reader.read_u128::<E>()?;
+        let container_flags = IoContainerFlags::from_bits_retain(reader.read_u8()?);
+        reader.read_u24::<E>()?; // padding
+        reader.read_u32::<E>()?; // padding
+        let partition_size = reader.read_u64::<E>()?;
+        reader.seek(SeekFrom::Start(toc_header_size as u64))?; // skip reserved
+        Ok(Self {
+            toc_magic,
+            version,
+            toc_header_size,
+            toc_entry_count,
+            toc_compressed_block_entry_count,
+            toc_compressed_block_entry_size,
+            compression_method_name_count,
+            compression_method_name_length,
+            compression_block_size,
+            directory_index_size,
+            partition_count,
+            container_id,
+            encryption_key_guid,
+            container_flags,
+            partition_size,
+            reserved: [0; 6]
+        })
+    }
+}
+
 impl IoStoreTocHeaderCommon for IoStoreTocHeaderType3 {
     fn new(container_id: u64, entries: u32, compressed_blocks: u32, compression_block_size: u32, dir_index_size: u32) -> impl IoStoreTocHeaderCommon {
         Self {
@@ -274,6 +341,7 @@ pub enum IoChunkType4 {
 impl From<u8> for IoChunkType4 {
     fn from(value: u8) -> Self {
         match value {
+            0 => IoChunkType4::Invalid,
             1 => IoChunkType4::InstallManifest,
             2 => IoChunkType4::ExportBundleData,
             3 => IoChunkType4::BulkData,
@@ -419,6 +487,23 @@ impl IoChunkId {
         }
         Ok(())
     }
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let hash = reader.read_u64::<E>()?; // 0x0
+        let index = reader.read_u16::<E>()?; // 0x8
+        reader.read_u8()?; // 0xa: padding
+        let obj_type = reader.read_u8()?; // 0xb
+        if obj_type > IoChunkType4::ContainerHeader.into() {
+            return Err(format!("Invalid chunk type {} (is this a UE5 container?)", obj_type).into());
+        }
+        Ok(Self { hash, index, obj_type: obj_type.into() })
+    }
+    pub fn list_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, count: usize) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut list = Vec::with_capacity(get_list_capacity(reader, count, 0xc)?);
+        for _ in 0..count {
+            list.push(IoChunkId::from_buffer::<R, E>(reader)?);
+        }
+        Ok(list)
+    }
     pub fn get_raw_hash(&self) -> u64 {
         self.hash
     }
@@ -458,6 +543,22 @@ impl IoOffsetAndLength {
         }
         Ok(())
     }
+    pub fn list_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, count: usize) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut list = Vec::with_capacity(get_list_capacity(reader, count, 0xa)?);
+        for _ in 0..count {
+            let mut data = [0; 0xa];
+            reader.read_exact(&mut data)?;
+            list.push(Self { data });
+        }
+        Ok(list)
+    }
+    // both values are stored as 5 byte big endian integers
+    pub fn get_offset(&self) -> u64 {
+        self.data[0..5].iter().fold(0, |v, b| v << 8 | *b as u64)
+    }
+    pub fn get_length(&self) -> u64 {
+        self.data[5..10].iter().fold(0, |v, b| v << 8 | *b as u64)
+    }
 }
 
 // (UE 5 ONLY) Perfect Hash
@@ -471,13 +572,12 @@ pub struct IoStoreTocCompressedBlockEntry {
 }
 
 impl IoStoreTocCompressedBlockEntry {
+    // The fields are packed into bytes rather than serialized, so they're little endian no matter which byte order the TOC is in
     pub fn new(offset: u64, length: u32) -> Self {
         type ByteBlock = Cursor<[u8; 0xc]>;
-        type E = byteorder::NativeEndian;
         let mut byte_builder = Cursor::new([0; 0xc]);
-        byte_builder.write_u32::<E>(offset as u32).unwrap();
-        byte_builder.seek(SeekFrom::Current(1)); // 0x4
-        let cmp_size = &length.to_ne_bytes()[0..3];
+        byte_builder.write_all(&offset.to_le_bytes()[0..5]).unwrap(); // 0x0
+        let cmp_size = &length.to_le_bytes()[0..3];
         byte_builder.write_all(cmp_size).unwrap(); // cmp_size
         byte_builder.write_all(cmp_size).unwrap(); // decmp_size
         Self { data: byte_builder.into_inner() }
@@ -492,6 +592,29 @@ impl IoStoreTocCompressedBlockEntry {
         }
         Ok(())
     }
+    pub fn list_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, count: usize) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut list = Vec::with_capacity(get_list_capacity(reader, count, 0xc)?);
+        for _ in 0..count {
+            let mut data = [0; 0xc];
+            reader.read_exact(&mut data)?;
+            list.push(Self { data });
+        }
+        Ok(list)
+    }
+    // 0x0: offset (5 bytes), 0x5: compressed size (3 bytes), 0x8: uncompressed size (3 bytes), 0xb: compression method index
+    pub fn get_offset(&self) -> u64 {
+        self.data[0..5].iter().rev().fold(0, |v, b| v << 8 | *b as u64)
+    }
+    pub fn get_compressed_size(&self) -> u32 {
+        self.data[5..8].iter().rev().fold(0, |v, b| v << 8 | *b as u32)
+    }
+    pub fn get_uncompressed_size(&self) -> u32 {
+        self.data[8..11].iter().rev().fold(0, |v, b| v << 8 | *b as u32)
+    }
+    // 0 is uncompressed, otherwise it's an index into the TOC's compression method names + 1
+    pub fn get_compression_method_index(&self) -> u8 {
+        self.data[11]
+    }
 }
 
 // (usually, compression info and signature data would be included here, but we have no reason to
@@ -525,6 +648,19 @@ impl IoDirectoryIndexEntry {
         }
         Ok(())
     }
+
+    pub fn list_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Vec<Self>, Box<dyn Error>> {
+        let count = reader.read_u32::<E>()?;
+        let mut list = Vec::with_capacity(get_list_capacity(reader, count as usize, 0x10)?);
+        for _ in 0..count {
+            let name = reader.read_u32::<E>()?;
+            let first_child = reader.read_u32::<E>()?;
+            let next_sibling = reader.read_u32::<E>()?;
+            let first_file = reader.read_u32::<E>()?;
+            list.push(Self { name, first_child, next_sibling, first_file });
+        }
+        Ok(list)
+    }
 }
 
 pub const IO_FILE_INDEX_ENTRY_SERIALIZED_SIZE: usize = 0xc;
@@ -538,7 +674,8 @@ pub struct IoFileIndexEntry {
     pub user_data: u32, // id for FIoChunkId, and FIoOffsetAndLength
     // NOT SERIALIZED
     pub file_size: u64,
-    pub os_path: String,
+    pub file_name: String,
+    pub source: TocFileSource,
     pub hash_path: String,
 }
 
@@ -557,6 +694,22 @@ impl IoFileIndexEntry {
         }
         Ok(())
     }
+
+    // Only the serialized fields are read, the rest are left empty since they're only used while building a TOC
+    pub fn list_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Vec<Self>, Box<dyn Error>> {
+        let count = reader.read_u32::<E>()?;
+        let mut list = Vec::with_capacity(get_list_capacity(reader, count as usize, 0xc)?);
+        for _ in 0..count {
+            let name = reader.read_u32::<E>()?;
+            let next_file = reader.read_u32::<E>()?;
+            let user_data = reader.read_u32::<E>()?;
+            list.push(Self { 
+                name, next_file, user_data, 
+                file_size: 0, file_name: String::new(), source: TocFileSource::OsPath(String::new()), hash_path: String::new()
+            });
+        }
+        Ok(list)
+    }
 }
 
 pub struct IoStringPool;
@@ -569,6 +722,14 @@ impl IoStringPool {
         }
         Ok(())
     }
+    pub fn list_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Vec<String>, Box<dyn Error>> {
+        let count = reader.read_u32::<E>()?;
+        let mut list = Vec::with_capacity(get_list_capacity(reader, count as usize, mem::size_of::<u32>())?); // at least the length
+        for _ in 0..count {
+            list.push(FString32NoHash::from_buffer::<R, E>(reader)?.unwrap_or_default());
+        }
+        Ok(list)
+    }
 }
 
 // NON NATIVE - REQUIRES SERIALIZATION
@@ -628,13 +789,165 @@ impl IoStoreTocEntryMeta {
 }
 
 pub struct ContainerHeader {
-    container_id: u64,
+    pub container_id: u64,
     pub packages: Vec<crate::io_package::ContainerHeaderPackage>,
+    pub names: Vec<String>, // only filled when reading, the emulator doesn't write any names
+    pub culture_package_map: Vec<(String, Vec<(u64, u64)>)>, // culture => (source package id, localized package id)
+    pub package_redirects: Vec<(u64, u64)>, // source package id => target package id
+    // lookups for packages and names, since game headers are merged together and can have tens of thousands of packages. packages
+    // need to be added through add_package to keep these in sync
+    package_indices: HashMap<u64, usize>, // package id => index in packages
+    name_set: HashSet<String>
 }
 impl ContainerHeader {
     // Write package header data into ucas
     pub fn new(container_id: u64) -> Self {
-        Self { container_id, packages: vec![] }
+        Self {
+            container_id, packages: vec![], names: vec![], culture_package_map: vec![], package_redirects: vec![],
+            package_indices: HashMap::new(), name_set: HashSet::new()
+        }
+    }
+
+    // Read an existing container header (4.25+ to 4.27), this is the last chunk in a .ucas
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let container_id = reader.read_u64::<E>()?;
+        let package_count = reader.read_u32::<E>()?;
+        let names = Self::read_byte_array::<R, E>(reader)?;
+        let name_hashes = Self::read_byte_array::<R, E>(reader)?;
+        let names = Self::read_name_batch::<E>(&names, name_hashes.len().saturating_sub(8) / 8)?;
+        let package_id_count = reader.read_u32::<E>()?;
+        if package_id_count != package_count {
+            return Err(format!("Container header has {} packages but {} package ids", package_count, package_id_count).into());
+        }
+        let mut package_ids = Vec::with_capacity(package_count as usize);
+        for _ in 0..package_count {
+            package_ids.push(reader.read_u64::<E>()?);
+        }
+        let store_entries = Self::read_byte_array::<R, E>(reader)?;
+        let mut packages = Vec::with_capacity(package_count as usize);
+        for (i, hash) in package_ids.into_iter().enumerate() {
+            packages.push(crate::io_package::ContainerHeaderPackage::from_buffer_store_entry::<E>(&store_entries, i, hash)?);
+        }
+        let culture_count = reader.read_u32::<E>()?;
+        let mut culture_package_map = Vec::with_capacity(culture_count as usize);
+        for _ in 0..culture_count {
+            let culture = FString32NoHash::from_buffer::<R, E>(reader)?.unwrap_or_default();
+            culture_package_map.push((culture, Self::read_package_id_pairs::<R, E>(reader)?));
+        }
+        let package_redirects = Self::read_package_id_pairs::<R, E>(reader)?;
+        let package_indices = packages.iter().enumerate().map(|(i, p)| (p.hash, i)).collect();
+        let name_set = names.iter().cloned().collect();
+        Ok(Self { container_id, packages, names, culture_package_map, package_redirects, package_indices, name_set })
+    }
+
+    fn read_byte_array<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Vec<u8>, Box<dyn Error>> {
+        let len = reader.read_u32::<E>()?;
+        let mut buf = vec![0; len as usize];
+        reader.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_package_id_pairs<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Vec<(u64, u64)>, Box<dyn Error>> {
+        let count = reader.read_u32::<E>()?;
+        let mut pairs = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            pairs.push((reader.read_u64::<E>()?, reader.read_u64::<E>()?));
+        }
+        Ok(pairs)
+    }
+
+    // Names are a name batch without the hashes (which are in NameHashes): a two byte header for each name (high bit of the first byte
+    // is set for UTF-16, the rest is the length), followed by the name's characters
+    pub fn read_name_batch<E: byteorder::ByteOrder>(names: &[u8], count: usize) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut reader = Cursor::new(names);
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            values.push(FString16::from_buffer_text::<Cursor<&[u8]>, E>(&mut reader)?);
+        }
+        Ok(values)
+    }
+
+    // Load order is a topological order of the import graph for the packages in this container, so that a package always comes after
+    // the packages that it imports. Imports from outside of the container are ignored, and cycles are broken at the package that
+    // was visited first
+    pub fn set_load_order(&mut self) {
+        let mut visited = vec![false; self.packages.len()];
+        let mut load_order = 0;
+        for i in 0..self.packages.len() {
+            if visited[i] {
+                continue;
+            }
+            // iterative DFS so that long import chains can't overflow the stack. the second value is the next import to check
+            let mut stack = vec![(i, 0)];
+            visited[i] = true;
+            while let Some((curr, next_import)) = stack.pop() {
+                match self.packages[curr].import_ids.get(next_import) {
+                    Some(import_id) => {
+                        stack.push((curr, next_import + 1));
+                        if let Some(&import) = self.package_indices.get(import_id) {
+                            if !visited[import] {
+                                visited[import] = true;
+                                stack.push((import, 0));
+                            }
+                        }
+                    },
+                    None => {
+                        self.packages[curr].load_order = load_order;
+                        load_order += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // Map a package to the package that replaces it when the game is set to culture
+    pub fn add_localized_package(&mut self, culture: &str, source_package_id: u64, localized_package_id: u64) {
+        match self.culture_package_map.iter_mut().find(|(c, _)| c == culture) {
+            Some((_, pairs)) => pairs.push((source_package_id, localized_package_id)),
+            None => self.culture_package_map.push((culture.to_owned(), vec![(source_package_id, localized_package_id)]))
+        }
+    }
+
+    pub fn get_package(&self, package_id: u64) -> Option<&crate::io_package::ContainerHeaderPackage> {
+        self.package_indices.get(&package_id).map(|&i| &self.packages[i])
+    }
+
+    // Add a package to the end of the header, unless a package with the same id is already in it
+    pub fn add_package(&mut self, package: crate::io_package::ContainerHeaderPackage) -> bool {
+        if self.contains_package(package.hash) {
+            return false;
+        }
+        self.package_indices.insert(package.hash, self.packages.len());
+        self.packages.push(package);
+        true
+    }
+
+    pub fn contains_package(&self, package_id: u64) -> bool {
+        self.package_indices.contains_key(&package_id)
+    }
+
+    // Combine another container's header into this one. Packages, localized packages and redirects that are already in this
+    // header are kept, so merge headers in order of priority (e.g the highest patch container first)
+    pub fn merge(&mut self, other: ContainerHeader) {
+        for package in other.packages {
+            self.add_package(package);
+        }
+        for name in other.names {
+            if self.name_set.insert(name.clone()) {
+                self.names.push(name);
+            }
+        }
+        for (culture, other_pairs) in other.culture_package_map {
+            match self.culture_package_map.iter_mut().find(|(c, _)| c.eq_ignore_ascii_case(&culture)) {
+                Some((_, pairs)) => {
+                    let mut sources: HashSet<u64> = pairs.iter().map(|p| p.0).collect();
+                    pairs.extend(other_pairs.into_iter().filter(|p| sources.insert(p.0)));
+                },
+                None => self.culture_package_map.push((culture, other_pairs))
+            }
+        }
+        let mut redirect_sources: HashSet<u64> = self.package_redirects.iter().map(|r| r.0).collect();
+        self.package_redirects.extend(other.package_redirects.into_iter().filter(|r| redirect_sources.insert(r.0)));
     }
     pub fn to_buffer<W: Write + Seek, E: byteorder::ByteOrder>(&self, writer: &mut W) -> Result<Vec<u8>, Box<dyn Error>> {
         // Container Header:
@@ -643,8 +956,8 @@ impl ContainerHeader {
         // - Name Hashes - one entry containing FNameHash::AlgorithmId
         // - Package Ids - hashes of each export bundle
         // - Store Entries - store entry data for each export bundle (import ids => graph package ids)
-        // Culture Package Map - empty
-        // Package Redirects - empty
+        // Culture Package Map - culture name => (source package id, localized package id) for packages in Content/L10N/[Culture]
+        // Package Redirects - source package id => target package id, declared in PackageRedirects.txt
         // Padding to align it to nearest 0x10
         let mut container_header_writer: Cursor<Vec<u8>> = Cursor::new(vec![]);
         container_header_writer.write_u64::<E>(self.container_id)?;
@@ -667,8 +980,20 @@ impl ContainerHeader {
         let store_entry_writer = store_entry_writer.into_inner();
         container_header_writer.write_u32::<E>(store_entry_writer.len() as u32)?;
         container_header_writer.write_all(&store_entry_writer);
-        container_header_writer.write_u32::<E>(0)?; // CulturePackageMap
-        container_header_writer.write_u32::<E>(0)?; // PackageRedirectss
+        container_header_writer.write_u32::<E>(self.culture_package_map.len() as u32)?; // CulturePackageMap
+        for (culture, pairs) in &self.culture_package_map {
+            FString32NoHash::to_buffer::<Cursor<Vec<u8>>, E>(culture, &mut container_header_writer)?;
+            container_header_writer.write_u32::<E>(pairs.len() as u32)?;
+            for (source, localized) in pairs {
+                container_header_writer.write_u64::<E>(*source)?;
+                container_header_writer.write_u64::<E>(*localized)?;
+            }
+        }
+        container_header_writer.write_u32::<E>(self.package_redirects.len() as u32)?; // PackageRedirects
+        for (source, target) in &self.package_redirects {
+            container_header_writer.write_u64::<E>(*source)?;
+            container_header_writer.write_u64::<E>(*target)?;
+        }
         let serialized = container_header_writer.into_inner();
         writer.write_all(&serialized); // Write into main buffer, then align to the nearest 0x10
         //PartitionSerializer::new(0x10).to_buffer_alignment::<W, E>(writer);
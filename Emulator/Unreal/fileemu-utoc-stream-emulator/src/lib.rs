@@ -2,10 +2,25 @@
 //! Here's the crate documentation.
 
 pub mod asset_collector; // Building tree of directories/files
+pub mod compression; // Decompressing IO Store compression blocks
+pub mod datatable_merge; // Merge rows of DataTables that are replaced by more than one mod
+pub mod delta_patch; // Apply mods' delta patches to the files that they replace
+pub mod dependency_check; // Find missing imports and import cycles in mod packages
+pub mod encryption; // AES keys for encrypted containers
+pub mod engine_detection; // Detect the engine version from the game's containers
 pub mod exports; // FFI (called from C#)
+pub mod game_packages; // Packages from the base game's containers
+pub mod game_profile; // Per game settings (engine version, project name, container layout, quirks)
 pub mod io_package; // Handling IO Store packages
 pub mod io_toc; // Types for IO Store Table of Contents
+pub mod pak_factory; // Build a pak for files that IO Store can't carry
 pub mod pak_package; // Handling cooked packages (WIP)
+pub mod pak_reader; // Read existing paks
+pub mod script_objects; // Script objects from the game's global container
+pub mod toc_extractor; // Extract files from IO Store containers
 pub mod toc_factory; // Build IO Store TOC
+pub mod toc_reader; // Read existing IO Store TOC
+pub mod unversioned; // Unversioned property serialization
+pub mod usmap; // Class and struct layouts for unversioned properties
 pub mod platform; // Platform agnostic abstractions
 pub mod string; // Unreal serialized string types
\ No newline at end of file
@@ -0,0 +1,440 @@
+use byteorder::WriteBytesExt;
+use sha1::{Sha1, Digest};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    io::{Cursor, Seek, Write},
+    path::{Path, PathBuf},
+    ptr::{addr_of, addr_of_mut},
+    rc::Rc
+};
+use crate::{
+    asset_collector::{MOUNT_POINT, PAK_ROOT_DIRECTORY, PROJECT_NAME, TocDirectoryRef, TocFileRef, TocFileSource},
+    game_profile::{self, Endianness, GameQuirks},
+    string::{FString32NoHash, FStringSerializer, Hasher},
+    toc_factory::{self, EngineVersion, PartitionBlock}
+};
+
+// IO Store can only carry packages (uasset, ubulk, uptnl), so every other file that mods add (sound banks, movies, json,
+// shader libraries...) goes into a pak with the same name as our container. The game has to mount this pak before it's
+// allowed to mount the .utoc, so this is built on it's own when the game first opens it rather than alongside the TOC.
+// Entries are uncompressed and unencrypted, so the pak is served the same way as the .ucas: each entry's header comes from
+// memory and it's data is read straight from the mod's file, followed by the index and footer
+pub const TARGET_PAK: &str = "UnrealEssentials_P.pak";
+
+pub static mut PAK_ENTRIES_OSPATH_POOL: Option<Vec<String>> = None;
+pub static mut PAK_ENTRIES_MEMORY_POOL: Option<Vec<Rc<Vec<u8>>>> = None; // entry headers and memory backed files
+pub static mut PAK_DATA: Option<PakData> = None;
+
+pub const PAK_MAGIC: u32 = 0x5A6F12E1;
+pub const PAK_COMPRESSION_METHOD_COUNT: usize = 5; // 4.22 (the first v8 pak) only had 4, but 4.23+ all use 5
+pub const PAK_COMPRESSION_METHOD_NAME_LENGTH: usize = 0x20;
+// FPakEntry for an uncompressed entry: offset, size, uncompressed size, compression method, SHA1 hash, flags and block size
+pub const PAK_ENTRY_SERIALIZED_SIZE: u64 = 0x35;
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum PakVersion {
+    V8 = 8, // FNameBasedCompressionMethod (4.22 - 4.24)
+    V9 = 9, // FrozenIndex (4.25)
+    V10 = 10, // PathHashIndex
+    V11 = 11 // Fnv64BugFix (4.26 - 4.27)
+}
+
+impl From<EngineVersion> for PakVersion {
+    fn from(value: EngineVersion) -> Self {
+        match value {
+            EngineVersion::UE4_25Plus => PakVersion::V9,
+            EngineVersion::UE4_26 | EngineVersion::UE4_27 => PakVersion::V11
+        }
+    }
+}
+
+impl TryFrom<u32> for PakVersion {
+    type Error = String;
+    fn try_from(value: u32) -> Result<PakVersion, Self::Error> {
+        match value {
+            8 => Ok(PakVersion::V8),
+            9 => Ok(PakVersion::V9),
+            10 => Ok(PakVersion::V10),
+            11 => Ok(PakVersion::V11),
+            _ => Err(format!("Unsupported pak version {} (expected 8 to 11)", value))
+        }
+    }
+}
+
+impl PakVersion {
+    // v10 replaced the list of entries in the index with entries packed into a byte array, which are found through a hash of
+    // their path and optionally a full directory index. v10 hashed the path with a broken FNV64, so for that version only the
+    // directory index is written, which the engine falls back to when there's no path hash index
+    pub fn has_encoded_entries(&self) -> bool {
+        *self >= PakVersion::V10
+    }
+    pub fn has_path_hash_index(&self) -> bool {
+        *self >= PakVersion::V11
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PakEntry {
+    pub path: String, // relative to the mount point
+    pub offset: u64, // offset of the entry's header, data comes right after it
+    pub size: u64,
+    pub source: String // display path of the file that it's read from
+}
+
+impl PakEntry {
+    // Full FPakEntry, used as the header before each entry's data and for each entry in pre-v10 indexes. The header before the
+    // data has an offset of 0, since the engine gets the offset from the index
+    pub fn to_buffer<W: Write + Seek, E: byteorder::ByteOrder>(&self, writer: &mut W, offset: u64) -> Result<(), Box<dyn Error>> {
+        writer.write_u64::<E>(offset)?;
+        writer.write_u64::<E>(self.size)?; // size
+        writer.write_u64::<E>(self.size)?; // uncompressed size
+        writer.write_u32::<E>(0)?; // compression method (none)
+        // Like IO Store's meta hashes, these are only checked by signed paks and would require reading every file, so leave them empty
+        writer.write_all(&[0; 20])?;
+        writer.write_u8(0)?; // flags (encrypted, deleted)
+        writer.write_u32::<E>(0)?; // compression block size
+        Ok(())
+    }
+    // FPakFile::EncodePakEntry for an uncompressed, unencrypted entry. The flags say which fields fit in 32 bits, and block size,
+    // block count, encryption and compression method are all 0
+    pub fn to_buffer_encoded<W: Write + Seek, E: byteorder::ByteOrder>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        let offset_32_bit = self.offset <= u32::MAX as u64;
+        let size_32_bit = self.size <= u32::MAX as u64;
+        let flags = (offset_32_bit as u32) << 31 | (size_32_bit as u32) << 30 | (size_32_bit as u32) << 29;
+        writer.write_u32::<E>(flags)?;
+        match offset_32_bit {
+            true => writer.write_u32::<E>(self.offset as u32)?,
+            false => writer.write_u64::<E>(self.offset)?
+        }
+        match size_32_bit { // uncompressed size (compressed size is only written for compressed entries)
+            true => writer.write_u32::<E>(self.size as u32)?,
+            false => writer.write_u64::<E>(self.size)?
+        }
+        Ok(())
+    }
+}
+
+// Path hash for the path hash index: FNV64 of the lowercased UTF-16 path, with the seed added to the offset basis
+pub fn get_path_hash(path: &str, seed: u64) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x00000100000001b3;
+    let mut hash = FNV_OFFSET.wrapping_add(seed);
+    for byte in path.to_lowercase().encode_utf16().flat_map(u16::to_le_bytes) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+fn get_sha1(buffer: &[u8]) -> [u8; 20] {
+    Sha1::digest(buffer).into()
+}
+
+// Split a path relative to the mount point into it's directory and file name (Engine/Content/Sound.bnk => Engine/Content/, Sound.bnk)
+// Files at the mount point are in the root directory, "/"
+fn split_path(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(i) => (&path[..i + 1], &path[i + 1..]),
+        None => ("/", path)
+    }
+}
+
+pub struct PakData {
+    pub version: PakVersion,
+    pub mount_point: String,
+    pub entries: Vec<PakEntry>,
+    pub failed_to_read: Vec<String>, // files that were left out, or replaced by a lower priority mod's file
+    index: Vec<u8>, // index (primary index, path hash index and directory index) and footer
+    virtual_blocks: Vec<PartitionBlock>
+}
+
+impl PakData {
+    pub fn get_size(&self) -> u64 {
+        self.entries.last().map_or(0, |e| e.offset + PAK_ENTRY_SERIALIZED_SIZE + e.size) + self.index.len() as u64
+    }
+
+    pub fn print(&self) {
+        println!("PAK BUILDER: {}", TARGET_PAK);
+        println!("Version: {}, mount point: {}", self.version as u32, self.mount_point);
+        println!("{} entries, {} bytes total", self.entries.len(), self.get_size());
+        for entry in &self.entries {
+            println!("  {} (0x{:x}, {} bytes) from {}", entry.path, entry.offset, entry.size, entry.source);
+        }
+        if !self.failed_to_read.is_empty() {
+            println!("{} files failed to read:", self.failed_to_read.len());
+            for i in &self.failed_to_read {
+                println!("  {}", i);
+            }
+        }
+    }
+
+    // Entries don't need to be aligned, so their data follows straight after their header
+    fn add_entry<E: byteorder::ByteOrder>(&mut self, path: String, file: &TocFileRef) -> Result<(), Box<dyn Error>> {
+        let file = file.borrow();
+        let offset = self.entries.last().map_or(0, |e| e.offset + PAK_ENTRY_SERIALIZED_SIZE + e.size);
+        let entry = PakEntry { path, offset, size: file.file_size, source: file.source.get_display_path().to_owned() };
+        let mut header = Cursor::new(Vec::with_capacity(PAK_ENTRY_SERIALIZED_SIZE as usize));
+        entry.to_buffer::<Cursor<Vec<u8>>, E>(&mut header, 0)?;
+        let header = Rc::new(header.into_inner());
+        self.virtual_blocks.push(PartitionBlock::new_memory(header.as_ptr(), offset, PAK_ENTRY_SERIALIZED_SIZE));
+        unsafe { (*addr_of_mut!(PAK_ENTRIES_MEMORY_POOL)).as_mut().unwrap().push(header); }
+        let data_start = offset + PAK_ENTRY_SERIALIZED_SIZE;
+        if file.file_size > 0 {
+            let block = match &file.source {
+                TocFileSource::OsPath(os_path) => {
+                    unsafe { (*addr_of_mut!(PAK_ENTRIES_OSPATH_POOL)).as_mut().unwrap().push(os_path.to_owned() + "\0"); } // make C formatted string
+                    let curr_ospath = unsafe { (*addr_of!(PAK_ENTRIES_OSPATH_POOL)).as_ref().unwrap().last().unwrap() };
+                    PartitionBlock::new_file(curr_ospath.as_ptr(), 0, data_start, file.file_size)
+                },
+                TocFileSource::OsPathSlice(os_path, file_offset) => {
+                    unsafe { (*addr_of_mut!(PAK_ENTRIES_OSPATH_POOL)).as_mut().unwrap().push(os_path.to_owned() + "\0"); }
+                    let curr_ospath = unsafe { (*addr_of!(PAK_ENTRIES_OSPATH_POOL)).as_ref().unwrap().last().unwrap() };
+                    PartitionBlock::new_file(curr_ospath.as_ptr(), *file_offset, data_start, file.file_size)
+                },
+                TocFileSource::Memory(buffer) => {
+                    unsafe { (*addr_of_mut!(PAK_ENTRIES_MEMORY_POOL)).as_mut().unwrap().push(Rc::clone(buffer)); }
+                    PartitionBlock::new_memory(buffer.as_ptr(), data_start, file.file_size)
+                },
+                TocFileSource::Producer(_) | TocFileSource::DeltaPatch(_) =>
+                    return Err(format!("{} wasn't resolved before being added to the pak", &entry.path).into())
+            };
+            self.virtual_blocks.push(block);
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    // Full directory index: every directory (including each parent directory) mapped to the files that it contains
+    fn get_directory_index(&self, entry_locations: &[i32]) -> BTreeMap<&str, BTreeMap<&str, i32>> {
+        let mut directories: BTreeMap<&str, BTreeMap<&str, i32>> = BTreeMap::new();
+        for (entry, location) in self.entries.iter().zip(entry_locations) {
+            let (directory, file_name) = split_path(&entry.path);
+            directories.entry(directory).or_default().insert(file_name, *location);
+            let mut parent = directory;
+            while parent != "/" {
+                parent = split_path(parent.trim_end_matches('/')).0;
+                directories.entry(parent).or_default();
+            }
+        }
+        directories
+    }
+
+    fn serialize_index<W: Write + Seek, E: byteorder::ByteOrder>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        let index_offset = self.entries.last().map_or(0, |e| e.offset + PAK_ENTRY_SERIALIZED_SIZE + e.size);
+        let mut primary_index: Cursor<Vec<u8>> = Cursor::new(vec![]);
+        let mut secondary_indexes: Cursor<Vec<u8>> = Cursor::new(vec![]);
+        FString32NoHash::to_buffer::<Cursor<Vec<u8>>, E>(&self.mount_point, &mut primary_index)?;
+        primary_index.write_u32::<E>(self.entries.len() as u32)?;
+        if self.version.has_encoded_entries() {
+            let mut encoded_entries: Cursor<Vec<u8>> = Cursor::new(vec![]);
+            let mut entry_locations = Vec::with_capacity(self.entries.len());
+            for entry in &self.entries {
+                entry_locations.push(encoded_entries.position() as i32);
+                entry.to_buffer_encoded::<Cursor<Vec<u8>>, E>(&mut encoded_entries)?;
+            }
+            // The seed only has to be unique to this pak, the engine reads it back from the index
+            let path_hash_seed = Hasher::get_cityhash64(&TARGET_PAK.to_lowercase());
+            let mut path_hash_index: Cursor<Vec<u8>> = Cursor::new(vec![]);
+            if self.version.has_path_hash_index() {
+                path_hash_index.write_u32::<E>(self.entries.len() as u32)?;
+                for (entry, location) in self.entries.iter().zip(&entry_locations) {
+                    path_hash_index.write_u64::<E>(get_path_hash(&entry.path, path_hash_seed))?;
+                    path_hash_index.write_i32::<E>(*location)?;
+                }
+                path_hash_index.write_u32::<E>(0)?; // pruned directory index, only used when the full directory index isn't loaded
+            }
+            let mut directory_index: Cursor<Vec<u8>> = Cursor::new(vec![]);
+            let directories = self.get_directory_index(&entry_locations);
+            directory_index.write_u32::<E>(directories.len() as u32)?;
+            for (directory, files) in directories {
+                FString32NoHash::to_buffer::<Cursor<Vec<u8>>, E>(directory, &mut directory_index)?;
+                directory_index.write_u32::<E>(files.len() as u32)?;
+                for (file_name, location) in files {
+                    FString32NoHash::to_buffer::<Cursor<Vec<u8>>, E>(file_name, &mut directory_index)?;
+                    directory_index.write_i32::<E>(location)?;
+                }
+            }
+            // The path hash and directory indexes are placed after the primary index, which has a fixed size from here
+            let primary_index_size = primary_index.position() + 8
+                + 4 + if self.version.has_path_hash_index() { 0x24 } else { 0 }
+                + 4 + 0x24
+                + 4 + encoded_entries.get_ref().len() as u64
+                + 4;
+            let path_hash_index_offset = index_offset + primary_index_size;
+            let directory_index_offset = path_hash_index_offset + path_hash_index.get_ref().len() as u64;
+            primary_index.write_u64::<E>(path_hash_seed)?;
+            primary_index.write_u32::<E>(self.version.has_path_hash_index() as u32)?;
+            if self.version.has_path_hash_index() {
+                primary_index.write_u64::<E>(path_hash_index_offset)?;
+                primary_index.write_u64::<E>(path_hash_index.get_ref().len() as u64)?;
+                primary_index.write_all(&get_sha1(path_hash_index.get_ref()))?;
+            }
+            primary_index.write_u32::<E>(1)?; // has full directory index
+            primary_index.write_u64::<E>(directory_index_offset)?;
+            primary_index.write_u64::<E>(directory_index.get_ref().len() as u64)?;
+            primary_index.write_all(&get_sha1(directory_index.get_ref()))?;
+            primary_index.write_u32::<E>(encoded_entries.get_ref().len() as u32)?;
+            primary_index.write_all(encoded_entries.get_ref())?;
+            primary_index.write_u32::<E>(0)?; // entries that can't be encoded
+            assert_eq!(primary_index.position(), primary_index_size, "Pak primary index size was miscalculated");
+            secondary_indexes.write_all(path_hash_index.get_ref())?;
+            secondary_indexes.write_all(directory_index.get_ref())?;
+        } else {
+            for entry in &self.entries {
+                FString32NoHash::to_buffer::<Cursor<Vec<u8>>, E>(&entry.path, &mut primary_index)?;
+                entry.to_buffer::<Cursor<Vec<u8>>, E>(&mut primary_index, entry.offset)?;
+            }
+        }
+        writer.write_all(primary_index.get_ref())?;
+        writer.write_all(secondary_indexes.get_ref())?;
+        // FPakInfo
+        writer.write_all(&[0; 0x10])?; // encryption key guid
+        writer.write_u8(0)?; // encrypted index
+        writer.write_u32::<E>(PAK_MAGIC)?;
+        writer.write_u32::<E>(self.version as u32)?;
+        writer.write_u64::<E>(index_offset)?;
+        writer.write_u64::<E>(primary_index.get_ref().len() as u64)?;
+        writer.write_all(&get_sha1(primary_index.get_ref()))?;
+        if self.version == PakVersion::V9 {
+            writer.write_u8(0)?; // index is frozen
+        }
+        // compression method names, all empty since nothing is compressed
+        writer.write_all(&[0; PAK_COMPRESSION_METHOD_NAME_LENGTH * PAK_COMPRESSION_METHOD_COUNT])?;
+        Ok(())
+    }
+}
+
+// Directories at the root of a mod's UnrealEssentials_P.utoc folder are inside of the game's project folder
+// (../../../[ProjectName]/Content/...), except for Engine, which is it's own folder next to the project
+fn get_pak_path(dir_names: &[String], file_name: &str, project_name: &str) -> String {
+    let mut components: Vec<&str> = vec![];
+    if dir_names.first().is_none_or(|d| d != "Engine") {
+        components.push(project_name);
+    }
+    components.extend(dir_names.iter().map(|d| d.as_str()));
+    components.push(file_name);
+    components.join("/")
+}
+
+fn flatten_pak_tree(dir: TocDirectoryRef, dir_names: &mut Vec<String>, files: &mut Vec<(Vec<String>, TocFileRef)>) {
+    let mut curr_file = dir.borrow().first_file.as_ref().map(Rc::clone);
+    while let Some(file) = curr_file {
+        files.push((dir_names.clone(), Rc::clone(&file)));
+        curr_file = file.borrow().next.as_ref().map(Rc::clone);
+    }
+    let mut curr_dir = dir.borrow().first_child.as_ref().map(Rc::clone);
+    while let Some(child) = curr_dir {
+        dir_names.push(child.borrow().name.clone());
+        flatten_pak_tree(Rc::clone(&child), dir_names, files);
+        dir_names.pop();
+        curr_dir = child.borrow().next_sibling.as_ref().map(Rc::clone);
+    }
+}
+
+// The game's project folder name, from the pak's path in [GameRoot]/[ProjectName]/Content/Paks/
+pub fn get_project_name(pak_path: &Path) -> Option<String> {
+    let paks = pak_path.parent()?;
+    let content = paks.parent()?;
+    if !paks.file_name()?.eq_ignore_ascii_case("Paks") || !content.file_name()?.eq_ignore_ascii_case("Content") {
+        return None;
+    }
+    Some(content.parent()?.file_name()?.to_str()?.to_owned())
+}
+
+// Build the pak from every file in the pak tree. Returns false if no mods added any files that need to go in the pak
+pub fn build_pak(project_name: &str) -> Result<bool, Box<dyn Error>> {
+    let root = match unsafe { &*addr_of!(PAK_ROOT_DIRECTORY) } {
+        Some(root) => Rc::clone(root),
+        None => return Ok(false)
+    };
+    let mut files = vec![];
+    flatten_pak_tree(root, &mut vec![], &mut files);
+    if files.is_empty() {
+        return Ok(false);
+    }
+    let game_profile = game_profile::get_game_profile();
+    if game_profile.has_quirk(GameQuirks::NO_COMPANION_PAK) {
+        println!("WARNING: {} won't mount {}, so {} files that aren't packages were left out", game_profile.name, TARGET_PAK, files.len());
+        return Ok(false);
+    }
+    unsafe { PAK_ENTRIES_OSPATH_POOL = Some(Vec::with_capacity(files.len())); }
+    unsafe { PAK_ENTRIES_MEMORY_POOL = Some(Vec::with_capacity(files.len())); }
+    let mut pak = PakData {
+        version: PakVersion::from(game_profile.engine_version),
+        mount_point: MOUNT_POINT.to_owned(),
+        entries: Vec::with_capacity(files.len()),
+        failed_to_read: vec![],
+        index: vec![],
+        virtual_blocks: vec![]
+    };
+    match game_profile.endianness {
+        Endianness::Little => add_pak_entries::<byteorder::LittleEndian>(&mut pak, &files, project_name)?,
+        Endianness::Big => add_pak_entries::<byteorder::BigEndian>(&mut pak, &files, project_name)?
+    }
+    unsafe { PAK_DATA = Some(pak); }
+    Ok(true)
+}
+
+fn add_pak_entries<E: byteorder::ByteOrder>(pak: &mut PakData, files: &[(Vec<String>, TocFileRef)], project_name: &str) -> Result<(), Box<dyn Error>> {
+    for (dir_names, file) in files {
+        let path = get_pak_path(dir_names, &file.borrow().name, project_name);
+        let mut errors = vec![];
+        let resolved = file.borrow_mut().resolve_or_fall_back(&mut errors);
+        for e in errors {
+            println!("WARNING: Failed to read {} for {}: {}", path, TARGET_PAK, e);
+            pak.failed_to_read.push(format!("{}: {}", path, e));
+        }
+        if resolved {
+            pak.add_entry::<E>(path, file)?;
+        }
+    }
+    let mut index = Cursor::new(vec![]);
+    pak.serialize_index::<Cursor<Vec<u8>>, E>(&mut index)?;
+    pak.index = index.into_inner();
+    Ok(())
+}
+
+pub fn get_virtual_pak(pak_path: &str) -> Option<(&Vec<PartitionBlock>, &Vec<u8>)> {
+    let path_check = PathBuf::from(pak_path);
+    let file_name = path_check.file_name()?.to_str()?;
+    if file_name != TARGET_PAK {
+        return None;
+    }
+    if unsafe { (*addr_of!(PAK_DATA)).is_none() } {
+        // the pak has to be built for the game's engine version, which is detected here if the TOC hasn't been built yet
+        if let Some(paks_path) = path_check.parent().filter(|p| !p.as_os_str().is_empty()) {
+            toc_factory::load_game_folder(paks_path);
+        }
+        let project_name = game_profile::get_game_profile().project_name.map(|p| p.to_owned())
+            .or_else(|| get_project_name(&path_check))
+            .unwrap_or_else(|| {
+                println!("WARNING: {} isn't inside of a game's Content/Paks folder, using {} as the project name", pak_path, PROJECT_NAME);
+                PROJECT_NAME.to_owned()
+            });
+        match build_pak(&project_name) {
+            Ok(true) => (),
+            Ok(false) => return None, // the existing pak is left alone
+            Err(e) => {
+                println!("WARNING: Couldn't build {}: {}", TARGET_PAK, e);
+                return None;
+            }
+        }
+    }
+    unsafe { (*addr_of!(PAK_DATA)).as_ref().map(|pak| (&pak.virtual_blocks, &pak.index)) }
+}
+
+// Write the pak made by the last call to build_pak to disk, the same way that C# lays it out
+pub fn write_pak(pak_path: &str) -> Result<(), Box<dyn Error>> {
+    let pak = unsafe { (*addr_of!(PAK_DATA)).as_ref().ok_or("No pak has been built yet")? };
+    toc_factory::write_partition_blocks(pak_path, &pak.virtual_blocks, &pak.index, 1)
+}
+
+/// # Safety
+/// Must not be called while a pak is being built on another thread. This checks if PAK_DATA has been assigned a value first, which
+/// only happens after building a pak
+pub unsafe fn print_pak_builder_results() {
+    if let Some(pak) = (*addr_of!(PAK_DATA)).as_ref() {
+        pak.print();
+    }
+}
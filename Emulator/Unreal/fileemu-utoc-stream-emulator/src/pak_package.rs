@@ -49,21 +49,7 @@ bitflags! {
     }
 }
 
-/*
-pub struct StandardEngine425 {
-
-}
-pub struct StandardEngine426 {
-
-}
-pub struct StandardEngine427 {
-
-}
-pub struct Persona3Reload {
-
-}
-*/
-
+// Per game settings (engine version, project name, custom versions...) are in game_profile
 
 pub const PACKAGE_ASSET_MAGIC: u32 = 0xC1832A9E;
 
@@ -80,9 +66,47 @@ pub const PACKAGE_ASSET_MAGIC: u32 = 0xC1832A9E;
 // Preload Dependencies
 
 #[repr(C)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CustomVersion {
-    key: GUID,
-    version: i32
+    pub key: GUID,
+    pub version: i32
+}
+
+impl CustomVersion {
+    // Read the custom versions from the start of a cooked package's FPackageFileSummary. Only the optimized layout (legacy file
+    // version -3 and lower) is supported, which is what every engine version this library supports writes. Keys are read as a
+    // single 128-bit value in the package's byte order
+    pub fn list_from_summary<R: Read, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Vec<Self>, Box<dyn Error>> {
+        if reader.read_u32::<E>()? != PACKAGE_ASSET_MAGIC {
+            return Err("Not a cooked package".into());
+        }
+        let legacy_file_version = reader.read_i32::<E>()?;
+        if legacy_file_version > -3 {
+            return Err(format!("Unsupported legacy file version {}", legacy_file_version).into());
+        }
+        if legacy_file_version != -4 {
+            reader.read_i32::<E>()?; // legacy UE3 version
+        }
+        reader.read_i32::<E>()?; // file version (UE4)
+        if legacy_file_version <= -8 {
+            reader.read_i32::<E>()?; // file version (UE5)
+        }
+        reader.read_i32::<E>()?; // file version (licensee)
+        let count = reader.read_i32::<E>()?;
+        if !(0..=Self::MAX_COUNT).contains(&count) {
+            return Err(format!("Invalid custom version count {}", count).into());
+        }
+        let mut versions = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let key = reader.read_u128::<E>()?;
+            let version = reader.read_i32::<E>()?;
+            versions.push(Self { key, version });
+        }
+        Ok(versions)
+    }
+
+    const MAX_COUNT: i32 = 0x400;
+    pub const SERIALIZED_SIZE: u64 = 0x14;
 }
 
 #[repr(C)]
@@ -525,35 +549,6 @@ pub trait GameName {
     fn project_path_to_game_path(&self, path: &str) -> Result<String, &'static str>;
 }
 
-pub struct GameNameImpl {
-    project_name: String, // the name given to your Unreal project
-    game_name: String // "Project Name" in Editor > Project Settings > Description
-}
-
-impl GameNameImpl {
-    pub fn new(project_name: &str, game_name: &str) -> Self {
-        let project_name = String::from(project_name);
-        let game_name = String::from(game_name);
-        Self { project_name, game_name }
-    }
-}
-
-impl GameName for GameNameImpl {
-    fn get_project_name(&self) -> &str {
-        &self.project_name
-    }
-    fn get_game_name(&self) -> &str {
-        &self.game_name
-    }
-    fn project_path_to_game_path(&self, path: &str) -> Result<String, &'static str> {
-        let path_match = String::from(&self.project_name) + "/Content";
-        match path.rfind(&path_match) {
-            Some(_) => Ok(String::from(path).replace(&path_match, &self.game_name)),
-            None => Err("Couldn't convert the project path to game path")
-        }
-    }
-}
-
 pub struct AssetPath {
     // Cache results for project_path and game_path here so we don't need to process that each time
     // Filenames have their respective extension removed
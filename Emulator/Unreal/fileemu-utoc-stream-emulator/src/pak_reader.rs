@@ -0,0 +1,393 @@
+use byteorder::ReadBytesExt;
+use crate::{
+    compression::CompressionMethod,
+    encryption::{self, AesKey},
+    io_toc::{self, GUID},
+    pak_factory::{PakVersion, PAK_COMPRESSION_METHOD_NAME_LENGTH, PAK_MAGIC},
+    string::{FString32NoHash, FStringDeserializer}
+};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf}
+};
+
+// Reads the index of an existing pak (v8 to v11, used by 4.22 to 4.27) so that mods that are distributed as a .pak can be
+// added to the tree the same way as loose files. Some notes about the layout:
+// - The footer (FPakInfo) is at the end of the file, and points to the index. It's size depends on the version: v9 has an
+//   extra "frozen index" byte, and 4.22's v8 only has 4 compression method names instead of 5
+// - Before v10, the index is a list of paths, each followed by a full FPakEntry
+// - From v10, the index packs entries into a byte array. Paths are only stored in the full directory index (and the path hash
+//   index, which only has hashes), which is placed after the primary index
+// - Encrypted indexes (and secondary indexes) are encrypted with AES-256-ECB using the key for the footer's key GUID
+// - Each entry's data is preceded by a copy of it's FPakEntry. Compression block offsets are relative to the entry's offset
+pub struct PakReader {
+    pub pak_path: PathBuf,
+    pub version: PakVersion,
+    pub encryption_key_guid: GUID,
+    pub encrypted_index: bool,
+    pub compression_methods: Vec<String>,
+    pub mount_point: String,
+    pub entries: Vec<PakReaderEntry>,
+    key: Option<AesKey> // set when either the index or any entry is encrypted
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PakReaderEntry {
+    pub path: String, // relative to the mount point
+    pub offset: u64, // offset of the entry's FPakEntry header
+    pub size: u64, // compressed size
+    pub uncompressed_size: u64,
+    pub compression_method: u32, // 0 is uncompressed, otherwise an index into compression_methods + 1
+    pub compression_blocks: Vec<(u64, u64)>, // start and end of each block, relative to offset
+    pub compression_block_size: u32,
+    pub encrypted: bool
+}
+
+// Size of an uncompressed FPakEntry: offset, size, uncompressed size, compression method, SHA1 hash, flags and compression block size
+const PAK_ENTRY_BASE_SIZE: u64 = 8 + 8 + 8 + 4 + 0x14 + 1 + 4;
+
+impl PakReaderEntry {
+    // Size of the FPakEntry header before the data
+    pub fn get_header_size(&self) -> u64 {
+        let block_size = match self.compression_method {
+            0 => 0,
+            _ => 4 + self.compression_blocks.len() as u64 * 0x10
+        };
+        PAK_ENTRY_BASE_SIZE + block_size
+    }
+
+    // Read a full FPakEntry (entries in pre-v10 indexes)
+    fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, path: String) -> Result<Self, Box<dyn Error>> {
+        let offset = reader.read_u64::<E>()?;
+        let size = reader.read_u64::<E>()?;
+        let uncompressed_size = reader.read_u64::<E>()?;
+        let compression_method = reader.read_u32::<E>()?;
+        reader.seek(SeekFrom::Current(0x14))?; // SHA1 hash
+        let mut compression_blocks = vec![];
+        if compression_method != 0 {
+            let block_count = reader.read_u32::<E>()? as usize;
+            compression_blocks.reserve(io_toc::get_list_capacity(reader, block_count, 0x10)?);
+            for _ in 0..block_count {
+                compression_blocks.push((reader.read_u64::<E>()?, reader.read_u64::<E>()?));
+            }
+        }
+        let flags = reader.read_u8()?;
+        let compression_block_size = reader.read_u32::<E>()?;
+        Ok(Self { path, offset, size, uncompressed_size, compression_method, compression_blocks, compression_block_size, encrypted: flags & 1 != 0 })
+    }
+
+    // FPakFile::DecodePakEntry (see PakEntry::to_buffer_encoded for the flags)
+    fn from_buffer_encoded<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, path: String) -> Result<Self, Box<dyn Error>> {
+        let flags = reader.read_u32::<E>()?;
+        let compression_block_size = match flags & 0x3f {
+            0x3f => reader.read_u32::<E>()?,
+            n => n << 11
+        };
+        let offset = match flags & (1 << 31) != 0 {
+            true => reader.read_u32::<E>()? as u64,
+            false => reader.read_u64::<E>()?
+        };
+        let uncompressed_size = match flags & (1 << 30) != 0 {
+            true => reader.read_u32::<E>()? as u64,
+            false => reader.read_u64::<E>()?
+        };
+        let compression_method = (flags >> 23) & 0x3f;
+        let size = match compression_method {
+            0 => uncompressed_size,
+            _ => match flags & (1 << 29) != 0 {
+                true => reader.read_u32::<E>()? as u64,
+                false => reader.read_u64::<E>()?
+            }
+        };
+        let encrypted = flags & (1 << 22) != 0;
+        let block_count = (flags >> 6) & 0xffff;
+        let mut entry = Self { path, offset, size, uncompressed_size, compression_method, compression_blocks: vec![], compression_block_size, encrypted };
+        // Blocks were only given a size, they're placed one after the other after the header (padded to AES blocks if encrypted)
+        let mut block_start = PAK_ENTRY_BASE_SIZE + match compression_method { 0 => 0, _ => 4 + block_count as u64 * 0x10 };
+        if block_count == 1 && !encrypted {
+            entry.compression_blocks.push((block_start, block_start + size));
+        } else {
+            for _ in 0..block_count {
+                let block_size = reader.read_u32::<E>()? as u64;
+                entry.compression_blocks.push((block_start, block_start + block_size));
+                block_start += if encrypted { encryption::align_to_aes_block(block_size) } else { block_size };
+            }
+        }
+        if compression_method == 0 {
+            entry.compression_blocks.clear();
+        }
+        Ok(entry)
+    }
+}
+
+// FPakInfo sizes: encryption key guid, encrypted index, magic, version, index offset, index size, index hash, then
+// compression method names (and v9's frozen index byte)
+const PAK_INFO_BASE_SIZE: u64 = 0x10 + 1 + 4 + 4 + 8 + 8 + 0x14;
+
+impl PakReader {
+    pub fn from_file(pak_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let pak_file = File::open(pak_path)?;
+        let mut reader = BufReader::new(pak_file);
+        match Self::find_footer::<BufReader<File>, byteorder::LittleEndian>(&mut reader) {
+            Ok(_) => Self::from_buffer::<BufReader<File>, byteorder::LittleEndian>(&mut reader, pak_path),
+            Err(_) => Self::from_buffer::<BufReader<File>, byteorder::BigEndian>(&mut reader, pak_path) // console paks
+        }
+    }
+
+    // Only read the pak's version from it's footer, without reading the index
+    pub fn read_version(pak_path: &Path) -> Result<PakVersion, Box<dyn Error>> {
+        let mut reader = BufReader::new(File::open(pak_path)?);
+        match Self::find_footer::<BufReader<File>, byteorder::LittleEndian>(&mut reader) {
+            Ok((_, version, _)) => Ok(version),
+            Err(_) => Ok(Self::find_footer::<BufReader<File>, byteorder::BigEndian>(&mut reader)?.1)
+        }
+    }
+
+    // Returns the footer's offset, the pak's version and the number of compression method names
+    fn find_footer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<(u64, PakVersion, u64), Box<dyn Error>> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        // try each footer size until the magic lines up
+        for (name_count, frozen_index) in [(5, false), (5, true), (4, false)] {
+            let footer_size = PAK_INFO_BASE_SIZE + frozen_index as u64 + name_count * PAK_COMPRESSION_METHOD_NAME_LENGTH as u64;
+            if footer_size > file_size {
+                continue;
+            }
+            reader.seek(SeekFrom::Start(file_size - footer_size + 0x11))?;
+            if reader.read_u32::<E>()? != PAK_MAGIC {
+                continue;
+            }
+            let version = PakVersion::try_from(reader.read_u32::<E>()?)?;
+            if (version == PakVersion::V9) == frozen_index {
+                return Ok((file_size - footer_size, version, name_count));
+            }
+        }
+        Err("Not a pak file, or it's version isn't supported (expected 8 to 11)".into())
+    }
+
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, pak_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let (footer_offset, version, name_count) = Self::find_footer::<R, E>(reader)?;
+        reader.seek(SeekFrom::Start(footer_offset))?;
+        let encryption_key_guid = reader.read_u128::<E>()?;
+        let encrypted_index = reader.read_u8()? != 0;
+        reader.seek(SeekFrom::Current(8))?; // magic and version
+        let index_offset = reader.read_u64::<E>()?;
+        let index_size = reader.read_u64::<E>()?;
+        reader.seek(SeekFrom::Current(0x14 + if version == PakVersion::V9 { 1 } else { 0 }))?; // index hash, frozen index
+        let mut compression_methods = Vec::with_capacity(name_count as usize);
+        for _ in 0..name_count {
+            let mut name = [0; PAK_COMPRESSION_METHOD_NAME_LENGTH];
+            reader.read_exact(&mut name)?;
+            let name_end = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+            if name_end > 0 {
+                compression_methods.push(String::from_utf8_lossy(&name[..name_end]).into_owned());
+            }
+        }
+        let key = match encrypted_index {
+            true => match encryption::get_key(encryption_key_guid) {
+                Some(key) => Some(key),
+                None => return Err(format!("Pak's index is encrypted, but no AES key was given for it (key GUID {:032X})", encryption_key_guid).into())
+            },
+            false => encryption::get_key(encryption_key_guid) // in case any entries are encrypted
+        };
+        let mut pak = Self {
+            pak_path: pak_path.to_owned(),
+            version,
+            encryption_key_guid,
+            encrypted_index,
+            compression_methods,
+            mount_point: String::new(),
+            entries: vec![],
+            key
+        };
+        let index = pak.read_index_block(reader, index_offset, index_size)?;
+        let mut index = Cursor::new(index);
+        // a wrong key turns the mount point's length into garbage, so check it before trying to read it
+        let mount_point_length = index.read_i32::<E>()?;
+        index.seek(SeekFrom::Start(0))?;
+        pak.mount_point = Some(mount_point_length).filter(|n| *n > 0 && (*n as u64) < index_size)
+            .and_then(|_| FString32NoHash::from_buffer::<Cursor<Vec<u8>>, E>(&mut index).ok().flatten())
+            .filter(|m| m.starts_with('/') || m.starts_with("../"))
+            .ok_or(match pak.encrypted_index {
+                true => "Wrong AES key, couldn't read the pak's index",
+                false => "Pak's index has an invalid mount point"
+            })?;
+        let entry_count = index.read_u32::<E>()? as usize;
+        match version.has_encoded_entries() {
+            true => pak.read_encoded_index::<R, E>(reader, &mut index, entry_count)?,
+            false => {
+                pak.entries.reserve(io_toc::get_list_capacity(&mut index, entry_count, 4 + PAK_ENTRY_BASE_SIZE as usize)?); // + path length
+                for _ in 0..entry_count {
+                    let path = FString32NoHash::from_buffer::<Cursor<Vec<u8>>, E>(&mut index)?.unwrap_or_default();
+                    pak.entries.push(PakReaderEntry::from_buffer::<Cursor<Vec<u8>>, E>(&mut index, path)?);
+                }
+            }
+        }
+        Ok(pak)
+    }
+
+    // Read part of the index, decrypting it if the index is encrypted
+    fn read_index_block<R: Read + Seek>(&self, reader: &mut R, offset: u64, size: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        if offset.checked_add(size).is_none_or(|end| end > file_size) {
+            return Err(format!("Pak's index (0x{:x} bytes at 0x{:x}) is outside of the file", size, offset).into());
+        }
+        let mut block = vec![0; size as usize];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut block)?;
+        if self.encrypted_index {
+            self.key.as_ref().unwrap().decrypt(&mut block)?;
+        }
+        Ok(block)
+    }
+
+    fn read_encoded_index<R: Read + Seek, E: byteorder::ByteOrder>(&mut self, reader: &mut R, index: &mut Cursor<Vec<u8>>, entry_count: usize) -> Result<(), Box<dyn Error>> {
+        index.seek(SeekFrom::Current(8))?; // path hash seed
+        if index.read_u32::<E>()? != 0 {
+            index.seek(SeekFrom::Current(0x24))?; // path hash index offset, size and hash (paths come from the directory index)
+        }
+        if index.read_u32::<E>()? == 0 {
+            return Err("Pak doesn't have a full directory index, so the names of it's files aren't known".into());
+        }
+        let directory_index_offset = index.read_u64::<E>()?;
+        let directory_index_size = index.read_u64::<E>()?;
+        index.seek(SeekFrom::Current(0x14))?;
+        let encoded_entries_size = index.read_u32::<E>()? as usize;
+        let mut encoded_entries = vec![0; io_toc::get_list_capacity(index, encoded_entries_size, 1)?];
+        index.read_exact(&mut encoded_entries)?;
+        let mut encoded_entries = Cursor::new(encoded_entries);
+        // entries that couldn't be encoded are full FPakEntries, which are referred to by negative locations (-1 is the first)
+        let unencoded_count = index.read_u32::<E>()? as usize;
+        let mut unencoded_entries = Vec::with_capacity(io_toc::get_list_capacity(index, unencoded_count, PAK_ENTRY_BASE_SIZE as usize)?);
+        for _ in 0..unencoded_count {
+            unencoded_entries.push(PakReaderEntry::from_buffer::<Cursor<Vec<u8>>, E>(index, String::new())?);
+        }
+        let directory_index = self.read_index_block(reader, directory_index_offset, directory_index_size)?;
+        let mut directory_index = Cursor::new(directory_index);
+        let mut entries: BTreeMap<i32, PakReaderEntry> = BTreeMap::new();
+        let directory_count = directory_index.read_u32::<E>()?;
+        for _ in 0..directory_count {
+            let directory = FString32NoHash::from_buffer::<Cursor<Vec<u8>>, E>(&mut directory_index)?.unwrap_or_default();
+            let directory = directory.trim_start_matches('/');
+            let file_count = directory_index.read_u32::<E>()?;
+            for _ in 0..file_count {
+                let file_name = FString32NoHash::from_buffer::<Cursor<Vec<u8>>, E>(&mut directory_index)?.unwrap_or_default();
+                let location = directory_index.read_i32::<E>()?;
+                let path = directory.to_owned() + &file_name;
+                let entry = match location {
+                    n if n >= 0 => {
+                        encoded_entries.seek(SeekFrom::Start(n as u64))?;
+                        PakReaderEntry::from_buffer_encoded::<Cursor<Vec<u8>>, E>(&mut encoded_entries, path)?
+                    },
+                    n => {
+                        let mut entry = unencoded_entries.get((-n - 1) as usize).ok_or("Pak's directory index refers to an entry that doesn't exist")?.clone();
+                        entry.path = path;
+                        entry
+                    }
+                };
+                entries.insert(location, entry);
+            }
+        }
+        if entries.len() != entry_count {
+            println!("WARNING: {} has {} entries, but only {} are in it's directory index", self.pak_path.display(), entry_count, entries.len());
+        }
+        self.entries = entries.into_values().collect(); // keep the order that they were written in
+        Ok(())
+    }
+
+    pub fn get_mount_point_relative(&self) -> &str {
+        self.mount_point.trim_start_matches("../").trim_start_matches('/')
+    }
+
+    pub fn get_compression_method(&self, index: u32) -> Result<CompressionMethod, Box<dyn Error>> {
+        match index {
+            0 => Ok(CompressionMethod::None),
+            n => match self.compression_methods.get(n as usize - 1) {
+                Some(name) => Ok(CompressionMethod::try_from(name.as_str())?),
+                None => Err(format!("Invalid compression method index {}", index).into())
+            }
+        }
+    }
+
+    // Check that an entry can be read by read_entry
+    pub fn check_entry_readable(&self, entry_index: usize) -> Result<(), Box<dyn Error>> {
+        let entry = &self.entries[entry_index];
+        if entry.encrypted && self.key.is_none() {
+            return Err(format!("Entry is encrypted, but no AES key was given for it (key GUID {:032X})", self.encryption_key_guid).into());
+        }
+        self.get_compression_method(entry.compression_method)?;
+        Ok(())
+    }
+
+    // Uncompressed, unencrypted entries can be read straight from the pak, in which case this returns the offset of their data
+    pub fn get_entry_file_offset(&self, entry_index: usize) -> Option<u64> {
+        let entry = &self.entries[entry_index];
+        match entry.compression_method == 0 && !entry.encrypted {
+            true => Some(entry.offset + entry.get_header_size()),
+            false => None
+        }
+    }
+
+    pub fn read_entry(&self, entry_index: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.read_entry_inner(entry_index, u64::MAX)
+    }
+
+    // Read at least the first size bytes of an entry (or all of it, if it's smaller), only decompressing the blocks that those are in
+    pub fn read_entry_start(&self, entry_index: usize, size: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.read_entry_inner(entry_index, size)
+    }
+
+    fn read_entry_inner(&self, entry_index: usize, max_size: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.check_entry_readable(entry_index)?;
+        let entry = &self.entries[entry_index];
+        let mut pak = File::open(&self.pak_path)?;
+        let pak_size = pak.metadata()?.len();
+        let mut read_range = |start: u64, size: u64| -> Result<Vec<u8>, Box<dyn Error>> {
+            let size = match entry.encrypted {
+                true => encryption::align_to_aes_block(size),
+                false => size
+            };
+            let start = entry.offset.checked_add(start).filter(|s| s.checked_add(size).is_some_and(|end| end <= pak_size))
+                .ok_or_else(|| format!("{} has data outside of the pak", entry.path))?;
+            let mut data = vec![0; size as usize];
+            pak.seek(SeekFrom::Start(start))?;
+            pak.read_exact(&mut data)?;
+            if entry.encrypted {
+                self.key.as_ref().unwrap().decrypt(&mut data)?;
+            }
+            Ok(data)
+        };
+        if entry.compression_method == 0 {
+            let mut data = read_range(entry.get_header_size(), entry.size.min(max_size))?;
+            data.truncate(entry.uncompressed_size.min(max_size) as usize);
+            return Ok(data);
+        }
+        let method = self.get_compression_method(entry.compression_method)?;
+        // the uncompressed size is only trusted as far as the entry's blocks could decompress to
+        let max_uncompressed_size = match entry.compression_block_size {
+            0 => entry.size,
+            n => entry.compression_blocks.len() as u64 * n as u64
+        };
+        let mut uncompressed = Vec::with_capacity(entry.uncompressed_size.min(max_uncompressed_size) as usize);
+        for (start, end) in &entry.compression_blocks {
+            if uncompressed.len() as u64 >= max_size {
+                return Ok(uncompressed);
+            }
+            let remaining = entry.uncompressed_size.checked_sub(uncompressed.len() as u64)
+                .ok_or_else(|| format!("{} decompressed to more than {} bytes", entry.path, entry.uncompressed_size))?;
+            let block_uncompressed_size = match entry.compression_block_size {
+                0 => remaining, // single block entries
+                n => remaining.min(n as u64)
+            } as usize;
+            let block_size = end.checked_sub(*start).ok_or_else(|| format!("{} has a compression block that ends before it starts", entry.path))?;
+            let block = read_range(*start, block_size)?;
+            uncompressed.extend(method.decompress_block(&block[..block_size as usize], block_uncompressed_size)?);
+        }
+        if uncompressed.len() as u64 != entry.uncompressed_size {
+            return Err(format!("{} decompressed to {} bytes, expected {}", entry.path, uncompressed.len(), entry.uncompressed_size).into());
+        }
+        Ok(uncompressed)
+    }
+}
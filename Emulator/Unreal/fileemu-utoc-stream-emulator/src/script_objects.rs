@@ -0,0 +1,136 @@
+use byteorder::ReadBytesExt;
+use crate::{
+    game_profile::Endianness,
+    io_toc::{ContainerHeader, IoChunkType4},
+    string::FMappedName,
+    toc_reader::TocReader
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::Cursor,
+    path::Path,
+    ptr::addr_of
+};
+
+// Script objects (classes, structs, enums and their CDOs from /Script/...) from the game's global.utoc. Packages only store the hash
+// of a script import's path (FPackageObjectIndex), so this is needed to turn those back into names
+// global.utoc contains three chunks that matter here (4.25+ to 4.27):
+//  LoaderGlobalNames: global name map, stored as a name batch (see ContainerHeader::read_name_batch)
+//  LoaderGlobalNameHashes: algorithm id, then a hash for each name in the name map
+//  LoaderInitialLoadMeta: script object count, then FScriptObjectEntry for each script object
+pub static mut SCRIPT_OBJECTS: Option<ScriptObjectDatabase> = None;
+
+pub const GLOBAL_TOC: &str = "global.utoc";
+pub const SCRIPT_OBJECT_ENTRY_SERIALIZED_SIZE: usize = 0x20;
+
+// FMappedName uses the top two bits of the index for the name map that it's from (package, container or global)
+const MAPPED_NAME_INDEX_MASK: u32 = (1 << 30) - 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptObject {
+    pub name: String,
+    pub global_index: u64, // FPackageObjectIndex, type ScriptImport
+    pub outer_index: u64, // parent object, or u64::MAX for /Script/ packages
+    pub cdo_class_index: u64 // class that this is the default object of, or u64::MAX
+}
+
+#[derive(Debug, Default)]
+pub struct ScriptObjectDatabase {
+    pub names: Vec<String>,
+    pub objects: Vec<ScriptObject>,
+    paths: HashMap<u64, String> // global index => full object path
+}
+
+impl ScriptObjectDatabase {
+    pub fn from_file(toc_path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::from_reader(&TocReader::from_file(toc_path)?)
+    }
+
+    pub fn from_reader(toc: &TocReader) -> Result<Self, Box<dyn Error>> {
+        match toc.endianness {
+            Endianness::Little => Self::from_reader_inner::<byteorder::LittleEndian>(toc),
+            Endianness::Big => Self::from_reader_inner::<byteorder::BigEndian>(toc)
+        }
+    }
+
+    fn from_reader_inner<E: byteorder::ByteOrder>(toc: &TocReader) -> Result<Self, Box<dyn Error>> {
+        let read_chunk = |chunk_type: IoChunkType4| -> Result<Vec<u8>, Box<dyn Error>> {
+            match toc.chunk_ids.iter().position(|c| c.get_type() == chunk_type) {
+                Some(i) => toc.read_chunk(i),
+                None => Err(format!("Global container doesn't have a {:?} chunk", chunk_type).into())
+            }
+        };
+        let name_hashes = read_chunk(IoChunkType4::LoaderGlobalNameHashes)?;
+        let names = ContainerHeader::read_name_batch::<E>(&read_chunk(IoChunkType4::LoaderGlobalNames)?, name_hashes.len().saturating_sub(8) / 8)?;
+        let initial_load = read_chunk(IoChunkType4::LoaderInitialLoadMeta)?;
+        let mut reader = Cursor::new(&initial_load);
+        let object_count = reader.read_u32::<E>()? as usize;
+        if initial_load.len() < 4 + object_count * SCRIPT_OBJECT_ENTRY_SERIALIZED_SIZE {
+            return Err(format!("Initial load meta has {} script objects, but is only 0x{:x} bytes", object_count, initial_load.len()).into());
+        }
+        let mut objects = Vec::with_capacity(object_count);
+        for _ in 0..object_count {
+            let object_name = FMappedName::from(reader.read_u64::<E>()?);
+            let global_index = reader.read_u64::<E>()?;
+            let outer_index = reader.read_u64::<E>()?;
+            let cdo_class_index = reader.read_u64::<E>()?;
+            let name_index = (object_name.get_name_index() & MAPPED_NAME_INDEX_MASK) as usize;
+            let name = names.get(name_index).ok_or_else(|| format!("Script object name index {} is outside of the global name map", name_index))?;
+            // FName numbers are stored + 1, with 0 meaning no number
+            let name = match object_name.get_extra_index() {
+                0 => name.to_owned(),
+                n => format!("{}_{}", name, n - 1)
+            };
+            objects.push(ScriptObject { name, global_index, outer_index, cdo_class_index });
+        }
+        let mut database = Self { names, objects, paths: HashMap::new() };
+        database.build_paths();
+        Ok(database)
+    }
+
+    // Objects are listed before anything that they own, but walk up the outer chain anyway in case a game doesn't do that
+    // Paths look like /Script/Engine.Actor and /Script/Engine.Default__Actor:SubObject, like they do in the editor
+    fn build_paths(&mut self) {
+        let indices: HashMap<u64, usize> = self.objects.iter().enumerate().map(|(i, o)| (o.global_index, i)).collect();
+        for object in &self.objects {
+            let mut chain = vec![object];
+            while let Some(outer) = indices.get(&chain.last().unwrap().outer_index).map(|i| &self.objects[*i]) {
+                if chain.len() > self.objects.len() { // broken outer chain, give up on this one
+                    break;
+                }
+                chain.push(outer);
+            }
+            let mut path = String::new();
+            for (depth, o) in chain.iter().rev().enumerate() {
+                match depth {
+                    0 => (),
+                    1 => path.push('.'),
+                    _ => path.push(':')
+                }
+                path.push_str(&o.name);
+            }
+            self.paths.insert(object.global_index, path);
+        }
+    }
+
+    pub fn get_path(&self, global_index: u64) -> Option<&str> {
+        self.paths.get(&global_index).map(|p| p.as_str())
+    }
+
+    pub fn get_object(&self, global_index: u64) -> Option<&ScriptObject> {
+        self.objects.iter().find(|o| o.global_index == global_index)
+    }
+}
+
+// Load global.utoc from the game's Paks folder, returning the number of script objects
+pub fn add_global_container(paks_path: &Path) -> Result<usize, Box<dyn Error>> {
+    let database = ScriptObjectDatabase::from_file(&paks_path.join(GLOBAL_TOC))?;
+    let object_count = database.objects.len();
+    unsafe { SCRIPT_OBJECTS = Some(database) };
+    Ok(object_count)
+}
+
+pub fn get_script_object_path(global_index: u64) -> Option<&'static str> {
+    unsafe { (*addr_of!(SCRIPT_OBJECTS)).as_ref()?.get_path(global_index) }
+}
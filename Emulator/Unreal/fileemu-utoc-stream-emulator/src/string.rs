@@ -6,8 +6,6 @@ use std::{
 };
 
 // Serialized versions of Unreal Engine's FString type. Mostly used as an intermediate between bytes and a full string
-// TODO: Make it not completely die if an inappropriate endianess is passed - would require some trait Length to check that
-// the read string length isn't larger than the entire file lol
 pub trait FStringDeserializer {
     // Take a byte stream (cursor variant required, provides a nice wrapper for a cursor + Seek functions) and alloc a new string
     // Implementors are required to provide a UTF-8 string that doesn't include the null terminator
@@ -69,30 +67,63 @@ pub const NAME_HASH_ALGORITHM: u64 = 0xC1640000; // FNameHash::AlgorithmId
 // Used in a couple places, mostly in PAK package headers (see FolderName, SavedByEngineVersion in FPackageFilePackageSummary). 
 // Serialized version of Unreal Engine's FString
 pub struct FString32NoHash;
-    // 0x0: len: u32
-    // 0x4: data: [u8; len]
+    // 0x0: len: i32 (includes the null terminator, negative if the string is UTF-16)
+    // 0x4: data: [u8; len] or [u16; -len]
 impl FString32NoHash {
-    fn from_buffer_inner<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Option<String>, Box<dyn Error>> {
-        let len = reader.read_u32::<E>()?; // length
-        if len < 1 {
+    fn from_buffer_inner<R: Read, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Option<String>, Box<dyn Error>> {
+        let len = reader.read_i32::<E>()?; // length
+        if len == 0 {
             return Ok(None); // we correctly parsed it, there's just nothing there lol
         }
-        let mut buf = vec![0; (len - 1) as usize]; // get rid of that pesky \0
-        reader.read_exact(&mut buf);
-        reader.seek(SeekFrom::Current(1));
-        Ok(Some(unsafe { String::from_utf8_unchecked(buf)}))
+        // the buffer only grows as far as there's data to read, so a corrupt length can't allocate up to 4 GB
+        let size = match len < 0 {
+            true => len.unsigned_abs() as u64 * 2,
+            false => len as u64
+        };
+        let mut bytes = vec![];
+        reader.take(size).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < size {
+            return Err(format!("String length {} is larger than the remaining data (0x{:x} bytes)", len, bytes.len()).into());
+        }
+        let mut value = match len < 0 {
+            true => {
+                let chars: Vec<u16> = bytes.chunks_exact(2).map(E::read_u16).collect();
+                String::from_utf16_lossy(&chars)
+            },
+            false => String::from_utf8_lossy(&bytes).into_owned()
+        };
+        if value.ends_with('\0') { // get rid of that pesky \0
+            value.pop();
+        }
+        Ok(Some(value))
     }
 
     fn to_buffer_text_inner<W: Write, E: byteorder::ByteOrder>(rstr: &str, writer: &mut W) -> Result<(), Box<dyn Error>> {
-        // add an extra byte for null terminator
-        let len = (rstr.len() + if !rstr.ends_with("\0") { 1 } else { 0 }) as u32;
-        writer.write_u32::<E>(len)?;
-        writer.write_all(rstr.as_bytes());
-        if !rstr.ends_with("\0") {
+        // check to see if our string has a null terminator, if we've made it in Rust, it won't, and if it's an import from another
+        // Unreal stream, it shouldn't
+        let rstr = rstr.strip_suffix('\0').unwrap_or(rstr);
+        if rstr.is_ascii() {
+            writer.write_i32::<E>(rstr.len() as i32 + 1)?; // add an extra byte for null terminator
+            writer.write_all(rstr.as_bytes())?;
             writer.write_u8(b'\0')?;
+        } else {
+            // anything that doesn't fit in ASCII is stored as UTF-16, which Unreal marks with a negative length
+            let chars: Vec<u16> = rstr.encode_utf16().chain([0]).collect();
+            writer.write_i32::<E>(-(chars.len() as i32))?;
+            for c in chars {
+                writer.write_u16::<E>(c)?;
+            }
         }
         Ok(())
     }
+
+    // Read/write an FString directly, for serializers that don't go through Cursor (see unversioned)
+    pub fn read<R: Read, E: byteorder::ByteOrder>(reader: &mut R) -> Result<String, Box<dyn Error>> {
+        Ok(FString32NoHash::from_buffer_inner::<R, E>(reader)?.unwrap_or_default())
+    }
+    pub fn write<W: Write, E: byteorder::ByteOrder>(rstr: &str, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        FString32NoHash::to_buffer_text_inner::<W, E>(rstr, writer)
+    }
 }
 
 impl FStringDeserializer for FString32NoHash {
@@ -102,21 +133,21 @@ impl FStringDeserializer for FString32NoHash {
 }
 impl FStringSerializer for FString32NoHash {
     fn to_buffer<W: Write, E: byteorder::ByteOrder>(rstr: &str, writer: &mut W) -> Result<(), Box<dyn Error>> {
-        // check to see if our string has a null terminator, if we've made it in Rust, it won't, and if it's an import from another
-        // Unreal stream, it shouldn't
         FString32NoHash::to_buffer_text_inner::<W, E>(rstr, writer)
     }
 }
 impl FStringSerializerText for FString32NoHash {
     fn to_buffer_text<W: Write, E: byteorder::ByteOrder>(rstr: &str, writer: &mut W) -> Result<(), Box<dyn Error>> {
-        // check to see if our string has a null terminator, if we've made it in Rust, it won't, and if it's an import from another
-        // Unreal stream, it shouldn't
         FString32NoHash::to_buffer_text_inner::<W, E>(rstr, writer)
     }
 }
 impl FStringSerializerExpectedLength for FString32NoHash {
     fn get_expected_length(value: &str) -> u64 {
-        let str_len = (value.len() + if !value.ends_with("\0") { 1 } else { 0 }) as u64; // include null terminator
+        let value = value.strip_suffix('\0').unwrap_or(value);
+        let str_len = match value.is_ascii() {
+            true => value.len() as u64 + 1, // include null terminator
+            false => (value.encode_utf16().count() as u64 + 1) * 2 // UTF-16 code units, not UTF-8 bytes
+        };
         str_len + 4 // 4 bytes at beginning to define string length
     }
 }
@@ -155,14 +186,50 @@ impl FString16 {
     pub fn check_hash(rstr: &str) -> u64 {
         Hasher::get_cityhash64(rstr)
     }
+    // Read the text portion of a string, for name maps that store their text and hashes in separate blocks (name batches).
+    // The high bit of the length is set for names stored as UTF-16
+    pub fn from_buffer_text<R: Read, E: byteorder::ByteOrder>(reader: &mut R) -> Result<String, Box<dyn Error>> {
+        let header = reader.read_u16::<byteorder::BigEndian>()?;
+        let len = (header & 0x7fff) as usize;
+        match header & 0x8000 != 0 {
+            true => {
+                let mut chars = Vec::with_capacity(len);
+                for _ in 0..len {
+                    chars.push(reader.read_u16::<E>()?);
+                }
+                Ok(String::from_utf16_lossy(&chars))
+            },
+            false => {
+                let mut chars = vec![0; len];
+                reader.read_exact(&mut chars)?;
+                Ok(String::from_utf8_lossy(&chars).into_owned())
+            }
+        }
+    }
 
+    // Names that can't be stored as ASCII are written as UTF-16, which is marked by the high bit of the length
     fn to_buffer_text_inner<W: Write, E: byteorder::ByteOrder>(rstr: &str, writer: &mut W) -> Result<(), Box<dyn Error>> {
-        writer.write_u16::<byteorder::BigEndian>(rstr.len().try_into()?)?; // length
-        writer.write_all(rstr.as_bytes());
+        if rstr.is_ascii() {
+            writer.write_u16::<byteorder::BigEndian>(rstr.len().try_into()?)?; // length
+            writer.write_all(rstr.as_bytes())?;
+        } else {
+            let chars: Vec<u16> = rstr.encode_utf16().collect();
+            let len: u16 = chars.len().try_into()?;
+            if len & 0x8000 != 0 {
+                return Err(format!("Name is too long to be serialized ({} characters)", len).into());
+            }
+            writer.write_u16::<byteorder::BigEndian>(len | 0x8000)?;
+            for c in chars {
+                writer.write_u16::<E>(c)?;
+            }
+        }
         Ok(())
     }
     fn to_buffer_hash_inner<W: Write, E: byteorder::ByteOrder>(rstr: &str, writer: &mut W) -> Result<(), Box<dyn Error>> {
-        writer.write_u64::<E>(Hasher::get_cityhash64(&rstr));
+        match rstr.is_ascii() {
+            true => writer.write_u64::<E>(Hasher::get_cityhash64(rstr))?,
+            false => writer.write_u64::<E>(Hasher16::get_cityhash64(rstr))?
+        };
         Ok(())
     }
 }
@@ -217,15 +284,13 @@ impl Hasher {
     }
 }
 
-// TODO: Switch IoStoreObjectIndex to use Hasher16 as a base implementation
 pub struct Hasher16;
 impl Hasher16 {
     pub fn get_cityhash64(bytes: &str) -> u64 {
         let to_hash = String::from(bytes).to_lowercase();
         // hash chars are sized according to if the platform supports wide characters, which is usually the case
-        let to_hash: Vec<u16> = to_hash.encode_utf16().collect();
-        // safety: Vec is contiguous, so a Vec<u8> of length `2 * n` will take the same memory as a Vec<u16> of len `n`
-        let to_hash = unsafe { std::slice::from_raw_parts(to_hash.as_ptr() as *const u8, to_hash.len() * 2) };
+        // the characters are hashed as little endian regardless of the host or target, since that's how the cooker hashed them
+        let to_hash: Vec<u8> = to_hash.encode_utf16().flat_map(u16::to_le_bytes).collect();
         // verified: the strings are identical (no null terminator) when using FString16
         cityhasher::hash(to_hash) // cityhash it
     }
@@ -0,0 +1,160 @@
+use crate::{
+    io_toc::IoChunkType4,
+    toc_reader::{TocReader, TocReaderFile}
+};
+use std::{
+    error::Error,
+    fs,
+    path::{Component, Path, PathBuf},
+    time::Instant
+};
+
+// Extract the files in an IO Store container's directory index into a folder, keeping their path relative to the mount point
+// (e.g P3R/Content/Folder/Asset.uasset). This works for any container that TocReader can read, as well as the container that
+// the emulator built, which is read straight from it's partition blocks so that it doesn't need to be dumped first
+
+// Path filters, matched case insensitively against the path relative to the mount point with forward slashes.
+// Patterns can use * (any characters, including /) and ? (any single character), while patterns without any wildcards
+// match everything that begins with them, so that a folder (e.g P3R/Content/Sound) can be given as a filter.
+// No filters means that every file is extracted
+#[derive(Debug, Clone, Default)]
+pub struct ExtractFilter {
+    patterns: Vec<String>
+}
+
+impl ExtractFilter {
+    pub fn new(patterns: &[&str]) -> Self {
+        Self { patterns: patterns.iter().map(|p| p.replace('\\', "/").to_lowercase()).collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        if self.patterns.is_empty() {
+            return true;
+        }
+        let path = path.to_lowercase();
+        self.patterns.iter().any(|p| match p.contains(['*', '?']) {
+            true => Self::matches_wildcard(p.as_bytes(), path.as_bytes()),
+            false => path.starts_with(p.as_str())
+        })
+    }
+
+    fn matches_wildcard(pattern: &[u8], path: &[u8]) -> bool {
+        // track the last * so that it can consume more characters when the rest of the pattern doesn't match
+        let (mut p, mut s) = (0, 0);
+        let mut last_star: Option<(usize, usize)> = None;
+        while s < path.len() {
+            if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == path[s]) {
+                p += 1;
+                s += 1;
+            } else if p < pattern.len() && pattern[p] == b'*' {
+                last_star = Some((p, s));
+                p += 1;
+            } else if let Some((star_p, star_s)) = last_star {
+                p = star_p + 1;
+                s = star_s + 1;
+                last_star = Some((star_p, star_s + 1));
+            } else {
+                return false;
+            }
+        }
+        pattern[p..].iter().all(|c| *c == b'*')
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ExtractFailedFile {
+    pub path: String,
+    pub reason: String
+}
+
+#[derive(Debug, Default)]
+pub struct ExtractResults {
+    pub extracted_files: Vec<String>, // output paths, relative to the output folder
+    pub extracted_size: u64,
+    pub filtered_count: u64,
+    pub failed_files: Vec<ExtractFailedFile>,
+    pub time_to_extract: u128 // in microseconds
+}
+
+impl ExtractResults {
+    pub fn print(&self) {
+        println!("Extracted {} files ({} KB) in {} ms", self.extracted_files.len(), self.extracted_size / 1024, self.time_to_extract as f64 / 1000f64);
+        if self.filtered_count > 0 {
+            println!("{} files didn't match the filter", self.filtered_count);
+        }
+        if !self.failed_files.is_empty() {
+            println!("{}", "-".repeat(80));
+            println!("FAILED TO EXTRACT: {} FILES", self.failed_files.len());
+            for i in &self.failed_files {
+                println!("File \"{}\", reason \"{}\"", i.path, i.reason);
+            }
+        }
+    }
+}
+
+// The directory index already contains the file's extension for most containers, but the chunk type is what decides how
+// the game loads it, so make sure that export data ends up as .uasset/.umap, bulk data as .ubulk and optional data as .uptnl
+pub fn get_extracted_path(file: &TocReaderFile, chunk_type: IoChunkType4) -> PathBuf {
+    let path = PathBuf::from(&file.path);
+    let extension = path.extension().map(|e| e.to_str().unwrap().to_lowercase());
+    let new_extension = match chunk_type {
+        IoChunkType4::ExportBundleData => match extension.as_deref() {
+            Some("uasset") | Some("umap") => return path,
+            _ => "uasset"
+        },
+        IoChunkType4::BulkData => "ubulk",
+        IoChunkType4::OptionalBulkData => "uptnl",
+        IoChunkType4::MemoryMappedBulkData => "m.ubulk",
+        _ => return path
+    };
+    path.with_extension(new_extension)
+}
+
+pub fn extract_container(toc_path: &Path, out_path: &Path, filter: &ExtractFilter) -> Result<ExtractResults, Box<dyn Error>> {
+    let toc = TocReader::from_file(toc_path)?;
+    Ok(extract_from_reader(&toc, out_path, filter))
+}
+
+// Extract from the container made by the last call to build_table_of_contents. Only valid until SafeToDropContainerMetadata
+pub fn extract_virtual_container(out_path: &Path, filter: &ExtractFilter) -> Result<ExtractResults, Box<dyn Error>> {
+    let toc = TocReader::from_virtual_container()?;
+    Ok(extract_from_reader(&toc, out_path, filter))
+}
+
+pub fn extract_from_reader(toc: &TocReader, out_path: &Path, filter: &ExtractFilter) -> ExtractResults {
+    let start = Instant::now();
+    let mut results = ExtractResults::default();
+    for file in toc.get_files() {
+        if !filter.matches(&file.path) {
+            results.filtered_count += 1;
+            continue;
+        }
+        let relative_path = get_extracted_path(&file, toc.chunk_ids[file.chunk_index].get_type());
+        if relative_path.components().any(|c| !matches!(c, Component::Normal(_))) {
+            results.failed_files.push(ExtractFailedFile { path: file.path, reason: String::from("Path leaves the output folder") });
+            continue;
+        }
+        match extract_file(toc, &file, &out_path.join(&relative_path)) {
+            Ok(size) => {
+                results.extracted_files.push(relative_path.to_str().unwrap().replace('\\', "/"));
+                results.extracted_size += size;
+            },
+            Err(e) => results.failed_files.push(ExtractFailedFile { path: file.path, reason: e.to_string() })
+        }
+    }
+    results.time_to_extract = start.elapsed().as_micros();
+    results
+}
+
+fn extract_file(toc: &TocReader, file: &TocReaderFile, out_file: &Path) -> Result<u64, Box<dyn Error>> {
+    let data = toc.read_chunk(file.chunk_index)?;
+    if let Some(parent) = out_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(out_file, &data)?;
+    Ok(data.len() as u64)
+}
@@ -1,20 +1,24 @@
 use std::{
     cell::RefCell,
+    collections::{BTreeMap, HashMap},
     error::Error,
     path::{Path, PathBuf},
     fs, fs::{DirEntry, File},
     io, io::{BufReader, Cursor, Read, Seek, SeekFrom, Write},
     mem,
+    ptr::{addr_of, addr_of_mut},
     rc::{Rc, Weak},
     time::Instant,
 };
 use crate::{
+    datatable_merge, delta_patch, dependency_check, engine_detection, game_packages, usmap,
+    game_profile::{self, ContainerHeaderPosition, Endianness, GameQuirks},
     asset_collector::{
-        MOUNT_POINT, PROJECT_NAME, SUITABLE_FILE_EXTENSIONS, ROOT_DIRECTORY, 
-        TocDirectory, TocDirectoryRef, TocFile, TocFileRef},
+        self, MOUNT_POINT, PROJECT_NAME, SUITABLE_FILE_EXTENSIONS, ROOT_DIRECTORY, 
+        TocDirectory, TocDirectoryRef, TocFile, TocFileRef, TocFileSource},
     io_package::{
         ContainerHeaderPackage,
-        ExportBundle, ExportBundleHeader4,
+        ExportBundle, IoPackage, ExportBundleHeader4,
         PackageIoSummaryDeserialize, 
         PackageSummary2},
     io_toc::{
@@ -26,21 +30,70 @@ use crate::{
         IoStoreTocCompressedBlockEntry, IoOffsetAndLength
     },
     platform::Metadata,
-    string::{FString32NoHash, FStringSerializer, FStringSerializerExpectedLength, Hasher, Hasher16}
+    string::{FString32NoHash, FStringSerializer, FStringSerializerExpectedLength, Hasher, Hasher16},
+    toc_reader::FileSliceReader
 };
+use sha1::{Sha1, Digest};
 
 pub const TOC_NAME:     &'static str = "UnrealEssentials_P";
 pub const TARGET_TOC:   &'static str = "UnrealEssentials_P.utoc";
 pub const TARGET_CAS:   &'static str = "UnrealEssentials_P.ucas";
 
 pub static mut CONTAINER_ENTRIES_OSPATH_POOL: Option<Vec<String>> = None;
+pub static mut CONTAINER_ENTRIES_MEMORY_POOL: Option<Vec<Rc<Vec<u8>>>> = None; // keeps memory backed blocks alive until C# is done with them
 pub static mut CONTAINER_DATA: Option<ContainerData> = None;
+pub static mut TOC_BUILDER_PROFILER: Option<TocBuilderProfiler> = None;
+// Files with identical contents share the first one's range of the container instead of getting their own. Off by default, since
+// every file that's the same size as another one has to be read while building the TOC
+pub static mut DEDUPLICATE_CHUNKS: bool = false;
+
+// Engine versions that a TOC can be built for. 4.25 (TocResolverType1) and UE5 aren't supported yet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EngineVersion {
+    UE4_25Plus,
+    UE4_26,
+    UE4_27
+}
+
+impl TryFrom<&str> for EngineVersion {
+    type Error = String;
+    fn try_from(value: &str) -> Result<EngineVersion, Self::Error> {
+        match value.trim_start_matches("UE").trim_start_matches('_') {
+            "4.25+" | "4_25Plus" => Ok(EngineVersion::UE4_25Plus),
+            "4.26" | "4_26" => Ok(EngineVersion::UE4_26),
+            "4.27" | "4_27" => Ok(EngineVersion::UE4_27),
+            _ => Err(format!("Unsupported engine version \"{}\" (expected 4.25+, 4.26 or 4.27)", value))
+        }
+    }
+}
+
+// The game's containers are next to ours: detect the engine version from them and load their packages so that mod imports can be
+// checked against them. Mappings for unversioned properties can also be put here, since there's no other way to give them to the
+// emulator. The game opens the pak before the TOC, so this is called by whichever gets built first, and only loads each thing once
+pub fn load_game_folder(paks_path: &Path) {
+    if unsafe { (*addr_of!(engine_detection::ENGINE_DETECTION)).is_none() } {
+        engine_detection::apply_engine_detection(paks_path);
+    }
+    if unsafe { (*addr_of!(game_packages::GAME_CONTAINER_HEADER)).is_none() } {
+        if let Err(e) = game_packages::add_game_containers_from_folder(paks_path) {
+            println!("WARNING: Couldn't read the game's containers from {}: {}", paks_path.display(), e);
+        }
+    }
+    if usmap::get_mappings().is_none() {
+        if let Err(e) = usmap::add_mappings_from_folder(paks_path) {
+            println!("WARNING: Couldn't read mappings from {}: {}", paks_path.display(), e);
+        }
+    }
+}
 
 pub fn build_table_of_contents(toc_path: &str) -> Option<Vec<u8>> {
     let path_check = PathBuf::from(toc_path); // build TOC here
     let file_name = path_check.file_name().unwrap().to_str().unwrap(); // unwrap, this is a file
     if file_name == TARGET_TOC { // check that we're targeting the correct UTOC
-        match unsafe { &ROOT_DIRECTORY } {
+        if let Some(paks_path) = path_check.parent().filter(|p| !p.as_os_str().is_empty()) {
+            load_game_folder(paks_path);
+        }
+        match unsafe { &*addr_of!(ROOT_DIRECTORY) } {
             Some(root) => Some(build_table_of_contents_inner(Rc::clone(root), toc_path)),
             None => {
                 println!("WARNING: No mod files were loaded for {}", file_name);
@@ -53,23 +106,42 @@ pub fn build_table_of_contents(toc_path: &str) -> Option<Vec<u8>> {
 }
 
 pub fn build_container_test(cas_path: &str) {
+    write_container(cas_path).unwrap();
+}
+
+// Write the virtual container made by the last call to build_table_of_contents to disk, the same way that C# lays it out
+pub fn write_container(cas_path: &str) -> Result<(), Box<dyn Error>> {
+    let container_data = unsafe { (*addr_of!(CONTAINER_DATA)).as_ref().ok_or("No container has been built yet")? };
+    write_partition_blocks(cas_path, &container_data.virtual_blocks, &container_data.header, container_data.alignment)
+}
+
+// Write each block at it's start offset, padding the end of each one to alignment, then add the trailing memory block (the
+// container header for a .ucas, or the index and footer for a .pak)
+pub fn write_partition_blocks(path: &str, blocks: &[PartitionBlock], trailer: &[u8], alignment: u32) -> Result<(), Box<dyn Error>> {
     use std::ffi::CStr;
-    use byteorder::WriteBytesExt;
     let mut writer: Cursor<Vec<u8>> = Cursor::new(vec![]);
-    let container_data = unsafe { CONTAINER_DATA.as_ref().unwrap() };
-    for i in &container_data.virtual_blocks {
-        let file_name = unsafe { CStr::from_ptr(i.os_path as *const i8).to_str().unwrap() };
-        let vec = fs::read(file_name).unwrap();
-        println!("cursor at 0x{:x} for {}", writer.stream_position().unwrap(), file_name);
-        writer.write_all(&vec).unwrap();
-        let alignment = writer.stream_position().unwrap() as u32 % DEFAULT_COMPRESSION_BLOCK_ALIGNMENT;
-        if alignment > 0 {
-            let diff = DEFAULT_COMPRESSION_BLOCK_ALIGNMENT - alignment;
-            writer.seek(SeekFrom::Current(diff as i64));
-        }
-    }
-    writer.write_all(&container_data.header).unwrap();
-    fs::write(cas_path, &writer.into_inner()).unwrap();
+    for i in blocks {
+        writer.seek(SeekFrom::Start(i.start))?;
+        if i.os_path.is_null() { // memory block
+            let data = unsafe { std::slice::from_raw_parts(i.data, i.length as usize) };
+            writer.write_all(data)?;
+        } else {
+            let file_name = unsafe { CStr::from_ptr(i.os_path as *const i8).to_str()? };
+            let mut file = File::open(file_name)?;
+            file.seek(SeekFrom::Start(i.file_offset))?;
+            let mut vec = vec![0; i.length as usize];
+            file.read_exact(&mut vec)?;
+            writer.write_all(&vec)?;
+        }
+        let remainder = writer.stream_position()? as u32 % alignment;
+        if remainder > 0 {
+            let diff = alignment - remainder;
+            writer.seek(SeekFrom::Current(diff as i64))?;
+        }
+    }
+    writer.write_all(trailer)?;
+    fs::write(path, writer.into_inner())?;
+    Ok(())
 }
 
 // Creates a TOC + CAS given a list of loose directories and files
@@ -95,20 +167,26 @@ pub trait TocResolverCommon { // Currently for 4.25+, 4.26 and 4.27
     //type ContainerHeaderType: PackageIoSummaryDeserialize; // Container Header in UCAS
     fn new<THeaderType: IoStoreTocHeaderCommon>(toc_name: &str, project_name: &str, block_align: u32) -> impl TocResolverCommon;
 
-    fn flatten_toc_tree(&mut self, tracker: &mut TocFlattenTracker, root: TocDirectoryRef);
+    fn flatten_toc_tree<
+        TSummary: PackageIoSummaryDeserialize,
+        EN: byteorder::ByteOrder
+    >(&mut self, tracker: &mut TocFlattenTracker, root: TocDirectoryRef);
 
     fn serialize<
-        TSummary: PackageIoSummaryDeserialize,
-        TIoTocHeader: IoStoreTocHeaderCommon
+        TIoTocHeader: IoStoreTocHeaderCommon,
+        EN: byteorder::ByteOrder
     >(&mut self, profiler: &mut TocBuilderProfiler, toc_path: &str) -> (Vec<u8>, ContainerData);
 
     // Common across all versions
     fn create_chunk_id(&self, file_path: &str, chunk_type: IoChunkType4) -> IoChunkId {
-        // replace [BaseDirectory]/Content with /Game/
+        IoChunkId::new(&Self::get_game_path(file_path), chunk_type)
+    }
+
+    // replace [BaseDirectory]/Content with /Game/
+    fn get_game_path(file_path: &str) -> String {
         let path_to_replace = PROJECT_NAME.to_owned() + "/Content";
-        if let Some((_, suffix)) = file_path.to_owned().split_once(&path_to_replace) {
-            let path_to_hash = String::from("/Game") + suffix;
-            IoChunkId::new(&path_to_hash, chunk_type)
+        if let Some((_, suffix)) = file_path.split_once(&path_to_replace) {
+            String::from("/Game") + suffix
         } else {
             panic!("Path \"{}\" is missing root containing project name + content. Path components were not handled properly", file_path);
         }
@@ -117,7 +195,7 @@ pub trait TocResolverCommon { // Currently for 4.25+, 4.26 and 4.27
     fn get_file_hash(&self, curr_file: &IoFileIndexEntry) -> IoChunkId {
         // unwrap a bunch. any errors related to this would've been handled in the asset collection stage
         let chunk_type = match SUITABLE_FILE_EXTENSIONS.iter().find(
-                |exist| **exist == PathBuf::from(&curr_file.os_path).extension().unwrap().to_str().unwrap()
+                |exist| **exist == PathBuf::from(&curr_file.file_name).extension().unwrap().to_str().unwrap()
             ) {
             Some(io_ext) => {
                 match *io_ext {
@@ -167,6 +245,11 @@ pub struct TocResolverType2 { // Currently for 4.25+, 4.26 and 4.27
     pub compression_blocks: Vec<IoStoreTocCompressedBlockEntry>,
     pub metas: Vec<IoStoreTocEntryMeta>,
     pub cas_pointer: u64, // Current virtual position of container file
+    file_refs: Vec<(TocDirectoryRef, TocFileRef)>, // where each of the flattened files is in the tree
+    chunk_id_collisions: Vec<String>,
+    store_entries: HashMap<u64, ContainerHeaderPackage>, // read from each package while flattening, by it's chunk id hash
+    incorrect_asset_format: Vec<String>,
+    incorrect_asset_format_size: u64
 }
 
 impl TocResolverCommon for TocResolverType2 {
@@ -190,34 +273,74 @@ impl TocResolverCommon for TocResolverType2 {
             offsets_and_lengths: vec![],
             compression_blocks: vec![],
             metas: vec![],
-            cas_pointer: 0
+            cas_pointer: 0,
+            file_refs: vec![],
+            chunk_id_collisions: vec![],
+            store_entries: HashMap::new(),
+            incorrect_asset_format: vec![],
+            incorrect_asset_format_size: 0
         }
     }
     // Flatten the tree of directories + files into a list of directories and list of files
-    fn flatten_toc_tree(&mut self, tracker: &mut TocFlattenTracker, root: TocDirectoryRef) {
+    fn flatten_toc_tree<
+        TSummary: PackageIoSummaryDeserialize,
+        EN: byteorder::ByteOrder // the game's byte order, which mod packages are expected to be in as well
+    >(&mut self, tracker: &mut TocFlattenTracker, root: TocDirectoryRef) {
         self.directories = self.flatten_toc_tree_dir(tracker, Rc::clone(&root));
+        // colliding files and unreadable packages are removed from the tree, so it has to be flattened again without them
+        if self.remove_chunk_id_collisions() {
+            self.reflatten_toc_tree(tracker, Rc::clone(&root));
+        }
+        if self.remove_invalid_packages::<TSummary, EN>() {
+            self.reflatten_toc_tree(tracker, Rc::clone(&root));
+        }
     }
     fn serialize<
-        TSummary: PackageIoSummaryDeserialize,
-        TIoTocHeader: IoStoreTocHeaderCommon
+        TIoTocHeader: IoStoreTocHeaderCommon,
+        EN: byteorder::ByteOrder // the game's byte order, which mod packages are expected to be in as well
     >(
         &mut self, 
         profiler: &mut TocBuilderProfiler, 
         toc_path: &str
     ) -> (Vec<u8>, ContainerData) {
         type CV = Cursor<Vec<u8>>;
-        type EN = byteorder::NativeEndian;
         let mut toc_storage: CV = Cursor::new(vec![]); // TOC Storage gets stored as a MemoryStream
         // CAS storage will be a MultiStream of FileStreams with a MemoryStream of gaps between it
         // Set capacity so that vec doesn't realloc
         unsafe { CONTAINER_ENTRIES_OSPATH_POOL = Some(Vec::with_capacity(self.files.len())); }
+        unsafe { CONTAINER_ENTRIES_MEMORY_POOL = Some(vec![]); }
         let mut container_header = ContainerHeader::new(self.toc_name_hash);
-        let mut container_data = ContainerData { header: vec![], virtual_blocks: vec![] };
+        let mut container_data = ContainerData { toc: vec![], header: vec![], virtual_blocks: vec![], alignment: self.compression_block_alignment };
         let file_count = self.files.len();
-        for i in 0..self.files.len() {
-            container_data.virtual_blocks.push(self.serialize_entry::<TSummary>(i, &mut container_header));
+        profiler.chunk_id_collisions = mem::take(&mut self.chunk_id_collisions);
+        profiler.incorrect_asset_format = mem::take(&mut self.incorrect_asset_format);
+        profiler.incorrect_asset_format_size = self.incorrect_asset_format_size;
+        for (i, duplicate_of) in self.find_duplicate_chunks().into_iter().enumerate() {
+            match duplicate_of {
+                Some(first) => {
+                    profiler.deduplicated_chunks.push(format!("{} shares it's data with {}",
+                        Self::describe_file(&self.files[i]), Self::describe_file(&self.files[first])));
+                    profiler.deduplicated_size += self.files[i].file_size;
+                },
+                None => profiler.successful_files_size += self.files[i].file_size
+            }
+            if let Some(block) = self.serialize_entry(i, duplicate_of, &mut container_header) {
+                container_data.virtual_blocks.push(block);
+            }
+            profiler.successful_files += 1;
         }
+        Self::add_package_redirects(&mut container_header);
+        container_header.set_load_order();
+        self.check_dependencies::<EN>(&container_header);
+        let entry_block_count = self.compression_blocks.len();
         container_data.header = self.serialize_container_header::<EN>(&mut container_header);
+        if game_profile::get_game_profile().container_header_position == ContainerHeaderPosition::First {
+            self.move_container_header_to_start(&mut container_data, entry_block_count);
+        }
+        profiler.container_header_hash = self.toc_name_hash;
+        profiler.compression_block_count = self.compression_blocks.len() as u64;
+        profiler.mount_point = MOUNT_POINT.to_owned();
+        profiler.directory_index_size = self.get_directory_index_size() as u64;
         // Write our TOC
         let toc_header = TIoTocHeader::new(
             self.toc_name_hash, 
@@ -263,6 +386,7 @@ impl TocResolverType2 {
         };
         // Iterate through each file
         if TocDirectory::has_files(Rc::clone(&node)) {
+            flat_value.first_file = tracker.resolved_files;
             let mut curr_file = Rc::clone(node.borrow().first_file.as_ref().unwrap());
             loop {
                 let mut flat_file = IoFileIndexEntry {
@@ -270,10 +394,12 @@ impl TocResolverType2 {
                     next_file: u32::MAX,
                     user_data: tracker.resolved_files,
                     file_size: curr_file.borrow().file_size,
-                    os_path: curr_file.borrow().os_file_path.clone(),
+                    file_name: curr_file.borrow().name.clone(),
+                    source: curr_file.borrow().source.clone(),
                     hash_path: String::new()
 
                 };
+                self.file_refs.push((Rc::clone(&node), Rc::clone(&curr_file)));
                 // travel upwards through parents to build hash path
                 // calculate hash after validation so it's easier to remove incorrectly formatted uassets
                 let mut path_comps: Vec<String> = vec![];
@@ -344,6 +470,186 @@ impl TocResolverType2 {
         gen_blocks
     }
 
+    // Redirects can only point to packages that are in the container, since the game won't know about a package that
+    // isn't listed in any container header
+    fn add_package_redirects(container_header: &mut ContainerHeader) {
+        for redirect in unsafe { (*addr_of!(asset_collector::PACKAGE_REDIRECTS)).iter().flatten() } {
+            if asset_collector::find_file(&(redirect.target.to_owned() + ".uasset")).is_none() {
+                println!("WARNING: Package redirect from {} to {} in mod {} was skipped, target package doesn't exist", &redirect.source, &redirect.target, &redirect.mod_id);
+                continue;
+            }
+            let source = Hasher16::get_cityhash64(&redirect.source);
+            match container_header.package_redirects.iter_mut().find(|r| r.0 == source) {
+                Some(existing) => existing.1 = Hasher16::get_cityhash64(&redirect.target), // later mods take priority
+                None => container_header.package_redirects.push((source, Hasher16::get_cityhash64(&redirect.target)))
+            }
+        }
+    }
+
+    // Files are matched case insensitively when they're added to the tree, so this should only find paths that hash to the same id.
+    // The engine will only ever find one of them, so only the higher priority file (from the mod that was loaded last) is kept and the
+    // other one is removed from the tree. Returns true if any files were removed
+    fn remove_chunk_id_collisions(&mut self) -> bool {
+        let mut seen: BTreeMap<IoChunkId, usize> = BTreeMap::new();
+        let mut removed = false;
+        for (i, file) in self.files.iter().enumerate() {
+            let chunk_id = self.get_file_hash(file);
+            let first = match seen.get(&chunk_id) {
+                Some(first) => *first,
+                None => {
+                    seen.insert(chunk_id, i);
+                    continue;
+                }
+            };
+            let (kept, dropped) = match self.file_refs[i].1.borrow().priority > self.file_refs[first].1.borrow().priority {
+                true => (i, first),
+                false => (first, i)
+            };
+            let collision = format!("{} and {} both have chunk id {:016X} ({:?}), only {} was kept",
+                Self::describe_file(&self.files[first]), Self::describe_file(file), chunk_id.get_raw_hash(), chunk_id.get_type(),
+                Self::describe_file(&self.files[kept]));
+            println!("WARNING: Chunk id collision in {}: {}", TARGET_TOC, collision);
+            self.chunk_id_collisions.push(collision);
+            let (dir, dropped_file) = &self.file_refs[dropped];
+            TocDirectory::remove_file(Rc::clone(dir), Rc::clone(dropped_file));
+            seen.insert(chunk_id, kept);
+            removed = true;
+        }
+        removed
+    }
+
+    // Packages have their summary and import graph read for the container header. Any that can't be read (such as an empty file or a
+    // package cooked for a pak) are removed from the tree so that the game keeps using it's own asset. Returns true if any files were removed
+    fn remove_invalid_packages<TSummary: PackageIoSummaryDeserialize, EN: byteorder::ByteOrder>(&mut self) -> bool {
+        let mut removed = false;
+        for (i, file) in self.files.iter().enumerate() {
+            let chunk_id = self.get_file_hash(file);
+            if chunk_id.get_type() != IoChunkType4::ExportBundleData {
+                continue;
+            }
+            match Self::read_store_entry::<TSummary, EN>(file, chunk_id.get_raw_hash()) {
+                Ok(package) => {
+                    self.store_entries.insert(chunk_id.get_raw_hash(), package);
+                },
+                Err(e) => {
+                    let invalid = format!("{}: {}", Self::describe_file(file), e);
+                    println!("WARNING: Package in {} couldn't be read and was left out: {}", TARGET_TOC, invalid);
+                    self.incorrect_asset_format.push(invalid);
+                    self.incorrect_asset_format_size += file.file_size;
+                    let (dir, invalid_file) = &self.file_refs[i];
+                    TocDirectory::remove_file(Rc::clone(dir), Rc::clone(invalid_file));
+                    removed = true;
+                }
+            }
+        }
+        removed
+    }
+
+    fn read_store_entry<TSummary: PackageIoSummaryDeserialize, EN: byteorder::ByteOrder>(file: &IoFileIndexEntry, hash: u64) -> Result<ContainerHeaderPackage, Box<dyn Error>> {
+        match &file.source {
+            TocFileSource::OsPath(os_path) => {
+                let mut file_reader = BufReader::with_capacity(Self::FILE_SUMMARY_READER_ALLOC, File::open(os_path)?);
+                ContainerHeaderPackage::from_package_summary::<
+                    ExportBundleHeader4, TSummary, BufReader<File>, EN
+                >(&mut file_reader, hash, file.file_size)
+            },
+            TocFileSource::OsPathSlice(os_path, offset) => {
+                let mut file_reader = FileSliceReader::new(
+                    BufReader::with_capacity(Self::FILE_SUMMARY_READER_ALLOC, File::open(os_path)?), *offset, file.file_size
+                )?;
+                ContainerHeaderPackage::from_package_summary::<
+                    ExportBundleHeader4, TSummary, FileSliceReader<BufReader<File>>, EN
+                >(&mut file_reader, hash, file.file_size)
+            },
+            TocFileSource::Memory(buffer) => {
+                let mut file_reader = Cursor::new(buffer.as_slice());
+                ContainerHeaderPackage::from_package_summary::<
+                    ExportBundleHeader4, TSummary, Cursor<&[u8]>, EN
+                >(&mut file_reader, hash, file.file_size)
+            },
+            TocFileSource::Producer(_) | TocFileSource::DeltaPatch(_) =>
+                Err(format!("Producer or patch for {} wasn't resolved before flattening", &file.hash_path).into())
+        }
+    }
+
+    fn reflatten_toc_tree(&mut self, tracker: &mut TocFlattenTracker, root: TocDirectoryRef) {
+        *tracker = TocFlattenTracker::new();
+        self.files.clear();
+        self.strings.clear();
+        self.file_refs.clear();
+        self.directories = self.flatten_toc_tree_dir(tracker, root);
+    }
+
+    // Returns the index of the first file with the same contents for each file that's a duplicate. Only files that are the same
+    // size as another file get hashed
+    fn find_duplicate_chunks(&self) -> Vec<Option<usize>> {
+        let mut duplicates = vec![None; self.files.len()];
+        if !unsafe { DEDUPLICATE_CHUNKS } {
+            return duplicates;
+        }
+        let mut same_size: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+        for (i, file) in self.files.iter().enumerate() {
+            same_size.entry(file.file_size).or_default().push(i);
+        }
+        for candidates in same_size.values().filter(|c| c.len() > 1) {
+            let mut seen: BTreeMap<[u8; 20], usize> = BTreeMap::new();
+            for i in candidates {
+                match Self::get_content_hash(&self.files[*i]) {
+                    Ok(hash) => match seen.get(&hash) {
+                        Some(first) => duplicates[*i] = Some(*first),
+                        None => { seen.insert(hash, *i); }
+                    },
+                    Err(e) => println!("WARNING: Couldn't check {} for duplicates: {}", self.files[*i].source.get_display_path(), e)
+                }
+            }
+        }
+        duplicates
+    }
+
+    fn get_content_hash(file: &IoFileIndexEntry) -> Result<[u8; 20], Box<dyn Error>> {
+        let mut hasher = Sha1::new();
+        match &file.source {
+            TocFileSource::OsPath(os_path) => { io::copy(&mut File::open(os_path)?, &mut hasher)?; },
+            TocFileSource::OsPathSlice(os_path, offset) => {
+                let mut os_file = File::open(os_path)?;
+                os_file.seek(SeekFrom::Start(*offset))?;
+                io::copy(&mut os_file.take(file.file_size), &mut hasher)?;
+            },
+            TocFileSource::Memory(buffer) => hasher.update(buffer.as_slice()),
+            TocFileSource::Producer(_) | TocFileSource::DeltaPatch(_) =>
+                return Err(format!("{} wasn't resolved before flattening", &file.hash_path).into())
+        }
+        Ok(hasher.finalize().into())
+    }
+
+    fn describe_file(file: &IoFileIndexEntry) -> String {
+        let extension = Path::new(&file.file_name).extension().map_or(String::new(), |e| format!(".{}", e.to_string_lossy()));
+        format!("{}{} ({})", Self::get_game_path(&file.hash_path), extension, file.source.get_display_path())
+    }
+
+    // Runs while the game is opening the TOC, which is before it can load any of the packages inside of it
+    fn check_dependencies<EN: byteorder::ByteOrder>(&self, container_header: &ContainerHeader) {
+        if game_profile::get_game_profile().has_quirk(GameQuirks::SKIP_DEPENDENCY_CHECK) {
+            return;
+        }
+        let package_files: HashMap<u64, usize> = self.files.iter().enumerate()
+            .filter(|(i, _)| self.chunk_ids[*i].get_type() == IoChunkType4::ExportBundleData)
+            .map(|(i, _)| (self.chunk_ids[i].get_raw_hash(), i))
+            .collect();
+        let package_paths = package_files.iter().map(|(id, i)| (*id, Self::get_game_path(&self.files[*i].hash_path))).collect();
+        // missing imports are reported with the path from the importing package's name map, so that has to be read again
+        let get_package_names = |id: u64| package_files.get(&id)
+            .and_then(|i| asset_collector::read_file(&self.file_refs[*i].1).ok())
+            .and_then(|data| IoPackage::from_buffer::<_, EN>(&mut Cursor::new(&data), data.len() as u64).ok())
+            .map_or(vec![], |package| package.names);
+        let report = dependency_check::check_dependencies(container_header, &package_paths, get_package_names);
+        if report.has_problems() {
+            println!("WARNING: Some packages in {} have missing or circular imports", TARGET_TOC);
+            report.print();
+        }
+        unsafe { dependency_check::DEPENDENCY_REPORT = Some(report) };
+    }
+
     fn serialize_container_header<TEndian: byteorder::ByteOrder>(&mut self, container_header: &mut ContainerHeader) -> Vec<u8> {
         let mut container_header_buffer = Cursor::new(vec![]);
         let container_header = container_header.to_buffer::<Cursor<Vec<u8>>, TEndian>(&mut container_header_buffer).unwrap(); // write our container header in the buffer
@@ -355,36 +661,85 @@ impl TocResolverType2 {
         container_header
     }
 
-    fn serialize_entry<TSummary: PackageIoSummaryDeserialize>(&mut self, index: usize, container_header: &mut ContainerHeader) -> PartitionBlock {
+    // The container header is always built last, since it needs every package in the container. Games that expect it to be the
+    // first chunk get it moved to the start afterwards, with every other chunk shifted along by the space that it takes up
+    fn move_container_header_to_start(&mut self, container_data: &mut ContainerData, entry_block_count: usize) {
+        let header_size = container_data.header.len() as u64;
+        let header_block_count = (self.compression_blocks.len() - entry_block_count) as u64;
+        let offset_shift = header_block_count * self.compression_block_size as u64; // virtual offsets are per compression block
+        let mut cas_shift = header_size;
+        let alignment_amount = cas_shift % self.compression_block_alignment as u64;
+        if alignment_amount > 0 {
+            cas_shift += self.compression_block_alignment as u64 - alignment_amount;
+        }
+        let mut compression_blocks = TocResolverType2::create_compression_blocks(header_size, 0, self.compression_block_size);
+        compression_blocks.extend(self.compression_blocks[..entry_block_count].iter()
+            .map(|b| IoStoreTocCompressedBlockEntry::new(b.get_offset() + cas_shift, b.get_uncompressed_size())));
+        self.compression_blocks = compression_blocks;
+        self.offsets_and_lengths.pop();
+        for offset_length in self.offsets_and_lengths.iter_mut() {
+            *offset_length = IoOffsetAndLength::new(offset_length.get_offset() + offset_shift, offset_length.get_length());
+        }
+        self.offsets_and_lengths.insert(0, IoOffsetAndLength::new(0, header_size));
+        self.chunk_ids.rotate_right(1);
+        self.metas.rotate_right(1);
+        self.files.iter_mut().for_each(|f| f.user_data += 1); // user data is the file's chunk index
+        for block in container_data.virtual_blocks.iter_mut() {
+            block.start += cas_shift;
+        }
+        let header = Rc::new(mem::take(&mut container_data.header));
+        container_data.virtual_blocks.insert(0, PartitionBlock::new_memory(header.as_ptr(), 0, header_size));
+        unsafe { (*addr_of_mut!(CONTAINER_ENTRIES_MEMORY_POOL)).as_mut().unwrap().push(header); }
+    }
+
+    // Duplicates point at the first file's range of the container, so they don't get any compression or partition blocks of their own
+    fn serialize_entry(&mut self, index: usize, duplicate_of: Option<usize>, container_header: &mut ContainerHeader) -> Option<PartitionBlock> {
         let target_file = &self.files[index];
         let generated_chunk_id = self.get_file_hash(target_file); // create the hash for the new file
         //println!("Created chunk id from {}: {:?}", &target_file.hash_path, generated_chunk_id);
         self.chunk_ids.push(generated_chunk_id); // push once we're sure that the file's valid
         let curr_file = &self.files[index]; // Generate FIoOffsetAndLength
         let file_offset = self.compression_blocks.len() as u64 * self.compression_block_size as u64;
-        let generated_offset_length = IoOffsetAndLength::new(file_offset, curr_file.file_size);
+        let generated_offset_length = match duplicate_of {
+            Some(first) => IoOffsetAndLength::new(self.offsets_and_lengths[first].get_offset(), curr_file.file_size),
+            None => IoOffsetAndLength::new(file_offset, curr_file.file_size)
+        };
         //println!("Created offset and length for {}: 0x{:X}, 0x{:X}", &curr_file.name, file_offset, curr_file.file_size);
         self.offsets_and_lengths.push(generated_offset_length);
         // Generate compression blocks
-        self.compression_blocks.append(&mut TocResolverType2::create_compression_blocks(target_file.file_size, self.cas_pointer, self.compression_block_size));
+        if duplicate_of.is_none() {
+            self.compression_blocks.append(&mut TocResolverType2::create_compression_blocks(target_file.file_size, self.cas_pointer, self.compression_block_size));
+        }
         self.metas.push(IoStoreTocEntryMeta::new_empty()); // Generate meta - SHA1 hash of the file's contents (doesn't seem to be required)
         if self.chunk_ids[index].get_type() == IoChunkType4::ExportBundleData {
-            let os_file = File::open(&target_file.os_path).unwrap(); // Export Bundles (.uasset) have store entry data written
-            let mut file_reader = BufReader::with_capacity(Self::FILE_SUMMARY_READER_ALLOC, os_file);
-            container_header.packages.push(ContainerHeaderPackage::from_package_summary::<
-                ExportBundleHeader4, TSummary, BufReader<File>, byteorder::NativeEndian
-            >(
-                &mut file_reader, 
-                self.chunk_ids[index].get_raw_hash(), curr_file.file_size
-            ));
+            // Export Bundles (.uasset) have store entry data written
+            let hash = self.chunk_ids[index].get_raw_hash();
+            container_header.add_package(self.store_entries.remove(&hash).unwrap()); // read in remove_invalid_packages
+            if let Some((culture, source_path)) = asset_collector::get_localized_package_source(&target_file.hash_path) {
+                container_header.add_localized_package(&culture, Hasher16::get_cityhash64(&source_path), hash);
+            }
+        }
+        if duplicate_of.is_some() {
+            return None;
         }
         // write into container data 
-        unsafe { CONTAINER_ENTRIES_OSPATH_POOL.as_mut().unwrap().push(target_file.os_path.to_owned() + "\0"); } // make C formatted string
-        let curr_ospath = unsafe { &CONTAINER_ENTRIES_OSPATH_POOL.as_ref().unwrap()[index] };
-        let new_partition_block = PartitionBlock {
-            os_path: curr_ospath.as_ptr(),
-            start: self.cas_pointer,
-            length: target_file.file_size
+        let new_partition_block = match &target_file.source {
+            TocFileSource::OsPath(os_path) => {
+                unsafe { (*addr_of_mut!(CONTAINER_ENTRIES_OSPATH_POOL)).as_mut().unwrap().push(os_path.to_owned() + "\0"); } // make C formatted string
+                let curr_ospath = unsafe { (*addr_of!(CONTAINER_ENTRIES_OSPATH_POOL)).as_ref().unwrap().last().unwrap() };
+                PartitionBlock::new_file(curr_ospath.as_ptr(), 0, self.cas_pointer, target_file.file_size)
+            },
+            TocFileSource::OsPathSlice(os_path, offset) => {
+                unsafe { (*addr_of_mut!(CONTAINER_ENTRIES_OSPATH_POOL)).as_mut().unwrap().push(os_path.to_owned() + "\0"); }
+                let curr_ospath = unsafe { (*addr_of!(CONTAINER_ENTRIES_OSPATH_POOL)).as_ref().unwrap().last().unwrap() };
+                PartitionBlock::new_file(curr_ospath.as_ptr(), *offset, self.cas_pointer, target_file.file_size)
+            },
+            TocFileSource::Memory(buffer) => {
+                unsafe { (*addr_of_mut!(CONTAINER_ENTRIES_MEMORY_POOL)).as_mut().unwrap().push(Rc::clone(buffer)); }
+                PartitionBlock::new_memory(buffer.as_ptr(), self.cas_pointer, target_file.file_size)
+            },
+            TocFileSource::Producer(_) | TocFileSource::DeltaPatch(_) =>
+                panic!("CRITICAL ERROR: Producer or patch for {} wasn't resolved before flattening", &target_file.hash_path)
         };
         self.cas_pointer += target_file.file_size; // move cas pointer
         let alignment_amount = self.cas_pointer % self.compression_block_alignment as u64;
@@ -392,19 +747,20 @@ impl TocResolverType2 {
             let diff = self.compression_block_alignment as u64 - alignment_amount;
             self.cas_pointer += diff;
         }
-        new_partition_block
+        Some(new_partition_block)
     }
 
     pub const FILE_SUMMARY_READER_ALLOC: usize = 0x2000;
 
     fn get_directory_index_size(&self) -> u32 {
-        // Get DirectoryIndexSize = Directory Entries + File Entries + Strings
+        // Get DirectoryIndexSize = Mount Point + Directory Entries + File Entries + Strings
         // Each section contains a u32 to note the object count
+        let mount_point_bytes = FString32NoHash::get_expected_length(MOUNT_POINT) as u32;
         let directory_index_bytes = (self.directories.len() * std::mem::size_of::<IoDirectoryIndexEntry>() + mem::size_of::<u32>()) as u32;
         let file_index_bytes = (self.files.len() * IO_FILE_INDEX_ENTRY_SERIALIZED_SIZE + mem::size_of::<u32>()) as u32;
         let mut string_index_bytes = mem::size_of::<u32>() as u32;
         self.strings.iter().for_each(|name| string_index_bytes += FString32NoHash::get_expected_length(name) as u32);
-        directory_index_bytes + file_index_bytes + string_index_bytes
+        mount_point_bytes + directory_index_bytes + file_index_bytes + string_index_bytes
     }
 }
 
@@ -415,36 +771,211 @@ impl TocResolverType2 {
 pub fn build_table_of_contents_inner(root: TocDirectoryRef, toc_path: &str) -> Vec<u8> {
     //println!("BUILD TABLE OF CONTENTS FOR {}", TARGET_TOC);
     let mut profiler = TocBuilderProfiler::new();
+    let game_profile = game_profile::get_game_profile();
+    profiler.game_profile = format!("{} ({}), {:?}, {:?} endian", game_profile.name, game_profile.id, game_profile.engine_version, game_profile.endianness);
     let mut resolver = TocResolverType2::new::<
         IoStoreTocHeaderType2
-    >(TARGET_TOC, PROJECT_NAME, DEFAULT_COMPRESSION_BLOCK_ALIGNMENT);
-    resolver.flatten_toc_tree(&mut TocFlattenTracker::new(), Rc::clone(&root));
-    let serialize_results = resolver.serialize::<PackageSummary2, IoStoreTocHeaderType3>(&mut profiler, toc_path);
-    unsafe { CONTAINER_DATA = Some(serialize_results.1) };
-    serialize_results.0
+    >(TARGET_TOC, PROJECT_NAME, game_profile.compression_block_alignment);
+    let root_path = root.borrow().name.clone();
+    resolve_producers(Rc::clone(&root), &root_path, &mut profiler);
+    apply_delta_patches(Rc::clone(&root));
+    merge_data_tables(Rc::clone(&root));
+    type LE = byteorder::LittleEndian;
+    type BE = byteorder::BigEndian;
+    match game_profile.endianness {
+        Endianness::Little => resolver.flatten_toc_tree::<PackageSummary2, LE>(&mut TocFlattenTracker::new(), Rc::clone(&root)),
+        Endianness::Big => resolver.flatten_toc_tree::<PackageSummary2, BE>(&mut TocFlattenTracker::new(), Rc::clone(&root))
+    }
+    profiler.set_flatten_time();
+    // 4.25+ and 4.26 use the same TOC layout as 4.27, minus the partition fields
+    let serialize_results = match (game_profile.engine_version, game_profile.endianness) {
+        (EngineVersion::UE4_25Plus | EngineVersion::UE4_26, Endianness::Little) =>
+            resolver.serialize::<IoStoreTocHeaderType2, LE>(&mut profiler, toc_path),
+        (EngineVersion::UE4_25Plus | EngineVersion::UE4_26, Endianness::Big) =>
+            resolver.serialize::<IoStoreTocHeaderType2, BE>(&mut profiler, toc_path),
+        (EngineVersion::UE4_27, Endianness::Little) => resolver.serialize::<IoStoreTocHeaderType3, LE>(&mut profiler, toc_path),
+        (EngineVersion::UE4_27, Endianness::Big) => resolver.serialize::<IoStoreTocHeaderType3, BE>(&mut profiler, toc_path)
+    };
+    profiler.set_serialize_time();
+    let (toc, mut container_data) = serialize_results;
+    container_data.toc = toc.clone(); // kept so that the virtual container can be read back (see VirtualContainerReader)
+    unsafe { CONTAINER_DATA = Some(container_data) };
+    unsafe { TOC_BUILDER_PROFILER = Some(profiler) };
+    toc
+}
+
+// Generated files need to know their size before anything reads from the tree. A file that can't produce it's contents is swapped for
+// the file that it replaced (if another mod had one), otherwise it's left out so that the game's file is used
+fn resolve_producers(dir: TocDirectoryRef, dir_path: &str, profiler: &mut TocBuilderProfiler) {
+    let mut curr_file = dir.borrow().first_file.clone();
+    while let Some(file) = curr_file {
+        curr_file = file.borrow().next.clone();
+        let file_path = format!("{}/{}", dir_path, file.borrow().name);
+        let mut errors = vec![];
+        let resolved = file.borrow_mut().resolve_or_fall_back(&mut errors);
+        for e in errors {
+            println!("WARNING: Failed to read {} for {}: {}", file_path, TARGET_TOC, e);
+            profiler.failed_to_read.push(format!("{}: {}", file_path, e));
+        }
+        if !resolved {
+            TocDirectory::remove_file(Rc::clone(&dir), file);
+        }
+    }
+    let mut curr_dir = dir.borrow().first_child.clone();
+    while let Some(child) = curr_dir {
+        let child_path = format!("{}/{}", dir_path, child.borrow().name);
+        resolve_producers(Rc::clone(&child), &child_path, profiler);
+        curr_dir = child.borrow().next_sibling.clone();
+    }
+}
+
+// Patched files are swapped into the tree in place of their patches, so this has to run before anything reads from it
+fn apply_delta_patches(root: TocDirectoryRef) {
+    let report = delta_patch::apply_patches(root);
+    if report.has_problems() {
+        println!("WARNING: Some patches in {} were refused", TARGET_TOC);
+        report.print();
+    }
+    unsafe { delta_patch::DELTA_PATCH_REPORT = Some(report) };
+}
+
+// Merged tables replace the highest priority mod's file in the tree, so this has to run before it's flattened
+fn merge_data_tables(root: TocDirectoryRef) {
+    let report = datatable_merge::merge_data_tables(root);
+    if report.has_problems() {
+        println!("WARNING: Some DataTables in {} couldn't be fully merged", TARGET_TOC);
+        report.print();
+    }
+    unsafe { datatable_merge::DATATABLE_MERGE_REPORT = Some(report) };
 }
 
 pub struct ContainerData {
+    toc: Vec<u8>,
     header: Vec<u8>,
-    virtual_blocks: Vec<PartitionBlock>
+    virtual_blocks: Vec<PartitionBlock>,
+    alignment: u32 // each block's end is padded to this
+}
+
+// Read + Seek over the virtual container, laid out the same way as write_partition_blocks writes it, so that the emulator's
+// container can be read with TocReader without writing it to disk first. Gaps between blocks read as zeroes
+pub struct VirtualContainerReader {
+    container_data: &'static ContainerData,
+    trailer_start: u64,
+    position: u64,
+    open_file: Option<(usize, File)> // block index and it's file, so that reads from the same block don't reopen it
+}
+
+impl VirtualContainerReader {
+    // Only valid until SafeToDropContainerMetadata is called
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let container_data = unsafe { (*addr_of!(CONTAINER_DATA)).as_ref().ok_or("No container has been built yet")? };
+        let alignment = container_data.alignment as u64;
+        let trailer_start = container_data.virtual_blocks.last().map_or(0, |b| (b.start + b.length).div_ceil(alignment) * alignment);
+        Ok(Self { container_data, trailer_start, position: 0, open_file: None })
+    }
+
+    pub fn get_toc(&self) -> &[u8] {
+        &self.container_data.toc
+    }
+
+    pub fn len(&self) -> u64 {
+        self.trailer_start + self.container_data.header.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Read from the block at index, returning how many bytes were read
+    fn read_block(&mut self, index: usize, buf: &mut [u8]) -> io::Result<usize> {
+        use std::ffi::CStr;
+        let block = &self.container_data.virtual_blocks[index];
+        let block_offset = self.position - block.start;
+        let to_read = buf.len().min((block.length - block_offset) as usize);
+        if block.os_path.is_null() { // memory block
+            let data = unsafe { std::slice::from_raw_parts(block.data, block.length as usize) };
+            buf[..to_read].copy_from_slice(&data[block_offset as usize..block_offset as usize + to_read]);
+            return Ok(to_read);
+        }
+        if self.open_file.as_ref().map(|(i, _)| *i) != Some(index) {
+            let os_path = unsafe { CStr::from_ptr(block.os_path as *const i8) }.to_str().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            self.open_file = Some((index, File::open(os_path)?));
+        }
+        let file = &mut self.open_file.as_mut().unwrap().1;
+        file.seek(SeekFrom::Start(block.file_offset + block_offset))?;
+        file.read_exact(&mut buf[..to_read])?;
+        Ok(to_read)
+    }
 }
 
+impl Read for VirtualContainerReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let blocks = &self.container_data.virtual_blocks;
+        let read = if self.position >= self.trailer_start {
+            let header = &self.container_data.header;
+            let start = (self.position - self.trailer_start).min(header.len() as u64) as usize;
+            let to_read = buf.len().min(header.len() - start);
+            buf[..to_read].copy_from_slice(&header[start..start + to_read]);
+            to_read
+        } else {
+            // blocks are sorted by their start offset
+            let next = blocks.partition_point(|b| b.start <= self.position);
+            match next.checked_sub(1).filter(|i| self.position < blocks[*i].start + blocks[*i].length) {
+                Some(index) => self.read_block(index, buf)?,
+                None => { // padding before the next block or the trailer
+                    let gap_end = blocks.get(next).map_or(self.trailer_start, |b| b.start);
+                    let to_read = buf.len().min((gap_end - self.position) as usize);
+                    buf[..to_read].fill(0);
+                    to_read
+                }
+            }
+        };
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for VirtualContainerReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len().checked_add_signed(n),
+            SeekFrom::Current(n) => self.position.checked_add_signed(n)
+        };
+        self.position = new_position.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start of the virtual container"))?;
+        Ok(self.position)
+    }
+}
+
+// A range of the virtual container. File blocks are read from os_path starting at file_offset, while memory blocks are read
+// from data, which stays valid until SafeToDropContainerMetadata is called. The unused pointer is null
 #[repr(C)]
 pub struct PartitionBlock {
     os_path: *const u8, // 0x0
     start: u64, // 0x8
     length: u64, // 0x10
+    data: *const u8, // 0x18
+    file_offset: u64, // 0x20
 }
 
-pub fn get_virtual_partition(cas_path: &str) -> Option<(&Vec<PartitionBlock>, &Vec<u8>)> {
+impl PartitionBlock {
+    pub fn new_file(os_path: *const u8, file_offset: u64, start: u64, length: u64) -> Self {
+        Self { os_path, start, length, data: std::ptr::null(), file_offset }
+    }
+    pub fn new_memory(data: *const u8, start: u64, length: u64) -> Self {
+        Self { os_path: std::ptr::null(), start, length, data, file_offset: 0 }
+    }
+}
+
+pub fn get_virtual_partition(cas_path: &str) -> Option<(&Vec<PartitionBlock>, &Vec<u8>, u32)> {
     // check that it's our target CAS
     // build virtual CAS here
     let path_check = PathBuf::from(cas_path);
     let file_name = path_check.file_name().unwrap().to_str().unwrap();
     if file_name == TARGET_CAS {
-        match unsafe { &CONTAINER_DATA } {
+        match unsafe { &*addr_of!(CONTAINER_DATA) } {
             Some(blocks) => {
-                Some((&blocks.virtual_blocks, &blocks.header))
+                Some((&blocks.virtual_blocks, &blocks.header, blocks.alignment))
             },
             None => None
         }
@@ -453,14 +984,24 @@ pub fn get_virtual_partition(cas_path: &str) -> Option<(&Vec<PartitionBlock>, &V
     }
 }
 
+/// # Safety
+/// Must not be called while a TOC is being built on another thread. This checks if TOC_BUILDER_PROFILER has been assigned a value
+/// first, which only happens after building a TOC
+pub unsafe fn print_toc_builder_results() {
+    if let Some(profiler) = (*addr_of!(TOC_BUILDER_PROFILER)).as_ref() {
+        profiler.display_results();
+    }
+}
+
 pub struct TocBuilderProfiler {
     // All file sizes are in bytes
     successful_files: u64,
-    successful_files_size: u64,
-    incorrect_asset_format: Vec<String>, // list of offending files, print out to console
+    successful_files_size: u64, // data written to the container, so deduplicated chunks aren't counted twice
+    pub incorrect_asset_format: Vec<String>, // list of offending files, print out to console
     incorrect_asset_format_size: u64,
-    failed_to_read: Vec<String>,
+    pub failed_to_read: Vec<String>,
     failed_to_read_size: u64,
+    game_profile: String,
     container_header_hash: u64,
     compression_block_count: u64,
     mount_point: String,
@@ -468,6 +1009,9 @@ pub struct TocBuilderProfiler {
     file_index_size: u64,
     string_index_size: u64,
     generated_meta_hashes: bool,
+    pub chunk_id_collisions: Vec<String>,
+    deduplicated_chunks: Vec<String>,
+    deduplicated_size: u64,
     start_time: Instant,
     time_to_flatten: u128,
     time_to_serialize: u128
@@ -482,6 +1026,7 @@ impl TocBuilderProfiler {
             incorrect_asset_format_size: 0,
             failed_to_read: vec![],
             failed_to_read_size: 0,
+            game_profile: String::new(),
             container_header_hash: 0,
             compression_block_count: 0,
             mount_point: String::new(),
@@ -489,6 +1034,9 @@ impl TocBuilderProfiler {
             file_index_size: 0,
             string_index_size: 0,
             generated_meta_hashes: false,
+            chunk_id_collisions: vec![],
+            deduplicated_chunks: vec![],
+            deduplicated_size: 0,
             start_time: Instant::now(),
             time_to_flatten: 0,
             time_to_serialize: 0
@@ -502,8 +1050,39 @@ impl TocBuilderProfiler {
         self.time_to_serialize = self.start_time.elapsed().as_micros();
     }
     fn display_results(&self) {
-        // TODO: Advanced display results
         println!("Flatten Time: {} ms", self.time_to_flatten as f64 / 1000f64);
         println!("Serialize Time: {} ms", self.time_to_serialize as f64 / 1000f64);
+        println!("{} files in container ({} KB)", self.successful_files, self.successful_files_size / 1024);
+        println!("Game profile: {}", self.game_profile);
+        println!("Container ID: {:X}, mount point \"{}\"", self.container_header_hash, self.mount_point);
+        println!("{} compression blocks, directory index is {} bytes", self.compression_block_count, self.directory_index_size);
+        if !self.chunk_id_collisions.is_empty() {
+            println!("{}", "-".repeat(80));
+            println!("CHUNK ID COLLISIONS: {} FILES", self.chunk_id_collisions.len());
+            for i in &self.chunk_id_collisions {
+                println!("{}", i);
+            }
+        }
+        if !self.failed_to_read.is_empty() {
+            println!("{}", "-".repeat(80));
+            println!("FAILED TO READ: {} FILES", self.failed_to_read.len());
+            for i in &self.failed_to_read {
+                println!("{}", i);
+            }
+        }
+        if !self.incorrect_asset_format.is_empty() {
+            println!("{}", "-".repeat(80));
+            println!("INCORRECT ASSET FORMAT: {} FILES ({} KB)", self.incorrect_asset_format.len(), self.incorrect_asset_format_size / 1024);
+            for i in &self.incorrect_asset_format {
+                println!("{}", i);
+            }
+        }
+        if !self.deduplicated_chunks.is_empty() {
+            println!("{}", "-".repeat(80));
+            println!("DEDUPLICATED CHUNKS: {} FILES ({} KB saved)", self.deduplicated_chunks.len(), self.deduplicated_size / 1024);
+            for i in &self.deduplicated_chunks {
+                println!("{}", i);
+            }
+        }
     }
 }
\ No newline at end of file
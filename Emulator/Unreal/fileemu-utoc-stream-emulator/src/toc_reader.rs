@@ -0,0 +1,451 @@
+use byteorder::ReadBytesExt;
+use crate::{
+    compression::CompressionMethod,
+    encryption::{self, AesKey},
+    engine_detection::TOC_HEADER_SIZE,
+    game_profile::Endianness,
+    io_toc::{
+        self, ContainerHeader, IoChunkId, IoChunkType4, IoContainerFlags, IoDirectoryIndexEntry, IoFileIndexEntry, IoOffsetAndLength,
+        IoStoreTocCompressedBlockEntry, IoStoreTocHeaderType3, IoStringPool
+    },
+    string::{FString32NoHash, FStringDeserializer},
+    toc_factory::{self, VirtualContainerReader}
+};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom},
+    path::{Path, PathBuf}
+};
+
+// Reads the table of contents of an existing IO Store container (4.25+ to 4.27) so that chunks can be located inside of it's
+// partitions. This is used for mods that ship their files as a .utoc/.ucas pair instead of loose files.
+// Some notes about the layout of the TOC, since it differs from what TocResolverType2 writes:
+// - Compression method names come right after the compression blocks (CompressionMethodNameCount * CompressionMethodNameLength)
+// - Signed containers then have a hash size, two signatures and a SHA1 hash for each compression block
+// - Directory index (if the container is indexed)
+// - Meta (ignored here)
+// Encrypted containers have their directory index and every compression block encrypted with AES-256-ECB, with each block
+// padded to 16 bytes. The key is picked from the keys given in encryption::add_key using the TOC's encryption key GUID
+// Big endian containers are read by checking the byte order of the header size, since everything before it is a single byte
+pub struct TocReader {
+    pub toc_path: PathBuf,
+    pub header: IoStoreTocHeaderType3,
+    pub chunk_ids: Vec<IoChunkId>,
+    pub offsets_and_lengths: Vec<IoOffsetAndLength>,
+    pub compression_blocks: Vec<IoStoreTocCompressedBlockEntry>,
+    pub compression_methods: Vec<String>,
+    pub mount_point: String,
+    pub directories: Vec<IoDirectoryIndexEntry>,
+    pub files: Vec<IoFileIndexEntry>,
+    pub strings: Vec<String>,
+    pub endianness: Endianness, // used to read the container header and packages from the container
+    key: Option<AesKey>, // only set for encrypted containers
+    is_virtual: bool // chunks are read from the emulator's container instead of .ucas files
+}
+
+pub trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+// A file in the container's directory index
+#[derive(Debug, Clone, PartialEq)]
+pub struct TocReaderFile {
+    pub path: String, // relative to the mount point (e.g P3R/Content/Folder/Asset.uasset)
+    pub chunk_index: usize, // index into chunk ids, offsets and lengths
+}
+
+pub const SHA_HASH_SERIALIZED_SIZE: u64 = 0x14;
+
+impl TocReader {
+    pub fn from_file(toc_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let toc_file = File::open(toc_path)?;
+        let mut reader = BufReader::new(toc_file);
+        match get_toc_endianness(&mut reader)? {
+            Endianness::Little => Self::from_buffer::<BufReader<File>, byteorder::LittleEndian>(&mut reader, toc_path),
+            Endianness::Big => Self::from_buffer::<BufReader<File>, byteorder::BigEndian>(&mut reader, toc_path)
+        }
+    }
+
+    // Read the TOC that the emulator built, with chunks read from it's virtual container (see toc_factory::VirtualContainerReader)
+    pub fn from_virtual_container() -> Result<Self, Box<dyn Error>> {
+        let container = VirtualContainerReader::new()?;
+        let mut reader = Cursor::new(container.get_toc());
+        let toc_path = Path::new(toc_factory::TARGET_TOC);
+        let mut toc = match get_toc_endianness(&mut reader)? {
+            Endianness::Little => Self::from_buffer::<Cursor<&[u8]>, byteorder::LittleEndian>(&mut reader, toc_path)?,
+            Endianness::Big => Self::from_buffer::<Cursor<&[u8]>, byteorder::BigEndian>(&mut reader, toc_path)?
+        };
+        toc.is_virtual = true;
+        Ok(toc)
+    }
+
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, toc_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let header = IoStoreTocHeaderType3::from_buffer::<R, E>(reader)?;
+        let chunk_ids = IoChunkId::list_from_buffer::<R, E>(reader, header.toc_entry_count as usize)?;
+        let offsets_and_lengths = IoOffsetAndLength::list_from_buffer::<R, E>(reader, header.toc_entry_count as usize)?;
+        let compression_blocks = IoStoreTocCompressedBlockEntry::list_from_buffer::<R, E>(reader, header.toc_compressed_block_entry_count as usize)?;
+        let mut compression_methods = Vec::with_capacity(io_toc::get_list_capacity(reader,
+            header.compression_method_name_count as usize, header.compression_method_name_length as usize)?);
+        for _ in 0..header.compression_method_name_count {
+            let mut name = vec![0; header.compression_method_name_length as usize];
+            reader.read_exact(&mut name)?;
+            let name_end = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+            compression_methods.push(String::from_utf8_lossy(&name[..name_end]).into_owned());
+        }
+        if header.container_flags.contains(IoContainerFlags::Signed) {
+            let hash_size = reader.read_i32::<E>()? as i64;
+            // TocSignature, BlockSignature, then an FSHAHash for each compression block
+            let signature_size = hash_size * 2 + (SHA_HASH_SERIALIZED_SIZE * header.toc_compressed_block_entry_count as u64) as i64;
+            reader.seek(SeekFrom::Current(signature_size))?;
+        }
+        let key = match header.container_flags.contains(IoContainerFlags::Encrypted) {
+            true => match encryption::get_key(header.encryption_key_guid) {
+                Some(key) => Some(key),
+                None => return Err(format!("Container is encrypted, but no AES key was given for it (key GUID {:032X})", header.encryption_key_guid).into())
+            },
+            false => None
+        };
+        let mut toc = Self {
+            toc_path: toc_path.to_owned(),
+            header,
+            chunk_ids,
+            offsets_and_lengths,
+            compression_blocks,
+            compression_methods,
+            mount_point: String::new(),
+            directories: vec![],
+            files: vec![],
+            strings: vec![],
+            endianness: Endianness::of::<E>(),
+            key,
+            is_virtual: false
+        };
+        if toc.header.container_flags.contains(IoContainerFlags::Indexed) && toc.header.directory_index_size > 0 {
+            let mut directory_index = vec![0; io_toc::get_list_capacity(reader, toc.header.directory_index_size as usize, 1)?];
+            reader.read_exact(&mut directory_index)?;
+            if let Err(e) = toc.read_directory_index::<E>(directory_index) {
+                return Err(match toc.key {
+                    Some(_) => format!("Wrong AES key, couldn't read the directory index ({})", e).into(),
+                    None => e
+                });
+            }
+        }
+        toc.validate()?;
+        if toc.key.is_some() {
+            toc.verify_key::<E>()?;
+        }
+        Ok(toc)
+    }
+
+    // Mod containers can be malformed, so check every index that get_files and get_chunk_blocks follow before they're used
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let block_size = self.header.compression_block_size as u64;
+        if block_size == 0 {
+            return Err("Compression block size is 0".into());
+        }
+        if self.offsets_and_lengths.len() != self.chunk_ids.len() {
+            return Err(format!("TOC has {} chunk ids but {} offsets and lengths", self.chunk_ids.len(), self.offsets_and_lengths.len()).into());
+        }
+        for (i, offset_length) in self.offsets_and_lengths.iter().enumerate() {
+            if offset_length.get_length() == 0 {
+                continue;
+            }
+            match offset_length.get_offset().checked_add(offset_length.get_length() - 1) {
+                Some(end) if end / block_size < self.compression_blocks.len() as u64 => (),
+                _ => return Err(format!("Chunk {} is outside of the container's compression blocks", i).into())
+            }
+        }
+        let check_string = |name: u32| match name == u32::MAX || (name as usize) < self.strings.len() {
+            true => Ok(()),
+            false => Err(format!("String index {} is out of range", name))
+        };
+        // walk the tree the same way that get_files does, so that it can't loop forever
+        let mut seen_directories = vec![false; self.directories.len()];
+        let mut seen_files = vec![false; self.files.len()];
+        let mut stack = match self.directories.is_empty() { true => vec![], false => vec![0] };
+        while let Some(dir_index) = stack.pop() {
+            if dir_index as usize >= self.directories.len() || seen_directories[dir_index as usize] {
+                return Err(format!("Directory index {} is out of range or used twice", dir_index).into());
+            }
+            seen_directories[dir_index as usize] = true;
+            let dir = &self.directories[dir_index as usize];
+            check_string(dir.name)?;
+            let mut curr_file = dir.first_file;
+            while curr_file != u32::MAX {
+                if curr_file as usize >= self.files.len() || seen_files[curr_file as usize] {
+                    return Err(format!("File index {} is out of range or used twice", curr_file).into());
+                }
+                seen_files[curr_file as usize] = true;
+                let file = &self.files[curr_file as usize];
+                check_string(file.name)?;
+                if file.user_data as usize >= self.chunk_ids.len() {
+                    return Err(format!("File {} points to chunk {}, which doesn't exist", curr_file, file.user_data).into());
+                }
+                curr_file = file.next_file;
+            }
+            if dir.next_sibling != u32::MAX {
+                stack.push(dir.next_sibling);
+            }
+            if dir.first_child != u32::MAX {
+                stack.push(dir.first_child);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_directory_index<E: byteorder::ByteOrder>(&mut self, mut directory_index: Vec<u8>) -> Result<(), Box<dyn Error>> {
+        if let Some(key) = &self.key {
+            key.decrypt(&mut directory_index)?;
+        }
+        let mut reader = Cursor::new(directory_index);
+        self.mount_point = FString32NoHash::from_buffer::<Cursor<Vec<u8>>, E>(&mut reader)?.unwrap_or_default();
+        self.directories = IoDirectoryIndexEntry::list_from_buffer::<Cursor<Vec<u8>>, E>(&mut reader)?;
+        self.files = IoFileIndexEntry::list_from_buffer::<Cursor<Vec<u8>>, E>(&mut reader)?;
+        self.strings = IoStringPool::list_from_buffer::<Cursor<Vec<u8>>, E>(&mut reader)?;
+        Ok(())
+    }
+
+    // Walk through the directory index to get the path of every file, relative to the mount point
+    pub fn get_files(&self) -> Vec<TocReaderFile> {
+        let mut files = vec![];
+        if let Some(root) = self.directories.first() {
+            // the root directory usually has no name so that it's children begin at the mount point,
+            // but TOCs made by TocResolverType2 name it after the project
+            let root_path = match root.name {
+                u32::MAX => String::new(),
+                n => self.strings[n as usize].to_owned() + "/"
+            };
+            self.get_files_inner(root, &root_path, &mut files);
+        }
+        files
+    }
+
+    fn get_files_inner(&self, dir: &IoDirectoryIndexEntry, path: &str, files: &mut Vec<TocReaderFile>) {
+        let mut curr_file = dir.first_file;
+        while curr_file != u32::MAX {
+            let file = &self.files[curr_file as usize];
+            files.push(TocReaderFile {
+                path: path.to_owned() + &self.strings[file.name as usize],
+                chunk_index: file.user_data as usize
+            });
+            curr_file = file.next_file;
+        }
+        let mut curr_child = dir.first_child;
+        while curr_child != u32::MAX {
+            let child = &self.directories[curr_child as usize];
+            let child_path = path.to_owned() + &self.strings[child.name as usize] + "/";
+            self.get_files_inner(child, &child_path, files);
+            curr_child = child.next_sibling;
+        }
+    }
+
+    // Decrypting with the wrong key doesn't fail by itself, so check that the container header decrypts to something that
+    // starts with this container's id. Containers without a container header fall back to checking the mount point
+    fn verify_key<E: byteorder::ByteOrder>(&self) -> Result<(), Box<dyn Error>> {
+        let wrong_key = format!("Wrong AES key for {} (key GUID {:032X})", self.toc_path.display(), self.header.encryption_key_guid);
+        match self.chunk_ids.iter().position(|c| c.get_type() == IoChunkType4::ContainerHeader) {
+            Some(i) => match self.read_chunk(i) {
+                Ok(header) if header.len() >= 8 && E::read_u64(&header[..8]) == self.header.container_id => Ok(()),
+                _ => Err(wrong_key.into())
+            },
+            None => match self.mount_point.starts_with("../") || self.mount_point.starts_with('/') {
+                true => Ok(()),
+                false => Err(wrong_key.into())
+            }
+        }
+    }
+
+    // Every container has one container header chunk, which lists the store entries for each package in the container
+    pub fn read_container_header(&self) -> Result<Option<ContainerHeader>, Box<dyn Error>> {
+        let index = match self.chunk_ids.iter().position(|c| c.get_type() == IoChunkType4::ContainerHeader) {
+            Some(i) => i,
+            None => return Ok(None)
+        };
+        let mut reader = Cursor::new(self.read_chunk(index)?);
+        Ok(Some(match self.endianness {
+            Endianness::Little => ContainerHeader::from_buffer::<Cursor<Vec<u8>>, byteorder::LittleEndian>(&mut reader)?,
+            Endianness::Big => ContainerHeader::from_buffer::<Cursor<Vec<u8>>, byteorder::BigEndian>(&mut reader)?
+        }))
+    }
+
+    pub fn get_mount_point_relative(&self) -> &str {
+        self.mount_point.trim_start_matches("../").trim_start_matches('/')
+    }
+
+    // 4.27 containers can be split into several partitions (Container.ucas, Container_s1.ucas, Container_s2.ucas...)
+    pub fn get_partition_path(&self, partition: u64) -> PathBuf {
+        let mut cas_path = self.toc_path.with_extension("ucas");
+        if partition > 0 {
+            let stem = cas_path.file_stem().unwrap().to_str().unwrap().to_owned();
+            cas_path.set_file_name(format!("{}_s{}.ucas", stem, partition));
+        }
+        cas_path
+    }
+
+    // Convert an offset from a compression block into a partition and an offset in that partition
+    pub fn get_partition_offset(&self, offset: u64) -> (u64, u64) {
+        match self.header.partition_size {
+            0 | u64::MAX => (0, offset),
+            n => (offset / n, offset % n)
+        }
+    }
+
+    pub fn get_compression_method_name(&self, index: u8) -> Option<&str> {
+        match index {
+            0 => Some("None"),
+            n => self.compression_methods.get(n as usize - 1).map(|s| s.as_str())
+        }
+    }
+
+    // Get the range of compression blocks that contains a chunk
+    pub fn get_chunk_blocks(&self, chunk_index: usize) -> &[IoStoreTocCompressedBlockEntry] {
+        let offset_length = &self.offsets_and_lengths[chunk_index];
+        if offset_length.get_length() == 0 {
+            return &[];
+        }
+        let block_size = self.header.compression_block_size as u64;
+        let first_block = (offset_length.get_offset() / block_size) as usize;
+        let last_block = ((offset_length.get_offset() + offset_length.get_length() - 1) / block_size) as usize;
+        &self.compression_blocks[first_block..=last_block]
+    }
+
+    // Uncompressed, unencrypted chunks with blocks that are laid out one after another can be read straight from the partition,
+    // in which case this returns the partition path and the chunk's offset in it
+    pub fn get_chunk_file_range(&self, chunk_index: usize) -> Option<(PathBuf, u64)> {
+        if self.is_virtual || self.header.container_flags.contains(IoContainerFlags::Encrypted) {
+            return None;
+        }
+        let blocks = self.get_chunk_blocks(chunk_index);
+        let first = blocks.first()?;
+        let mut expected_offset = first.get_offset();
+        for block in blocks {
+            if block.get_compression_method_index() != 0
+            || block.get_offset() != expected_offset
+            || block.get_compressed_size() != block.get_uncompressed_size() {
+                return None;
+            }
+            expected_offset += block.get_compressed_size() as u64;
+        }
+        let (first_partition, first_offset) = self.get_partition_offset(first.get_offset());
+        let (last_partition, _) = self.get_partition_offset(expected_offset - 1);
+        if first_partition != last_partition {
+            return None;
+        }
+        let block_offset = self.offsets_and_lengths[chunk_index].get_offset() % self.header.compression_block_size as u64;
+        Some((self.get_partition_path(first_partition), first_offset + block_offset))
+    }
+
+    fn open_partition(&self, partition: u64) -> Result<Box<dyn ReadSeek>, Box<dyn Error>> {
+        Ok(match self.is_virtual {
+            true => Box::new(VirtualContainerReader::new()?),
+            false => Box::new(File::open(self.get_partition_path(partition))?)
+        })
+    }
+
+    pub fn get_compression_method(&self, index: u8) -> Result<CompressionMethod, Box<dyn Error>> {
+        match self.get_compression_method_name(index) {
+            Some(name) => Ok(CompressionMethod::try_from(name)?),
+            None => Err(format!("Invalid compression method index {}", index).into())
+        }
+    }
+
+    // Check that every block used by a chunk can be read by read_chunk
+    pub fn check_chunk_readable(&self, chunk_index: usize) -> Result<(), Box<dyn Error>> {
+        for block in self.get_chunk_blocks(chunk_index) {
+            self.get_compression_method(block.get_compression_method_index())?;
+        }
+        Ok(())
+    }
+
+    // Read and decompress the blocks for a chunk then return the exact range of bytes that it covers
+    pub fn read_chunk(&self, chunk_index: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.check_chunk_readable(chunk_index)?;
+        let offset_length = &self.offsets_and_lengths[chunk_index];
+        let blocks = self.get_chunk_blocks(chunk_index);
+        let mut uncompressed = Vec::with_capacity(blocks.len() * self.header.compression_block_size as usize);
+        let mut partition_file: Option<(u64, Box<dyn ReadSeek>)> = None;
+        for block in blocks {
+            let (partition, offset) = self.get_partition_offset(block.get_offset());
+            if partition_file.as_ref().map(|(p, _)| *p) != Some(partition) {
+                partition_file = Some((partition, self.open_partition(partition)?));
+            }
+            let cas = &mut partition_file.as_mut().unwrap().1;
+            cas.seek(SeekFrom::Start(offset))?;
+            let compressed_size = block.get_compressed_size() as usize;
+            let mut block_data = match &self.key {
+                Some(_) => vec![0; encryption::align_to_aes_block(compressed_size as u64) as usize],
+                None => vec![0; compressed_size]
+            };
+            cas.read_exact(&mut block_data)?;
+            if let Some(key) = &self.key {
+                key.decrypt(&mut block_data)?;
+            }
+            let method = self.get_compression_method(block.get_compression_method_index())?;
+            uncompressed.extend(method.decompress_block(&block_data[..compressed_size], block.get_uncompressed_size() as usize)?);
+        }
+        let start = (offset_length.get_offset() % self.header.compression_block_size as u64) as usize;
+        let end = start + offset_length.get_length() as usize;
+        if end > uncompressed.len() {
+            return Err(format!("Chunk {} is larger than it's compression blocks", chunk_index).into());
+        }
+        Ok(uncompressed[start..end].to_vec())
+    }
+}
+
+// The header size (0x14) is the first field wider than a byte. Anything that isn't a big endian 4.25+ to 4.27 header size
+// is read as little endian, so that the header reports what's wrong with it
+pub fn get_toc_endianness<R: Read + Seek>(reader: &mut R) -> Result<Endianness, Box<dyn Error>> {
+    let start = reader.stream_position()?;
+    reader.seek(SeekFrom::Start(start + 0x14))?;
+    let mut header_size = [0; 4];
+    let endianness = match reader.read_exact(&mut header_size) {
+        Ok(_) if u32::from_be_bytes(header_size) == TOC_HEADER_SIZE => Endianness::Big,
+        _ => Endianness::Little
+    };
+    reader.seek(SeekFrom::Start(start))?;
+    Ok(endianness)
+}
+
+// Read + Seek over a range of a file, so that readers which seek to absolute positions (such as package summaries)
+// can be used on chunks stored inside of another container
+pub struct FileSliceReader<R: Read + Seek> {
+    inner: R,
+    start: u64,
+    length: u64
+}
+
+impl<R: Read + Seek> FileSliceReader<R> {
+    pub fn new(mut inner: R, start: u64, length: u64) -> std::io::Result<Self> {
+        inner.seek(SeekFrom::Start(start))?;
+        Ok(Self { inner, start, length })
+    }
+
+    // Position relative to the start of the slice. Seeks never go before the start, so this only fails if inner was moved elsewhere
+    fn get_position(&mut self) -> std::io::Result<u64> {
+        self.inner.stream_position()?.checked_sub(self.start)
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Reader is before the start of the slice"))
+    }
+}
+
+impl<R: Read + Seek> Read for FileSliceReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let position = self.get_position()?;
+        let remaining = self.length.saturating_sub(position) as usize;
+        let to_read = buf.len().min(remaining);
+        self.inner.read(&mut buf[..to_read])
+    }
+}
+
+impl<R: Read + Seek> Seek for FileSliceReader<R> {
+    // Like Cursor, seeking past the end is allowed (reads there return nothing) but seeking before the start is an error
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.length.checked_add_signed(n),
+            SeekFrom::Current(n) => self.get_position()?.checked_add_signed(n)
+        }.filter(|p| self.start.checked_add(*p).is_some())
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Invalid seek to a negative or overflowing position"))?;
+        self.inner.seek(SeekFrom::Start(self.start + new_position))?;
+        Ok(new_position)
+    }
+}
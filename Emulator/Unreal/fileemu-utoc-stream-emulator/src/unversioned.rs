@@ -0,0 +1,529 @@
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use crate::{
+    string::FString32NoHash,
+    usmap::{Usmap, UsmapPropertyType}
+};
+use std::{
+    error::Error,
+    io::{Read, Write}
+};
+
+// Unversioned property serialization (FUnversionedPropertySerialization), used by cooked packages with PKG_UnversionedProperties.
+// Instead of a tag for each property, a struct's properties start with a header that says which of the struct's properties are
+// serialized (see Usmap::get_schema for the order of these):
+//  Fragments (u16): skip count (7 bits), has zeroes (1 bit), is last (1 bit), value count (7 bits)
+//  Zero mask: one bit for each value in fragments that have zeroes, stored as a u8, u16, or u32s depending on the number of bits
+// followed by the value of each property that isn't zero. Zero properties are set to their default value, and have no data
+// Names are stored as an index into the package's name map, so properties are read with the package's names and written with a
+// function that returns (or adds) the index of a name in the package that they'll be written to
+
+pub const PKG_UNVERSIONED_PROPERTIES: u32 = 0x2000;
+
+// Structs that have their own serialization instead of properties. These don't contain any names or objects
+pub const NATIVE_STRUCT_SIZES: &[(&str, usize)] = &[
+    ("Vector", 0xc), ("Vector2D", 0x8), ("Vector4", 0x10), ("Rotator", 0xc), ("Quat", 0x10), ("Color", 0x4), ("LinearColor", 0x10),
+    ("Guid", 0x10), ("IntPoint", 0x8), ("IntVector", 0xc), ("Box", 0x19), ("Box2D", 0x11), ("Plane", 0x10), ("Matrix", 0x40),
+    ("DateTime", 0x8), ("Timespan", 0x8), ("FrameNumber", 0x4)
+];
+
+const FRAGMENT_SKIP_MAX: u8 = 0x7f;
+const FRAGMENT_VALUE_MAX: u8 = 0x7f;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Fragment {
+    skip_num: u8,
+    has_any_zeroes: bool,
+    is_last: bool,
+    value_num: u8
+}
+
+impl Fragment {
+    fn unpack(value: u16) -> Self {
+        Self {
+            skip_num: (value & 0x7f) as u8,
+            has_any_zeroes: value & 0x80 != 0,
+            is_last: value & 0x100 != 0,
+            value_num: (value >> 9) as u8
+        }
+    }
+    fn pack(&self) -> u16 {
+        self.skip_num as u16 | (self.has_any_zeroes as u16) << 7 | (self.is_last as u16) << 8 | (self.value_num as u16) << 9
+    }
+}
+
+// Called with each FPackageIndex in a set of properties, see UnversionedProperties::visit_objects
+pub type ObjectVisitor<'a> = dyn FnMut(&mut i32) -> Result<(), Box<dyn Error>> + 'a;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameValue {
+    pub name: String,
+    pub number: u32 // stored + 1, with 0 meaning no number
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextHistory {
+    None(Option<String>), // culture invariant string
+    Base { namespace: String, key: String, source: String },
+    StringTableEntry { table: NameValue, key: String }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextValue {
+    pub flags: u32,
+    pub history: TextHistory
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StructValue {
+    Native(String, Vec<u8>), // struct name, data (see NATIVE_STRUCT_SIZES)
+    SoftObjectPath(NameValue, String), // asset path, sub path
+    GameplayTagContainer(Vec<NameValue>),
+    Properties(UnversionedProperties)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int8(i8),
+    Int16(i16),
+    Int(i32),
+    Int64(i64),
+    Byte(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+    Float(f32),
+    Double(f64),
+    Name(NameValue),
+    Str(String),
+    Text(TextValue),
+    Object(i32), // FPackageIndex: negative for imports (-1 is import 0), positive for exports (1 is export 0)
+    SoftObject(NameValue, String),
+    LazyObject([u8; 0x10]),
+    Delegate(i32, NameValue),
+    MulticastDelegate(Vec<(i32, NameValue)>),
+    FieldPath(Vec<NameValue>, i32),
+    Struct(StructValue),
+    Array(Vec<PropertyValue>),
+    Set { removed: Vec<PropertyValue>, items: Vec<PropertyValue> },
+    Map { removed: Vec<PropertyValue>, entries: Vec<(PropertyValue, PropertyValue)> }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub index: usize, // index in the struct's schema
+    pub name: String,
+    pub array_index: u8,
+    pub value: Option<PropertyValue> // None if the property is zero
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnversionedProperties {
+    pub struct_name: String,
+    pub properties: Vec<Property>
+}
+
+impl UnversionedProperties {
+    pub fn get(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    // Call f on every object reference in the properties, so that they can be moved to another package's imports
+    pub fn visit_objects(&mut self, f: &mut ObjectVisitor) -> Result<(), Box<dyn Error>> {
+        for property in &mut self.properties {
+            if let Some(value) = property.value.as_mut() {
+                value.visit_objects(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PropertyValue {
+    pub fn visit_objects(&mut self, f: &mut ObjectVisitor) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Object(i) | Self::Delegate(i, _) | Self::FieldPath(_, i) => f(i)?,
+            Self::MulticastDelegate(delegates) => for (i, _) in delegates {
+                f(i)?;
+            },
+            Self::Struct(StructValue::Properties(properties)) => properties.visit_objects(f)?,
+            Self::Array(items) => for i in items {
+                i.visit_objects(f)?;
+            },
+            Self::Set { removed, items } => for i in removed.iter_mut().chain(items) {
+                i.visit_objects(f)?;
+            },
+            Self::Map { removed, entries } => {
+                for i in removed {
+                    i.visit_objects(f)?;
+                }
+                for (k, v) in entries {
+                    k.visit_objects(f)?;
+                    v.visit_objects(f)?;
+                }
+            },
+            _ => ()
+        }
+        Ok(())
+    }
+}
+
+pub struct UnversionedReader<'a> {
+    pub mappings: &'a Usmap,
+    pub names: &'a [String] // package name map
+}
+
+impl UnversionedReader<'_> {
+    pub fn read_properties<R: Read, E: byteorder::ByteOrder>(&self, struct_name: &str, reader: &mut R) -> Result<UnversionedProperties, Box<dyn Error>> {
+        let schema = self.mappings.get_schema(struct_name)?;
+        let mut fragments = vec![];
+        loop {
+            let fragment = Fragment::unpack(reader.read_u16::<E>()?);
+            fragments.push(fragment);
+            if fragment.is_last {
+                break;
+            }
+        }
+        let zero_bits = fragments.iter().filter(|f| f.has_any_zeroes).map(|f| f.value_num as usize).sum();
+        let zero_mask = read_zero_mask::<R, E>(reader, zero_bits)?;
+        let mut properties = vec![];
+        let mut index = 0;
+        let mut zero_bit = 0;
+        for fragment in fragments {
+            index += fragment.skip_num as usize;
+            for _ in 0..fragment.value_num {
+                let is_zero = fragment.has_any_zeroes && zero_mask[zero_bit];
+                zero_bit += fragment.has_any_zeroes as usize;
+                let (property, array_index) = schema.get(index).copied().flatten()
+                    .ok_or_else(|| format!("Struct {} doesn't have a property at index {}", struct_name, index))?;
+                let value = match is_zero {
+                    true => None,
+                    false => Some(self.read_value::<R, E>(&property.property_type, reader)
+                        .map_err(|e| format!("{}.{}: {}", struct_name, property.name, e))?)
+                };
+                properties.push(Property { index, name: property.name.to_owned(), array_index, value });
+                index += 1;
+            }
+        }
+        Ok(UnversionedProperties { struct_name: struct_name.to_owned(), properties })
+    }
+
+    fn read_name<R: Read, E: byteorder::ByteOrder>(&self, reader: &mut R) -> Result<NameValue, Box<dyn Error>> {
+        let index = reader.read_u32::<E>()?;
+        let number = reader.read_u32::<E>()?;
+        let name = self.names.get(index as usize).ok_or_else(|| format!("Name index {} is out of range", index))?;
+        Ok(NameValue { name: name.to_owned(), number })
+    }
+
+    fn read_items<R: Read, E: byteorder::ByteOrder>(&self, item_type: &UsmapPropertyType, reader: &mut R) -> Result<Vec<PropertyValue>, Box<dyn Error>> {
+        let count = reader.read_i32::<E>()?;
+        let mut items = Vec::with_capacity(count.clamp(0, 0x1000) as usize);
+        for _ in 0..count {
+            items.push(self.read_value::<R, E>(item_type, reader)?);
+        }
+        Ok(items)
+    }
+
+    pub fn read_value<R: Read, E: byteorder::ByteOrder>(&self, property_type: &UsmapPropertyType, reader: &mut R) -> Result<PropertyValue, Box<dyn Error>> {
+        Ok(match property_type {
+            UsmapPropertyType::Bool => PropertyValue::Bool(reader.read_u8()? != 0),
+            UsmapPropertyType::Int8 => PropertyValue::Int8(reader.read_i8()?),
+            UsmapPropertyType::Int16 => PropertyValue::Int16(reader.read_i16::<E>()?),
+            UsmapPropertyType::Int => PropertyValue::Int(reader.read_i32::<E>()?),
+            UsmapPropertyType::Int64 => PropertyValue::Int64(reader.read_i64::<E>()?),
+            UsmapPropertyType::Byte => PropertyValue::Byte(reader.read_u8()?),
+            UsmapPropertyType::UInt16 => PropertyValue::UInt16(reader.read_u16::<E>()?),
+            UsmapPropertyType::UInt32 => PropertyValue::UInt32(reader.read_u32::<E>()?),
+            UsmapPropertyType::UInt64 => PropertyValue::UInt64(reader.read_u64::<E>()?),
+            UsmapPropertyType::Float => PropertyValue::Float(reader.read_f32::<E>()?),
+            UsmapPropertyType::Double => PropertyValue::Double(reader.read_f64::<E>()?),
+            UsmapPropertyType::Name => PropertyValue::Name(self.read_name::<R, E>(reader)?),
+            UsmapPropertyType::Str => PropertyValue::Str(read_string::<R, E>(reader)?),
+            UsmapPropertyType::Text => PropertyValue::Text(self.read_text::<R, E>(reader)?),
+            UsmapPropertyType::Object | UsmapPropertyType::WeakObject | UsmapPropertyType::Interface => PropertyValue::Object(reader.read_i32::<E>()?),
+            UsmapPropertyType::SoftObject => PropertyValue::SoftObject(self.read_name::<R, E>(reader)?, read_string::<R, E>(reader)?),
+            UsmapPropertyType::LazyObject => {
+                let mut guid = [0; 0x10];
+                reader.read_exact(&mut guid)?;
+                PropertyValue::LazyObject(guid)
+            },
+            UsmapPropertyType::Delegate => PropertyValue::Delegate(reader.read_i32::<E>()?, self.read_name::<R, E>(reader)?),
+            UsmapPropertyType::MulticastDelegate => {
+                let mut delegates = vec![];
+                for _ in 0..reader.read_i32::<E>()? {
+                    delegates.push((reader.read_i32::<E>()?, self.read_name::<R, E>(reader)?));
+                }
+                PropertyValue::MulticastDelegate(delegates)
+            },
+            UsmapPropertyType::FieldPath => {
+                let mut path = vec![];
+                for _ in 0..reader.read_i32::<E>()? {
+                    path.push(self.read_name::<R, E>(reader)?);
+                }
+                PropertyValue::FieldPath(path, reader.read_i32::<E>()?)
+            },
+            UsmapPropertyType::Struct(struct_name) => PropertyValue::Struct(self.read_struct::<R, E>(struct_name, reader)?),
+            // enums are stored as their underlying integer
+            UsmapPropertyType::Enum(inner, _) => self.read_value::<R, E>(inner, reader)?,
+            UsmapPropertyType::Array(inner) => PropertyValue::Array(self.read_items::<R, E>(inner, reader)?),
+            UsmapPropertyType::Set(inner) => {
+                let removed = self.read_items::<R, E>(inner, reader)?;
+                PropertyValue::Set { removed, items: self.read_items::<R, E>(inner, reader)? }
+            },
+            UsmapPropertyType::Map(key_type, value_type) => {
+                let removed = self.read_items::<R, E>(key_type, reader)?;
+                let count = reader.read_i32::<E>()?;
+                let mut entries = Vec::with_capacity(count.clamp(0, 0x1000) as usize);
+                for _ in 0..count {
+                    let key = self.read_value::<R, E>(key_type, reader)?;
+                    entries.push((key, self.read_value::<R, E>(value_type, reader)?));
+                }
+                PropertyValue::Map { removed, entries }
+            },
+            t => return Err(format!("Unsupported property type {:?}", t).into())
+        })
+    }
+
+    fn read_struct<R: Read, E: byteorder::ByteOrder>(&self, struct_name: &str, reader: &mut R) -> Result<StructValue, Box<dyn Error>> {
+        if let Some((_, size)) = NATIVE_STRUCT_SIZES.iter().find(|(n, _)| *n == struct_name) {
+            let mut data = vec![0; *size];
+            reader.read_exact(&mut data)?;
+            return Ok(StructValue::Native(struct_name.to_owned(), data));
+        }
+        Ok(match struct_name {
+            "SoftObjectPath" | "SoftClassPath" => StructValue::SoftObjectPath(self.read_name::<R, E>(reader)?, read_string::<R, E>(reader)?),
+            "GameplayTagContainer" => {
+                let mut tags = vec![];
+                for _ in 0..reader.read_i32::<E>()? {
+                    tags.push(self.read_name::<R, E>(reader)?);
+                }
+                StructValue::GameplayTagContainer(tags)
+            },
+            _ => StructValue::Properties(self.read_properties::<R, E>(struct_name, reader)?)
+        })
+    }
+
+    // FText is stored as it's history. Only the history types that are used by cooked properties are handled
+    fn read_text<R: Read, E: byteorder::ByteOrder>(&self, reader: &mut R) -> Result<TextValue, Box<dyn Error>> {
+        let flags = reader.read_u32::<E>()?;
+        let history = match reader.read_i8()? {
+            -1 => TextHistory::None(match reader.read_i32::<E>()? != 0 {
+                true => Some(read_string::<R, E>(reader)?),
+                false => None
+            }),
+            0 => TextHistory::Base {
+                namespace: read_string::<R, E>(reader)?,
+                key: read_string::<R, E>(reader)?,
+                source: read_string::<R, E>(reader)?
+            },
+            11 => TextHistory::StringTableEntry { table: self.read_name::<R, E>(reader)?, key: read_string::<R, E>(reader)? },
+            t => return Err(format!("Unsupported text history type {}", t).into())
+        };
+        Ok(TextValue { flags, history })
+    }
+}
+
+pub struct UnversionedWriter<'a> {
+    pub get_name_index: &'a mut dyn FnMut(&str) -> u32
+}
+
+impl UnversionedWriter<'_> {
+    // Properties have to be sorted by their index. The header is built the same way as FUnversionedHeaderBuilder, so unchanged
+    // properties are written back exactly as they were read
+    pub fn write_properties<W: Write, E: byteorder::ByteOrder>(&mut self, properties: &UnversionedProperties, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        if properties.properties.windows(2).any(|p| p[0].index >= p[1].index) {
+            return Err(format!("Properties of {} aren't sorted by their index", properties.struct_name).into());
+        }
+        let mut fragments = vec![Fragment::default()];
+        let mut zero_mask = vec![];
+        // fragments without zeroes don't need their bits in the zero mask
+        let trim_zero_mask = |fragment: &Fragment, zero_mask: &mut Vec<bool>| if !fragment.has_any_zeroes {
+            zero_mask.truncate(zero_mask.len() - fragment.value_num as usize);
+        };
+        let mut next_index = 0;
+        for property in &properties.properties {
+            while next_index < property.index { // skipped
+                let last = fragments.last().unwrap();
+                if last.value_num > 0 || last.skip_num == FRAGMENT_SKIP_MAX {
+                    trim_zero_mask(last, &mut zero_mask);
+                    fragments.push(Fragment::default());
+                }
+                fragments.last_mut().unwrap().skip_num += 1;
+                next_index += 1;
+            }
+            let last = fragments.last().unwrap();
+            if last.value_num == FRAGMENT_VALUE_MAX {
+                trim_zero_mask(last, &mut zero_mask);
+                fragments.push(Fragment::default());
+            }
+            let last = fragments.last_mut().unwrap();
+            last.value_num += 1;
+            last.has_any_zeroes |= property.value.is_none();
+            zero_mask.push(property.value.is_none());
+            next_index += 1;
+        }
+        trim_zero_mask(fragments.last().unwrap(), &mut zero_mask);
+        while fragments.len() > 1 && fragments.last().unwrap().value_num == 0 {
+            fragments.pop();
+        }
+        fragments.last_mut().unwrap().is_last = true;
+        for fragment in fragments {
+            writer.write_u16::<E>(fragment.pack())?;
+        }
+        write_zero_mask::<W, E>(writer, &zero_mask)?;
+        for value in properties.properties.iter().filter_map(|p| p.value.as_ref()) {
+            self.write_value::<W, E>(value, writer)?;
+        }
+        Ok(())
+    }
+
+    fn write_name<W: Write, E: byteorder::ByteOrder>(&mut self, name: &NameValue, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        writer.write_u32::<E>((self.get_name_index)(&name.name))?;
+        writer.write_u32::<E>(name.number)?;
+        Ok(())
+    }
+
+    fn write_items<W: Write, E: byteorder::ByteOrder>(&mut self, items: &[PropertyValue], writer: &mut W) -> Result<(), Box<dyn Error>> {
+        writer.write_i32::<E>(items.len() as i32)?;
+        for i in items {
+            self.write_value::<W, E>(i, writer)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_value<W: Write, E: byteorder::ByteOrder>(&mut self, value: &PropertyValue, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        match value {
+            PropertyValue::Bool(v) => writer.write_u8(*v as u8)?,
+            PropertyValue::Int8(v) => writer.write_i8(*v)?,
+            PropertyValue::Int16(v) => writer.write_i16::<E>(*v)?,
+            PropertyValue::Int(v) => writer.write_i32::<E>(*v)?,
+            PropertyValue::Int64(v) => writer.write_i64::<E>(*v)?,
+            PropertyValue::Byte(v) => writer.write_u8(*v)?,
+            PropertyValue::UInt16(v) => writer.write_u16::<E>(*v)?,
+            PropertyValue::UInt32(v) => writer.write_u32::<E>(*v)?,
+            PropertyValue::UInt64(v) => writer.write_u64::<E>(*v)?,
+            PropertyValue::Float(v) => writer.write_f32::<E>(*v)?,
+            PropertyValue::Double(v) => writer.write_f64::<E>(*v)?,
+            PropertyValue::Name(v) => self.write_name::<W, E>(v, writer)?,
+            PropertyValue::Str(v) => write_string::<W, E>(v, writer)?,
+            PropertyValue::Text(v) => self.write_text::<W, E>(v, writer)?,
+            PropertyValue::Object(v) => writer.write_i32::<E>(*v)?,
+            PropertyValue::SoftObject(path, sub_path) => {
+                self.write_name::<W, E>(path, writer)?;
+                write_string::<W, E>(sub_path, writer)?;
+            },
+            PropertyValue::LazyObject(v) => writer.write_all(v)?,
+            PropertyValue::Delegate(object, function) => {
+                writer.write_i32::<E>(*object)?;
+                self.write_name::<W, E>(function, writer)?;
+            },
+            PropertyValue::MulticastDelegate(delegates) => {
+                writer.write_i32::<E>(delegates.len() as i32)?;
+                for (object, function) in delegates {
+                    writer.write_i32::<E>(*object)?;
+                    self.write_name::<W, E>(function, writer)?;
+                }
+            },
+            PropertyValue::FieldPath(path, owner) => {
+                writer.write_i32::<E>(path.len() as i32)?;
+                for name in path {
+                    self.write_name::<W, E>(name, writer)?;
+                }
+                writer.write_i32::<E>(*owner)?;
+            },
+            PropertyValue::Struct(v) => match v {
+                StructValue::Native(_, data) => writer.write_all(data)?,
+                StructValue::SoftObjectPath(path, sub_path) => {
+                    self.write_name::<W, E>(path, writer)?;
+                    write_string::<W, E>(sub_path, writer)?;
+                },
+                StructValue::GameplayTagContainer(tags) => {
+                    writer.write_i32::<E>(tags.len() as i32)?;
+                    for tag in tags {
+                        self.write_name::<W, E>(tag, writer)?;
+                    }
+                },
+                StructValue::Properties(properties) => self.write_properties::<W, E>(properties, writer)?
+            },
+            PropertyValue::Array(items) => self.write_items::<W, E>(items, writer)?,
+            PropertyValue::Set { removed, items } => {
+                self.write_items::<W, E>(removed, writer)?;
+                self.write_items::<W, E>(items, writer)?;
+            },
+            PropertyValue::Map { removed, entries } => {
+                self.write_items::<W, E>(removed, writer)?;
+                writer.write_i32::<E>(entries.len() as i32)?;
+                for (key, value) in entries {
+                    self.write_value::<W, E>(key, writer)?;
+                    self.write_value::<W, E>(value, writer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_text<W: Write, E: byteorder::ByteOrder>(&mut self, text: &TextValue, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        writer.write_u32::<E>(text.flags)?;
+        match &text.history {
+            TextHistory::None(culture_invariant) => {
+                writer.write_i8(-1)?;
+                writer.write_i32::<E>(culture_invariant.is_some() as i32)?;
+                if let Some(v) = culture_invariant {
+                    write_string::<W, E>(v, writer)?;
+                }
+            },
+            TextHistory::Base { namespace, key, source } => {
+                writer.write_i8(0)?;
+                for v in [namespace, key, source] {
+                    write_string::<W, E>(v, writer)?;
+                }
+            },
+            TextHistory::StringTableEntry { table, key } => {
+                writer.write_i8(11)?;
+                self.write_name::<W, E>(table, writer)?;
+                write_string::<W, E>(key, writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Stored as a single u8 or u16 if there are few enough bits, otherwise as u32s. The first property is the lowest bit
+fn read_zero_mask<R: Read, E: byteorder::ByteOrder>(reader: &mut R, bit_count: usize) -> Result<Vec<bool>, Box<dyn Error>> {
+    let words = match bit_count {
+        0 => vec![],
+        1..=8 => vec![reader.read_u8()? as u32],
+        9..=16 => vec![reader.read_u16::<E>()? as u32],
+        _ => (0..bit_count.div_ceil(32)).map(|_| reader.read_u32::<E>()).collect::<Result<_, _>>()?
+    };
+    Ok((0..bit_count).map(|i| words[i / 32] & 1 << (i % 32) != 0).collect())
+}
+
+fn write_zero_mask<W: Write, E: byteorder::ByteOrder>(writer: &mut W, zero_mask: &[bool]) -> Result<(), Box<dyn Error>> {
+    let mut words = vec![0u32; zero_mask.len().div_ceil(32)];
+    for (i, _) in zero_mask.iter().enumerate().filter(|(_, z)| **z) {
+        words[i / 32] |= 1 << (i % 32);
+    }
+    match zero_mask.len() {
+        0 => (),
+        1..=8 => writer.write_u8(words[0] as u8)?,
+        9..=16 => writer.write_u16::<E>(words[0] as u16)?,
+        _ => for w in words {
+            writer.write_u32::<E>(w)?;
+        }
+    }
+    Ok(())
+}
+
+// FString: length including the null terminator, negative for UTF-16 (see string::FString32NoHash)
+fn read_string<R: Read, E: byteorder::ByteOrder>(reader: &mut R) -> Result<String, Box<dyn Error>> {
+    FString32NoHash::read::<R, E>(reader)
+}
+
+fn write_string<W: Write, E: byteorder::ByteOrder>(value: &str, writer: &mut W) -> Result<(), Box<dyn Error>> {
+    match value.is_empty() {
+        true => writer.write_i32::<E>(0)?, // cooked empty strings have no null terminator
+        false => FString32NoHash::write::<W, E>(value, writer)?
+    }
+    Ok(())
+}
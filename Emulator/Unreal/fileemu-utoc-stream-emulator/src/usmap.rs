@@ -0,0 +1,310 @@
+use byteorder::ReadBytesExt;
+use crate::game_profile::{self, Endianness};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{Cursor, Read},
+    path::Path,
+    ptr::addr_of
+};
+
+// Class and struct layouts dumped from a running game (.usmap). Cooked packages that use unversioned properties only store which of
+// a struct's properties are serialized, so these are needed to know the name and type of each property (see unversioned)
+// .usmap layout:
+//  Magic (u16), version (u8)
+//  PackageVersioning+: has versioning (bool32), then FPackageFileVersion, custom versions and net CL if it's set
+//  Compression method (u8), compressed size (u32), decompressed size (u32), then the (compressed) data:
+//      Names: count (u32), then each name's length (u8, or u16 from LongFName) and text
+//      Enums: count (u32), then each enum's name and entry count (u8, or u16 from LargeEnums) and entries
+//      Structs: count (u32), then each struct's name, super name, property count, serializable property count and properties
+// Every name after the name list is a u32 index into it, with u32::MAX for none
+pub static mut MAPPINGS: Option<Usmap> = None;
+
+pub const USMAP_MAGIC: u16 = 0x30C4;
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[repr(u8)]
+pub enum UsmapVersion {
+    Initial,
+    PackageVersioning,
+    LongFName,
+    LargeEnums,
+    ExplicitEnumValues
+}
+
+impl TryFrom<u8> for UsmapVersion {
+    type Error = String;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Initial),
+            1 => Ok(Self::PackageVersioning),
+            2 => Ok(Self::LongFName),
+            3 => Ok(Self::LargeEnums),
+            4 => Ok(Self::ExplicitEnumValues),
+            v => Err(format!("Unsupported usmap version {}", v))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UsmapPropertyType {
+    Byte,
+    Bool,
+    Int,
+    Float,
+    Object,
+    Name,
+    Delegate,
+    Double,
+    Array(Box<UsmapPropertyType>),
+    Struct(String),
+    Str,
+    Text,
+    Interface,
+    MulticastDelegate,
+    WeakObject,
+    LazyObject,
+    AssetObject,
+    SoftObject,
+    UInt64,
+    UInt32,
+    UInt16,
+    Int64,
+    Int16,
+    Int8,
+    Map(Box<UsmapPropertyType>, Box<UsmapPropertyType>),
+    Set(Box<UsmapPropertyType>),
+    Enum(Box<UsmapPropertyType>, String), // underlying type, enum name
+    FieldPath,
+    Optional(Box<UsmapPropertyType>),
+    Unknown(u8)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsmapProperty {
+    pub name: String,
+    pub schema_index: u16, // first index of this property in it's struct
+    pub array_size: u8, // number of indices taken by this property (for C style arrays)
+    pub property_type: UsmapPropertyType
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsmapStruct {
+    pub name: String,
+    pub super_name: Option<String>,
+    pub property_count: u16, // indices used by this struct, not including it's super struct
+    pub properties: Vec<UsmapProperty> // only properties that are serialized
+}
+
+// A property and it's array index, or None for indices that aren't serialized
+pub type SchemaSlot<'a> = Option<(&'a UsmapProperty, u8)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsmapEnum {
+    pub name: String,
+    pub entries: Vec<(u64, String)> // value, name
+}
+
+#[derive(Debug, Default)]
+pub struct Usmap {
+    pub names: Vec<String>,
+    pub enums: HashMap<String, UsmapEnum>,
+    pub structs: HashMap<String, UsmapStruct>,
+    pub file_version_ue4: Option<i32>
+}
+
+impl Usmap {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let data = fs::read(path)?;
+        match game_profile::get_game_profile().endianness {
+            Endianness::Little => Self::from_buffer::<Cursor<Vec<u8>>, byteorder::LittleEndian>(&mut Cursor::new(data)),
+            Endianness::Big => Self::from_buffer::<Cursor<Vec<u8>>, byteorder::BigEndian>(&mut Cursor::new(data))
+        }
+    }
+
+    pub fn from_buffer<R: Read, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let magic = reader.read_u16::<E>()?;
+        if magic != USMAP_MAGIC {
+            return Err(format!("Not a usmap file (magic is 0x{:x}, expected 0x{:x})", magic, USMAP_MAGIC).into());
+        }
+        let version = UsmapVersion::try_from(reader.read_u8()?)?;
+        let mut file_version_ue4 = None;
+        if version >= UsmapVersion::PackageVersioning && reader.read_i32::<E>()? != 0 {
+            file_version_ue4 = Some(reader.read_i32::<E>()?);
+            reader.read_i32::<E>()?; // UE5 file version
+            for _ in 0..reader.read_i32::<E>()? { // custom versions: GUID, version
+                reader.read_exact(&mut [0; 0x14])?;
+            }
+            reader.read_u32::<E>()?; // net CL
+        }
+        let compression_method = reader.read_u8()?;
+        let compressed_size = reader.read_u32::<E>()? as usize;
+        let decompressed_size = reader.read_u32::<E>()? as usize;
+        let mut compressed = vec![0; compressed_size];
+        reader.read_exact(&mut compressed)?;
+        let data = Self::decompress(compression_method, &compressed, decompressed_size)?;
+        let mut mappings = Self { file_version_ue4, ..Default::default() };
+        mappings.read_data::<E>(&mut Cursor::new(&data), version)?;
+        Ok(mappings)
+    }
+
+    // Oodle is proprietary, so mappings compressed with it have to be decompressed (or dumped uncompressed) first
+    fn decompress(method: u8, compressed: &[u8], decompressed_size: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut data = Vec::with_capacity(decompressed_size);
+        match method {
+            0 => data.extend_from_slice(compressed),
+            1 => return Err("Usmap is compressed with Oodle, which isn't supported".into()),
+            2 => { brotli_decompressor::Decompressor::new(compressed, 0x1000).read_to_end(&mut data)?; },
+            3 => { ruzstd::StreamingDecoder::new(compressed).map_err(|e| format!("Zstandard: {}", e))?.read_to_end(&mut data)?; },
+            m => return Err(format!("Unknown usmap compression method {}", m).into())
+        }
+        if data.len() != decompressed_size {
+            return Err(format!("Usmap decompressed to 0x{:x} bytes, expected 0x{:x}", data.len(), decompressed_size).into());
+        }
+        Ok(data)
+    }
+
+    fn read_data<E: byteorder::ByteOrder>(&mut self, reader: &mut Cursor<&Vec<u8>>, version: UsmapVersion) -> Result<(), Box<dyn Error>> {
+        for _ in 0..reader.read_u32::<E>()? {
+            let length = match version >= UsmapVersion::LongFName {
+                true => reader.read_u16::<E>()? as usize,
+                false => reader.read_u8()? as usize
+            };
+            let mut name = vec![0; length];
+            reader.read_exact(&mut name)?;
+            self.names.push(String::from_utf8_lossy(&name).into_owned());
+        }
+        for _ in 0..reader.read_u32::<E>()? {
+            let name = self.read_name::<E>(reader)?;
+            let entry_count = match version >= UsmapVersion::LargeEnums {
+                true => reader.read_u16::<E>()? as u64,
+                false => reader.read_u8()? as u64
+            };
+            let mut entries = Vec::with_capacity(entry_count as usize);
+            for i in 0..entry_count {
+                let value = match version >= UsmapVersion::ExplicitEnumValues {
+                    true => reader.read_u64::<E>()?,
+                    false => i
+                };
+                entries.push((value, self.read_name::<E>(reader)?));
+            }
+            self.enums.insert(name.clone(), UsmapEnum { name, entries });
+        }
+        for _ in 0..reader.read_u32::<E>()? {
+            let name = self.read_name::<E>(reader)?;
+            let super_name = self.read_optional_name::<E>(reader)?;
+            let property_count = reader.read_u16::<E>()?;
+            let serializable_count = reader.read_u16::<E>()?;
+            let mut properties = Vec::with_capacity(serializable_count as usize);
+            for _ in 0..serializable_count {
+                let schema_index = reader.read_u16::<E>()?;
+                let array_size = reader.read_u8()?;
+                let name = self.read_name::<E>(reader)?;
+                let property_type = self.read_property_type::<E>(reader)?;
+                properties.push(UsmapProperty { name, schema_index, array_size, property_type });
+            }
+            self.structs.insert(name.clone(), UsmapStruct { name, super_name, property_count, properties });
+        }
+        Ok(())
+    }
+
+    fn read_optional_name<E: byteorder::ByteOrder>(&self, reader: &mut Cursor<&Vec<u8>>) -> Result<Option<String>, Box<dyn Error>> {
+        match reader.read_u32::<E>()? {
+            u32::MAX => Ok(None),
+            i => Ok(Some(self.names.get(i as usize).ok_or_else(|| format!("Usmap name index {} is out of range", i))?.to_owned()))
+        }
+    }
+
+    fn read_name<E: byteorder::ByteOrder>(&self, reader: &mut Cursor<&Vec<u8>>) -> Result<String, Box<dyn Error>> {
+        Ok(self.read_optional_name::<E>(reader)?.unwrap_or_default())
+    }
+
+    fn read_property_type<E: byteorder::ByteOrder>(&self, reader: &mut Cursor<&Vec<u8>>) -> Result<UsmapPropertyType, Box<dyn Error>> {
+        Ok(match reader.read_u8()? {
+            0 => UsmapPropertyType::Byte,
+            1 => UsmapPropertyType::Bool,
+            2 => UsmapPropertyType::Int,
+            3 => UsmapPropertyType::Float,
+            4 => UsmapPropertyType::Object,
+            5 => UsmapPropertyType::Name,
+            6 => UsmapPropertyType::Delegate,
+            7 => UsmapPropertyType::Double,
+            8 => UsmapPropertyType::Array(Box::new(self.read_property_type::<E>(reader)?)),
+            9 => UsmapPropertyType::Struct(self.read_name::<E>(reader)?),
+            10 => UsmapPropertyType::Str,
+            11 => UsmapPropertyType::Text,
+            12 => UsmapPropertyType::Interface,
+            13 => UsmapPropertyType::MulticastDelegate,
+            14 => UsmapPropertyType::WeakObject,
+            15 => UsmapPropertyType::LazyObject,
+            16 => UsmapPropertyType::AssetObject,
+            17 => UsmapPropertyType::SoftObject,
+            18 => UsmapPropertyType::UInt64,
+            19 => UsmapPropertyType::UInt32,
+            20 => UsmapPropertyType::UInt16,
+            21 => UsmapPropertyType::Int64,
+            22 => UsmapPropertyType::Int16,
+            23 => UsmapPropertyType::Int8,
+            24 => {
+                let key = self.read_property_type::<E>(reader)?;
+                UsmapPropertyType::Map(Box::new(key), Box::new(self.read_property_type::<E>(reader)?))
+            },
+            25 => UsmapPropertyType::Set(Box::new(self.read_property_type::<E>(reader)?)),
+            26 => {
+                let inner = self.read_property_type::<E>(reader)?;
+                UsmapPropertyType::Enum(Box::new(inner), self.read_name::<E>(reader)?)
+            },
+            27 => UsmapPropertyType::FieldPath,
+            28 => UsmapPropertyType::Optional(Box::new(self.read_property_type::<E>(reader)?)),
+            t => UsmapPropertyType::Unknown(t)
+        })
+    }
+
+    // Every index that a struct serializes it's properties with, in order. A struct's own properties come first, followed by it's
+    // super struct's. Indices for properties that aren't serialized are None
+    pub fn get_schema(&self, struct_name: &str) -> Result<Vec<SchemaSlot<'_>>, Box<dyn Error>> {
+        let mut schema = vec![];
+        let mut curr_struct = Some(struct_name);
+        while let Some(name) = curr_struct {
+            let usmap_struct = self.structs.get(name).ok_or_else(|| format!("Struct {} isn't in the mappings", name))?;
+            let start = schema.len();
+            schema.resize(start + usmap_struct.property_count as usize, None);
+            for property in &usmap_struct.properties {
+                for i in 0..property.array_size {
+                    let index = start + property.schema_index as usize + i as usize;
+                    *schema.get_mut(index).ok_or_else(|| format!("Property {} is outside of struct {}", property.name, name))? = Some((property, i));
+                }
+            }
+            curr_struct = usmap_struct.super_name.as_deref();
+        }
+        Ok(schema)
+    }
+
+    pub fn get_enum_name(&self, enum_name: &str, value: u64) -> Option<&str> {
+        self.enums.get(enum_name)?.entries.iter().find(|(v, _)| *v == value).map(|(_, n)| n.as_str())
+    }
+}
+
+pub fn add_mappings(path: &Path) -> Result<(), Box<dyn Error>> {
+    let mappings = Usmap::from_file(path)?;
+    unsafe { MAPPINGS = Some(mappings) };
+    Ok(())
+}
+
+// Load the first .usmap in a folder, if there is one (the game's Paks folder is checked when a TOC is built)
+pub fn add_mappings_from_folder(folder: &Path) -> Result<bool, Box<dyn Error>> {
+    let mut usmap_paths: Vec<_> = fs::read_dir(folder)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("usmap")))
+        .collect();
+    usmap_paths.sort();
+    match usmap_paths.first() {
+        Some(path) => add_mappings(path).map(|_| true),
+        None => Ok(false)
+    }
+}
+
+pub fn get_mappings() -> Option<&'static Usmap> {
+    unsafe { (*addr_of!(MAPPINGS)).as_ref() }
+}
@@ -0,0 +1,61 @@
+// Build a container from files whose paths only differ by case, which all hash to the same chunk id. Two mods ship T/Tbl.ubulk and
+// t/TBL.ubulk, which are matched when they're added to the tree. A third file, t/tbl.ubulk, is put straight into a second "t" folder
+// so that it skips the matching, like a file that was added without going through the asset collector would
+use fileemu_utoc_stream_emulator::{
+    asset_collector::{self, TocDirectory, TocFile, TocFileSource},
+    toc_factory::{self, TocBuilderProfiler},
+    toc_reader::TocReader
+};
+use std::{fs, rc::Rc, sync::OnceLock};
+
+const MODS: [(&str, &str, u8); 2] = [("lower", "T/Tbl.ubulk", 1), ("higher", "t/TBL.ubulk", 2)];
+
+// Build the container, returning the contents of each file in it by path
+fn get_built_files() -> &'static Vec<(String, Vec<u8>)> {
+    static BUILT: OnceLock<Vec<(String, Vec<u8>)>> = OnceLock::new();
+    BUILT.get_or_init(|| {
+        let work = std::env::temp_dir().join("utoc-emulator-chunk-id-collisions");
+        let _ = fs::remove_dir_all(&work);
+        for (mod_name, path, fill) in MODS {
+            let file_path = work.join(mod_name).join("FEmulator/UTOC/UnrealEssentials_P.utoc/Content").join(path);
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(file_path, [fill; 0x10]).unwrap();
+            asset_collector::add_from_folders(mod_name, work.join(mod_name).to_str().unwrap());
+        }
+        let root = unsafe { (*std::ptr::addr_of!(asset_collector::ROOT_DIRECTORY)).as_ref().unwrap() };
+        let content = TocDirectory::get_child_dir(Rc::clone(root), "Content").unwrap();
+        let unmatched = TocDirectory::new_rc("t");
+        TocDirectory::add_directory(content, Rc::clone(&unmatched));
+        TocDirectory::add_or_replace_file(unmatched, TocFile::new_rc("tbl.ubulk", 0x10, TocFileSource::Memory(Rc::new(vec![3; 0x10]))));
+        let output = work.join("out");
+        fs::create_dir_all(&output).unwrap();
+        let toc_path = output.join(toc_factory::TARGET_TOC);
+        fs::write(&toc_path, toc_factory::build_table_of_contents(toc_path.to_str().unwrap()).unwrap()).unwrap();
+        toc_factory::write_container(output.join(toc_factory::TARGET_CAS).to_str().unwrap()).unwrap();
+        let toc = TocReader::from_file(&toc_path).unwrap();
+        toc.get_files().into_iter().map(|f| (f.path.clone(), toc.read_chunk(f.chunk_index).unwrap())).collect()
+    })
+}
+
+fn get_profiler() -> &'static TocBuilderProfiler {
+    get_built_files();
+    unsafe { (*std::ptr::addr_of!(toc_factory::TOC_BUILDER_PROFILER)).as_ref().unwrap() }
+}
+
+#[test]
+fn files_differing_by_case_give_one_toc_entry() {
+    let matching: Vec<_> = get_built_files().iter().filter(|(path, _)| path.to_lowercase().ends_with("/t/tbl.ubulk")).collect();
+    assert_eq!(matching.len(), 1, "{:?}", matching.iter().map(|(path, _)| path).collect::<Vec<_>>());
+    // the file added last has the highest priority
+    assert_eq!(matching[0].1, vec![3; 0x10]);
+}
+
+#[test]
+fn colliding_chunk_ids_are_reported() {
+    let collisions = &get_profiler().chunk_id_collisions;
+    assert_eq!(collisions.len(), 1, "{:?}", collisions);
+    // the two mods' files were matched in the tree (in the first mod's folder), so the collision is between the higher priority mod's
+    // file and the unmatched one
+    assert!(collisions[0].contains("/Game/T/TBL.ubulk") && collisions[0].contains("/Game/t/tbl.ubulk"), "{}", collisions[0]);
+    assert!(collisions[0].ends_with("only /Game/t/tbl.ubulk (<memory>) was kept"), "{}", collisions[0]);
+}
@@ -0,0 +1,117 @@
+// Shared by the container tests. The emulator keeps it's state in statics, so the container is only built once for each test
+// binary and every test reads from the same copy
+use aes::{
+    Aes256,
+    cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit}
+};
+use fileemu_utoc_stream_emulator::{asset_collector, toc_factory};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock
+};
+
+pub const BULK_SIZE: usize = 0x28000; // three compression blocks
+
+pub fn get_fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_resources").join(name)
+}
+
+// Contents of every file in the mod, by it's path under Content
+pub fn get_mod_files() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("T/Tbl.uasset", fs::read(get_fixture("endianness/package_little.uasset")).unwrap()),
+        ("T/Tbl.ubulk", (0..BULK_SIZE as u32).map(|i| (i * 7 + i / 0x300) as u8).collect())
+    ]
+}
+
+// Build UnrealEssentials_P.utoc/.ucas from get_mod_files, returning the TOC's path
+pub fn get_built_container() -> &'static Path {
+    static BUILT_TOC: OnceLock<PathBuf> = OnceLock::new();
+    BUILT_TOC.get_or_init(|| {
+        // named after the test binary, so that running the tests again replaces the last run's files
+        let test_name = std::env::current_exe().unwrap().file_stem().unwrap().to_string_lossy().into_owned();
+        let work = std::env::temp_dir().join(format!("utoc-emulator-{}", test_name));
+        let _ = fs::remove_dir_all(&work);
+        let content = work.join("mod/FEmulator/UTOC/UnrealEssentials_P.utoc/Content");
+        for (path, data) in get_mod_files() {
+            let file_path = content.join(path);
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(file_path, data).unwrap();
+        }
+        asset_collector::add_from_folders("test", work.join("mod").to_str().unwrap());
+        let output = work.join("out");
+        fs::create_dir_all(&output).unwrap();
+        let toc_path = output.join(toc_factory::TARGET_TOC);
+        fs::write(&toc_path, toc_factory::build_table_of_contents(toc_path.to_str().unwrap()).unwrap()).unwrap();
+        toc_factory::write_container(output.join(toc_factory::TARGET_CAS).to_str().unwrap()).unwrap();
+        toc_path
+    })
+}
+
+// Codec used for every compression block when a container is rewritten, with the name that goes in the TOC
+pub struct BlockCodec {
+    pub name: &'static str,
+    pub compress: fn(&[u8]) -> Vec<u8>
+}
+
+fn encrypt(key: &Aes256, data: &mut Vec<u8>) {
+    data.resize(data.len().next_multiple_of(0x10), 0);
+    for block in data.chunks_exact_mut(0x10) {
+        key.encrypt_block(GenericArray::from_mut_slice(block));
+    }
+}
+
+// Rewrite the built container the way a packer would, with every compression block compressed and/or encrypted with an AES key
+// (and it's GUID). This only handles what the emulator writes: a little endian 4.27 TOC with a single partition and no
+// compression methods, so the method name can be added after the compression blocks
+pub fn rewrite_container(name: &str, codec: Option<&BlockCodec>, key: Option<(u128, [u8; 0x20])>) -> PathBuf {
+    let cipher = key.map(|(_, k)| Aes256::new(GenericArray::from_slice(&k)));
+    let source = get_built_container();
+    let toc = fs::read(source).unwrap();
+    let cas = fs::read(source.with_extension("ucas")).unwrap();
+    let read_u32 = |offset: usize| u32::from_le_bytes(toc[offset..offset + 4].try_into().unwrap()) as usize;
+    let blocks_start = 0x90 + read_u32(0x18) * (0xc + 0xa);
+    let blocks_end = blocks_start + read_u32(0x1c) * 0xc;
+    let mut new_toc = toc[..blocks_start].to_vec();
+    let mut new_cas = vec![];
+    for block in toc[blocks_start..blocks_end].chunks_exact(0xc) {
+        let read_le = |bytes: &[u8]| bytes.iter().rev().fold(0, |v, b| v << 8 | *b as usize);
+        let (offset, size) = (read_le(&block[..5]), read_le(&block[8..0xb]));
+        let mut data = match codec {
+            Some(codec) => (codec.compress)(&cas[offset..offset + size]),
+            None => cas[offset..offset + size].to_vec()
+        };
+        new_toc.extend_from_slice(&(new_cas.len() as u64).to_le_bytes()[..5]);
+        new_toc.extend_from_slice(&(data.len() as u32).to_le_bytes()[..3]); // compressed size doesn't include AES padding
+        new_toc.extend_from_slice(&(size as u32).to_le_bytes()[..3]);
+        new_toc.push(codec.is_some() as u8); // method index, 0 is reserved for uncompressed
+        if let Some(cipher) = &cipher {
+            encrypt(cipher, &mut data);
+        }
+        new_cas.extend(data);
+        new_cas.resize(new_cas.len().next_multiple_of(0x10), 0);
+    }
+    if let Some(codec) = codec {
+        let mut method_name = [0; 0x20];
+        method_name[..codec.name.len()].copy_from_slice(codec.name.as_bytes());
+        new_toc.extend_from_slice(&method_name);
+        new_toc[0x24..0x28].copy_from_slice(&1u32.to_le_bytes()); // compression method count
+        new_toc[0x50] |= 1; // IoContainerFlags::Compressed
+    }
+    let mut directory_index = toc[blocks_end..blocks_end + read_u32(0x30)].to_vec();
+    if let (Some(cipher), Some((guid, _))) = (&cipher, key) {
+        encrypt(cipher, &mut directory_index);
+        new_toc[0x30..0x34].copy_from_slice(&(directory_index.len() as u32).to_le_bytes());
+        new_toc[0x40..0x50].copy_from_slice(&guid.to_le_bytes());
+        new_toc[0x50] |= 2; // IoContainerFlags::Encrypted
+    }
+    new_toc.extend(directory_index);
+    new_toc.extend_from_slice(&toc[blocks_end + read_u32(0x30)..]); // metas
+    let folder = source.parent().unwrap().join(name);
+    fs::create_dir_all(&folder).unwrap();
+    let toc_path = folder.join(toc_factory::TARGET_TOC);
+    fs::write(&toc_path, new_toc).unwrap();
+    fs::write(folder.join(toc_factory::TARGET_CAS), new_cas).unwrap();
+    toc_path
+}
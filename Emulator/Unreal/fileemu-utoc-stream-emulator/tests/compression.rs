@@ -0,0 +1,60 @@
+// Decompress blocks for each supported codec, both on their own and through a container that has every block compressed
+mod common;
+
+use common::BlockCodec;
+use fileemu_utoc_stream_emulator::{
+    compression::CompressionMethod,
+    toc_reader::TocReader
+};
+use std::io::Write;
+
+fn compress_zlib(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+const CODECS: [(CompressionMethod, BlockCodec); 2] = [
+    (CompressionMethod::Zlib, BlockCodec { name: "Zlib", compress: compress_zlib }),
+    (CompressionMethod::Lz4, BlockCodec { name: "LZ4", compress: lz4_flex::block::compress })
+];
+
+#[test]
+fn blocks_decompress_for_each_codec() {
+    let data: Vec<u8> = (0..0x10000u32).map(|i| (i / 0x40) as u8 ^ (i % 3) as u8).collect();
+    assert_eq!(CompressionMethod::None.decompress_block(&data, data.len()).unwrap(), data);
+    assert!(CompressionMethod::None.decompress_block(&data[..0x100], data.len()).is_err());
+    for (method, codec) in &CODECS {
+        let compressed = (codec.compress)(&data);
+        assert!(compressed.len() < data.len());
+        assert_eq!(method.decompress_block(&compressed, data.len()).unwrap(), data, "{}", codec.name);
+        // a block that doesn't decompress to it's stated size is an error, rather than a chunk with the wrong contents
+        assert!(method.decompress_block(&compressed, data.len() + 1).is_err(), "{}", codec.name);
+        assert!(method.decompress_block(&compressed[..compressed.len() / 2], data.len()).is_err(), "{}", codec.name);
+    }
+}
+
+#[test]
+fn method_names_are_matched_case_insensitively() {
+    for name in ["None", "", "Zlib", "zlib", "LZ4", "lz4"] {
+        assert!(CompressionMethod::try_from(name).is_ok(), "{}", name);
+    }
+    assert!(CompressionMethod::try_from("Oodle").unwrap_err().contains("proprietary"));
+    assert!(CompressionMethod::try_from("Brotli").is_err());
+}
+
+#[test]
+fn compressed_container_reads_like_uncompressed() {
+    let uncompressed = TocReader::from_file(common::get_built_container()).unwrap();
+    for (_, codec) in &CODECS {
+        let toc = TocReader::from_file(&common::rewrite_container(codec.name, Some(codec), None)).unwrap();
+        assert_eq!(toc.compression_methods, vec![codec.name.to_owned()]);
+        assert_eq!(toc.get_files(), uncompressed.get_files());
+        for i in 0..toc.chunk_ids.len() {
+            assert!(toc.get_chunk_blocks(i).iter().all(|b| b.get_compression_method_index() == 1));
+            assert!(toc.get_chunk_file_range(i).is_none()); // compressed chunks can't be read straight from the .ucas
+            assert_eq!(toc.read_chunk(i).unwrap(), uncompressed.read_chunk(i).unwrap(), "{} chunk {}", codec.name, i);
+        }
+        assert_eq!(toc.read_container_header().unwrap().unwrap().packages, uncompressed.read_container_header().unwrap().unwrap().packages);
+    }
+}
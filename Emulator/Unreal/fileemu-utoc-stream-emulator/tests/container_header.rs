@@ -0,0 +1,83 @@
+// Write container headers and read them back, then merge game headers the way the base game's containers are loaded
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use fileemu_utoc_stream_emulator::{
+    game_packages,
+    io_package::ContainerHeaderPackage,
+    io_toc::ContainerHeader
+};
+use std::{io::Cursor, path::Path};
+
+fn make_package(hash: u64, export_count: u32, import_ids: Vec<u64>) -> ContainerHeaderPackage {
+    ContainerHeaderPackage { hash, export_bundle_size: 0x100 * export_count as u64, export_count, export_bundle_count: 1, load_order: 0, import_ids }
+}
+
+fn make_header() -> ContainerHeader {
+    let mut header = ContainerHeader::new(0x1234_5678_9abc_def0);
+    header.add_package(make_package(0x10, 2, vec![0x20, 0x30]));
+    header.add_package(make_package(0x20, 1, vec![]));
+    header.add_package(make_package(0x30, 3, vec![0x20]));
+    header.add_localized_package("fr", 0x10, 0x11);
+    header.add_localized_package("ja", 0x10, 0x12);
+    header.add_localized_package("fr", 0x20, 0x21);
+    header.package_redirects = vec![(0x40, 0x10), (0x50, 0x30)];
+    header
+}
+
+fn round_trip<E: ByteOrder>(header: &ContainerHeader) -> ContainerHeader {
+    let data = header.to_buffer::<Cursor<Vec<u8>>, E>(&mut Cursor::new(vec![])).unwrap();
+    ContainerHeader::from_buffer::<Cursor<Vec<u8>>, E>(&mut Cursor::new(data)).unwrap()
+}
+
+#[test]
+fn written_header_reads_back() {
+    let mut header = make_header();
+    header.set_load_order();
+    for read in [round_trip::<LittleEndian>(&header), round_trip::<BigEndian>(&header)] {
+        assert_eq!(read.container_id, header.container_id);
+        assert_eq!(read.packages, header.packages);
+        assert_eq!(read.culture_package_map, header.culture_package_map);
+        assert_eq!(read.package_redirects, header.package_redirects);
+        assert!(read.names.is_empty());
+        assert_eq!(read.get_package(0x30).unwrap().import_ids, vec![0x20]);
+        assert!(!read.contains_package(0x40));
+    }
+    // imports load before the packages that import them
+    let load_order = |hash: u64| header.get_package(hash).unwrap().load_order;
+    assert!(load_order(0x20) < load_order(0x30));
+    assert!(load_order(0x30) < load_order(0x10));
+}
+
+#[test]
+fn merge_keeps_higher_priority_entries() {
+    let mut patch = make_header();
+    let mut base = ContainerHeader::new(0x1);
+    base.add_package(make_package(0x10, 5, vec![]));
+    base.add_package(make_package(0x60, 1, vec![0x10]));
+    base.add_localized_package("FR", 0x10, 0x99);
+    base.add_localized_package("FR", 0x60, 0x61);
+    base.add_localized_package("de", 0x10, 0x13);
+    base.package_redirects = vec![(0x40, 0x60), (0x70, 0x60)];
+    // merged headers go through the same serialization as the game's headers
+    patch.merge(round_trip::<LittleEndian>(&base));
+    assert_eq!(patch.packages.len(), 4);
+    assert_eq!(patch.get_package(0x10).unwrap().export_count, 2);
+    assert_eq!(patch.get_package(0x60).unwrap().import_ids, vec![0x10]);
+    assert!(!patch.add_package(make_package(0x60, 1, vec![])));
+    assert_eq!(patch.culture_package_map, vec![
+        (String::from("fr"), vec![(0x10, 0x11), (0x20, 0x21), (0x60, 0x61)]),
+        (String::from("ja"), vec![(0x10, 0x12)]),
+        (String::from("de"), vec![(0x10, 0x13)])
+    ]);
+    assert_eq!(patch.package_redirects, vec![(0x40, 0x10), (0x50, 0x30), (0x70, 0x60)]);
+}
+
+#[test]
+fn patch_containers_are_numbered() {
+    let patch_number = |name: &str| game_packages::get_patch_number(Path::new(name));
+    assert_eq!(patch_number("pakchunk0-WindowsNoEditor.utoc"), None);
+    assert_eq!(patch_number("pakchunk0-WindowsNoEditor_P.utoc"), Some(1));
+    assert_eq!(patch_number("pakchunk0-WindowsNoEditor_1_P.utoc"), Some(2));
+    assert_eq!(patch_number("pakchunk0-WindowsNoEditor_2_P.utoc"), Some(3));
+    assert_eq!(patch_number("pakchunk0-WindowsNoEditor_10_P.utoc"), Some(11));
+    assert_eq!(patch_number("Paks/pakchunk0_P.utoc"), Some(1));
+}
@@ -0,0 +1,229 @@
+// Merge DataTables that are replaced by two mods, for both tagged and unversioned properties. The fixtures in test_resources/datatable
+// are three versions of the same table, with the rows (Value, Tag):
+//  base: RowA (1, TagA), RowB (2, TagB), RowC (3, TagC)
+//  mod1: RowA (10, TagA), RowB (20, TagB), RowC (3, TagC)
+//  mod2: RowA (1, TagA), RowB (30, TagB), RowC (3, TagX), RowD (4, TagD), with two extra names so that it's name indices don't match
+// Every row has a Ref to import 1 (package 0x1111). Unversioned tables use MyRow from mappings.usmap, with Paks/global.utoc holding
+// the script objects for DataTable and MyRow
+use byteorder::{ByteOrder, LittleEndian};
+use fileemu_utoc_stream_emulator::{
+    asset_collector,
+    datatable_merge::{self, DataTableMergeReport},
+    io_package::IoPackage,
+    script_objects,
+    toc_factory,
+    toc_reader::TocReader,
+    unversioned::{PropertyValue, UnversionedReader},
+    usmap::{self, Usmap, UsmapPropertyType}
+};
+use std::{
+    collections::HashMap,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::OnceLock
+};
+
+const PACKAGE_IMPORT: u64 = 2 << 62 | 0x1111;
+
+fn get_fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_resources/datatable").join(name)
+}
+
+// Row name => (Value, Tag, the import that Ref points to)
+type Rows = HashMap<String, (i32, String, u64)>;
+
+// Build a container from base, mod1 and mod2 (in that order of priority), returning the contents of each file in it by path. mod2 also
+// has T/Single.uasset, a table that no other mod replaces
+fn get_built_files() -> &'static Vec<(String, Vec<u8>)> {
+    static BUILT: OnceLock<Vec<(String, Vec<u8>)>> = OnceLock::new();
+    BUILT.get_or_init(|| {
+        let work = std::env::temp_dir().join("utoc-emulator-datatable-merge");
+        let _ = fs::remove_dir_all(&work);
+        script_objects::add_global_container(&get_fixture("Paks")).unwrap();
+        usmap::add_mappings(&get_fixture("mappings.usmap")).unwrap();
+        for version in ["base", "mod1", "mod2"] {
+            let content = work.join(version).join("FEmulator/UTOC/UnrealEssentials_P.utoc/Content");
+            for (folder, kind) in [("T", "tagged"), ("U", "unversioned")] {
+                fs::create_dir_all(content.join(folder)).unwrap();
+                fs::copy(get_fixture(&format!("{}_{}.uasset", kind, version)), content.join(folder).join("Tbl.uasset")).unwrap();
+            }
+            if version == "mod2" {
+                fs::copy(get_fixture("tagged_mod2.uasset"), content.join("T/Single.uasset")).unwrap();
+            }
+            asset_collector::add_from_folders(version, work.join(version).to_str().unwrap());
+        }
+        let output = work.join("out");
+        fs::create_dir_all(&output).unwrap();
+        let toc_path = output.join(toc_factory::TARGET_TOC);
+        fs::write(&toc_path, toc_factory::build_table_of_contents(toc_path.to_str().unwrap()).unwrap()).unwrap();
+        toc_factory::write_container(output.join(toc_factory::TARGET_CAS).to_str().unwrap()).unwrap();
+        let toc = TocReader::from_file(&toc_path).unwrap();
+        toc.get_files().into_iter().map(|f| (f.path.clone(), toc.read_chunk(f.chunk_index).unwrap())).collect()
+    })
+}
+
+fn get_built_file(name: &str) -> &'static Vec<u8> {
+    get_built_files().iter().find(|(path, _)| path.ends_with(name)).map(|(_, data)| data).unwrap()
+}
+
+// The merged tagged and unversioned tables
+fn get_merged_tables() -> &'static (IoPackage, IoPackage) {
+    static MERGED: OnceLock<(IoPackage, IoPackage)> = OnceLock::new();
+    MERGED.get_or_init(|| {
+        let read_table = |path: &str| {
+            let data = get_built_file(path);
+            IoPackage::from_buffer::<Cursor<&[u8]>, LittleEndian>(&mut Cursor::new(data), data.len() as u64).unwrap()
+        };
+        (read_table("T/Tbl.uasset"), read_table("U/Tbl.uasset"))
+    })
+}
+
+fn get_report() -> &'static DataTableMergeReport {
+    get_merged_tables();
+    unsafe { (*std::ptr::addr_of!(datatable_merge::DATATABLE_MERGE_REPORT)).as_ref().unwrap() }
+}
+
+fn read_name(package: &IoPackage, data: &[u8], pos: &mut usize) -> String {
+    let name = package.names[LittleEndian::read_u32(&data[*pos..]) as usize].to_owned();
+    *pos += 8;
+    name
+}
+
+fn read_i32(data: &[u8], pos: &mut usize) -> i32 {
+    *pos += 4;
+    LittleEndian::read_i32(&data[*pos - 4..])
+}
+
+fn get_import(package: &IoPackage, index: i32) -> u64 {
+    package.imports[(-index - 1) as usize]
+}
+
+// Only handles the properties in the fixture's rows
+fn read_tagged_rows(package: &IoPackage) -> Rows {
+    let data = &package.export_data[0];
+    let mut pos = 0;
+    assert_eq!(read_name(package, data, &mut pos), "None");
+    assert_eq!(read_i32(data, &mut pos), 0); // no object GUID
+    let mut rows = HashMap::new();
+    for _ in 0..read_i32(data, &mut pos) {
+        let row_name = read_name(package, data, &mut pos);
+        let mut row = (0, String::new(), 0);
+        loop {
+            let property = read_name(package, data, &mut pos);
+            if property == "None" {
+                break;
+            }
+            let property_type = read_name(package, data, &mut pos);
+            pos += 9; // size, array index, has property GUID
+            match property_type.as_str() {
+                "IntProperty" => row.0 = read_i32(data, &mut pos),
+                "NameProperty" => row.1 = read_name(package, data, &mut pos),
+                "ObjectProperty" => row.2 = get_import(package, read_i32(data, &mut pos)),
+                t => panic!("Unexpected property type {}", t)
+            }
+        }
+        rows.insert(row_name, row);
+    }
+    assert_eq!(&data[pos..], b"TAIL");
+    rows
+}
+
+fn read_unversioned_rows(package: &IoPackage) -> Rows {
+    let reader = UnversionedReader { mappings: usmap::get_mappings().unwrap(), names: &package.names };
+    let data = &package.export_data[0];
+    let mut cursor = Cursor::new(&data[..]);
+    let table = reader.read_properties::<Cursor<&[u8]>, LittleEndian>("DataTable", &mut cursor).unwrap();
+    assert!(matches!(table.get("RowStruct").unwrap().value, Some(PropertyValue::Object(-1))));
+    let mut pos = cursor.position() as usize;
+    assert_eq!(read_i32(data, &mut pos), 0);
+    let mut rows = HashMap::new();
+    for _ in 0..read_i32(data, &mut pos) {
+        let row_name = read_name(package, data, &mut pos);
+        let mut cursor = Cursor::new(&data[pos..]);
+        let row = reader.read_properties::<Cursor<&[u8]>, LittleEndian>("MyRow", &mut cursor).unwrap();
+        pos += cursor.position() as usize;
+        let value = match &row.get("Value").unwrap().value {
+            Some(PropertyValue::Int(v)) => *v,
+            v => panic!("Unexpected value {:?}", v)
+        };
+        let tag = match &row.get("Tag").unwrap().value {
+            Some(PropertyValue::Name(n)) => n.name.to_owned(),
+            v => panic!("Unexpected tag {:?}", v)
+        };
+        let import = match &row.get("Ref").unwrap().value {
+            Some(PropertyValue::Object(i)) => get_import(package, *i),
+            v => panic!("Unexpected ref {:?}", v)
+        };
+        rows.insert(row_name, (value, tag, import));
+    }
+    assert_eq!(&data[pos..], b"TAIL");
+    rows
+}
+
+fn get_expected_rows() -> Rows {
+    [("RowA", 10, "TagA"), ("RowB", 30, "TagB"), ("RowC", 3, "TagX"), ("RowD", 4, "TagD")].into_iter()
+        .map(|(row, value, tag)| (row.to_owned(), (value, tag.to_owned(), PACKAGE_IMPORT)))
+        .collect()
+}
+
+#[test]
+fn tagged_tables_merge_rows_from_both_mods() {
+    let (tagged, _) = get_merged_tables();
+    assert_eq!(read_tagged_rows(tagged), get_expected_rows());
+}
+
+#[test]
+fn unversioned_tables_merge_rows_from_both_mods() {
+    let (_, unversioned) = get_merged_tables();
+    assert_eq!(read_unversioned_rows(unversioned), get_expected_rows());
+}
+
+#[test]
+fn rows_changed_by_both_mods_are_conflicts() {
+    let report = get_report();
+    assert!(report.unmerged_tables.is_empty(), "{:?}", report.unmerged_tables);
+    for table_path in ["/Game/T/Tbl", "/Game/U/Tbl"] {
+        let merged = report.merged_tables.iter().find(|t| t.table_path == table_path).unwrap();
+        assert_eq!((merged.mod_count, merged.changed_rows, merged.base_from_game), (3, 4, false));
+        let conflicts: Vec<_> = report.conflicts.iter().filter(|c| c.table_path == table_path).collect();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].row, "RowB");
+        assert!(Path::new(&conflicts[0].overridden_source).starts_with(std::env::temp_dir().join("utoc-emulator-datatable-merge/mod1")));
+        assert!(Path::new(&conflicts[0].source).starts_with(std::env::temp_dir().join("utoc-emulator-datatable-merge/mod2")));
+    }
+}
+
+#[test]
+fn tables_replaced_by_one_mod_are_served_as_is() {
+    assert_eq!(get_built_file("T/Single.uasset"), &fs::read(get_fixture("tagged_mod2.uasset")).unwrap());
+    let mut merged: Vec<_> = get_report().merged_tables.iter().map(|t| t.table_path.as_str()).collect();
+    merged.sort();
+    assert_eq!(merged, vec!["/Game/T/Tbl", "/Game/U/Tbl"]);
+    // merged tables are served from memory, so they don't match any mod's file
+    for version in ["base", "mod1", "mod2"] {
+        assert_ne!(get_built_file("T/Tbl.uasset"), &fs::read(get_fixture(&format!("tagged_{}.uasset", version))).unwrap());
+    }
+}
+
+#[test]
+fn mappings_are_read_from_every_version() {
+    // mappings_v4.usmap is the same as mappings.usmap, written as the latest version with Zstandard compression
+    let initial = Usmap::from_file(&get_fixture("mappings.usmap")).unwrap();
+    let latest = Usmap::from_file(&get_fixture("mappings_v4.usmap")).unwrap();
+    assert_eq!(initial.structs, latest.structs);
+    assert_eq!(initial.file_version_ue4, None);
+    assert_eq!(latest.file_version_ue4, Some(522));
+    let row = &latest.structs["MyRow"];
+    assert_eq!((row.super_name.as_deref(), row.property_count, row.properties.len()), (None, 8, 7));
+    let label = row.properties.iter().find(|p| p.name == "Label").unwrap();
+    assert_eq!((label.schema_index, label.array_size, &label.property_type), (6, 2, &UsmapPropertyType::Str));
+    let items = row.properties.iter().find(|p| p.name == "Items").unwrap();
+    assert_eq!(items.property_type, UsmapPropertyType::Array(Box::new(UsmapPropertyType::Struct(String::from("Vector")))));
+    assert_eq!(latest.structs["DataTable"].super_name.as_deref(), Some("Object"));
+    // enum values are only stored from ExplicitEnumValues, before that they're numbered from 0
+    assert_eq!(initial.get_enum_name("EMode", 2), Some("Auto"));
+    assert_eq!(latest.get_enum_name("EMode", 20), Some("Auto"));
+    assert_eq!(latest.get_enum_name("EMode", 2), None);
+    assert!(Usmap::from_file(&get_fixture("tagged_base.uasset")).is_err());
+}
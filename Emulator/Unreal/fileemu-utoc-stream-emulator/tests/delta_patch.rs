@@ -0,0 +1,147 @@
+// Make delta patches and apply them, both directly and from mods when a container is built. The mods are (in order of priority):
+//  base: P/Data.ubulk and P/Other.ubulk
+//  patch: P/Data.ubulk.patch made from base's Data.ubulk, P/Other.ubulk.patch made from a different version of Other.ubulk and
+//  P/Missing.ubulk.patch, which has no file to patch since the game's containers aren't loaded
+use byteorder::LittleEndian;
+use fileemu_utoc_stream_emulator::{
+    asset_collector,
+    delta_patch::{self, DeltaPatch, DeltaPatchReport, PatchOperation},
+    toc_factory,
+    toc_reader::TocReader
+};
+use std::{
+    fs,
+    io::Cursor,
+    path::PathBuf,
+    sync::OnceLock
+};
+
+fn make_source(size: u32, seed: u32) -> Vec<u8> {
+    (0..size).map(|i| (i.wrapping_mul(seed) ^ i >> 7) as u8).collect()
+}
+
+// Replace, insert and remove data at a few places in the source
+fn make_target(source: &[u8]) -> Vec<u8> {
+    let mut target = source[..0x1000].to_vec();
+    target.extend_from_slice(&[0xEE; 0x40]);
+    target.extend_from_slice(&source[0x1000..0x5000]);
+    target.extend_from_slice(&source[0x5100..0x9000]);
+    target.extend_from_slice(b"changed");
+    target.extend_from_slice(&source[0x9007..]);
+    target
+}
+
+#[test]
+fn patch_turns_source_into_target() {
+    let source = make_source(0x10000, 31);
+    let target = make_target(&source);
+    let patch = DeltaPatch::create(&source, &target);
+    assert_eq!(patch.apply(&source).unwrap(), target);
+    let inserted: usize = patch.operations.iter().map(|o| match o { PatchOperation::Insert(data) => data.len(), _ => 0 }).sum();
+    assert!(inserted < 0x100, "0x{:x} bytes inserted", inserted);
+    let bytes = patch.to_bytes().unwrap();
+    assert!(bytes.len() < 0x200, "patch is 0x{:x} bytes", bytes.len());
+    assert_eq!(DeltaPatch::from_buffer::<Cursor<&[u8]>, LittleEndian>(&mut Cursor::new(&bytes)).unwrap(), patch);
+    // files that have nothing in common are stored as one insert
+    let other = make_source(0x100, 7);
+    assert_eq!(DeltaPatch::create(&[], &other).operations, vec![PatchOperation::Insert(other.clone())]);
+    assert_eq!(DeltaPatch::create(&source, &other).apply(&source).unwrap(), other);
+    assert_eq!(DeltaPatch::create(&source, &[]).apply(&source).unwrap(), vec![]);
+}
+
+#[test]
+fn patch_is_refused_for_a_changed_source() {
+    let source = make_source(0x10000, 31);
+    let patch = DeltaPatch::create(&source, &make_target(&source));
+    let mut changed = source.clone();
+    changed[0x8000] ^= 1;
+    for base in [&changed, &source[..0xffff].to_vec()] {
+        let error = patch.apply(base).unwrap_err();
+        assert!(error.contains("different version of the file"), "{}", error);
+    }
+    // corrupted patches are refused too
+    let mut copy_outside = patch.clone();
+    copy_outside.operations.insert(0, PatchOperation::Copy { offset: 0xfff0, length: 0x20 });
+    assert!(copy_outside.apply(&source).unwrap_err().contains("outside of the source file"));
+    let mut wrong_target = patch.clone();
+    wrong_target.target_checksum ^= 1;
+    assert!(wrong_target.apply(&source).unwrap_err().contains("corrupted"));
+    let bytes = patch.to_bytes().unwrap();
+    assert!(DeltaPatch::from_buffer::<Cursor<&[u8]>, LittleEndian>(&mut Cursor::new(&bytes[..bytes.len() - 1])).is_err());
+    assert!(DeltaPatch::from_buffer::<Cursor<&[u8]>, LittleEndian>(&mut Cursor::new(&bytes[4..])).is_err());
+}
+
+fn get_work_folder() -> PathBuf {
+    std::env::temp_dir().join("utoc-emulator-delta-patch")
+}
+
+// Build the container from the base and patch mods, returning the contents of each file in it by path
+fn get_patched_files() -> &'static Vec<(String, Vec<u8>)> {
+    static PATCHED: OnceLock<Vec<(String, Vec<u8>)>> = OnceLock::new();
+    PATCHED.get_or_init(|| {
+        let work = get_work_folder();
+        let _ = fs::remove_dir_all(&work);
+        let content = |mod_name: &str| work.join(mod_name).join("FEmulator/UTOC/UnrealEssentials_P.utoc/Content/P");
+        let data = make_source(0x30000, 31);
+        let other = make_source(0x8000, 13);
+        let patches = [
+            ("Data.ubulk.patch", DeltaPatch::create(&data, &make_target(&data))),
+            ("Other.ubulk.patch", DeltaPatch::create(&make_source(0x8000, 17), &other[..0x4000])),
+            ("Missing.ubulk.patch", DeltaPatch::create(&data, &other))
+        ];
+        fs::create_dir_all(content("base")).unwrap();
+        fs::create_dir_all(content("patch")).unwrap();
+        fs::write(content("base").join("Data.ubulk"), &data).unwrap();
+        fs::write(content("base").join("Other.ubulk"), &other).unwrap();
+        for (name, patch) in patches {
+            fs::write(content("patch").join(name), patch.to_bytes().unwrap()).unwrap();
+        }
+        for mod_name in ["base", "patch"] {
+            asset_collector::add_from_folders(mod_name, work.join(mod_name).to_str().unwrap());
+        }
+        let output = work.join("out");
+        fs::create_dir_all(&output).unwrap();
+        let toc_path = output.join(toc_factory::TARGET_TOC);
+        fs::write(&toc_path, toc_factory::build_table_of_contents(toc_path.to_str().unwrap()).unwrap()).unwrap();
+        toc_factory::write_container(output.join(toc_factory::TARGET_CAS).to_str().unwrap()).unwrap();
+        let toc = TocReader::from_file(&toc_path).unwrap();
+        toc.get_files().into_iter().map(|f| (f.path.clone(), toc.read_chunk(f.chunk_index).unwrap())).collect()
+    })
+}
+
+fn get_report() -> &'static DeltaPatchReport {
+    get_patched_files();
+    unsafe { (*std::ptr::addr_of!(delta_patch::DELTA_PATCH_REPORT)).as_ref().unwrap() }
+}
+
+fn get_patched_file(name: &str) -> Option<&'static Vec<u8>> {
+    get_patched_files().iter().find(|(path, _)| path.ends_with(name)).map(|(_, data)| data)
+}
+
+#[test]
+fn mod_patches_apply_to_lower_priority_files() {
+    let data = make_source(0x30000, 31);
+    assert_eq!(get_patched_file("P/Data.ubulk").unwrap(), &make_target(&data));
+    let report = get_report();
+    assert_eq!(report.applied.len(), 1);
+    let applied = &report.applied[0];
+    assert_eq!(applied.file_path, "/Game/P/Data.ubulk");
+    assert!(applied.patch_path.ends_with("Data.ubulk.patch"));
+    assert!(applied.base.contains("base"), "{}", applied.base);
+}
+
+#[test]
+fn refused_mod_patches_fall_back_to_the_replaced_file() {
+    let report = get_report();
+    assert!(report.has_problems());
+    assert_eq!(report.refused.len(), 2);
+    let other = report.refused.iter().find(|r| r.file_path == "/Game/P/Other.ubulk").unwrap();
+    assert!(other.reason.contains("different version of the file"), "{}", other.reason);
+    assert!(other.fallback.as_ref().unwrap().ends_with("Other.ubulk"));
+    assert_eq!(get_patched_file("P/Other.ubulk").unwrap(), &make_source(0x8000, 13));
+    // with nothing to fall back on, the file is left out so that the game's file is used
+    let missing = report.refused.iter().find(|r| r.file_path == "/Game/P/Missing.ubulk").unwrap();
+    assert!(missing.reason.contains("weren't loaded"), "{}", missing.reason);
+    assert_eq!(missing.fallback, None);
+    assert!(get_patched_file("P/Missing.ubulk").is_none());
+}
@@ -0,0 +1,74 @@
+// Build a container from packages with missing and circular imports, and check the dependency report made before it's served. The
+// game's containers are stood in for by a container header with one package, /Game/Base/Present. The mod has:
+//  D/A: imports /Game/Base/Present, /Game/D/Missing (which is in it's name map), a package id that has no name and D/B
+//  D/B: imports D/A
+use byteorder::LittleEndian;
+use fileemu_utoc_stream_emulator::{
+    asset_collector,
+    dependency_check::{self, DependencyReport, MissingImport},
+    game_packages,
+    io_package::{ContainerHeaderPackage, FGraphPackage, IoPackage},
+    io_toc::ContainerHeader,
+    string::Hasher16,
+    toc_factory
+};
+use std::{fs, io::Cursor, path::PathBuf, sync::OnceLock};
+
+const UNNAMED_IMPORT: u64 = 0x1234_5678_9abc_def0;
+
+fn make_package(names: &[&str], import_ids: &[u64]) -> Vec<u8> {
+    let data = fs::read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_resources/endianness/package_little.uasset")).unwrap();
+    let mut package = IoPackage::from_buffer::<Cursor<&[u8]>, LittleEndian>(&mut Cursor::new(&data), data.len() as u64).unwrap();
+    package.names.extend(names.iter().map(|n| n.to_string()));
+    package.graph_packages = import_ids.iter().map(|id| FGraphPackage { imported_package_id: *id, external_arcs: vec![] }).collect();
+    package.to_bytes::<LittleEndian>().unwrap()
+}
+
+fn get_report() -> &'static DependencyReport {
+    static BUILT: OnceLock<()> = OnceLock::new();
+    BUILT.get_or_init(|| {
+        let mut game_header = ContainerHeader::new(1);
+        game_header.add_package(ContainerHeaderPackage {
+            hash: Hasher16::get_cityhash64("/Game/Base/Present"), export_bundle_size: 0, export_count: 1, export_bundle_count: 1,
+            load_order: 0, import_ids: vec![]
+        });
+        unsafe { game_packages::GAME_CONTAINER_HEADER = Some(game_header) };
+        let work = std::env::temp_dir().join("utoc-emulator-dependency-check");
+        let _ = fs::remove_dir_all(&work);
+        let content = work.join("mod/FEmulator/UTOC/UnrealEssentials_P.utoc/Content/D");
+        fs::create_dir_all(&content).unwrap();
+        let id = Hasher16::get_cityhash64;
+        fs::write(content.join("A.uasset"), make_package(
+            &["/Game/Base/Present", "/Game/D/Missing", "/Game/D/B"],
+            &[id("/Game/Base/Present"), id("/Game/D/Missing"), UNNAMED_IMPORT, id("/Game/D/B")]
+        )).unwrap();
+        fs::write(content.join("B.uasset"), make_package(&["/Game/D/A"], &[id("/Game/D/A")])).unwrap();
+        asset_collector::add_from_folders("test", work.join("mod").to_str().unwrap());
+        let output = work.join("out");
+        fs::create_dir_all(&output).unwrap();
+        let toc_path = output.join(toc_factory::TARGET_TOC);
+        fs::write(&toc_path, toc_factory::build_table_of_contents(toc_path.to_str().unwrap()).unwrap()).unwrap();
+    });
+    unsafe { (*std::ptr::addr_of!(dependency_check::DEPENDENCY_REPORT)).as_ref().unwrap() }
+}
+
+#[test]
+fn unresolved_imports_are_reported() {
+    let report = get_report();
+    assert!(report.checked_game_packages);
+    assert_eq!(report.checked_packages, 2);
+    assert!(report.has_problems());
+    assert_eq!(report.missing_imports, vec![
+        MissingImport { package_path: String::from("/Game/D/A"), import_id: Hasher16::get_cityhash64("/Game/D/Missing"), import_path: Some(String::from("/Game/D/Missing")) },
+        MissingImport { package_path: String::from("/Game/D/A"), import_id: UNNAMED_IMPORT, import_path: None }
+    ]);
+}
+
+#[test]
+fn import_cycles_are_reported() {
+    let cycles = &get_report().cycles;
+    assert_eq!(cycles.len(), 1);
+    let mut cycle = cycles[0].clone();
+    cycle.sort();
+    assert_eq!(cycle, vec!["/Game/D/A", "/Game/D/B"]);
+}
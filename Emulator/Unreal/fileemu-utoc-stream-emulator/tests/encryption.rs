@@ -0,0 +1,85 @@
+// Read containers encrypted with AES-256-ECB using the right key, the wrong key and no key at all
+mod common;
+
+use aes::{
+    Aes256,
+    cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit}
+};
+use common::BlockCodec;
+use fileemu_utoc_stream_emulator::{
+    encryption::{self, AesKey},
+    toc_reader::TocReader
+};
+use std::sync::Once;
+
+const KEY: [u8; 0x20] = *b"0123456789abcdefFEDCBA9876543210";
+const OTHER_KEY: [u8; 0x20] = *b"not the key for these containers";
+const KEY_GUID: u128 = 0x1111;
+const WRONG_KEY_GUID: u128 = 0x2222;
+const MISSING_KEY_GUID: u128 = 0x3333;
+
+// Keys are global, so they're all added once before any test reads a container. There's no default key (GUID 0), so a
+// container with a GUID that isn't listed here has no key
+fn add_keys() {
+    static ADD_KEYS: Once = Once::new();
+    ADD_KEYS.call_once(|| {
+        encryption::add_key(KEY_GUID, AesKey::new(KEY));
+        encryption::add_key(WRONG_KEY_GUID, AesKey::new(OTHER_KEY));
+    });
+}
+
+fn compress_lz4(data: &[u8]) -> Vec<u8> {
+    lz4_flex::block::compress(data)
+}
+
+#[test]
+fn keys_are_read_from_hex() {
+    let hex: String = KEY.iter().map(|b| format!("{:02X}", b)).collect();
+    let data: Vec<u8> = (0..0x40u8).collect();
+    let mut encrypted = data.clone();
+    let cipher = Aes256::new(GenericArray::from_slice(&KEY));
+    encrypted.chunks_exact_mut(0x10).for_each(|b| cipher.encrypt_block(GenericArray::from_mut_slice(b)));
+    for key in [format!("0x{}", hex), hex.to_lowercase(), format!("  {}\n", hex)] {
+        let mut decrypted = encrypted.clone();
+        AesKey::from_hex(&key).unwrap().decrypt(&mut decrypted).unwrap();
+        assert_eq!(decrypted, data, "{}", key);
+    }
+    let mut decrypted = encrypted.clone();
+    AesKey::new(OTHER_KEY).decrypt(&mut decrypted).unwrap();
+    assert_ne!(decrypted, data);
+    assert!(AesKey::from_hex(&hex[2..]).is_err());
+    assert!(AesKey::from_hex(&format!("G{}", &hex[1..])).is_err());
+    assert!(AesKey::new(KEY).decrypt(&mut encrypted[..0x18]).is_err()); // not a multiple of the AES block size
+}
+
+#[test]
+fn encrypted_container_reads_with_the_right_key() {
+    add_keys();
+    let plain = TocReader::from_file(common::get_built_container()).unwrap();
+    let lz4 = BlockCodec { name: "LZ4", compress: compress_lz4 };
+    for (name, codec) in [("encrypted", None), ("encrypted_lz4", Some(&lz4))] {
+        let toc = TocReader::from_file(&common::rewrite_container(name, codec, Some((KEY_GUID, KEY)))).unwrap();
+        assert_eq!(toc.header.encryption_key_guid, KEY_GUID);
+        assert_eq!(toc.mount_point, plain.mount_point);
+        assert_eq!(toc.get_files(), plain.get_files());
+        for i in 0..toc.chunk_ids.len() {
+            assert!(toc.get_chunk_file_range(i).is_none()); // encrypted chunks can't be read straight from the .ucas
+            assert_eq!(toc.read_chunk(i).unwrap(), plain.read_chunk(i).unwrap(), "{} chunk {}", name, i);
+        }
+    }
+}
+
+#[test]
+fn encrypted_container_is_refused_with_the_wrong_key() {
+    add_keys();
+    let wrong_key = common::rewrite_container("wrong_key", None, Some((KEY_GUID, KEY)));
+    // the container is encrypted with KEY, but says that it uses the GUID that OTHER_KEY was added for
+    let mut toc = std::fs::read(&wrong_key).unwrap();
+    toc[0x40..0x50].copy_from_slice(&WRONG_KEY_GUID.to_le_bytes());
+    std::fs::write(&wrong_key, toc).unwrap();
+    let error = TocReader::from_file(&wrong_key).err().unwrap().to_string();
+    assert!(error.contains("Wrong AES key"), "{}", error);
+    let missing_key = common::rewrite_container("missing_key", None, Some((MISSING_KEY_GUID, KEY)));
+    let error = TocReader::from_file(&missing_key).err().unwrap().to_string();
+    assert!(error.contains("no AES key"), "{}", error);
+}
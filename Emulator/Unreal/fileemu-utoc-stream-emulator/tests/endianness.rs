@@ -0,0 +1,102 @@
+// Big endian output is checked against the little endian fixtures in test_resources/endianness: both containers were built
+// from the same package (package_little.uasset and it's byte swapped copy, package_big.uasset) and a data.json for the pak,
+// so everything read back from them should match apart from the byte order
+use fileemu_utoc_stream_emulator::{
+    game_profile::Endianness,
+    io_package::{IoPackage, IoStoreObjectIndex},
+    io_toc::{IoChunkId, IoChunkType4, IoStoreTocCompressedBlockEntry},
+    pak_reader::PakReader,
+    string::Hasher16,
+    toc_reader::TocReader
+};
+use std::{
+    fs,
+    io::Cursor,
+    path::PathBuf
+};
+
+fn get_fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_resources/endianness").join(name)
+}
+
+#[test]
+fn hashes_dont_depend_on_byte_order() {
+    // chunk id of /Game/T/Tbl in both fixtures. Paths are hashed as lowercase UTF-16LE, whatever the host is
+    assert_eq!(Hasher16::get_cityhash64("/Game/T/Tbl"), 0xFD6EBF64940E4E0F);
+    let chunk_id = IoChunkId::new("/Game/T/Tbl", IoChunkType4::ExportBundleData);
+    let mut little = Cursor::new(vec![]);
+    let mut big = Cursor::new(vec![]);
+    chunk_id.to_buffer::<Cursor<Vec<u8>>, byteorder::LittleEndian>(&mut little).unwrap();
+    chunk_id.to_buffer::<Cursor<Vec<u8>>, byteorder::BigEndian>(&mut big).unwrap();
+    assert_eq!(little.get_ref()[..8], 0xFD6EBF64940E4E0Fu64.to_le_bytes());
+    assert_eq!(big.get_ref()[..8], 0xFD6EBF64940E4E0Fu64.to_be_bytes());
+    let import = IoStoreObjectIndex::ScriptImport(String::from("/Script/Engine.DataTable"));
+    let mut little = Cursor::new(vec![]);
+    let mut big = Cursor::new(vec![]);
+    import.to_buffer::<Cursor<Vec<u8>>, byteorder::LittleEndian>(&mut little).unwrap();
+    import.to_buffer::<Cursor<Vec<u8>>, byteorder::BigEndian>(&mut big).unwrap();
+    little.get_mut().reverse();
+    assert_eq!(little.get_ref(), big.get_ref());
+}
+
+#[test]
+fn compression_blocks_are_the_same_in_both_byte_orders() {
+    let block = IoStoreTocCompressedBlockEntry::new(0x12_3456_789A, 0x10000);
+    assert_eq!(block.get_offset(), 0x12_3456_789A);
+    assert_eq!(block.get_compressed_size(), 0x10000);
+    assert_eq!(block.get_uncompressed_size(), 0x10000);
+    let mut little = Cursor::new(vec![]);
+    let mut big = Cursor::new(vec![]);
+    block.to_buffer::<Cursor<Vec<u8>>, byteorder::LittleEndian>(&mut little).unwrap();
+    block.to_buffer::<Cursor<Vec<u8>>, byteorder::BigEndian>(&mut big).unwrap();
+    assert_eq!(little.get_ref(), big.get_ref());
+}
+
+#[test]
+fn package_converts_between_byte_orders() {
+    let little = fs::read(get_fixture("package_little.uasset")).unwrap();
+    let big = fs::read(get_fixture("package_big.uasset")).unwrap();
+    let package = IoPackage::from_buffer::<Cursor<&[u8]>, byteorder::LittleEndian>(&mut Cursor::new(&little), little.len() as u64).unwrap();
+    assert_eq!(package.to_bytes::<byteorder::BigEndian>().unwrap(), big);
+    let package = IoPackage::from_buffer::<Cursor<&[u8]>, byteorder::BigEndian>(&mut Cursor::new(&big), big.len() as u64).unwrap();
+    assert_eq!(package.to_bytes::<byteorder::LittleEndian>().unwrap(), little);
+}
+
+#[test]
+fn big_endian_toc_reads_like_little_endian() {
+    let little = TocReader::from_file(&get_fixture("little/UnrealEssentials_P.utoc")).unwrap();
+    let big = TocReader::from_file(&get_fixture("big/UnrealEssentials_P.utoc")).unwrap();
+    assert_eq!(little.endianness, Endianness::Little);
+    assert_eq!(big.endianness, Endianness::Big);
+    assert_eq!(little.header.toc_entry_count, big.header.toc_entry_count);
+    assert_eq!(little.header.container_id, big.header.container_id);
+    assert_eq!(little.header.directory_index_size, big.header.directory_index_size);
+    assert_eq!(little.chunk_ids, big.chunk_ids);
+    for (l, b) in little.offsets_and_lengths.iter().zip(&big.offsets_and_lengths) {
+        assert_eq!((l.get_offset(), l.get_length()), (b.get_offset(), b.get_length()));
+    }
+    assert_eq!(little.mount_point, big.mount_point);
+    assert_eq!(little.strings, big.strings);
+    assert_eq!(little.get_files(), big.get_files());
+    let little_header = little.read_container_header().unwrap().unwrap();
+    let big_header = big.read_container_header().unwrap().unwrap();
+    assert_eq!(little_header.container_id, big_header.container_id);
+    assert_eq!(little_header.packages, big_header.packages);
+    // the package itself is stored in the container's byte order
+    let package = little.get_files().into_iter().find(|f| f.path.ends_with("Tbl.uasset")).unwrap();
+    assert_eq!(little.read_chunk(package.chunk_index).unwrap(), fs::read(get_fixture("package_little.uasset")).unwrap());
+    assert_eq!(big.read_chunk(package.chunk_index).unwrap(), fs::read(get_fixture("package_big.uasset")).unwrap());
+}
+
+#[test]
+fn big_endian_pak_reads_like_little_endian() {
+    let little = PakReader::from_file(&get_fixture("little/UnrealEssentials_P.pak")).unwrap();
+    let big = PakReader::from_file(&get_fixture("big/UnrealEssentials_P.pak")).unwrap();
+    assert_eq!(little.version, big.version);
+    assert_eq!(little.mount_point, big.mount_point);
+    assert_eq!(little.entries.len(), big.entries.len());
+    for (i, (l, b)) in little.entries.iter().zip(&big.entries).enumerate() {
+        assert_eq!((&l.path, l.offset, l.size), (&b.path, b.offset, b.size));
+        assert_eq!(little.read_entry(i).unwrap(), big.read_entry(i).unwrap());
+    }
+}
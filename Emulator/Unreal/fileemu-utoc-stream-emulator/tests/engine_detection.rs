@@ -0,0 +1,112 @@
+// Detect the engine version from Paks folders made by building the container and pak for each engine version, renamed to look like
+// the game's own pakchunk0-Windows.utoc/.ucas/.pak. The mod has T/Tbl.uasset for the container (which the package summary probe
+// reads) and Movies/Intro.bk2 for the pak
+use fileemu_utoc_stream_emulator::{
+    asset_collector,
+    engine_detection::{self, EngineDetection, PackageProbe},
+    game_profile::{self, ContainerHeaderPosition, Endianness},
+    pak_factory::{self, PakVersion},
+    toc_factory::{self, EngineVersion}
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock
+};
+
+const PROFILES: [&str; 3] = ["ue4.25+", "ue4.26", "ue4.27"];
+
+fn get_work_folder() -> PathBuf {
+    std::env::temp_dir().join("utoc-emulator-engine-detection")
+}
+
+// Build every version's Paks folder, returning the folder that has the game files for a profile
+fn get_paks_folder(profile: &str) -> PathBuf {
+    static BUILT: OnceLock<()> = OnceLock::new();
+    BUILT.get_or_init(|| {
+        let work = get_work_folder();
+        let _ = fs::remove_dir_all(&work);
+        let content = work.join("mod/FEmulator/UTOC/UnrealEssentials_P.utoc/Content");
+        fs::create_dir_all(content.join("T")).unwrap();
+        fs::create_dir_all(content.join("Movies")).unwrap();
+        fs::copy(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_resources/endianness/package_little.uasset"), content.join("T/Tbl.uasset")).unwrap();
+        fs::write(content.join("Movies/Intro.bk2"), [0x42; 0x100]).unwrap();
+        asset_collector::add_from_folders("test", work.join("mod").to_str().unwrap());
+        for profile in PROFILES {
+            game_profile::set_game_profile(profile).unwrap();
+            let output = work.join(profile).join("out");
+            fs::create_dir_all(&output).unwrap();
+            let toc_path = output.join(toc_factory::TARGET_TOC);
+            fs::write(&toc_path, toc_factory::build_table_of_contents(toc_path.to_str().unwrap()).unwrap()).unwrap();
+            toc_factory::write_container(output.join(toc_factory::TARGET_CAS).to_str().unwrap()).unwrap();
+            assert!(pak_factory::build_pak("MyGame").unwrap());
+            pak_factory::write_pak(output.join(pak_factory::TARGET_PAK).to_str().unwrap()).unwrap();
+            let paks = work.join(profile).join("Paks");
+            fs::create_dir_all(&paks).unwrap();
+            for (built, game) in [(toc_factory::TARGET_TOC, "utoc"), (toc_factory::TARGET_CAS, "ucas"), (pak_factory::TARGET_PAK, "pak")] {
+                fs::copy(output.join(built), paks.join(format!("pakchunk0-Windows.{}", game))).unwrap();
+            }
+        }
+    });
+    get_work_folder().join(profile).join("Paks")
+}
+
+fn detect(paks_path: &Path) -> EngineDetection {
+    engine_detection::detect_engine_version(paks_path).unwrap().unwrap()
+}
+
+// Copy a profile's Paks folder with only some of it's files
+fn copy_paks_folder(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    get_paks_folder(PROFILES[0]); // building clears the work folder, so it has to happen first
+    let paks = get_work_folder().join(name);
+    let _ = fs::remove_dir_all(&paks);
+    fs::create_dir_all(&paks).unwrap();
+    for (profile, file) in files {
+        fs::copy(get_paks_folder(profile).join(file), paks.join(file)).unwrap();
+    }
+    paks
+}
+
+#[test]
+fn engine_version_is_detected_from_containers_and_paks() {
+    for (profile, engine_version, pak_version) in [
+        ("ue4.25+", EngineVersion::UE4_25Plus, PakVersion::V9),
+        ("ue4.26", EngineVersion::UE4_26, PakVersion::V11),
+        ("ue4.27", EngineVersion::UE4_27, PakVersion::V11)
+    ] {
+        let detection = detect(&get_paks_folder(profile));
+        assert!(!detection.is_ambiguous(), "{}: {:?}", profile, detection.problems);
+        assert_eq!(detection.engine_version, Some(engine_version), "{}", profile);
+        assert_eq!(detection.pak_versions, vec![(String::from("pakchunk0-Windows.pak"), Ok(pak_version))]);
+        assert_eq!(detection.endianness, Some(Endianness::Little));
+        assert!(matches!(detection.container_header_position, Some(ContainerHeaderPosition::Last)));
+        assert!(matches!(&detection.package_probe, PackageProbe::Matched(path) if path.ends_with("T/Tbl.uasset")));
+        // the built container is left out, so that the emulator's own files can't affect the detection
+        fs::copy(get_paks_folder("ue4.27").join("pakchunk0-Windows.utoc"), get_paks_folder(profile).join(toc_factory::TARGET_TOC)).unwrap();
+        assert_eq!(detect(&get_paks_folder(profile)).engine_version, Some(engine_version), "{}", profile);
+        fs::remove_file(get_paks_folder(profile).join(toc_factory::TARGET_TOC)).unwrap();
+    }
+}
+
+#[test]
+fn ambiguous_folders_arent_given_a_version() {
+    // 4.25+ and 4.26 containers are the same, so they can only be told apart by their paks
+    let detection = detect(&copy_paks_folder("no-pak", &[("ue4.26", "pakchunk0-Windows.utoc"), ("ue4.26", "pakchunk0-Windows.ucas")]));
+    assert!(detection.is_ambiguous());
+    assert_eq!(detection.engine_version, None);
+    assert!(detection.problems[0].starts_with("Could be any of [UE4_25Plus, UE4_26]"), "{:?}", detection.problems);
+    // a 4.27 container with a pak written by 4.25+
+    let detection = detect(&copy_paks_folder("mismatched", &[
+        ("ue4.27", "pakchunk0-Windows.utoc"), ("ue4.27", "pakchunk0-Windows.ucas"), ("ue4.25+", "pakchunk0-Windows.pak")
+    ]));
+    assert_eq!(detection.engine_version, None);
+    assert!(detection.problems[0].starts_with("Pak versions ([V9])"), "{:?}", detection.problems);
+    // containers with different TOC versions
+    fs::copy(get_paks_folder("ue4.27").join("pakchunk0-Windows.utoc"), copy_paks_folder("mixed", &[]).join("pakchunk1-Windows.utoc")).unwrap();
+    fs::copy(get_paks_folder("ue4.26").join("pakchunk0-Windows.utoc"), get_work_folder().join("mixed/pakchunk0-Windows.utoc")).unwrap();
+    let detection = detect(&get_work_folder().join("mixed"));
+    assert_eq!(detection.engine_version, None);
+    assert!(detection.problems[0].starts_with("Containers have different TOC versions"), "{:?}", detection.problems);
+    // folders without containers aren't checked
+    assert!(engine_detection::detect_engine_version(&copy_paks_folder("empty", &[("ue4.26", "pakchunk0-Windows.pak")])).unwrap().is_none());
+}
@@ -0,0 +1,49 @@
+// Extract the container that the emulator built, both from the written .utoc/.ucas and straight from it's virtual container
+mod common;
+
+use fileemu_utoc_stream_emulator::{
+    io_toc::IoChunkType4,
+    toc_extractor::{self, ExtractFilter},
+    toc_reader::TocReader
+};
+use std::{fs, io::{Read, Seek, SeekFrom}};
+
+#[test]
+fn virtual_container_reads_like_the_written_one() {
+    let written = TocReader::from_file(common::get_built_container()).unwrap();
+    let virtual_toc = TocReader::from_virtual_container().unwrap();
+    assert_eq!(virtual_toc.get_files(), written.get_files());
+    for i in 0..written.chunk_ids.len() {
+        assert!(virtual_toc.get_chunk_file_range(i).is_none());
+        assert_eq!(virtual_toc.read_chunk(i).unwrap(), written.read_chunk(i).unwrap(), "chunk {}", i);
+    }
+    // the whole container, including the padding between blocks
+    let mut reader = fileemu_utoc_stream_emulator::toc_factory::VirtualContainerReader::new().unwrap();
+    let mut data = vec![];
+    reader.read_to_end(&mut data).unwrap();
+    assert_eq!(data, fs::read(common::get_built_container().with_extension("ucas")).unwrap());
+    reader.seek(SeekFrom::End(-8)).unwrap();
+    let mut tail = vec![];
+    reader.read_to_end(&mut tail).unwrap();
+    assert_eq!(tail, data[data.len() - 8..]);
+}
+
+#[test]
+fn virtual_and_written_containers_extract_the_same_files() {
+    let output = common::get_built_container().parent().unwrap().join("extracted");
+    let filter = ExtractFilter::default();
+    let written = toc_extractor::extract_container(common::get_built_container(), &output.join("written"), &filter).unwrap();
+    let virtual_results = toc_extractor::extract_virtual_container(&output.join("virtual"), &filter).unwrap();
+    assert!(written.failed_files.is_empty() && virtual_results.failed_files.is_empty());
+    assert_eq!(virtual_results.extracted_files, written.extracted_files);
+    for (path, data) in common::get_mod_files() {
+        let extracted = virtual_results.extracted_files.iter().find(|f| f.ends_with(&format!("Content/{}", path))).unwrap();
+        assert_eq!(fs::read(output.join("virtual").join(extracted)).unwrap(), data, "{}", path);
+        assert_eq!(fs::read(output.join("written").join(extracted)).unwrap(), data, "{}", path);
+    }
+    // only the package
+    let packages = toc_extractor::extract_virtual_container(&output.join("filtered"), &ExtractFilter::new(&["*.uasset"])).unwrap();
+    assert_eq!(packages.extracted_files.len(), 1);
+    assert_eq!(packages.filtered_count, 1);
+    assert!(TocReader::from_virtual_container().unwrap().chunk_ids.iter().any(|c| c.get_type() == IoChunkType4::BulkData));
+}
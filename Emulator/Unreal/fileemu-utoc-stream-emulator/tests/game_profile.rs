@@ -0,0 +1,68 @@
+// Look up game profiles by id and check what their custom versions and quirks change
+use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
+use fileemu_utoc_stream_emulator::{
+    asset_collector,
+    game_profile::{self, ContainerHeaderPosition, GameProfile, GameQuirks},
+    pak_factory,
+    pak_package::{CustomVersion, PACKAGE_ASSET_MAGIC},
+    toc_factory::EngineVersion
+};
+
+#[test]
+fn profiles_are_found_by_id() {
+    let profile = game_profile::find_game_profile("Scarlet-Nexus").unwrap();
+    assert_eq!(profile.id, "scarlet-nexus");
+    assert_eq!(profile.engine_version, EngineVersion::UE4_25Plus);
+    assert_eq!(profile.project_name, Some("ScarletNexus"));
+    assert_eq!(profile.container_header_position, ContainerHeaderPosition::First);
+    assert_eq!(game_profile::find_game_profile("p3r").unwrap().project_name, Some("Xrd777"));
+    for (id, version) in [("ue4.25+", EngineVersion::UE4_25Plus), ("ue4.26", EngineVersion::UE4_26), ("ue4.27", EngineVersion::UE4_27)] {
+        let profile = game_profile::get_standard_profile(version);
+        assert_eq!((profile.id, profile.engine_version, profile.project_name), (id, version, None));
+        assert_eq!(profile.quirks, GameQuirks::empty());
+    }
+    assert!(game_profile::find_game_profile("ue4.28").is_none());
+    let error = game_profile::set_game_profile("ue4.28").unwrap_err();
+    assert!(error.contains("ue4.25+, ue4.26, ue4.27, p3r, scarlet-nexus"), "{}", error);
+}
+
+// The start of a 4.27 cooked package's summary, up to the end of it's custom versions
+fn make_summary<E: ByteOrder>(versions: &[(u128, i32)]) -> Vec<u8> {
+    let mut summary = vec![];
+    summary.write_u32::<E>(PACKAGE_ASSET_MAGIC).unwrap();
+    for value in [-7, 864, 522, 0, versions.len() as i32] { // legacy file version, legacy UE3 version, UE4 version, licensee version
+        summary.write_i32::<E>(value).unwrap();
+    }
+    for (key, version) in versions {
+        summary.write_u128::<E>(*key).unwrap();
+        summary.write_i32::<E>(*version).unwrap();
+    }
+    summary
+}
+
+#[test]
+fn newer_custom_versions_are_found() {
+    let package_versions = [(0x1111, 3), (0x2222, 7), (0x3333, 1)];
+    let versions = CustomVersion::list_from_summary::<_, LittleEndian>(&mut make_summary::<LittleEndian>(&package_versions).as_slice()).unwrap();
+    assert_eq!(versions, CustomVersion::list_from_summary::<_, BigEndian>(&mut make_summary::<BigEndian>(&package_versions).as_slice()).unwrap());
+    assert_eq!(versions.iter().map(|v| (v.key, v.version)).collect::<Vec<_>>(), package_versions);
+    let profile = GameProfile {
+        custom_versions: &[(0x1111, 3), (0x2222, 5), (0x4444, 9)],
+        ..game_profile::find_game_profile("ue4.27").unwrap().clone()
+    };
+    // 0x3333 is left for the engine to report, since the game doesn't know about it
+    assert_eq!(profile.get_newer_custom_versions(&versions), vec![(0x2222, 7, 5)]);
+    let summary = make_summary::<LittleEndian>(&package_versions);
+    assert!(CustomVersion::list_from_summary::<_, LittleEndian>(&mut &summary[..summary.len() - 1]).is_err());
+    assert!(CustomVersion::list_from_summary::<_, LittleEndian>(&mut &summary[4..]).is_err());
+}
+
+#[test]
+fn companion_pak_is_left_out_for_games_that_wont_mount_it() {
+    asset_collector::add_virtual_file_from_buffer("test", "/Game/Data/Settings.json", b"{}".to_vec()).unwrap();
+    game_profile::get_game_profile_mut().quirks = GameQuirks::NO_COMPANION_PAK;
+    assert!(game_profile::get_game_profile().has_quirk(GameQuirks::NO_COMPANION_PAK));
+    assert!(!pak_factory::build_pak("MyGame").unwrap());
+    game_profile::get_game_profile_mut().quirks = GameQuirks::empty();
+    assert!(pak_factory::build_pak("MyGame").unwrap());
+}
@@ -0,0 +1,130 @@
+// Read the endianness fixture's package, write it back out unchanged, then edit it's maps and export data and check what the writer
+// recalculates. The fixture only has one export bundle, so it's also split into several bundles to check the order they're read in
+use byteorder::LittleEndian;
+use fileemu_utoc_stream_emulator::{
+    io_package::{
+        ContainerHeaderPackage, ExportBundleCommandType, ExportBundleEntry, ExportBundleHeader4, IoPackage, IoStoreObjectIndex,
+        PackageSummary2
+    },
+    string::FMappedName
+};
+use std::{fs, io::Cursor, path::PathBuf};
+
+fn read_package(data: &[u8]) -> IoPackage {
+    IoPackage::from_buffer::<Cursor<&[u8]>, LittleEndian>(&mut Cursor::new(data), data.len() as u64).unwrap()
+}
+
+fn get_fixture_package() -> Vec<u8> {
+    fs::read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_resources/endianness/package_little.uasset")).unwrap()
+}
+
+#[test]
+fn unchanged_package_writes_the_same_bytes() {
+    let data = get_fixture_package();
+    let package = read_package(&data);
+    assert_eq!(package.get_package_name().unwrap(), "/Game/T/Tbl");
+    assert_eq!(package.size, data.len() as u64);
+    let (offset, size) = package.export_data_ranges[0].unwrap();
+    assert_eq!(offset, package.summary.get_header_size());
+    assert_eq!(package.export_data[0], data[offset as usize..(offset + size) as usize]);
+    assert_eq!(package.to_bytes::<LittleEndian>().unwrap(), data);
+}
+
+#[test]
+fn edited_package_has_new_offsets_and_sizes() {
+    let data = get_fixture_package();
+    let original = read_package(&data);
+    let mut package = read_package(&data);
+    package.names.push(String::from("AddedName"));
+    let added_import = IoStoreObjectIndex::get_script_import_hash("/Script/Engine.Texture2D");
+    package.imports.push(added_import);
+    package.export_data[0].extend_from_slice(&[0xAB; 0x10]);
+    // second export that isn't in any bundle, which the writer adds to the last one
+    let mut export = package.exports[0].clone();
+    export.object_name = FMappedName::from(package.names.len() as u64 - 1); // AddedName, with no number
+    export.cooked_serial_offset = original.exports[0].cooked_serial_offset + original.exports[0].cooked_serial_size;
+    package.exports.push(export);
+    package.export_data.push(vec![0xCD; 0x20]);
+
+    let written = package.to_bytes::<LittleEndian>().unwrap();
+    let edited = read_package(&written);
+    let summary = &edited.summary;
+    assert_eq!(edited.names, package.names);
+    assert_eq!(edited.imports, package.imports);
+    assert_eq!(edited.get_export_name(1).unwrap(), "AddedName");
+    assert_eq!(summary.name_map_hashes_offset % 8, 0);
+    assert_eq!(summary.name_map_hashes_size as usize, (package.names.len() + 1) * 8); // + the algorithm id
+    assert_eq!(summary.import_map_offset, summary.name_map_hashes_offset + summary.name_map_hashes_size);
+    assert_eq!(summary.export_map_offset - summary.import_map_offset, 8 * package.imports.len() as i32);
+    assert_eq!(summary.export_bundles_offset - summary.export_map_offset, 0x48 * 2);
+    assert!(summary.import_map_offset > original.summary.import_map_offset);
+    // export sizes come from their data, and exports after a changed one move by the change in size
+    assert_eq!(edited.exports[0].cooked_serial_offset, original.exports[0].cooked_serial_offset);
+    assert_eq!(edited.exports[0].cooked_serial_size, original.exports[0].cooked_serial_size + 0x10);
+    assert_eq!(edited.exports[1].cooked_serial_offset, package.exports[1].cooked_serial_offset + 0x10);
+    assert_eq!(edited.exports[1].cooked_serial_size, 0x20);
+    let last_bundle = edited.export_bundles.last().unwrap();
+    for command_type in [ExportBundleCommandType::Create, ExportBundleCommandType::Serialize] {
+        assert!(last_bundle.iter().any(|e| e.local_export_index == 1 && e.command_type == command_type));
+    }
+    assert_eq!(edited.graph_packages, original.graph_packages);
+    assert_eq!(edited.export_data, package.export_data);
+    assert_eq!(edited.size, summary.get_header_size() + package.export_data.iter().map(|d| d.len() as u64).sum::<u64>());
+    assert_eq!(edited.to_bytes::<LittleEndian>().unwrap(), written);
+}
+
+// Three exports in their own bundles, with the third export loaded before the second
+fn get_package_with_bundles() -> IoPackage {
+    let mut package = read_package(&get_fixture_package());
+    for (i, size) in [(1, 0x20), (2, 0x30)] {
+        let mut export = package.exports[0].clone();
+        export.cooked_serial_offset = package.exports[i - 1].cooked_serial_offset + package.exports[i - 1].cooked_serial_size;
+        export.cooked_serial_size = size as i64;
+        package.exports.push(export);
+        package.export_data.push(vec![i as u8; size]);
+    }
+    let bundle = |i: u32| [ExportBundleCommandType::Create, ExportBundleCommandType::Serialize]
+        .map(|command_type| ExportBundleEntry { local_export_index: i, command_type }).to_vec();
+    package.export_bundles = vec![bundle(0), bundle(2), bundle(1)];
+    package
+}
+
+#[test]
+fn export_bundles_are_read_in_load_order() {
+    let package = get_package_with_bundles();
+    let written = package.to_bytes::<LittleEndian>().unwrap();
+    let edited = read_package(&written);
+    assert_eq!(edited.export_bundles, package.export_bundles);
+    assert_eq!(edited.export_data, package.export_data);
+    // export data is stored in the order that it's bundle is loaded
+    let offset = |i: usize| edited.export_data_ranges[i].unwrap().0;
+    assert!(offset(0) < offset(2) && offset(2) < offset(1));
+    assert_eq!(offset(1), offset(2) + 0x30);
+    let store_entry = ContainerHeaderPackage::from_package_summary::<ExportBundleHeader4, PackageSummary2, _, LittleEndian>(
+        &mut Cursor::new(&written), 0x10, written.len() as u64).unwrap();
+    assert_eq!((store_entry.export_count, store_entry.export_bundle_count), (3, 3));
+    assert_eq!(store_entry.import_ids, edited.graph_packages.iter().map(|p| p.imported_package_id).collect::<Vec<_>>());
+}
+
+#[test]
+fn export_bundles_that_dont_fit_their_section_are_refused() {
+    let written = get_package_with_bundles().to_bytes::<LittleEndian>().unwrap();
+    let summary = read_package(&written).summary;
+    let read_store_entry = |data: &[u8]| ContainerHeaderPackage::from_package_summary::<ExportBundleHeader4, PackageSummary2, _, LittleEndian>(
+        &mut Cursor::new(data), 0x10, data.len() as u64);
+    let read = |data: &[u8]| IoPackage::from_buffer::<Cursor<&[u8]>, LittleEndian>(&mut Cursor::new(data), data.len() as u64);
+    // second bundle's header points to the wrong entries
+    let mut wrong_entries = written.clone();
+    let second_header = summary.export_bundles_offset as usize + 8;
+    wrong_entries[second_header..second_header + 4].copy_from_slice(&5u32.to_le_bytes());
+    assert!(read_store_entry(&wrong_entries).unwrap_err().to_string().contains("Export bundle 1 starts at entry 5, expected entry 2"));
+    assert!(read(&wrong_entries).is_err());
+    // graph data (at 0x34 in the summary) that starts before the export bundles
+    let mut graph_before_bundles = written.clone();
+    graph_before_bundles[0x34..0x38].copy_from_slice(&(summary.export_bundles_offset - 8).to_le_bytes());
+    assert!(read_store_entry(&graph_before_bundles).unwrap_err().to_string().contains("before the export bundles"));
+    // graph data that starts partway through the entries
+    let mut graph_inside_bundles = written.clone();
+    graph_inside_bundles[0x34..0x38].copy_from_slice(&(summary.graph_data_offset - 8).to_le_bytes());
+    assert!(read_store_entry(&graph_inside_bundles).unwrap_err().to_string().contains("export bundles section is 0x"));
+}
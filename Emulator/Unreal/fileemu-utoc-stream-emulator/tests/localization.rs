@@ -0,0 +1,69 @@
+// Build a container from a mod with a package and localized versions of it for two cultures, plus a localized package with no
+// source package, and check the culture package map in the built container header
+use fileemu_utoc_stream_emulator::{
+    asset_collector,
+    io_toc::ContainerHeader,
+    string::Hasher16,
+    toc_factory,
+    toc_reader::TocReader
+};
+use std::{fs, path::PathBuf, sync::OnceLock};
+
+const PACKAGES: [&str; 4] = ["T/Tbl", "L10N/fr/T/Tbl", "L10N/ja/T/Tbl", "L10N/fr/T/Other"];
+
+fn get_container_header() -> &'static ContainerHeader {
+    static HEADER: OnceLock<ContainerHeader> = OnceLock::new();
+    HEADER.get_or_init(|| {
+        let work = std::env::temp_dir().join("utoc-emulator-localization");
+        let _ = fs::remove_dir_all(&work);
+        let content = work.join("mod/FEmulator/UTOC/UnrealEssentials_P.utoc/Content");
+        let package = fs::read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_resources/endianness/package_little.uasset")).unwrap();
+        for path in PACKAGES {
+            let file_path = content.join(path.to_owned() + ".uasset");
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(file_path, &package).unwrap();
+        }
+        asset_collector::add_from_folders("test", work.join("mod").to_str().unwrap());
+        let output = work.join("out");
+        fs::create_dir_all(&output).unwrap();
+        let toc_path = output.join(toc_factory::TARGET_TOC);
+        fs::write(&toc_path, toc_factory::build_table_of_contents(toc_path.to_str().unwrap()).unwrap()).unwrap();
+        toc_factory::write_container(output.join(toc_factory::TARGET_CAS).to_str().unwrap()).unwrap();
+        TocReader::from_file(&toc_path).unwrap().read_container_header().unwrap().unwrap()
+    })
+}
+
+fn get_package_id(path: &str) -> u64 {
+    Hasher16::get_cityhash64(&format!("/Game/{}", path))
+}
+
+#[test]
+fn localized_packages_are_mapped_to_their_source() {
+    let header = get_container_header();
+    // localized packages are still packages in the container, the map only says which one to load for each culture
+    for path in PACKAGES {
+        assert!(header.contains_package(get_package_id(path)), "{}", path);
+    }
+    let mut cultures = header.culture_package_map.clone();
+    cultures.iter_mut().for_each(|(_, pairs)| pairs.sort());
+    cultures.sort();
+    let mut french = vec![
+        (get_package_id("T/Tbl"), get_package_id("L10N/fr/T/Tbl")),
+        (get_package_id("T/Other"), get_package_id("L10N/fr/T/Other"))
+    ];
+    french.sort();
+    assert_eq!(cultures, vec![
+        (String::from("fr"), french),
+        (String::from("ja"), vec![(get_package_id("T/Tbl"), get_package_id("L10N/ja/T/Tbl"))])
+    ]);
+}
+
+#[test]
+fn localized_package_paths_give_their_culture_and_source() {
+    let source = |path: &str| asset_collector::get_localized_package_source(&format!("UnrealEssentials/Content/{}", path));
+    assert_eq!(source("L10N/ja/UI/Title"), Some((String::from("ja"), String::from("/Game/UI/Title"))));
+    assert_eq!(source("l10n/fr/T/Tbl"), Some((String::from("fr"), String::from("/Game/T/Tbl"))));
+    assert_eq!(source("T/Tbl"), None);
+    assert_eq!(source("L10N/ja"), None);
+    assert_eq!(source("T/L10N/ja/Tbl"), None);
+}
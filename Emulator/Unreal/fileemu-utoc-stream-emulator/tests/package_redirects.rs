@@ -0,0 +1,66 @@
+// Build a container from two mods that declare package redirects in their PackageRedirects.txt, and check the redirects in the built
+// container header. The first mod ships T/Tbl and T/Other, the second one redirects a package that the first mod also redirects
+use fileemu_utoc_stream_emulator::{
+    asset_collector::{self, PackageRedirect},
+    io_toc::ContainerHeader,
+    string::Hasher16,
+    toc_factory,
+    toc_reader::TocReader
+};
+use std::{fs, path::PathBuf, sync::OnceLock};
+
+const MODS: [(&str, &str); 2] = [
+    ("first", "# replaced tables\n/Game/Old/Tbl = /Game/T/Tbl.uasset\n\n/Game/Old/Other = /Game/T/Other\n/Game/Old/Missing = /Game/T/Missing\n"),
+    ("second", "/Game/Old/Other = /Game/T/Tbl\n")
+];
+
+fn get_container_header() -> &'static ContainerHeader {
+    static HEADER: OnceLock<ContainerHeader> = OnceLock::new();
+    HEADER.get_or_init(|| {
+        let work = std::env::temp_dir().join("utoc-emulator-package-redirects");
+        let _ = fs::remove_dir_all(&work);
+        let package = fs::read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_resources/endianness/package_little.uasset")).unwrap();
+        for (mod_name, redirects) in MODS {
+            let emulator_folder = work.join(mod_name).join("FEmulator/UTOC");
+            let content = emulator_folder.join("UnrealEssentials_P.utoc/Content/T");
+            fs::create_dir_all(&content).unwrap();
+            if mod_name == "first" {
+                fs::write(content.join("Tbl.uasset"), &package).unwrap();
+                fs::write(content.join("Other.uasset"), &package).unwrap();
+            }
+            fs::write(emulator_folder.join(asset_collector::PACKAGE_REDIRECTS_FILE), redirects).unwrap();
+            asset_collector::add_from_folders(mod_name, work.join(mod_name).to_str().unwrap());
+        }
+        let output = work.join("out");
+        fs::create_dir_all(&output).unwrap();
+        let toc_path = output.join(toc_factory::TARGET_TOC);
+        fs::write(&toc_path, toc_factory::build_table_of_contents(toc_path.to_str().unwrap()).unwrap()).unwrap();
+        toc_factory::write_container(output.join(toc_factory::TARGET_CAS).to_str().unwrap()).unwrap();
+        TocReader::from_file(&toc_path).unwrap().read_container_header().unwrap().unwrap()
+    })
+}
+
+#[test]
+fn redirects_to_packages_in_the_container_are_written() {
+    let id = Hasher16::get_cityhash64;
+    // Old/Other is redirected by both mods, the second mod's redirect takes priority. Old/Missing's target isn't in any mod
+    assert_eq!(get_container_header().package_redirects, vec![
+        (id("/Game/Old/Tbl"), id("/Game/T/Tbl")),
+        (id("/Game/Old/Other"), id("/Game/T/Tbl"))
+    ]);
+}
+
+#[test]
+fn redirect_manifests_are_parsed() {
+    let redirect = |source: &str, target: &str| PackageRedirect { mod_id: String::from("test"), source: source.to_owned(), target: target.to_owned() };
+    assert_eq!(asset_collector::parse_package_redirects("test", MODS[0].1).unwrap(), vec![
+        redirect("/Game/Old/Tbl", "/Game/T/Tbl"),
+        redirect("/Game/Old/Other", "/Game/T/Other"),
+        redirect("/Game/Old/Missing", "/Game/T/Missing")
+    ]);
+    assert_eq!(asset_collector::parse_package_redirects("test", "\\Game\\A = \\Game\\B\n").unwrap(), vec![redirect("/Game/A", "/Game/B")]);
+    let error = |manifest: &str| asset_collector::parse_package_redirects("test", manifest).unwrap_err();
+    assert_eq!(error("/Game/A = /Game/B\n/Game/C"), "Line 2: expected \"Source = Target\"");
+    assert!(error("/Game/A = /Engine/B").contains("must begin with /Game/"));
+    assert!(error("/Game/A = /Game/a.uasset").contains("redirects to itself"));
+}
@@ -0,0 +1,82 @@
+// Build the pak for files that can't go in the container, then read it back for the pak versions used by 4.25 (v9, with a frozen
+// index) and 4.26/4.27 (v11, with an encoded index). A second mod ships test_resources/pak/broken.pak, which has a new
+// Content/Data/Settings.json and Content/Data/Broken.json with compression blocks that can't be decompressed
+use fileemu_utoc_stream_emulator::{
+    asset_collector::{self, MOUNT_POINT},
+    game_profile,
+    pak_factory::{self, PakVersion},
+    pak_reader::PakReader
+};
+use std::{fs, path::PathBuf};
+
+// Contents of every file in the mod, by it's path from the mod's root
+fn get_mod_files() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("Content/Movies/Intro.bk2", (0..0x30000u32).map(|i| ((i * 13) ^ (i >> 9)) as u8).collect()),
+        ("Content/Data/Settings.json", b"{ \"Enabled\": true }".to_vec()),
+        ("Content/Data/Empty.json", vec![]),
+        ("Engine/Config/BaseGame.ini", b"[/Script/Engine.GameSession]\n".to_vec())
+    ]
+}
+
+fn get_pak_path(path: &str) -> String {
+    match path.starts_with("Engine/") {
+        true => path.to_owned(),
+        false => format!("MyGame/{}", path)
+    }
+}
+
+// The pak's state is global, so each version is built and written one after the other in the same test
+#[test]
+fn built_pak_reads_back_for_each_version() {
+    let work = std::env::temp_dir().join("utoc-emulator-pak");
+    let _ = fs::remove_dir_all(&work);
+    let root = work.join("mod/FEmulator/UTOC/UnrealEssentials_P.utoc");
+    for (path, data) in get_mod_files() {
+        let file_path = root.join(path);
+        fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        fs::write(file_path, data).unwrap();
+    }
+    asset_collector::add_from_folders("test", work.join("mod").to_str().unwrap());
+    let broken = work.join("broken/FEmulator/UTOC/UnrealEssentials_P.utoc");
+    fs::create_dir_all(&broken).unwrap();
+    fs::copy(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_resources/pak/broken.pak"), broken.join("broken.pak")).unwrap();
+    asset_collector::add_from_folders("broken", work.join("broken").to_str().unwrap());
+    // the broken mod's files are only read when the pak is first built. Settings.json falls back to the first mod's file, while
+    // Broken.json has nothing to fall back on so it's left out
+    game_profile::set_game_profile("ue4.27").unwrap();
+    assert!(pak_factory::build_pak("MyGame").unwrap());
+    let failed = unsafe { &(*std::ptr::addr_of!(pak_factory::PAK_DATA)).as_ref().unwrap().failed_to_read };
+    assert_eq!(failed.len(), 2, "{:?}", failed);
+    assert!(failed.iter().any(|f| f.starts_with("MyGame/Content/Data/Settings.json: Failed to read")), "{:?}", failed);
+    assert!(failed.iter().any(|f| f.starts_with("MyGame/Content/Data/Broken.json: Failed to read")), "{:?}", failed);
+    for (profile, version) in [("ue4.25+", PakVersion::V9), ("ue4.26", PakVersion::V11), ("ue4.27", PakVersion::V11)] {
+        game_profile::set_game_profile(profile).unwrap();
+        assert!(pak_factory::build_pak("MyGame").unwrap());
+        let pak_path = work.join(profile).join(pak_factory::TARGET_PAK);
+        fs::create_dir_all(pak_path.parent().unwrap()).unwrap();
+        pak_factory::write_pak(pak_path.to_str().unwrap()).unwrap();
+
+        assert_eq!(PakReader::read_version(&pak_path).unwrap(), version);
+        let pak = PakReader::from_file(&pak_path).unwrap();
+        assert_eq!(pak.version, version);
+        assert_eq!(pak.mount_point, MOUNT_POINT);
+        assert!(pak.compression_methods.is_empty());
+        let mut paths: Vec<_> = pak.entries.iter().map(|e| e.path.as_str()).collect();
+        paths.sort();
+        let mut expected: Vec<_> = get_mod_files().into_iter().map(|(path, _)| get_pak_path(path)).collect();
+        expected.sort();
+        assert_eq!(paths, expected, "{}", profile);
+        for (path, data) in get_mod_files() {
+            let index = pak.entries.iter().position(|e| e.path == get_pak_path(path)).unwrap();
+            let entry = &pak.entries[index];
+            assert_eq!((entry.size, entry.uncompressed_size, entry.compression_method), (data.len() as u64, data.len() as u64, 0));
+            assert_eq!(pak.read_entry(index).unwrap(), data, "{} {}", profile, path);
+            assert_eq!(pak.read_entry_start(index, 4).unwrap(), data[..data.len().min(4)]);
+            // entry data is stored right after it's header
+            let data_offset = pak.get_entry_file_offset(index).unwrap();
+            assert_eq!(data_offset, entry.offset + entry.get_header_size());
+            assert_eq!(fs::read(&pak_path).unwrap()[data_offset as usize..data_offset as usize + data.len()], data);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+// Read script objects from a global.utoc, then resolve script import hashes to their paths. The global container is made by building a
+// container with the global name map, name hashes and initial load meta as files, then changing their chunk types to the loader chunks
+// the way that the game's global.utoc stores them
+use byteorder::LittleEndian;
+use fileemu_utoc_stream_emulator::{
+    asset_collector,
+    io_package::{IoPackage, IoStoreObjectIndex},
+    io_toc::IoChunkType4,
+    script_objects::{self, ScriptObject, ScriptObjectDatabase},
+    toc_factory,
+    toc_reader::TocReader
+};
+use std::{fs, io::Cursor, path::{Path, PathBuf}, sync::OnceLock};
+
+const NAMES: [&str; 4] = ["/Script/Engine", "DataTable", "Default__DataTable", "Actor"];
+const GLOBAL_NAME_MAP: u64 = 1 << 31; // name map type in the top bits of the name index
+
+fn script_import(path: &str) -> u64 {
+    IoStoreObjectIndex::get_script_import_hash(path)
+}
+
+// (name index, name number, path of the object, path of it's outer, path of the class that it's the default object of)
+type TestObject = (u64, u64, &'static str, Option<&'static str>, Option<&'static str>);
+
+fn get_objects() -> Vec<TestObject> {
+    vec![
+        (0, 0, "/Script/Engine", None, None),
+        (1, 0, "/Script/Engine.DataTable", Some("/Script/Engine"), None),
+        (2 | GLOBAL_NAME_MAP, 0, "/Script/Engine.Default__DataTable", Some("/Script/Engine"), Some("/Script/Engine.DataTable")),
+        (3, 2, "/Script/Engine.Actor_1", Some("/Script/Engine"), None)
+    ]
+}
+
+fn make_global_chunks() -> [(&'static str, IoChunkType4, Vec<u8>); 3] {
+    let mut names = vec![];
+    let mut hashes = 0xC1640000u64.to_le_bytes().to_vec(); // algorithm id
+    for name in NAMES {
+        names.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        names.extend_from_slice(name.as_bytes());
+        hashes.extend_from_slice(&0u64.to_le_bytes()); // only the count of hashes is used
+    }
+    let mut initial_load = (get_objects().len() as u32).to_le_bytes().to_vec();
+    for (name_index, number, path, outer, cdo_class) in get_objects() {
+        for value in [name_index | number << 32, script_import(path), outer.map_or(u64::MAX, script_import), cdo_class.map_or(u64::MAX, script_import)] {
+            initial_load.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    [
+        ("Names.ubulk", IoChunkType4::LoaderGlobalNames, names),
+        ("NameHashes.ubulk", IoChunkType4::LoaderGlobalNameHashes, hashes),
+        ("InitialLoad.ubulk", IoChunkType4::LoaderInitialLoadMeta, initial_load)
+    ]
+}
+
+// Build the global container in a Paks folder, returning the folder's path
+fn get_paks_folder() -> &'static Path {
+    static PAKS: OnceLock<PathBuf> = OnceLock::new();
+    PAKS.get_or_init(|| {
+        let work = std::env::temp_dir().join("utoc-emulator-script-objects");
+        let _ = fs::remove_dir_all(&work);
+        for (name, _, data) in make_global_chunks() {
+            asset_collector::add_virtual_file_from_buffer("test", &format!("/Game/Global/{}", name), data).unwrap();
+        }
+        let output = work.join("out");
+        fs::create_dir_all(&output).unwrap();
+        let toc_path = output.join(toc_factory::TARGET_TOC);
+        fs::write(&toc_path, toc_factory::build_table_of_contents(toc_path.to_str().unwrap()).unwrap()).unwrap();
+        toc_factory::write_container(output.join(toc_factory::TARGET_CAS).to_str().unwrap()).unwrap();
+        let mut toc = fs::read(&toc_path).unwrap();
+        for file in TocReader::from_file(&toc_path).unwrap().get_files() {
+            let (_, chunk_type, _) = make_global_chunks().into_iter().find(|(name, _, _)| file.path.ends_with(name)).unwrap();
+            toc[0x90 + file.chunk_index * 0xc + 0xb] = chunk_type.into(); // chunk ids come right after the header
+        }
+        let paks = work.join("Paks");
+        fs::create_dir_all(&paks).unwrap();
+        fs::write(paks.join(script_objects::GLOBAL_TOC), toc).unwrap();
+        fs::copy(output.join(toc_factory::TARGET_CAS), paks.join("global.ucas")).unwrap();
+        paks
+    })
+}
+
+#[test]
+fn script_objects_are_read_from_the_global_container() {
+    let database = ScriptObjectDatabase::from_file(&get_paks_folder().join(script_objects::GLOBAL_TOC)).unwrap();
+    assert_eq!(database.names, NAMES);
+    assert_eq!(database.objects.len(), 4);
+    assert_eq!(database.get_object(script_import("/Script/Engine.Default__DataTable")).unwrap(), &ScriptObject {
+        name: String::from("Default__DataTable"),
+        global_index: script_import("/Script/Engine.Default__DataTable"),
+        outer_index: script_import("/Script/Engine"),
+        cdo_class_index: script_import("/Script/Engine.DataTable")
+    });
+    for (_, _, path, _, _) in get_objects() {
+        assert_eq!(database.get_path(script_import(path)), Some(path));
+    }
+    assert_eq!(database.get_path(script_import("/Script/Engine.Missing")), None);
+}
+
+#[test]
+fn script_imports_resolve_to_their_path() {
+    assert_eq!(script_objects::add_global_container(get_paks_folder()).unwrap(), 4);
+    let data_table = script_import("/Script/Engine.DataTable");
+    assert_eq!(IoStoreObjectIndex::from_raw(data_table), IoStoreObjectIndex::ScriptImport(String::from("/Script/Engine.DataTable")));
+    assert_eq!(IoStoreObjectIndex::from_raw(script_import("/Script/Engine.Missing")), IoStoreObjectIndex::ScriptImport(String::new()));
+    // the fixture is a DataTable, so it's export's class is a script import of it
+    let data = fs::read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_resources/endianness/package_little.uasset")).unwrap();
+    let package = IoPackage::from_buffer::<Cursor<&[u8]>, LittleEndian>(&mut Cursor::new(&data), data.len() as u64).unwrap();
+    match &package.exports[0].class_name {
+        IoStoreObjectIndex::ImportHash(hash) => assert_eq!(script_objects::get_script_object_path(*hash), Some("/Script/Engine.DataTable")),
+        other => panic!("Export class should be an import, got {:?}", other)
+    }
+}
@@ -0,0 +1,69 @@
+// Build a container from mods with files that can't go in it as they are, and check what the TOC builder did with them. On top of the
+// files from common::get_mod_files, there's a virtual package that's empty and a mod that ships the first container compressed, but
+// with it's .ucas cut short so that none of it's chunks can be read
+mod common;
+
+use common::BlockCodec;
+use fileemu_utoc_stream_emulator::{
+    asset_collector,
+    toc_factory::{self, TocBuilderProfiler},
+    toc_reader::TocReader
+};
+use std::{fs, io::Write, path::PathBuf, sync::OnceLock};
+
+fn compress_zlib(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+// Build the container a second time with the extra mods, returning the contents of each file in it by path
+fn get_built_files() -> &'static Vec<(String, Vec<u8>)> {
+    static BUILT: OnceLock<Vec<(String, Vec<u8>)>> = OnceLock::new();
+    BUILT.get_or_init(|| {
+        let compressed = common::rewrite_container("compressed", Some(&BlockCodec { name: "Zlib", compress: compress_zlib }), None);
+        let work = std::env::temp_dir().join("utoc-emulator-toc-factory");
+        let _ = fs::remove_dir_all(&work);
+        let broken = work.join("broken/FEmulator/UTOC/UnrealEssentials_P.utoc");
+        fs::create_dir_all(&broken).unwrap();
+        fs::copy(&compressed, broken.join("Broken.utoc")).unwrap();
+        fs::write(broken.join("Broken.ucas"), []).unwrap();
+        asset_collector::add_from_folders("broken", work.join("broken").to_str().unwrap());
+        asset_collector::add_virtual_file_from_buffer("test", "/Game/A/Empty.uasset", vec![]).unwrap();
+        let output = work.join("out");
+        fs::create_dir_all(&output).unwrap();
+        let toc_path = output.join(toc_factory::TARGET_TOC);
+        fs::write(&toc_path, toc_factory::build_table_of_contents(toc_path.to_str().unwrap()).unwrap()).unwrap();
+        toc_factory::write_container(output.join(toc_factory::TARGET_CAS).to_str().unwrap()).unwrap();
+        let toc = TocReader::from_file(&toc_path).unwrap();
+        toc.get_files().into_iter().map(|f| (f.path.clone(), toc.read_chunk(f.chunk_index).unwrap())).collect()
+    })
+}
+
+fn get_profiler() -> &'static TocBuilderProfiler {
+    get_built_files();
+    unsafe { (*std::ptr::addr_of!(toc_factory::TOC_BUILDER_PROFILER)).as_ref().unwrap() }
+}
+
+fn get_built_file(name: &str) -> Option<&'static Vec<u8>> {
+    get_built_files().iter().find(|(path, _)| PathBuf::from(path).ends_with(name)).map(|(_, data)| data)
+}
+
+#[test]
+fn unreadable_packages_are_left_out() {
+    assert!(get_built_file("T/Tbl.uasset").is_some());
+    assert!(get_built_file("A/Empty.uasset").is_none());
+    let invalid = &get_profiler().incorrect_asset_format;
+    assert_eq!(invalid.len(), 1);
+    assert!(invalid[0].starts_with("/Game/A/Empty.uasset"), "{}", invalid[0]);
+}
+
+#[test]
+fn unreadable_container_chunks_fall_back_to_the_replaced_file() {
+    for (path, data) in common::get_mod_files() {
+        assert_eq!(get_built_file(path).unwrap(), &data, "{}", path);
+    }
+    let failed = &get_profiler().failed_to_read;
+    assert_eq!(failed.len(), 2);
+    assert!(failed.iter().all(|f| f.contains("Failed to read chunk") && f.contains("Broken.utoc")), "{:?}", failed);
+}
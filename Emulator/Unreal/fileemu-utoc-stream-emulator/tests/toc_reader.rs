@@ -0,0 +1,119 @@
+// Build a container from a mod folder, then read it back with TocReader
+mod common;
+
+use fileemu_utoc_stream_emulator::{
+    io_toc::IoChunkType4,
+    toc_reader::{FileSliceReader, TocReader}
+};
+use std::{
+    fs,
+    io::{Cursor, ErrorKind, Read, Seek, SeekFrom},
+    path::Path
+};
+
+#[test]
+fn built_toc_reads_back() {
+    let toc = TocReader::from_file(common::get_built_container()).unwrap();
+    let files = toc.get_files();
+    let mod_files = common::get_mod_files();
+    assert_eq!(files.len(), mod_files.len());
+    assert_eq!(toc.chunk_ids.len(), mod_files.len() + 1); // + 1 for the container header
+    for (path, data) in &mod_files {
+        let file = files.iter().find(|f| f.path.ends_with(&format!("Content/{}", path))).unwrap();
+        assert_eq!(&toc.read_chunk(file.chunk_index).unwrap(), data, "{}", path);
+        // the emulator never compresses or encrypts, so every chunk can be read straight from the .ucas
+        let (cas_path, offset) = toc.get_chunk_file_range(file.chunk_index).unwrap();
+        let cas = fs::read(cas_path).unwrap();
+        assert_eq!(&cas[offset as usize..offset as usize + data.len()], data.as_slice());
+    }
+    let bulk = files.iter().find(|f| f.path.ends_with(".ubulk")).unwrap();
+    assert_eq!(toc.get_chunk_blocks(bulk.chunk_index).len(), 3);
+    let header = toc.read_container_header().unwrap().unwrap();
+    assert_eq!(header.container_id, toc.header.container_id);
+    assert_eq!(header.packages.len(), 1);
+    assert!(toc.chunk_ids.iter().any(|c| c.get_type() == IoChunkType4::ContainerHeader));
+}
+
+// Offsets of the directory index's lists in a TOC written by the emulator, which has no compression methods or signatures
+struct DirectoryIndexOffsets {
+    offsets_and_lengths: usize,
+    directories: usize,
+    files: usize
+}
+
+fn get_directory_index_offsets(toc: &[u8]) -> DirectoryIndexOffsets {
+    let read_u32 = |offset: usize| u32::from_le_bytes(toc[offset..offset + 4].try_into().unwrap()) as usize;
+    let entry_count = read_u32(0x18);
+    let block_count = read_u32(0x1c);
+    let mount_point = 0x90 + entry_count * (0xc + 0xa) + block_count * 0xc;
+    let directories = mount_point + 4 + read_u32(mount_point) + 4;
+    let files = directories + read_u32(directories - 4) * 0x10 + 4;
+    DirectoryIndexOffsets { offsets_and_lengths: 0x90 + entry_count * 0xc, directories, files }
+}
+
+fn read_corrupted(name: &str, corrupt: impl Fn(&mut Vec<u8>)) {
+    let source = common::get_built_container();
+    let mut toc = fs::read(source).unwrap();
+    corrupt(&mut toc);
+    let folder = source.parent().unwrap().join(name);
+    fs::create_dir_all(&folder).unwrap();
+    fs::copy(source.with_extension("ucas"), folder.join("UnrealEssentials_P.ucas")).unwrap();
+    let toc_path = folder.join("UnrealEssentials_P.utoc");
+    fs::write(&toc_path, toc).unwrap();
+    assert!(TocReader::from_file(Path::new(&toc_path)).is_err(), "Corrupted TOC ({}) was read without an error", name);
+}
+
+#[test]
+fn malformed_toc_is_refused() {
+    let set_u32 = |toc: &mut Vec<u8>, offset: usize, value: u32| toc[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    // file points to a chunk that doesn't exist
+    read_corrupted("bad_chunk", |toc| {
+        let files = get_directory_index_offsets(toc).files;
+        set_u32(toc, files + 8, 0x1000);
+    });
+    // directory lists itself as it's first child
+    read_corrupted("directory_cycle", |toc| {
+        let directories = get_directory_index_offsets(toc).directories;
+        set_u32(toc, directories + 4, 0);
+    });
+    // file name is past the end of the string pool
+    read_corrupted("bad_string", |toc| {
+        let files = get_directory_index_offsets(toc).files;
+        set_u32(toc, files, 0x1000);
+    });
+    // chunk offset is past the last compression block
+    read_corrupted("bad_offset", |toc| {
+        let offsets_and_lengths = get_directory_index_offsets(toc).offsets_and_lengths;
+        toc[offsets_and_lengths..offsets_and_lengths + 5].fill(0xff);
+    });
+    // more chunk ids than there is data for
+    read_corrupted("bad_count", |toc| set_u32(toc, 0x18, u32::MAX));
+    read_corrupted("truncated", |toc| {
+        let files = get_directory_index_offsets(toc).files;
+        toc.truncate(files);
+    });
+}
+
+#[test]
+fn slice_reader_refuses_seeks_before_the_start() {
+    let data: Vec<u8> = (0..0x40).collect();
+    let mut slice = FileSliceReader::new(Cursor::new(&data), 0x10, 0x20).unwrap();
+    let mut read = vec![];
+    slice.read_to_end(&mut read).unwrap();
+    assert_eq!(read, data[0x10..0x30]);
+    assert_eq!(slice.seek(SeekFrom::End(-4)).unwrap(), 0x1c);
+    assert_eq!(slice.seek(SeekFrom::Current(-0xc)).unwrap(), 0x10);
+    let mut byte = [0];
+    slice.read_exact(&mut byte).unwrap();
+    assert_eq!(byte[0], 0x20);
+    // past the end reads nothing, before the start is refused like Cursor does, without moving the reader
+    assert_eq!(slice.seek(SeekFrom::End(8)).unwrap(), 0x28);
+    assert_eq!(slice.read(&mut byte).unwrap(), 0);
+    for pos in [SeekFrom::Current(-0x29), SeekFrom::End(-0x21), SeekFrom::Start(u64::MAX)] {
+        assert_eq!(slice.seek(pos).unwrap_err().kind(), ErrorKind::InvalidInput, "{:?}", pos);
+    }
+    assert_eq!(slice.stream_position().unwrap(), 0x28);
+    assert_eq!(slice.seek(SeekFrom::Start(0)).unwrap(), 0);
+    slice.read_exact(&mut byte).unwrap();
+    assert_eq!(byte[0], 0x10);
+}
@@ -0,0 +1,535 @@
+use clap::{Parser, Subcommand};
+use fileemu_utoc_stream_emulator::{
+    asset_collector,
+    datatable_merge, dependency_check, engine_detection,
+    delta_patch::{self, DeltaPatch, PatchOperation, DELTA_PATCH_EXTENSION},
+    encryption::{self, AesKey},
+    game_packages,
+    game_profile::{self, Endianness},
+    io_package::{IoPackage, IoStoreObjectIndex},
+    io_toc::{ContainerHeader, IoDirectoryIndexEntry},
+    pak_factory::{self, TARGET_PAK},
+    script_objects::{self, ScriptObjectDatabase},
+    toc_extractor::{self, ExtractFilter, ExtractResults},
+    toc_factory::{self, EngineVersion, TARGET_CAS, TARGET_TOC},
+    toc_reader::TocReader,
+    unversioned::{UnversionedReader, UnversionedWriter, PKG_UNVERSIONED_PROPERTIES},
+    usmap::{self, Usmap}
+};
+use std::{
+    error::Error,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::ExitCode
+};
+
+// Command line tool for building and examining IO Store containers without having to start Reloaded.
+// Mod folders are the same folders that Reloaded would pass to the emulator (the folder containing FEmulator/UTOC)
+#[derive(Parser)]
+#[command(version, about = "Build, inspect and extract IO Store containers outside of the game")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Build UnrealEssentials_P.utoc/.ucas (and .pak for files that aren't packages) from a list of mod folders
+    Build {
+        /// Mod folders, in load order (later mods replace files from earlier ones)
+        #[arg(required = true)]
+        mods: Vec<PathBuf>,
+        /// Folder to write the container to
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// Game profile to build for (ue4.25+, ue4.26, ue4.27, or a game specific profile such as p3r). Defaults to the
+        /// version detected from --game-paks, or ue4.27
+        #[arg(long)]
+        game: Option<String>,
+        /// Engine version to build for (4.25+, 4.26 or 4.27), replacing the game profile's engine version
+        #[arg(short, long)]
+        engine_version: Option<String>,
+        /// Write a big endian container and pak, and read mod packages as big endian
+        #[arg(long)]
+        big_endian: bool,
+        /// Store files with identical contents once, with every copy pointing at the same data in the container
+        #[arg(long)]
+        dedup: bool,
+        /// Print the asset collector and TOC builder reports
+        #[arg(short, long)]
+        report: bool,
+        /// The game's Paks folder, used to check that every package that mods import exists
+        #[arg(short, long)]
+        game_paks: Option<PathBuf>,
+        /// Mappings (.usmap) for packages that use unversioned properties
+        #[arg(short, long)]
+        usmap: Option<PathBuf>,
+        /// The game's project folder name, which files in the pak are placed under. Defaults to the game profile's project
+        /// name, or the folder above Content/Paks if the output folder is a game's Paks folder
+        #[arg(short, long)]
+        project: Option<String>,
+        /// AES key for mods shipped as encrypted containers or paks
+        #[arg(short, long)]
+        aes_key: Option<String>
+    },
+    /// Print the header, chunks and directory tree of a TOC
+    Inspect {
+        toc: PathBuf,
+        /// Print every chunk id with it's offset and length
+        #[arg(short, long)]
+        chunks: bool,
+        /// Print the directory index as a tree
+        #[arg(short, long)]
+        tree: bool,
+        /// Print the packages, culture package map and redirects in the container header
+        #[arg(short, long)]
+        packages: bool,
+        /// Print the script objects in a global container (global.utoc)
+        #[arg(short, long)]
+        script_objects: bool,
+        /// AES key for encrypted containers
+        #[arg(short, long)]
+        aes_key: Option<String>
+    },
+    /// Extract the files in a container into a folder
+    Extract {
+        toc: PathBuf,
+        output: PathBuf,
+        /// Only extract paths that match these filters (* and ? wildcards, or a folder prefix)
+        #[arg(short, long)]
+        filter: Vec<String>,
+        /// AES key for encrypted containers
+        #[arg(short, long)]
+        aes_key: Option<String>
+    },
+    /// Print the summary, name map, imports, exports, export bundles and graph of an IO Store package (.uasset)
+    Package {
+        package: PathBuf,
+        /// The game's Paks folder, used to resolve script imports from global.utoc
+        #[arg(short, long)]
+        game_paks: Option<PathBuf>,
+        /// Write the package back out to this path after reading it, to check that it survives a round trip
+        #[arg(short, long)]
+        rewrite: Option<PathBuf>,
+        /// Mappings (.usmap) used to print the unversioned properties of each export (needs --game-paks to resolve classes)
+        #[arg(short, long)]
+        usmap: Option<PathBuf>,
+        /// Read the package (and write it back out) as big endian
+        #[arg(long)]
+        big_endian: bool
+    },
+    /// Make a delta patch (.patch) that turns a file from the game into a modified version of it
+    MakePatch {
+        /// The game's version of the file (extract it from the game's container first)
+        base: PathBuf,
+        /// The modified file
+        modified: PathBuf,
+        /// Path to write the patch to, defaults to the modified file's path with .patch added
+        #[arg(short, long)]
+        output: Option<PathBuf>
+    },
+    /// Build a container from a list of mod folders in memory and print the asset collector and TOC builder reports
+    Report {
+        #[arg(required = true)]
+        mods: Vec<PathBuf>,
+        #[arg(long)]
+        game: Option<String>,
+        #[arg(short, long)]
+        engine_version: Option<String>,
+        #[arg(long)]
+        big_endian: bool,
+        #[arg(long)]
+        dedup: bool,
+        /// The game's Paks folder, used to check that every package that mods import exists
+        #[arg(short, long)]
+        game_paks: Option<PathBuf>,
+        /// Mappings (.usmap) for packages that use unversioned properties
+        #[arg(short, long)]
+        usmap: Option<PathBuf>,
+        /// The game's project folder name, which files in the pak are placed under
+        #[arg(short, long)]
+        project: Option<String>,
+        /// AES key for mods shipped as encrypted containers or paks
+        #[arg(short, long)]
+        aes_key: Option<String>,
+        /// Extract the files in the built container into this folder, reading them the same way the game would
+        #[arg(short = 'x', long)]
+        extract: Option<PathBuf>
+    }
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Build { mods, output, game, engine_version, big_endian, dedup, report, game_paks, usmap, project, aes_key } =>
+            set_game_profile(game.as_deref(), engine_version.as_deref(), big_endian, project)
+                .and_then(|_| set_aes_key(aes_key.as_deref()))
+                .map(|_| unsafe { toc_factory::DEDUPLICATE_CHUNKS = dedup })
+                .and_then(|_| build(&mods, &output, report, game_paks.as_deref(), usmap.as_deref())),
+        Command::Inspect { toc, chunks, tree, packages, script_objects, aes_key } =>
+            inspect(&toc, chunks, tree, packages, script_objects, aes_key.as_deref()),
+        Command::Extract { toc, output, filter, aes_key } => extract(&toc, &output, &filter, aes_key.as_deref()),
+        Command::Package { package, game_paks, rewrite, usmap, big_endian } =>
+            set_game_profile(None, None, big_endian, None)
+                .and_then(|_| print_package(&package, game_paks.as_deref(), rewrite.as_deref(), usmap.as_deref())),
+        Command::MakePatch { base, modified, output } => make_patch(&base, &modified, output.as_deref()),
+        Command::Report { mods, game, engine_version, big_endian, dedup, game_paks, usmap, project, aes_key, extract } =>
+            set_game_profile(game.as_deref(), engine_version.as_deref(), big_endian, project)
+                .and_then(|_| set_aes_key(aes_key.as_deref()))
+                .map(|_| unsafe { toc_factory::DEDUPLICATE_CHUNKS = dedup })
+                .and_then(|_| report(&mods, game_paks.as_deref(), usmap.as_deref(), extract.as_deref()))
+    };
+    match result {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn set_game_profile(game: Option<&str>, engine_version: Option<&str>, big_endian: bool, project: Option<String>) -> Result<(), Box<dyn Error>> {
+    if let Some(id) = game {
+        game_profile::set_game_profile(id)?;
+    }
+    if let Some(version) = engine_version {
+        game_profile::get_game_profile_mut().engine_version = EngineVersion::try_from(version)?;
+    }
+    if big_endian {
+        game_profile::get_game_profile_mut().endianness = Endianness::Big;
+    }
+    if let Some(project) = project {
+        // profiles only hold static strings, and this is only set once per run
+        game_profile::get_game_profile_mut().project_name = Some(Box::leak(project.into_boxed_str()));
+    }
+    Ok(())
+}
+
+fn set_aes_key(aes_key: Option<&str>) -> Result<(), Box<dyn Error>> {
+    if let Some(key) = aes_key {
+        encryption::add_key(0, AesKey::from_hex(key)?);
+    }
+    Ok(())
+}
+
+// Collect every mod then build the TOC, returning the TOC's contents. The container itself is kept in toc_factory::CONTAINER_DATA
+fn build_toc(mods: &[PathBuf], toc_path: &Path, game_paks: Option<&Path>, usmap: Option<&Path>) -> Result<Vec<u8>, Box<dyn Error>> {
+    if let Some(paks_path) = game_paks {
+        engine_detection::apply_engine_detection(paks_path);
+        let package_count = game_packages::add_game_containers_from_folder(paks_path)?;
+        println!("Loaded {} packages from the game's containers", package_count);
+    }
+    if let Some(usmap_path) = usmap {
+        usmap::add_mappings(usmap_path)?;
+    }
+    for mod_path in mods {
+        if !mod_path.is_dir() {
+            return Err(format!("Mod folder {} doesn't exist", mod_path.display()).into());
+        }
+        // Reloaded uses the mod's folder name as it's id
+        let mod_id = fs::canonicalize(mod_path)?.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+        asset_collector::add_from_folders(&mod_id, mod_path.to_str().ok_or("Mod path isn't valid UTF-8")?);
+    }
+    toc_factory::build_table_of_contents(toc_path.to_str().ok_or("Output path isn't valid UTF-8")?)
+        .ok_or_else(|| "No files were loaded from the given mods".into())
+}
+
+// Build the pak from the files that mods added which aren't packages. Returns false if there weren't any
+fn build_pak(pak_path: &Path) -> Result<bool, Box<dyn Error>> {
+    if !asset_collector::has_pak_files() {
+        return Ok(false);
+    }
+    let project_name = match game_profile::get_game_profile().project_name.map(|p| p.to_owned())
+        .or_else(|| pak_factory::get_project_name(pak_path)) {
+        Some(p) => p,
+        None => {
+            println!("WARNING: No project name was given, so files in {} are placed under {}", TARGET_PAK, asset_collector::PROJECT_NAME);
+            asset_collector::PROJECT_NAME.to_owned()
+        }
+    };
+    pak_factory::build_pak(&project_name)
+}
+
+fn print_reports() {
+    unsafe {
+        asset_collector::print_asset_collector_results();
+        println!("{}", "-".repeat(80));
+        engine_detection::print_engine_detection_report();
+        println!("{}", "-".repeat(80));
+        toc_factory::print_toc_builder_results();
+        println!("{}", "-".repeat(80));
+        pak_factory::print_pak_builder_results();
+        println!("{}", "-".repeat(80));
+        dependency_check::print_dependency_report();
+        println!("{}", "-".repeat(80));
+        delta_patch::print_delta_patch_report();
+        println!("{}", "-".repeat(80));
+        datatable_merge::print_datatable_merge_report();
+    }
+}
+
+fn build(mods: &[PathBuf], output: &Path, report: bool, game_paks: Option<&Path>, usmap: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(output)?;
+    let toc_path = output.join(TARGET_TOC);
+    let cas_path = output.join(TARGET_CAS);
+    let pak_path = output.join(TARGET_PAK);
+    let toc = build_toc(mods, &toc_path, game_paks, usmap)?;
+    fs::write(&toc_path, toc)?;
+    toc_factory::write_container(cas_path.to_str().unwrap())?;
+    let has_pak = build_pak(&fs::canonicalize(output)?.join(TARGET_PAK))?;
+    if has_pak {
+        pak_factory::write_pak(pak_path.to_str().ok_or("Output path isn't valid UTF-8")?)?;
+    }
+    if report {
+        print_reports();
+    }
+    match has_pak {
+        true => println!("Wrote {}, {} and {}", toc_path.display(), cas_path.display(), pak_path.display()),
+        false => println!("Wrote {} and {}", toc_path.display(), cas_path.display())
+    }
+    Ok(())
+}
+
+fn report(mods: &[PathBuf], game_paks: Option<&Path>, usmap: Option<&Path>, extract: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    build_toc(mods, Path::new(TARGET_TOC), game_paks, usmap)?;
+    build_pak(Path::new(TARGET_PAK))?;
+    print_reports();
+    match extract {
+        Some(output) => check_extract_results(toc_extractor::extract_virtual_container(output, &ExtractFilter::default())?),
+        None => Ok(())
+    }
+}
+
+fn inspect(toc_path: &Path, chunks: bool, tree: bool, packages: bool, script_objects: bool, aes_key: Option<&str>) -> Result<(), Box<dyn Error>> {
+    set_aes_key(aes_key)?;
+    let toc = TocReader::from_file(toc_path)?;
+    let header = &toc.header;
+    println!("Version: {:?}", header.version);
+    println!("Byte order: {:?}", toc.endianness);
+    println!("Container ID: {:X}", header.container_id);
+    println!("Flags: {:?}", header.container_flags);
+    println!("Encryption key GUID: {:032X}", header.encryption_key_guid);
+    println!("Entries: {}", header.toc_entry_count);
+    println!("Compression blocks: {} (block size 0x{:x})", header.toc_compressed_block_entry_count, header.compression_block_size);
+    println!("Compression methods: {:?}", toc.compression_methods);
+    println!("Partitions: {} (partition size 0x{:x})", header.partition_count, header.partition_size);
+    println!("Mount point: \"{}\"", toc.mount_point);
+    println!("Directory index: {} directories, {} files, {} strings", toc.directories.len(), toc.files.len(), toc.strings.len());
+    if chunks {
+        println!("{}", "-".repeat(80));
+        for (i, chunk_id) in toc.chunk_ids.iter().enumerate() {
+            let offset_length = &toc.offsets_and_lengths[i];
+            println!("{:>6} {:016X} {:<24} offset 0x{:x} length 0x{:x}",
+                i, chunk_id.get_raw_hash(), format!("{:?}", chunk_id.get_type()), offset_length.get_offset(), offset_length.get_length());
+        }
+    }
+    if tree {
+        println!("{}", "-".repeat(80));
+        if let Some(root) = toc.directories.first() {
+            print_tree(&toc, root, 0);
+        }
+    }
+    if packages {
+        println!("{}", "-".repeat(80));
+        match toc.read_container_header()? {
+            Some(header) => print_container_header(&header),
+            None => println!("Container doesn't have a container header")
+        }
+    }
+    if script_objects {
+        println!("{}", "-".repeat(80));
+        let database = ScriptObjectDatabase::from_reader(&toc)?;
+        println!("{} names, {} script objects", database.names.len(), database.objects.len());
+        for object in &database.objects {
+            println!("{:016X} {}", object.global_index, database.get_path(object.global_index).unwrap_or(&object.name));
+        }
+    }
+    Ok(())
+}
+
+fn print_container_header(header: &ContainerHeader) {
+    println!("Container header for {:X}: {} packages, {} names", header.container_id, header.packages.len(), header.names.len());
+    for package in &header.packages {
+        println!("{:016X} size 0x{:x}, {} exports, {} export bundles, load order {}, imports {:X?}",
+            package.hash, package.export_bundle_size, package.export_count, package.export_bundle_count, package.load_order, package.import_ids);
+    }
+    for (culture, pairs) in &header.culture_package_map {
+        println!("Culture \"{}\": {} localized packages", culture, pairs.len());
+        for (source, localized) in pairs {
+            println!("  {:016X} => {:016X}", source, localized);
+        }
+    }
+    for (source, target) in &header.package_redirects {
+        println!("Redirect {:016X} => {:016X}", source, target);
+    }
+}
+
+fn print_package(package_path: &Path, game_paks: Option<&Path>, rewrite: Option<&Path>, usmap: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    if let Some(paks_path) = game_paks {
+        script_objects::add_global_container(paks_path)?;
+    }
+    if let Some(usmap_path) = usmap {
+        usmap::add_mappings(usmap_path)?;
+    }
+    let package = IoPackage::from_file(package_path)?;
+    let name_or_index = |index: &IoStoreObjectIndex| match index {
+        IoStoreObjectIndex::Export(i) => format!("export {} ({})", i, package.get_export_name(*i as usize).unwrap_or_default()),
+        IoStoreObjectIndex::ImportHash(h) => match IoStoreObjectIndex::from_raw(*h) {
+            IoStoreObjectIndex::ScriptImport(path) if !path.is_empty() => path,
+            _ => format!("{:016X}", h)
+        },
+        IoStoreObjectIndex::Empty => String::from("none"),
+        other => format!("{:?}", other)
+    };
+    println!("Package: {} (flags 0x{:x}, header 0x{:x} bytes, package 0x{:x} bytes)",
+        package.get_package_name().unwrap_or_default(), package.summary.package_flags, package.summary.get_header_size(), package.size);
+    println!("{}", "-".repeat(80));
+    println!("NAMES: {}", package.names.len());
+    for (i, name) in package.names.iter().enumerate() {
+        println!("{:>6} {}", i, name);
+    }
+    println!("{}", "-".repeat(80));
+    println!("IMPORTS: {}", package.imports.len());
+    for (i, import) in package.imports.iter().enumerate() {
+        println!("{:>6} {}", i, name_or_index(&IoStoreObjectIndex::from_raw_lossless(*import)));
+    }
+    println!("{}", "-".repeat(80));
+    println!("EXPORTS: {}", package.exports.len());
+    for (i, export) in package.exports.iter().enumerate() {
+        let range = match package.export_data_ranges[i] {
+            Some((offset, size)) => format!("data 0x{:x}..0x{:x}", offset, offset + size),
+            None => String::from("not serialized")
+        };
+        println!("{:>6} {} class {}, outer {}, flags 0x{:x}, {}", i, package.get_export_name(i).unwrap_or_default(),
+            name_or_index(&export.class_name), name_or_index(&export.outer_index), export.object_flags, range);
+    }
+    println!("{}", "-".repeat(80));
+    println!("EXPORT BUNDLES: {}", package.export_bundles.len());
+    for (i, bundle) in package.export_bundles.iter().enumerate() {
+        let entries: Vec<_> = bundle.iter().map(|e| format!("{:?} {}", e.command_type, e.local_export_index)).collect();
+        println!("{:>6} {}", i, entries.join(", "));
+    }
+    println!("{}", "-".repeat(80));
+    println!("IMPORTED PACKAGES: {}", package.graph_packages.len());
+    for graph_package in &package.graph_packages {
+        let arcs: Vec<_> = graph_package.external_arcs.iter().map(|a| format!("{} -> {}", a.from_export_bundle_index, a.to_export_bundle_index)).collect();
+        println!("{:016X} arcs [{}]", graph_package.imported_package_id, arcs.join(", "));
+    }
+    if let Some(mappings) = usmap::get_mappings() {
+        println!("{}", "-".repeat(80));
+        match game_profile::get_game_profile().endianness {
+            Endianness::Little => print_export_properties::<byteorder::LittleEndian>(&package, &fs::read(package_path)?, mappings),
+            Endianness::Big => print_export_properties::<byteorder::BigEndian>(&package, &fs::read(package_path)?, mappings)
+        }
+    }
+    if let Some(rewrite_path) = rewrite {
+        let data = match game_profile::get_game_profile().endianness {
+            Endianness::Little => package.to_bytes::<byteorder::LittleEndian>()?,
+            Endianness::Big => package.to_bytes::<byteorder::BigEndian>()?
+        };
+        fs::write(rewrite_path, &data)?;
+        println!("{}", "-".repeat(80));
+        println!("Wrote {} ({} bytes)", rewrite_path.display(), data.len());
+    }
+    Ok(())
+}
+
+// Decode the unversioned properties at the start of each export whose class is a script class, then encode them again
+// to check that the writer gives back the same bytes
+fn print_export_properties<E: byteorder::ByteOrder>(package: &IoPackage, package_data: &[u8], mappings: &Usmap) {
+    if package.summary.package_flags & PKG_UNVERSIONED_PROPERTIES == 0 {
+        println!("Package uses tagged properties");
+        return;
+    }
+    let reader = UnversionedReader { mappings, names: &package.names };
+    for (i, export) in package.exports.iter().enumerate() {
+        let export_name = package.get_export_name(i).unwrap_or_default();
+        let class_path = match export.class_name {
+            IoStoreObjectIndex::ImportHash(h) => script_objects::get_script_object_path(h),
+            _ => None
+        };
+        let (Some(class_path), Some(data)) = (class_path, package.get_export_data(package_data, i)) else {
+            println!("PROPERTIES of export {} ({}): class isn't a script object", i, export_name);
+            continue;
+        };
+        let class_name = class_path.rsplit(['.', ':']).next().unwrap_or(class_path);
+        let mut cursor = Cursor::new(data);
+        let properties = match reader.read_properties::<Cursor<&[u8]>, E>(class_name, &mut cursor) {
+            Ok(p) => p,
+            Err(e) => {
+                println!("PROPERTIES of export {} ({}): couldn't read {}: {}", i, export_name, class_name, e);
+                continue;
+            }
+        };
+        let read_size = cursor.position() as usize;
+        println!("PROPERTIES of export {} ({}): {}, 0x{:x} of 0x{:x} bytes", i, export_name, class_name, read_size, data.len());
+        for property in &properties.properties {
+            match &property.value {
+                Some(value) => println!("{:>6} {}[{}] = {:?}", property.index, property.name, property.array_index, value),
+                None => println!("{:>6} {}[{}] = (zero)", property.index, property.name, property.array_index)
+            }
+        }
+        let mut writer = Cursor::new(vec![]);
+        let mut get_name_index = |n: &str| package.names.iter().position(|p| p == n).unwrap_or(usize::MAX) as u32;
+        match (UnversionedWriter { get_name_index: &mut get_name_index }).write_properties::<Cursor<Vec<u8>>, E>(&properties, &mut writer) {
+            Ok(_) if writer.get_ref()[..] == data[..read_size] => (),
+            Ok(_) => println!("WARNING: Properties of export {} don't encode back to the same bytes", i),
+            Err(e) => println!("WARNING: Couldn't encode properties of export {}: {}", i, e)
+        }
+    }
+}
+
+fn make_patch(base_path: &Path, modified_path: &Path, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let base = fs::read(base_path)?;
+    let modified = fs::read(modified_path)?;
+    let patch = DeltaPatch::create(&base, &modified);
+    // check the patch before writing it, so that a broken patch never ends up in a mod
+    if patch.apply(&base)? != modified {
+        return Err("Patch doesn't recreate the modified file".into());
+    }
+    let output = output.map_or_else(|| PathBuf::from(format!("{}.{}", modified_path.display(), DELTA_PATCH_EXTENSION)), Path::to_owned);
+    let data = patch.to_bytes()?;
+    fs::write(&output, &data)?;
+    let copied: u64 = patch.operations.iter().map(|o| match o {
+        PatchOperation::Copy { length, .. } => *length as u64,
+        PatchOperation::Insert(_) => 0
+    }).sum();
+    println!("Wrote {} ({} bytes, {} operations, {} of {} bytes copied from the base file)",
+        output.display(), data.len(), patch.operations.len(), copied, modified.len());
+    Ok(())
+}
+
+fn print_tree(toc: &TocReader, dir: &IoDirectoryIndexEntry, depth: usize) {
+    let name = match dir.name {
+        u32::MAX => "(root)",
+        n => &toc.strings[n as usize]
+    };
+    println!("{}{}/", "  ".repeat(depth), name);
+    let mut curr_child = dir.first_child;
+    while curr_child != u32::MAX {
+        let child = &toc.directories[curr_child as usize];
+        print_tree(toc, child, depth + 1);
+        curr_child = child.next_sibling;
+    }
+    let mut curr_file = dir.first_file;
+    while curr_file != u32::MAX {
+        let file = &toc.files[curr_file as usize];
+        let length = toc.offsets_and_lengths[file.user_data as usize].get_length();
+        println!("{}{} ({} bytes)", "  ".repeat(depth + 1), toc.strings[file.name as usize], length);
+        curr_file = file.next_file;
+    }
+}
+
+fn extract(toc_path: &Path, output: &Path, filter: &[String], aes_key: Option<&str>) -> Result<(), Box<dyn Error>> {
+    set_aes_key(aes_key)?;
+    let filter = ExtractFilter::new(&filter.iter().map(|f| f.as_str()).collect::<Vec<_>>());
+    check_extract_results(toc_extractor::extract_container(toc_path, output, &filter)?)
+}
+
+fn check_extract_results(results: ExtractResults) -> Result<(), Box<dyn Error>> {
+    results.print();
+    match results.failed_files.is_empty() {
+        true => Ok(()),
+        false => Err(format!("{} files couldn't be extracted", results.failed_files.len()).into())
+    }
+}
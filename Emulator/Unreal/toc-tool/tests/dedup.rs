@@ -0,0 +1,51 @@
+// Build a mod with the same .ubulk under three paths, with and without --dedup. Deduplicated copies should point at the first copy's
+// data, while every file still reads back with the same contents
+use fileemu_utoc_stream_emulator::toc_reader::TocReader;
+use std::{
+    fs,
+    path::Path,
+    process::Command
+};
+
+fn make_mod(path: &Path) {
+    let content = path.join("FEmulator/UTOC/UnrealEssentials_P.utoc/Content");
+    fs::create_dir_all(content.join("Tex/A")).unwrap();
+    fs::create_dir_all(content.join("Tex/B")).unwrap();
+    let shared: Vec<u8> = (0..0x18000u32).map(|i| (i * 7 + i / 0x100) as u8).collect();
+    let unique: Vec<u8> = (0..0x18000u32).map(|i| (i * 13 + 1) as u8).collect();
+    for file in ["Tex/A/T.ubulk", "Tex/B/T.ubulk", "Copy.ubulk"] {
+        fs::write(content.join(file), &shared).unwrap();
+    }
+    fs::write(content.join("Unique.ubulk"), unique).unwrap();
+}
+
+fn build(work: &Path, output: &str, args: &[&str]) -> TocReader {
+    let status = Command::new(env!("CARGO_BIN_EXE_toc-tool"))
+        .arg("build").arg(work.join("mod")).arg("-o").arg(work.join(output)).args(["-p", "MyGame"]).args(args)
+        .output().unwrap();
+    assert!(status.status.success(), "{}", String::from_utf8_lossy(&status.stderr));
+    TocReader::from_file(&work.join(output).join("UnrealEssentials_P.utoc")).unwrap()
+}
+
+#[test]
+fn identical_files_share_container_data() {
+    let work = std::env::temp_dir().join(format!("toc-tool-dedup-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&work);
+    make_mod(&work.join("mod"));
+    let full = build(&work, "full", &[]);
+    let dedup = build(&work, "dedup", &["--dedup"]);
+    let mut shared_offsets = vec![];
+    for (full_file, dedup_file) in full.get_files().into_iter().zip(dedup.get_files()) {
+        assert_eq!(full_file.path, dedup_file.path);
+        assert_eq!(full.read_chunk(full_file.chunk_index).unwrap(), dedup.read_chunk(dedup_file.chunk_index).unwrap(), "{}", dedup_file.path);
+        if dedup_file.path.ends_with("T.ubulk") || dedup_file.path.ends_with("Copy.ubulk") {
+            shared_offsets.push(dedup.offsets_and_lengths[dedup_file.chunk_index].get_offset());
+        }
+    }
+    assert_eq!(shared_offsets.len(), 3);
+    assert!(shared_offsets.iter().all(|o| *o == shared_offsets[0]));
+    assert!(dedup.compression_blocks.len() < full.compression_blocks.len());
+    let cas_size = |output: &str| fs::metadata(work.join(output).join("UnrealEssentials_P.ucas")).unwrap().len();
+    assert!(cas_size("dedup") < cas_size("full"));
+    fs::remove_dir_all(&work).unwrap();
+}
@@ -0,0 +1,44 @@
+// Build the same mod for both byte orders and compare the container and pak with the fixtures in the emulator's
+// test_resources/endianness. The emulator keeps it's state in statics, so each build runs in it's own toc-tool process
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command
+};
+
+fn get_fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../fileemu-utoc-stream-emulator/test_resources/endianness").join(name)
+}
+
+// Lay out a mod folder the same way the fixtures were built from: one package and one file for the pak
+fn make_mod(path: &Path, package: &str) {
+    let content = path.join("FEmulator/UTOC/UnrealEssentials_P.utoc/Content");
+    fs::create_dir_all(content.join("T")).unwrap();
+    fs::copy(get_fixture(package), content.join("T/Tbl.uasset")).unwrap();
+    fs::write(content.join("data.json"), "{\"a\":1}\n").unwrap();
+}
+
+fn build_and_compare(name: &str, package: &str, args: &[&str]) {
+    let work = std::env::temp_dir().join(format!("toc-tool-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&work);
+    make_mod(&work.join("mod"), package);
+    let status = Command::new(env!("CARGO_BIN_EXE_toc-tool"))
+        .arg("build").arg(work.join("mod")).arg("-o").arg(work.join("out")).args(["-p", "MyGame"]).args(args)
+        .output().unwrap();
+    assert!(status.status.success(), "{}", String::from_utf8_lossy(&status.stderr));
+    for file in ["UnrealEssentials_P.utoc", "UnrealEssentials_P.ucas", "UnrealEssentials_P.pak"] {
+        assert!(fs::read(work.join("out").join(file)).unwrap() == fs::read(get_fixture(&format!("{}/{}", name, file))).unwrap(),
+            "{} doesn't match the {} fixture", file, name);
+    }
+    fs::remove_dir_all(&work).unwrap();
+}
+
+#[test]
+fn little_endian_build_matches_fixture() {
+    build_and_compare("little", "package_little.uasset", &[]);
+}
+
+#[test]
+fn big_endian_build_matches_fixture() {
+    build_and_compare("big", "package_big.uasset", &["--big-endian"]);
+}